@@ -0,0 +1,165 @@
+//! Benchmarks for the hot paths of loading and rendering songs.
+//!
+//! The projects are generated in code, so no binary files need to be checked in.
+//! Run with `cargo bench`.
+
+#![expect(
+    missing_docs,
+    reason = "criterion_group! generates an undocumented function"
+)]
+
+use {
+    criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main},
+    ptcow::{
+        Delay, Event, EventPayload, Herd, MooInstructions, MooPlan, NoiseData, NoiseTable,
+        Overdrive, PcmData, SampleRate, Song, SourceSampleRate, StartPosPlan, UnitIdx, Voice,
+        VoiceData, VoiceIdx, moo_prepare, noise_to_pcm, presets, serialize_project,
+    },
+    std::hint::black_box,
+};
+
+const OUT_SAMPLE_RATE: SampleRate = SampleRate::new(44_100).unwrap();
+
+fn drums() -> Vec<(&'static str, NoiseData)> {
+    use presets::noise::{crash, hihat_open, kick_808, snare};
+    vec![
+        ("kick_808", kick_808()),
+        ("snare", snare()),
+        ("hihat_open", hihat_open()),
+        ("crash", crash()),
+    ]
+}
+
+/// A project with `units` units, playing wave, noise and PCM voices
+fn project(units: u8) -> Vec<u8> {
+    let mut ins = MooInstructions::new(OUT_SAMPLE_RATE);
+    ins.voices.extend(presets::wave::factory_voices());
+    let table = NoiseTable::generate();
+    for (name, mut noise) in drums() {
+        let mut pcm = noise_to_pcm(&mut noise, &table);
+        if name == "snare" {
+            pcm = half_rate(&pcm);
+            let mut voice = Voice::from_data(VoiceData::Pcm(pcm));
+            voice.name = "snare pcm".into();
+            ins.voices.push(voice);
+        }
+        let mut voice = Voice::from_data(VoiceData::Noise(noise));
+        voice.name = name.into();
+        ins.voices.push(voice);
+    }
+    let mut song = Song::default();
+    let mut herd = Herd::default();
+    let n_voices = ins.voices.len();
+    for u in 0..units {
+        herd.add_unit(format!("unit {u}"));
+        let eve = |tick, payload| Event {
+            payload,
+            unit: UnitIdx(u),
+            tick,
+        };
+        song.events.eves.push(eve(0, EventPayload::SetVoice(VoiceIdx(u % n_voices))));
+        for i in 0..32u32 {
+            let tick = i * 480 + u32::from(u);
+            let key = ptcow::DEFAULT_KEY + ptcow::Key::semitones(i32::from(u % 12))
+                - ptcow::Key::semitones((i % 5).cast_signed());
+            song.events.eves.push(eve(tick, EventPayload::Key(key)));
+            song.events.eves.push(eve(tick, EventPayload::On { duration: 400 }));
+        }
+    }
+    // Effects on the group every unit plays into, so the effect passes are measured too
+    let mut delay = Delay::default();
+    delay.rate = 33.0;
+    delay.freq = 3.0;
+    herd.delays.push(delay);
+    let mut ovr = Overdrive::default();
+    ovr.on = true;
+    ovr.cut_percent = 50.0;
+    ovr.amp_mul = 2.0;
+    herd.overdrives.push(ovr);
+    song.events.eves.sort_by_key(|eve| eve.tick);
+    song.recalculate_length();
+    serialize_project(&song, &herd, &ins).unwrap()
+}
+
+/// Drop every other sample, to get a PCM sound that needs to be resampled
+fn half_rate(pcm: &PcmData) -> PcmData {
+    let frame = usize::from(pcm.ch as u8) * usize::from(pcm.bps as u8) / 8;
+    let smp: Vec<u8> = pcm.smp.chunks_exact(frame).step_by(2).flatten().copied().collect();
+    PcmData {
+        sps: SourceSampleRate(pcm.sps.get() / 2),
+        num_samples: u32::try_from(smp.len() / frame).unwrap(),
+        smp,
+        ..pcm.clone()
+    }
+}
+
+fn bench_read_song(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_song");
+    for units in [1, 8, 50] {
+        let data = project(units);
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(units), &data, |b, data| {
+            b.iter(|| ptcow::read_song(black_box(data), OUT_SAMPLE_RATE).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn bench_noise_to_pcm(c: &mut Criterion) {
+    let mut group = c.benchmark_group("noise_to_pcm");
+    let table = NoiseTable::generate();
+    for (name, noise) in drums() {
+        group.bench_with_input(name, &noise, |b, noise| {
+            b.iter(|| noise_to_pcm(&mut noise.clone(), &table));
+        });
+    }
+    group.finish();
+}
+
+fn bench_to_converted(c: &mut Criterion) {
+    let mut group = c.benchmark_group("to_converted");
+    let mut noise = presets::noise::crash();
+    let pcm = noise_to_pcm(&mut noise, &NoiseTable::generate());
+    let inputs = [
+        ("stereo 16 bit", pcm.clone()),
+        ("stereo 16 bit, half rate", half_rate(&pcm)),
+    ];
+    for (name, pcm) in inputs {
+        group.throughput(Throughput::Elements(pcm.num_samples.into()));
+        group.bench_with_input(name, &pcm, |b, pcm| {
+            b.iter(|| pcm.to_converted(black_box(SampleRate::new(48_000).unwrap())));
+        });
+    }
+    group.finish();
+}
+
+fn bench_moo(c: &mut Criterion) {
+    const FRAMES: usize = 4096;
+    let mut group = c.benchmark_group("moo");
+    for units in [1, 50] {
+        let (song, mut herd, mut ins) = ptcow::read_song(&project(units), OUT_SAMPLE_RATE).unwrap();
+        let plan = MooPlan {
+            start_pos: StartPosPlan::Sample(0),
+            meas_end: None,
+            meas_repeat: None,
+            loop_: true,
+        };
+        moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+        let mut buf = vec![0i16; FRAMES * 2];
+        // Samples per second is the number of frames times the throughput
+        group.throughput(Throughput::Elements(FRAMES as u64));
+        group.bench_function(BenchmarkId::from_parameter(units), |b| {
+            b.iter(|| herd.moo(&ins, &song, black_box(&mut buf), true));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_read_song,
+    bench_noise_to_pcm,
+    bench_to_converted,
+    bench_moo
+);
+criterion_main!(benches);
@@ -4,25 +4,44 @@
 use {
     clap::Parser,
     crossterm::{QueueableCommand, SynchronizedUpdate, cursor, terminal},
-    ptcow::{Herd, MooInstructions, MooPlan, SampleRate, Unit, VoiceData, moo_prepare},
+    ptcow::{
+        Bps, ChNum, Event, EventPayload, GroupIdx, Herd, Key, LoopPoints, Meas, MixPolicy,
+        MooInstructions, MooPlan, NoiseData, Note, PcmData, SampleRate, Song, Unit, UnitIdx, Voice,
+        VoiceData, moo_prepare,
+    },
     std::{
-        io::{ErrorKind, IsTerminal, Write as _},
+        fmt::Write as _,
+        fs::File,
+        io::{BufWriter, ErrorKind, IsTerminal, Write},
         iter::zip,
-        path::PathBuf,
+        num::NonZero,
+        path::{Path, PathBuf},
         sync::{
             Arc,
             atomic::{AtomicBool, Ordering},
         },
+        time::{Duration, Instant, SystemTime},
     },
     string_width::DisplayWidth,
 };
 
 #[derive(clap::Parser)]
+#[expect(clippy::struct_excessive_bools, reason = "Command line flags")]
 struct Args {
     /// Path to song
-    path: PathBuf,
+    #[arg(required_unless_present = "voice")]
+    path: Option<PathBuf>,
+    /// Play a single note of an instrument file (.ptvoice or .ptnoise) instead of a song
+    #[arg(long, conflicts_with = "path")]
+    voice: Option<PathBuf>,
+    /// Key of the note played with `--voice` (for example `C5`, `F#3` or `Bb4`) [default: A4]
+    #[arg(long, value_parser = parse_key, conflicts_with = "path")]
+    key: Option<Key>,
+    /// Length of the note played with `--voice` (for example `2s` or `500ms`) [default: 1s]
+    #[arg(long, value_parser = parse_secs, conflicts_with = "path")]
+    dur: Option<f64>,
     /// Output sample rate
-    #[arg(short = 'r', long, default_value = "44100")]
+    #[arg(short = 'r', long, value_parser = parse_sample_rate, default_value = "44100")]
     sample_rate: SampleRate,
     /// Buffer size in bytes to render to
     #[arg(short = 'b', long, default_value = "16384")]
@@ -30,61 +49,106 @@ struct Args {
     /// Don't loop the song
     #[arg(long)]
     no_loop: bool,
+    /// Where to write the sample data (a file, named pipe, or `-` for stdout)
+    #[arg(short = 'o', long, default_value = "-")]
+    out: PathBuf,
+    /// Stop after this many seconds of output
+    #[arg(short = 'd', long)]
+    duration: Option<f64>,
+    /// Stop after the song looped this many times (0 plays it once)
+    #[arg(short = 'l', long, conflicts_with_all = ["no_loop", "voice"])]
+    loop_count: Option<u32>,
+    /// Start playing from this measure (counted from 0)
+    #[arg(long, conflicts_with_all = ["voice", "loops"])]
+    start_meas: Option<Meas>,
+    /// End (or repeat) at the start of this measure, instead of the song's end
+    #[arg(long, value_parser = clap::value_parser!(Meas).range(1..), conflicts_with = "voice")]
+    end_meas: Option<Meas>,
+    /// Repeat from this measure when looping, instead of the song's repeat point
+    #[arg(long, conflicts_with = "voice")]
+    repeat_meas: Option<Meas>,
+    /// Render the song with this many loops at once, instead of playing it.
+    ///
+    /// If the output file name ends with `.wav`, a WAV file is written.
+    #[arg(long, conflicts_with_all = ["no_loop", "voice", "loop_count", "duration"])]
+    loops: Option<u32>,
+    /// Fade out over this long after `--loops` (for example `8s`)
+    #[arg(long, value_parser = parse_secs, requires = "loops")]
+    fade: Option<f64>,
+    /// With `--loops`, render a WAV file for every unit or group into the `--out` directory,
+    /// along with a `manifest.json` describing them
+    #[arg(long, value_enum, requires = "loops")]
+    stems: Option<StemKind>,
+    /// Turn the song down just enough that it doesn't clip (scans the song before playing)
+    #[arg(long)]
+    auto_gain: bool,
     /// Disable visualization/info dump
     #[arg(long)]
     no_vis: bool,
+    /// Reload the song when the file changes, without restarting playback
+    #[arg(short = 'w', long, conflicts_with_all = ["voice", "loops"])]
+    watch: bool,
+}
+
+/// What to render separately with `--stems`
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum StemKind {
+    /// One file per unit
+    Unit,
+    /// One file per group, with the effects of the group
+    Group,
+}
+
+impl Args {
+    /// Length of the note played with `--voice`, in seconds
+    fn note_secs(&self) -> f64 {
+        self.dur.unwrap_or(1.0)
+    }
 }
 
 fn main() -> std::io::Result<()> {
     let args = Args::parse();
     let vis = !args.no_vis;
     let mut stderr = std::io::stderr().lock();
-    if vis {
+    let (mut song, mut herd, mut ins) = load(&args, vis, &mut stderr)?;
+    let meas_end = args.end_meas.unwrap_or_else(|| song.master.end_meas());
+    let meas_repeat = args.repeat_meas.unwrap_or(song.master.loop_points.repeat);
+    if (args.end_meas.is_some() || args.repeat_meas.is_some()) && meas_repeat >= meas_end {
         writeln!(
             stderr,
-            "File: {}\nRate: {}\nBufsize: {}",
-            args.path.display(),
-            args.sample_rate,
-            args.buf_size
+            "The repeat measure ({meas_repeat}) must be before the end measure ({meas_end})"
         )?;
+        return Err(std::io::Error::other("Invalid measure range"));
     }
-    let data = match std::fs::read(&args.path) {
-        Ok(data) => data,
-        Err(e) => {
-            writeln!(stderr, "Failed to read '{}': {e}", args.path.display())?;
-            return Err(std::io::Error::other("File read error"));
-        }
-    };
-    let (song, mut herd, mut ins) = match ptcow::read_song(&data, args.sample_rate) {
-        Ok((song, herd, ins)) => (song, herd, ins),
-        Err(e) => {
-            writeln!(
-                stderr,
-                "Failed to read '{}' as PxTone: {e}",
-                args.path.display()
-            )?;
-            return Err(std::io::Error::other("PxTone read error"));
-        }
-    };
+    if let Some(loops) = args.loops {
+        // Rendering loops goes by the loop points of the song
+        song.master.loop_points = LoopPoints {
+            repeat: meas_repeat,
+            last: NonZero::new(meas_end),
+        };
+        return export_looped(&args, &song, &mut herd, &mut ins, loops, &mut stderr);
+    }
+    // A preview plays its note once
+    let no_loop = args.no_loop || args.voice.is_some();
     let plan = MooPlan {
-        start_pos: ptcow::StartPosPlan::Sample(0),
-        meas_end: None,
-        meas_repeat: None,
-        loop_: !args.no_loop,
+        start_pos: ptcow::StartPosPlan::Meas(args.start_meas.unwrap_or(0)),
+        meas_end: args.end_meas,
+        meas_repeat: args.repeat_meas,
+        loop_: !no_loop,
     };
-    moo_prepare(&mut ins, &mut herd, &song, &plan);
+    moo_prepare(&mut ins, &mut herd, &song, &plan).map_err(std::io::Error::other)?;
+    report_auto_gain(&herd, &mut stderr)?;
+    if args.voice.is_some() {
+        herd.smp_end = preview_end(&args, &ins);
+    }
 
     let mut buf = vec![0i16; args.buf_size];
-    let mut writer = std::io::stdout().lock();
-    if writer.is_terminal() {
-        writeln!(
-            stderr,
-            "You don't want to write sample data to a terminal. Trust me."
-        )?;
-        return Err(std::io::Error::other(
-            "Attempting to write sample data to terminal",
-        ));
-    }
+    let mut writer = open_output(&args, &mut stderr)?;
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let frame_limit = args.duration.map(|secs| (secs * f64::from(args.sample_rate)) as u64);
+    let mut frames_written: u64 = 0;
+    let mut loops = 0;
+    let mut watch = args.watch.then(|| Watch::new(args.path.clone().unwrap()));
     let stop = Arc::new(AtomicBool::new(false));
     if vis {
         stderr.queue(terminal::EnterAlternateScreen)?;
@@ -97,37 +161,473 @@ fn main() -> std::io::Result<()> {
         .unwrap();
     }
 
-    while herd.moo(&ins, &song, &mut buf, true) {
-        let result = writer.write_all(bytemuck::cast_slice(&buf));
-        if let Err(e) = result {
-            match e.kind() {
-                ErrorKind::BrokenPipe => {
-                    break;
-                }
-                _ => panic!("I/O error: {e}"),
+    let mut result = Ok(());
+    loop {
+        // Render up to the end of the song at most, so loops can be counted exactly
+        let until_end =
+            usize::try_from(herd.smp_end.saturating_sub(herd.smp_count)).unwrap_or(usize::MAX);
+        let mut frames = std::cmp::min(buf.len() / 2, until_end);
+        if let Some(limit) = frame_limit {
+            #[expect(clippy::cast_possible_truncation)]
+            let remaining = limit.saturating_sub(frames_written).min(usize::MAX as u64) as usize;
+            frames = frames.min(remaining);
+        }
+        if frames == 0 {
+            break;
+        }
+        let chunk = &mut buf[..frames * 2];
+        if !herd.moo(&ins, &song, chunk, true) {
+            break;
+        }
+        if let Err(e) = writer.write_all(bytemuck::cast_slice(chunk)) {
+            result = write_result(e);
+            break;
+        }
+        frames_written += frames as u64;
+        if frames == until_end {
+            loops += 1;
+            if no_loop || args.loop_count.is_some_and(|count| loops > count) {
+                break;
             }
         }
         if stop.load(Ordering::Relaxed) {
             writeln!(stderr, "Gotta stop!")?;
             break;
         }
+        if let Some(data) = watch.as_mut().and_then(Watch::poll)
+            && let Err(e) = ptcow::reload_song(&data, &mut song, &mut herd, &mut ins)
+        {
+            writeln!(stderr, "Failed to reload song: {e}")?;
+        }
         if vis {
             stderr.sync_update(|stderr| print(stderr, &song, &herd, &ins))??;
         }
     }
+    if result.is_ok()
+        && let Err(e) = writer.flush()
+    {
+        result = write_result(e);
+    }
     stderr.queue(terminal::LeaveAlternateScreen)?;
     stderr.queue(cursor::Show)?;
     stderr.flush()?;
+    result
+}
+
+/// Watches the song file for changes (see [`Args::watch`])
+struct Watch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl Watch {
+    /// How often to check the file for changes
+    const INTERVAL: Duration = Duration::from_millis(250);
+
+    fn new(path: PathBuf) -> Self {
+        let modified = modified(&path);
+        Self {
+            path,
+            modified,
+            last_check: Instant::now(),
+        }
+    }
+    /// The new contents of the file, if it changed since the last call
+    fn poll(&mut self) -> Option<Vec<u8>> {
+        if self.last_check.elapsed() < Self::INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+        let modified = modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        std::fs::read(&self.path).ok()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Load the song to play, or build one for previewing a voice (see [`Args::voice`])
+fn load(
+    args: &Args,
+    vis: bool,
+    stderr: &mut std::io::StderrLock,
+) -> std::io::Result<(Song, Herd, MooInstructions)> {
+    let path = args.voice.as_ref().or(args.path.as_ref()).unwrap();
+    if vis {
+        writeln!(
+            stderr,
+            "File: {}\nRate: {}\nBufsize: {}",
+            path.display(),
+            args.sample_rate.get(),
+            args.buf_size
+        )?;
+    }
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            writeln!(stderr, "Failed to read '{}': {e}", path.display())?;
+            return Err(std::io::Error::other("File read error"));
+        }
+    };
+    let loaded = if args.voice.is_some() {
+        let key = args.key.unwrap_or(ptcow::DEFAULT_KEY);
+        voice_preview(path, &data, key, args.note_secs(), args.sample_rate)
+    } else {
+        ptcow::read_song(&data, args.sample_rate)
+    };
+    match loaded {
+        Ok((song, herd, mut ins)) => {
+            if args.auto_gain {
+                ins.mix_policy = MixPolicy::AutoGain;
+            }
+            Ok((song, herd, ins))
+        }
+        Err(e) => {
+            writeln!(stderr, "Failed to read '{}' as PxTone: {e}", path.display())?;
+            Err(std::io::Error::other("PxTone read error"))
+        }
+    }
+}
+
+/// Build a song that plays a single note with the instrument in `data`
+fn voice_preview(
+    path: &Path,
+    data: &[u8],
+    key: Key,
+    secs: f64,
+    sample_rate: SampleRate,
+) -> ptcow::ReadResult<(Song, Herd, MooInstructions)> {
+    let voice = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("ptnoise") => Voice::from_data(VoiceData::Noise(NoiseData::from_ptnoise(data)?)),
+        _ => Voice::from_ptvoice(data)?,
+    };
+    let mut ins = MooInstructions::new(sample_rate);
+    ins.voices.push(voice);
+    let mut herd = Herd::default();
+    herd.units.push(Unit::default());
+    let mut song = Song::default();
+    let timing = song.master.timing;
+    let ticks_per_sec = f64::from(timing.bpm) / 60.0 * f64::from(timing.ticks_per_beat);
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let duration = (secs * ticks_per_sec).ceil().max(1.0) as u32;
+    let eve = |payload| Event {
+        payload,
+        unit: UnitIdx(0),
+        tick: 0,
+    };
+    song.events.eves = vec![
+        eve(EventPayload::Key(key)),
+        eve(EventPayload::On { duration }),
+    ];
+    song.recalculate_length();
+    ptcow::rebuild_tones(&mut ins, sample_rate, &mut [], &mut [], &song.master);
+    Ok((song, herd, ins))
+}
+
+/// Where a preview ends: after the note, and the release of its envelope
+fn preview_end(args: &Args, ins: &MooInstructions) -> ptcow::SampleT {
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let note = (args.note_secs() * f64::from(args.sample_rate)).ceil() as ptcow::SampleT;
+    let release = ins
+        .voices
+        .iter()
+        .flat_map(Voice::slots)
+        .map(|slot| slot.inst.env_release)
+        .max()
+        .unwrap_or(0);
+    note.saturating_add(release.into())
+}
+
+/// Parse a note name like `C5`, `F#3` or `Bb4` into a [`Key`]
+fn parse_key(name: &str) -> Result<Key, String> {
+    let err = || format!("Invalid note `{name}` (expected something like `C5`, `F#3` or `Bb4`)");
+    let mut chars = name.chars();
+    let semitone = match chars.next().map(|c| c.to_ascii_lowercase()) {
+        Some('c') => 0,
+        Some('d') => 2,
+        Some('e') => 4,
+        Some('f') => 5,
+        Some('g') => 7,
+        Some('a') => 9,
+        Some('b') => 11,
+        _ => return Err(err()),
+    };
+    let rest = chars.as_str();
+    let (semitone, octave) = match rest.as_bytes().first() {
+        Some(b'#') => (semitone + 1, &rest[1..]),
+        Some(b'b') => (semitone - 1, &rest[1..]),
+        _ => (semitone, rest),
+    };
+    let octave: i32 = octave.parse().map_err(|_| err())?;
+    let key = Key::from_note(Note::C, octave) + Key::semitones(semitone);
+    if (0..=0xFFFF * 2).contains(&key.0) {
+        Ok(key)
+    } else {
+        Err(format!("Note `{name}` is out of range"))
+    }
+}
+
+/// Parse a length like `2s`, `500ms` or `1.5` (seconds)
+fn parse_secs(text: &str) -> Result<f64, String> {
+    let (num, scale) = text.strip_suffix("ms").map_or_else(
+        || (text.strip_suffix('s').unwrap_or(text), 1.0),
+        |ms| (ms, 0.001),
+    );
+    match num.trim().parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs > 0.0 => Ok(secs * scale),
+        _ => Err(format!(
+            "Invalid length `{text}` (expected something like `2s` or `500ms`)"
+        )),
+    }
+}
+
+fn parse_sample_rate(text: &str) -> Result<SampleRate, String> {
+    match text.trim().parse().ok().and_then(SampleRate::new) {
+        Some(rate) if rate.is_supported() => Ok(rate),
+        _ => Err(format!(
+            "Invalid sample rate `{text}` (expected a number of Hz from {} to {})",
+            SampleRate::MIN_SUPPORTED.get(),
+            SampleRate::MAX_SUPPORTED.get()
+        )),
+    }
+}
+
+/// Render the song offline (see [`Args::loops`])
+fn export_looped(
+    args: &Args,
+    song: &Song,
+    herd: &mut Herd,
+    ins: &mut MooInstructions,
+    loops: u32,
+    stderr: &mut std::io::StderrLock,
+) -> std::io::Result<()> {
+    if let Some(kind) = args.stems {
+        return export_stems(args, song, herd, ins, loops, kind, stderr);
+    }
+    let fade = Duration::from_secs_f64(args.fade.unwrap_or(0.0));
+    let buf = ptcow::render_looped(song, herd, ins, loops, fade).map_err(std::io::Error::other)?;
+    report_auto_gain(herd, stderr)?;
+    let bytes = if args.out.extension().is_some_and(|ext| ext == "wav") {
+        wav_bytes(&buf, args.sample_rate)?
+    } else {
+        bytemuck::cast_slice(&buf).to_vec()
+    };
+    let mut writer = open_output(args, stderr)?;
+    match writer.write_all(&bytes).and_then(|()| writer.flush()) {
+        Ok(()) => Ok(()),
+        Err(e) => write_result(e),
+    }
+}
+
+/// Render every unit or group on its own (see [`Args::stems`])
+fn export_stems(
+    args: &Args,
+    song: &Song,
+    herd: &mut Herd,
+    ins: &mut MooInstructions,
+    loops: u32,
+    kind: StemKind,
+    stderr: &mut std::io::StderrLock,
+) -> std::io::Result<()> {
+    let fade = Duration::from_secs_f64(args.fade.unwrap_or(0.0));
+    // The stems are rendered without auto gain, so they add up to the mix.
+    // The gain of the whole mix goes into the manifest instead.
+    let mut gain = 1.0;
+    if ins.mix_policy == MixPolicy::AutoGain {
+        moo_prepare(
+            ins,
+            herd,
+            song,
+            &MooPlan {
+                start_pos: ptcow::StartPosPlan::Sample(0),
+                meas_end: None,
+                meas_repeat: None,
+                loop_: true,
+            },
+        )
+        .map_err(std::io::Error::other)?;
+        report_auto_gain(herd, stderr)?;
+        gain = herd.auto_gain().unwrap_or(1.0);
+        ins.mix_policy = MixPolicy::Clip;
+    }
+    // The name of every stem, and the units playing in it
+    let stems: Vec<(String, Vec<UnitIdx>)> = match kind {
+        StemKind::Unit => herd
+            .units
+            .enumerated()
+            .filter(|(_, unit)| !unit.mute)
+            .map(|(idx, unit)| {
+                (
+                    format!("unit-{:02}-{}", idx.0, file_name(&unit.name)),
+                    vec![idx],
+                )
+            })
+            .collect(),
+        StemKind::Group => (0..=GroupIdx::MAX.0)
+            .map(|group| {
+                let units = herd
+                    .units
+                    .enumerated()
+                    .filter(|(_, unit)| !unit.mute && unit.group.0 == group)
+                    .map(|(idx, _)| idx)
+                    .collect();
+                (format!("group-{group}"), units)
+            })
+            .filter(|(_, units): &(String, Vec<UnitIdx>)| !units.is_empty())
+            .collect(),
+    };
+    if let Err(e) = std::fs::create_dir_all(&args.out) {
+        writeln!(stderr, "Failed to create '{}': {e}", args.out.display())?;
+        return Err(std::io::Error::other("Output directory error"));
+    }
+    let muted: Vec<bool> = herd.units.iter().map(|unit| unit.mute).collect();
+    let mut manifest = Vec::new();
+    let mut frames = 0;
+    for (name, units) in stems {
+        for (idx, unit) in herd.units.enumerated_mut() {
+            unit.mute = !units.contains(&idx);
+        }
+        let buf =
+            ptcow::render_looped(song, herd, ins, loops, fade).map_err(std::io::Error::other)?;
+        frames = buf.len() / 2;
+        let Some(offset) = buf.iter().position(|&smp| smp != 0).map(|pos| pos / 2) else {
+            writeln!(stderr, "Skipping silent stem {name}")?;
+            continue;
+        };
+        let file = format!("{name}.wav");
+        std::fs::write(args.out.join(&file), wav_bytes(&buf, args.sample_rate)?)?;
+        let indices: Vec<String> = units.iter().map(|unit| unit.0.to_string()).collect();
+        let names: Vec<String> = units
+            .iter()
+            .filter_map(|&unit| herd.units.get(unit))
+            .map(|unit| json_string(&unit.name))
+            .collect();
+        manifest.push(format!(
+            "    {{\"file\": {}, \"units\": [{}], \"unit_names\": [{}], \"offset\": {offset}, \"gain\": {gain}}}",
+            json_string(&file),
+            indices.join(", "),
+            names.join(", "),
+        ));
+    }
+    for (unit, mute) in herd.units.iter_mut().zip(muted) {
+        unit.mute = mute;
+    }
+    let manifest = format!(
+        "{{\n  \"sample_rate\": {},\n  \"frames\": {frames},\n  \"stems\": [\n{}\n  ]\n}}\n",
+        args.sample_rate.get(),
+        manifest.join(",\n"),
+    );
+    std::fs::write(args.out.join("manifest.json"), manifest)
+}
+
+/// `name`, with the characters that don't belong in a file name replaced
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// `text` as a quoted JSON string
+fn json_string(text: &str) -> String {
+    let mut out = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                // Writing to a `String` can't fail
+                _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `buf` as a 16 bit stereo WAV file
+fn wav_bytes(buf: &[i16], sample_rate: SampleRate) -> std::io::Result<Vec<u8>> {
+    Ok(PcmData {
+        ch: ChNum::Stereo,
+        sps: sample_rate.into(),
+        bps: Bps::B16,
+        num_samples: (buf.len() / 2).try_into().map_err(std::io::Error::other)?,
+        smp: bytemuck::cast_slice(buf).to_vec(),
+    }
+    .to_wav())
+}
+
+/// Print the gain applied by `--auto-gain`
+fn report_auto_gain(herd: &Herd, stderr: &mut std::io::StderrLock) -> std::io::Result<()> {
+    if let Some(gain) = herd.auto_gain() {
+        writeln!(
+            stderr,
+            "Auto gain: {:.1} dB",
+            ptcow::gain::to_db(gain.into())
+        )?;
+    }
     Ok(())
 }
 
+/// Open the output for the sample data (see [`Args::out`])
+fn open_output(
+    args: &Args,
+    stderr: &mut std::io::StderrLock,
+) -> std::io::Result<BufWriter<Box<dyn Write>>> {
+    let out = &args.out;
+    let writer: Box<dyn Write> = if out.as_os_str() == "-" {
+        let stdout = std::io::stdout().lock();
+        if stdout.is_terminal() {
+            writeln!(
+                stderr,
+                "You don't want to write sample data to a terminal. Trust me."
+            )?;
+            return Err(std::io::Error::other(
+                "Attempting to write sample data to terminal",
+            ));
+        }
+        Box::new(stdout)
+    } else {
+        match File::create(out) {
+            Ok(file) => Box::new(file),
+            Err(e) => {
+                writeln!(stderr, "Failed to open '{}': {e}", out.display())?;
+                return Err(std::io::Error::other("Output open error"));
+            }
+        }
+    };
+    Ok(BufWriter::new(writer))
+}
+
+/// The reader closing the pipe is a normal way to stop, not an error
+fn write_result(e: std::io::Error) -> std::io::Result<()> {
+    if e.kind() == ErrorKind::BrokenPipe {
+        Ok(())
+    } else {
+        Err(e)
+    }
+}
+
 fn print(
     stderr: &mut std::io::StderrLock,
-    song: &ptcow::Song,
+    song: &Song,
     herd: &Herd,
     ins: &MooInstructions,
 ) -> std::io::Result<()> {
-    let ratio = f64::from(herd.smp_count) / f64::from(herd.smp_end);
+    #[expect(clippy::cast_precision_loss)]
+    let ratio = herd.smp_count as f64 / herd.smp_end as f64;
     stderr.queue(terminal::Clear(terminal::ClearType::All))?;
     if !song.text.name.is_empty() {
         writeln!(stderr, "= {} =", song.text.name)?;
@@ -143,8 +643,11 @@ fn print(
         ratio * 100.,
     )?;
     let (name_widths, name_max) = name_widths(&herd.units);
-    for (unit, nw) in zip(herd.units.iter(), name_widths) {
-        let val: i32 = unit.pan_time_bufs.iter().flatten().sum();
+    for ((idx, unit), nw) in zip(herd.units.enumerated(), name_widths) {
+        let rms = herd
+            .unit_levels()
+            .get(idx.usize())
+            .map_or(0.0, |level| level.rms[0].max(level.rms[1]));
         let name: &str = &unit.name;
         let voice = &ins.voices[unit.voice_idx];
         let data_emoji = match voice.base.data {
@@ -154,7 +657,7 @@ fn print(
             VoiceData::OggV(_) => "🐠", // Ogg/Vorbis logo is a fish
         };
 
-        let ratio = f64::from(val.abs()) / 4_194_304.0;
+        let ratio = f64::from(rms) / 32768.0;
         #[expect(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
         let n_moo = (ratio * 64.).ceil() as usize;
         let fill = " ".repeat(name_max - nw);
@@ -0,0 +1,384 @@
+//! Round-trip testing of a corpus of project files.
+//!
+//! Every project file in a directory tree is read, serialized and read again. Each file is
+//! classified as byte-exact, byte mismatch (the song reads back the same), semantic mismatch,
+//! read error or write error, and the results can be printed as a table.
+//!
+//! Known differences can be tracked in a manifest, a text file with one line per file that is
+//! known not to round-trip byte-exact (file names are relative to the directory):
+//!
+//! ```text
+//! # Comments start with `#`
+//! <file name>: <difference>, <difference>, ...
+//! ```
+//!
+//! A difference is either a chunk code (`matePCM`, `Event V5`, ...), which allows that chunk
+//! to differ, be missing, or be added, or one of:
+//! - `header`: The version header differs
+//! - `order`: The chunks both files have are in a different order
+//! - `trailing`: The bytes after the end chunk differ
+//! - `unreadable`: The file can't be read
+//! - `unwritable`: The file can be read, but not serialized again
+
+use {
+    crate::{fail, pass},
+    ptcow::{ChunkDiff, Herd, MooInstructions, ProjectReadError, Song},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        error::Error,
+        fmt::Write as _,
+        path::{Path, PathBuf},
+    },
+};
+
+type Manifest = BTreeMap<String, BTreeSet<String>>;
+
+fn parse_manifest(text: &str) -> Result<Manifest, String> {
+    let mut manifest = Manifest::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((file, diffs)) = line.split_once(':') else {
+            return Err(format!(
+                "Manifest line {}: Expected `<file>: <diffs>`",
+                i + 1
+            ));
+        };
+        manifest.entry(file.trim().to_owned()).or_default().extend(
+            diffs
+                .split(',')
+                .map(str::trim)
+                .filter(|diff| !diff.is_empty())
+                .map(str::to_owned),
+        );
+    }
+    Ok(manifest)
+}
+
+/// The name of a difference, as used in the manifest
+fn diff_name(diff: &ChunkDiff) -> String {
+    match diff {
+        ChunkDiff::Changed { code, .. }
+        | ChunkDiff::OnlyLeft { code, .. }
+        | ChunkDiff::OnlyRight { code, .. } => String::from_utf8_lossy(code).trim_end().to_owned(),
+        ChunkDiff::Header => "header".to_owned(),
+        ChunkDiff::Order => "order".to_owned(),
+        ChunkDiff::Trailing => "trailing".to_owned(),
+    }
+}
+
+/// How a file survived the round-trip
+#[derive(Debug, PartialEq, Eq)]
+enum Status {
+    /// Re-serialized byte-exact
+    Ok,
+    /// The bytes differ in these chunks, but the song reads back the same
+    ByteMismatch(Vec<String>),
+    /// These parts of the song read back differently
+    SemanticMismatch {
+        parts: Vec<&'static str>,
+        /// The chunks the bytes differ in
+        chunks: Vec<String>,
+    },
+    /// The file couldn't be read
+    ReadError {
+        error: ProjectReadError,
+        /// The chunk the error happened in, if the error has a position
+        tag: Option<String>,
+    },
+    /// The file could be read, but not serialized
+    WriteError(String),
+}
+
+impl Status {
+    const fn label(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::ByteMismatch(_) => "byte-mismatch",
+            Self::SemanticMismatch { .. } => "semantic-mismatch",
+            Self::ReadError { .. } => "read-error",
+            Self::WriteError(_) => "write-error",
+        }
+    }
+    const fn is_failure(&self) -> bool {
+        !matches!(self, Self::Ok | Self::ByteMismatch(_))
+    }
+    fn details(&self) -> String {
+        match self {
+            Self::Ok => String::new(),
+            Self::ByteMismatch(chunks) => chunks.join(", "),
+            Self::SemanticMismatch { parts, .. } => parts.join(", "),
+            Self::ReadError { error, tag } => tag
+                .as_ref()
+                .map_or_else(|| error.to_string(), |tag| format!("in `{tag}`: {error}")),
+            Self::WriteError(error) => error.clone(),
+        }
+    }
+    /// The names of the differences, as used in the manifest
+    fn diff_names(&self) -> BTreeSet<String> {
+        match self {
+            Self::Ok => BTreeSet::new(),
+            Self::ByteMismatch(chunks) | Self::SemanticMismatch { chunks, .. } => {
+                chunks.iter().cloned().collect()
+            }
+            Self::ReadError { .. } => BTreeSet::from(["unreadable".to_owned()]),
+            Self::WriteError(_) => BTreeSet::from(["unwritable".to_owned()]),
+        }
+    }
+}
+
+/// The parts of the song that differ between the two reads
+fn semantic_diffs(
+    (song_a, herd_a, ins_a): &(Song, Herd, MooInstructions),
+    (song_b, herd_b, ins_b): &(Song, Herd, MooInstructions),
+) -> Vec<&'static str> {
+    let mut diffs = Vec::new();
+    if song_a.fmt.ver != song_b.fmt.ver
+        || song_a.fmt.kind != song_b.fmt.kind
+        || song_a.fmt.exe_ver != song_b.fmt.exe_ver
+    {
+        diffs.push("format");
+    }
+    if song_a.text.name != song_b.text.name || song_a.text.comment != song_b.text.comment {
+        diffs.push("text");
+    }
+    let master = |song: &Song| {
+        let timing = song.master.timing;
+        (
+            timing.ticks_per_beat,
+            timing.bpm.to_bits(),
+            timing.beats_per_meas,
+            song.master.loop_points.repeat,
+            song.master.loop_points.last,
+        )
+    };
+    if master(song_a) != master(song_b) {
+        diffs.push("master");
+    }
+    let events = |song: &Song| {
+        song.events
+            .iter()
+            .map(|eve| (eve.unit, eve.tick, eve.payload))
+            .collect::<Vec<_>>()
+    };
+    if events(song_a) != events(song_b) {
+        diffs.push("events");
+    }
+    let units = |herd: &Herd| herd.units.iter().map(|unit| unit.name.clone()).collect::<Vec<_>>();
+    if units(herd_a) != units(herd_b) {
+        diffs.push("units");
+    }
+    let voices = |ins: &MooInstructions| {
+        ins.voices.iter().map(|voice| voice.name.clone()).collect::<Vec<_>>()
+    };
+    if voices(ins_a) != voices(ins_b) {
+        diffs.push("voices");
+    }
+    let effects = |herd: &Herd| {
+        let delays = herd.delays.iter().map(|delay| {
+            (
+                format!("{:?}", delay.unit),
+                delay.group,
+                delay.rate.to_bits(),
+                delay.freq.to_bits(),
+            )
+        });
+        let overdrives = herd
+            .overdrives
+            .iter()
+            .map(|ovr| (ovr.group, ovr.cut_percent.to_bits(), ovr.amp_mul.to_bits()));
+        (delays.collect::<Vec<_>>(), overdrives.collect::<Vec<_>>())
+    };
+    if effects(herd_a) != effects(herd_b) {
+        diffs.push("effects");
+    }
+    if song_a.seed != song_b.seed {
+        diffs.push("seed");
+    }
+    diffs
+}
+
+fn roundtrip(data: &[u8]) -> Status {
+    let first = match ptcow::read_song(data, ptcow::NATIVE_SAMPLE_RATE) {
+        Ok(first) => first,
+        Err(error) => {
+            let tag = match error {
+                ProjectReadError::Data { cursor, .. } => ptcow::chunk_at_offset(data, cursor)
+                    .map(|code| String::from_utf8_lossy(&code).trim_end().to_owned()),
+                _ => None,
+            };
+            return Status::ReadError { error, tag };
+        }
+    };
+    let reserialized = match ptcow::serialize_project(&first.0, &first.1, &first.2) {
+        Ok(bytes) => bytes,
+        Err(error) => return Status::WriteError(error.to_string()),
+    };
+    if reserialized == data {
+        return Status::Ok;
+    }
+    let chunks = ptcow::diff_chunks(data, &reserialized)
+        .map(|diffs| diffs.iter().map(diff_name).collect())
+        .unwrap_or_default();
+    let parts = ptcow::read_song(&reserialized, ptcow::NATIVE_SAMPLE_RATE).map_or_else(
+        |_| vec!["unreadable"],
+        |second| semantic_diffs(&first, &second),
+    );
+    if parts.is_empty() {
+        Status::ByteMismatch(chunks)
+    } else {
+        Status::SemanticMismatch { parts, chunks }
+    }
+}
+
+/// Every project file under `dir`, sorted
+fn project_files(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            project_files(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "ptcop" || ext == "pttune") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The results as a table, followed by the number of files of each status
+fn table(results: &[(String, Status)]) -> String {
+    let name_width = results.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max(4);
+    let mut out = String::new();
+    let _ = writeln!(out, "{:name_width$}  {:17}  Details", "File", "Result");
+    for (name, status) in results {
+        let _ = writeln!(
+            out,
+            "{name:name_width$}  {:17}  {}",
+            status.label(),
+            status.details()
+        );
+    }
+    let _ = writeln!(out);
+    for label in [
+        "ok",
+        "byte-mismatch",
+        "semantic-mismatch",
+        "read-error",
+        "write-error",
+    ] {
+        let count = results.iter().filter(|(_, status)| status.label() == label).count();
+        let _ = writeln!(out, "{label:17}  {count}");
+    }
+    out
+}
+
+/// Round-trip every project file under `dir`, with their names relative to `dir`
+fn roundtrip_dir(dir: &Path) -> Result<Vec<(String, Status)>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    project_files(dir, &mut paths)?;
+    paths.sort();
+    let mut results = Vec::new();
+    for path in &paths {
+        let name = path.strip_prefix(dir).unwrap_or(path).display().to_string();
+        results.push((name, roundtrip(&std::fs::read(path)?)));
+    }
+    Ok(results)
+}
+
+/// Check the round-trips against the known differences in the manifest
+pub fn run(dir: &Path, manifest_path: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let default_manifest = dir.join("manifest.txt");
+    let manifest = match manifest_path {
+        Some(path) => parse_manifest(&std::fs::read_to_string(path)?)?,
+        None if default_manifest.exists() => {
+            parse_manifest(&std::fs::read_to_string(default_manifest)?)?
+        }
+        None => Manifest::new(),
+    };
+    let results = roundtrip_dir(dir)?;
+    let mut failures = 0;
+    let no_known = BTreeSet::new();
+    for (name, status) in &results {
+        let known = manifest.get(name).unwrap_or(&no_known);
+        let diffs = status.diff_names();
+        let new: Vec<_> = diffs.difference(known).map(String::as_str).collect();
+        if new.is_empty() {
+            pass(name);
+        } else {
+            fail(&format!("{name}: new differences: {}", new.join(", ")));
+            failures += 1;
+        }
+        let fixed: Vec<_> = known.difference(&diffs).map(String::as_str).collect();
+        if !fixed.is_empty() {
+            eprintln!(
+                "       {name}: known differences no longer occur: {}",
+                fixed.join(", ")
+            );
+        }
+    }
+    for name in manifest.keys() {
+        if !results.iter().any(|(file, _)| file == name) {
+            eprintln!("       Manifest entry '{name}' doesn't match any file");
+        }
+    }
+    if failures == 0 {
+        Ok(())
+    } else {
+        Err(format!("{failures} of {} files have new differences", results.len()).into())
+    }
+}
+
+/// Print a table of the round-trip results, without a manifest.
+///
+/// Only semantic mismatches, read errors and write errors count as failures.
+pub fn run_table(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let results = roundtrip_dir(dir)?;
+    print!("{}", table(&results));
+    let failures = results.iter().filter(|(_, status)| status.is_failure()).count();
+    if failures == 0 {
+        Ok(())
+    } else {
+        Err(format!("{failures} of {} files failed to round-trip", results.len()).into())
+    }
+}
+
+#[test]
+fn test_parse_manifest() {
+    let manifest = parse_manifest(
+        "# Known differences\n\
+         a.ptcop: matePCM , Event V5 # Ogg saved as PCM\n\
+         \n\
+         b.pttune: order\n\
+         a.ptcop: trailing",
+    )
+    .unwrap();
+    assert_eq!(manifest.len(), 2);
+    assert_eq!(
+        manifest["a.ptcop"],
+        BTreeSet::from(["matePCM", "Event V5", "trailing"].map(str::to_owned))
+    );
+    assert!(parse_manifest("no colon").is_err());
+}
+
+#[test]
+fn test_roundtrip_status() {
+    let song = Song::default();
+    let herd = Herd::default();
+    let ins = MooInstructions::new(ptcow::NATIVE_SAMPLE_RATE);
+    let data = ptcow::serialize_project(&song, &herd, &ins).unwrap();
+    assert_eq!(roundtrip(&data), Status::Ok);
+    // Cut off in the middle of the first chunk
+    let status = roundtrip(&data[..30]);
+    assert!(
+        matches!(&status, Status::ReadError { tag: Some(tag), .. } if tag == "MasterV5"),
+        "{status:?}"
+    );
+    let results = [
+        ("a.ptcop".to_owned(), Status::Ok),
+        ("b.pttune".to_owned(), status),
+    ];
+    let table = table(&results);
+    assert!(table.contains("read-error"));
+    assert!(table.lines().any(|line| line.starts_with("ok") && line.ends_with('1')));
+}
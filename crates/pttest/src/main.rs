@@ -1,9 +1,16 @@
 //! Testing utilities for ptcow
 
+mod corpus;
+mod render;
+mod soak;
+
 use {
     anstyle::AnsiColor,
     clap::Parser,
-    ptcow::NoiseTable,
+    ptcow::{
+        EnvPt, NoiseData, NoiseDesignOscillator, NoiseDesignUnit, NoiseDesignUnitFlags, NoiseTable,
+        NoiseType,
+    },
     std::{
         error::Error,
         io::{self},
@@ -13,8 +20,47 @@ use {
 
 #[derive(clap::Parser)]
 enum Args {
-    DumpNoiseTables { out_path: PathBuf },
+    DumpNoiseTables {
+        out_path: PathBuf,
+    },
     CompareNoiseTables,
+    /// Render a noise using every wave type
+    DumpNoiseRenders {
+        out_path: PathBuf,
+    },
+    CompareNoiseRenders,
+    /// Round-trip every project file in a directory, and compare against the known differences
+    /// listed in the manifest (`manifest.txt` in the directory by default)
+    RoundtripCorpus {
+        dir: PathBuf,
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+    },
+    /// Round-trip every project file in a directory tree, and print a table of the results
+    /// (ok, byte mismatch, semantic mismatch, or read error)
+    RoundtripDir {
+        dir: PathBuf,
+    },
+    /// Render a song at 44100 Hz without looping, and compare it against a reference render
+    /// (raw 16 bit stereo PCM)
+    RenderCompare {
+        song: PathBuf,
+        reference: PathBuf,
+        /// How much a sample can differ from the reference, to allow for rounding differences
+        #[arg(long, default_value_t = 0)]
+        tolerance: u16,
+        /// Write the render as the new reference instead of comparing
+        #[arg(long)]
+        bless: bool,
+    },
+    /// Play a song looping for many hours (accelerated), and check that the playback
+    /// still works, and sounds the same after every loop
+    Soak {
+        song: PathBuf,
+        /// How many hours of playback to simulate
+        #[arg(long, default_value_t = 24.0)]
+        hours: f64,
+    },
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -25,6 +71,17 @@ fn main() -> Result<(), Box<dyn Error>> {
     match args {
         Args::DumpNoiseTables { out_path } => dump_noise_tables_file(out_path)?,
         Args::CompareNoiseTables => cmp_noise_tables()?,
+        Args::DumpNoiseRenders { out_path } => std::fs::write(out_path, dump_noise_renders_buf())?,
+        Args::CompareNoiseRenders => cmp_noise_renders()?,
+        Args::RoundtripCorpus { dir, manifest } => corpus::run(&dir, manifest.as_deref())?,
+        Args::RoundtripDir { dir } => corpus::run_table(&dir)?,
+        Args::RenderCompare {
+            song,
+            reference,
+            tolerance,
+            bless,
+        } => render::run(&song, &reference, tolerance, bless)?,
+        Args::Soak { song, hours } => soak::run(&song, hours)?,
     }
     Ok(())
 }
@@ -44,6 +101,61 @@ fn dump_noise_tables_file(out_path: PathBuf) -> io::Result<()> {
     Ok(())
 }
 
+/// Renders of a noise fixture for every [`NoiseType`], one after the other.
+///
+/// Each wave type is used for the main, frequency and volume oscillators,
+/// so all the oscillator paths of the builder are covered.
+fn dump_noise_renders_buf() -> Vec<u8> {
+    let table = NoiseTable::generate();
+    let mut out = Vec::new();
+    for type_ in NoiseType::ALL {
+        let osc = |freq, volume| NoiseDesignOscillator {
+            type_,
+            freq,
+            volume,
+            offset: 25.0,
+            invert: false,
+        };
+        let mut unit = NoiseDesignUnit {
+            pan: 30,
+            main: osc(440.0, 80.0),
+            freq: osc(6.0, 30.0),
+            volu: osc(4.0, 60.0),
+            ser_flags: NoiseDesignUnitFlags::all(),
+            ..NoiseDesignUnit::default()
+        };
+        unit.enves.push(EnvPt { x: 0, y: 100 });
+        unit.enves.push(EnvPt { x: 100, y: 0 });
+        let mut noise = NoiseData {
+            smp_num_44k: 4410,
+            units: std::iter::once(unit).collect(),
+        };
+        out.extend_from_slice(&ptcow::noise_to_pcm(&mut noise, &table).smp);
+    }
+    out
+}
+
+fn cmp_noise_renders() -> Result<(), Box<dyn Error>> {
+    let path = basedir().join("clean-noise-renders.pcm");
+    if !path.exists() {
+        return Err(format!("Need clean file at '{}'", path.display()).into());
+    }
+    let clean = std::fs::read(&path)?;
+    let dirty = dump_noise_renders_buf();
+    if clean == dirty {
+        pass("Noise renders match");
+    } else {
+        let dirty_path = basedir().join("dirty-noise-renders.pcm");
+        let _ = std::fs::write(&dirty_path, dirty);
+        fail(&format!(
+            "Noise render mismatch.\n{} vs {}",
+            path.display(),
+            dirty_path.display()
+        ));
+    }
+    Ok(())
+}
+
 fn basedir() -> PathBuf {
     std::env::temp_dir().join("ptcow-test")
 }
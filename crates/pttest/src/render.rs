@@ -0,0 +1,133 @@
+//! Golden render comparison: moo a song, and compare the output against a reference render.
+//!
+//! Renders are raw interleaved 16 bit stereo PCM at 44100 Hz, played once from the start
+//! to the end of the song without looping, which is also what the original PxTone
+//! can export.
+
+use {
+    crate::{basedir, fail, pass},
+    ptcow::{MooPlan, NATIVE_SAMPLE_RATE, StartPosPlan},
+    std::{error::Error, path::Path},
+};
+
+/// Render the song in `data` once, without looping
+fn render(data: &[u8]) -> Result<Vec<i16>, Box<dyn Error>> {
+    let (song, mut herd, mut ins) = ptcow::read_song(data, NATIVE_SAMPLE_RATE)?;
+    let plan = MooPlan {
+        start_pos: StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: false,
+    };
+    ptcow::moo_prepare(&mut ins, &mut herd, &song, &plan)?;
+    let mut buf = vec![0i16; usize::try_from(herd.smp_end)? * 2];
+    herd.moo(&ins, &song, &mut buf, true);
+    Ok(buf)
+}
+
+/// How a render differs from the reference
+#[derive(Debug, PartialEq, Eq)]
+struct Comparison {
+    /// Number of samples that differ by more than the tolerance
+    over: usize,
+    /// Index of the first sample that differs by more than the tolerance
+    first_over: Option<usize>,
+    /// The largest difference of any sample
+    max_diff: u16,
+    /// Whether the lengths differ
+    len_differs: bool,
+}
+
+impl Comparison {
+    const fn passed(&self) -> bool {
+        self.over == 0 && !self.len_differs
+    }
+}
+
+fn compare(rendered: &[i16], reference: &[i16], tolerance: u16) -> Comparison {
+    let mut cmp = Comparison {
+        over: 0,
+        first_over: None,
+        max_diff: 0,
+        len_differs: rendered.len() != reference.len(),
+    };
+    for (i, (&a, &b)) in rendered.iter().zip(reference).enumerate() {
+        let diff = a.abs_diff(b);
+        cmp.max_diff = cmp.max_diff.max(diff);
+        if diff > tolerance {
+            cmp.over += 1;
+            cmp.first_over.get_or_insert(i);
+        }
+    }
+    cmp
+}
+
+pub fn run(
+    song_path: &Path,
+    reference_path: &Path,
+    tolerance: u16,
+    bless: bool,
+) -> Result<(), Box<dyn Error>> {
+    let rendered = render(&std::fs::read(song_path)?)?;
+    if bless {
+        std::fs::write(reference_path, bytemuck::cast_slice(&rendered))?;
+        pass(&format!("Wrote reference '{}'", reference_path.display()));
+        return Ok(());
+    }
+    let reference = std::fs::read(reference_path)?;
+    let reference: Vec<i16> = reference
+        .as_chunks::<2>()
+        .0
+        .iter()
+        .map(|&bytes| i16::from_le_bytes(bytes))
+        .collect();
+    let cmp = compare(&rendered, &reference, tolerance);
+    let name = song_path.display();
+    if cmp.passed() {
+        pass(&format!("{name} (max difference {})", cmp.max_diff));
+        return Ok(());
+    }
+    let dirty_path = basedir().join("dirty-render.pcm");
+    let _ = std::fs::write(&dirty_path, bytemuck::cast_slice(&rendered));
+    fail(&format!(
+        "{name} doesn't match '{}'",
+        reference_path.display()
+    ));
+    if cmp.len_differs {
+        eprintln!(
+            "       Length: {} frames, reference has {}",
+            rendered.len() / 2,
+            reference.len() / 2
+        );
+    }
+    if let Some(first) = cmp.first_over {
+        #[expect(clippy::cast_precision_loss)]
+        let secs = (first / 2) as f64 / f64::from(NATIVE_SAMPLE_RATE);
+        eprintln!(
+            "       {} samples differ by more than {tolerance} (max {}), first at frame {} ({secs:.3}s)",
+            cmp.over,
+            cmp.max_diff,
+            first / 2
+        );
+    }
+    eprintln!("       Render written to '{}'", dirty_path.display());
+    Err("Render mismatch".into())
+}
+
+#[test]
+fn test_compare() {
+    let reference = [0, 100, -100, 5];
+    assert!(compare(&reference, &reference, 0).passed());
+    let cmp = compare(&[0, 102, -100, 9], &reference, 2);
+    assert_eq!(
+        cmp,
+        Comparison {
+            over: 1,
+            first_over: Some(3),
+            max_diff: 4,
+            len_differs: false,
+        }
+    );
+    assert!(compare(&[0, 102, -100, 9], &reference, 4).passed());
+    assert!(!compare(&reference[..2], &reference, 0).passed());
+}
@@ -0,0 +1,83 @@
+//! Accelerated soak test: many hours of looped playback, in a fraction of the time.
+//!
+//! Most of the playback is advanced silently, which is much faster than rendering.
+//! Right after every loop, a window of audio is rendered and compared against the window
+//! rendered after the first loop. The playback is reset when looping, so the windows
+//! should be identical no matter how long the song has been playing.
+
+use {
+    crate::{fail, pass},
+    ptcow::{LoopPolicy, MooPlan, NATIVE_SAMPLE_RATE, SampleT, SilentMode, StartPosPlan},
+    std::{error::Error, path::Path, time::Instant},
+};
+
+pub fn run(song_path: &Path, hours: f64) -> Result<(), Box<dyn Error>> {
+    let (song, mut herd, mut ins) =
+        ptcow::read_song(&std::fs::read(song_path)?, NATIVE_SAMPLE_RATE)?;
+    // Delays and pan time buffers would carry echoes from one loop into the next
+    ins.loop_policy = LoopPolicy::Clear;
+    let plan = MooPlan {
+        start_pos: StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: true,
+    };
+    ptcow::moo_prepare(&mut ins, &mut herd, &song, &plan)?;
+    let loop_len = herd.smp_end.saturating_sub(herd.smp_repeat);
+    if loop_len < 2 {
+        return Err("The song is too short to loop".into());
+    }
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let target = (hours * 3600.0 * f64::from(NATIVE_SAMPLE_RATE)) as SampleT;
+    let window_len = loop_len.min(SampleT::from(NATIVE_SAMPLE_RATE));
+    let mut window = vec![0i16; usize::try_from(window_len)? * 2];
+    let mut reference = None;
+    let mut played: SampleT = 0;
+    let mut loops: u64 = 0;
+    let started = Instant::now();
+    loop {
+        // Advance to the last sample, and moo it, which loops back to the repeat position
+        played += herd.smp_end - herd.smp_count;
+        if !herd.advance_silent(&ins, &song, herd.smp_end - 1, SilentMode::Tones)
+            || !herd.moo(&ins, &song, &mut [0i16; 2], true)
+        {
+            fail(&format!("Playback ended after {loops} loops"));
+            return Err("Soak test failed".into());
+        }
+        loops += 1;
+        if herd.smp_count != herd.smp_repeat {
+            fail(&format!(
+                "Loop {loops} went to sample {} instead of the repeat position {}",
+                herd.smp_count, herd.smp_repeat
+            ));
+            return Err("Soak test failed".into());
+        }
+        if played >= target {
+            break;
+        }
+        herd.moo(&ins, &song, &mut window, true);
+        played += window_len;
+        match &reference {
+            None => reference = Some(window.clone()),
+            Some(reference) => {
+                if let Some(i) = window.iter().zip(reference).position(|(a, b)| a != b) {
+                    fail(&format!(
+                        "Loop {loops} sounds different from the first loop, from frame {}",
+                        i / 2
+                    ));
+                    return Err("Soak test failed".into());
+                }
+            }
+        }
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    #[expect(clippy::cast_precision_loss)]
+    let secs = played as f64 / f64::from(NATIVE_SAMPLE_RATE);
+    pass(&format!(
+        "{}: {loops} loops, {:.1} hours of playback in {elapsed:.1}s ({:.0}x real time)",
+        song_path.display(),
+        secs / 3600.0,
+        secs / elapsed
+    ));
+    Ok(())
+}
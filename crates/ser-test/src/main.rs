@@ -5,7 +5,7 @@ use std::process::ExitCode;
 fn main() -> ExitCode {
     let in_file = std::env::args().nth(1).expect("Need .ptcop file as arg");
     let in_bytes = std::fs::read(in_file).unwrap();
-    let (song, herd, ins) = ptcow::read_song(&in_bytes, 44_100).unwrap();
+    let (song, herd, ins) = ptcow::read_song(&in_bytes, ptcow::NATIVE_SAMPLE_RATE).unwrap();
     let out_bytes = ptcow::serialize_project(&song, &herd, &ins).unwrap();
     if in_bytes != out_bytes {
         eprintln!("Mismatch.");
@@ -0,0 +1,418 @@
+//! Analysis of songs, to help with debugging mixes and gathering statistics
+
+use {
+    crate::{
+        DEFAULT_KEY, EventPayload, Herd, Key, MooInstructions, Song, UnitIdx, VoiceIdx,
+        event::{DEFAULT_VELOCITY, DEFAULT_VOLUME},
+        timing::{Tick, meas_to_tick, play_length},
+    },
+    std::ops::RangeInclusive,
+};
+
+/// Report about the [`Unit`](crate::Unit)s of a song, created by [`mix_report`]
+#[derive(Debug, Default)]
+pub struct MixReport {
+    /// Report for every unit of the herd, in order
+    pub units: Vec<UnitReport>,
+}
+
+impl MixReport {
+    /// The reports of the units that have issues
+    pub fn problems(&self) -> impl Iterator<Item = &UnitReport> {
+        self.units.iter().filter(|unit| !unit.issues.is_empty())
+    }
+}
+
+/// Report about a single [`Unit`](crate::Unit)
+#[derive(Debug)]
+pub struct UnitReport {
+    /// The index of the unit
+    pub unit: UnitIdx,
+    /// Number of notes ([`On`](EventPayload::On) events) the unit plays
+    pub notes: usize,
+    /// Number of notes that can actually be heard
+    pub audible_notes: usize,
+    /// The issues found
+    pub issues: Vec<MixIssue>,
+}
+
+/// A potential problem with a unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixIssue {
+    /// The unit doesn't have any notes
+    NoNotes,
+    /// The unit is muted
+    Muted,
+    /// This many notes are played with a voice that doesn't exist
+    MissingVoice {
+        /// The number of affected notes
+        notes: usize,
+        /// The first missing voice the unit tried to use
+        voice: VoiceIdx,
+    },
+    /// This many notes are played with zero volume
+    ZeroVolume(usize),
+    /// This many notes are played with zero velocity
+    ZeroVelocity(usize),
+    /// The loudest note of the unit can exceed the 16 bit sample range on its own,
+    /// so it clips its group
+    Clipping {
+        /// Estimated peak amplitude
+        peak: i32,
+    },
+    /// All audible notes are panned hard to one side, the other channel is silent
+    OneSided {
+        /// The channel that can be heard (0 is left, 1 is right)
+        channel: u8,
+    },
+}
+
+/// Playback state of a unit, as far as the analysis is concerned
+struct UnitState {
+    voice: VoiceIdx,
+    volume: i32,
+    velocity: i32,
+    pan_vols: [i32; 2],
+}
+
+impl Default for UnitState {
+    fn default() -> Self {
+        Self {
+            voice: VoiceIdx(0),
+            volume: DEFAULT_VOLUME.into(),
+            velocity: DEFAULT_VELOCITY.into(),
+            pan_vols: [64, 64],
+        }
+    }
+}
+
+/// Accumulated mix statistics of a unit
+#[derive(Default)]
+struct UnitMix {
+    state: UnitState,
+    notes: usize,
+    audible: usize,
+    missing_voice: Option<(usize, VoiceIdx)>,
+    zero_volume: usize,
+    zero_velocity: usize,
+    peak: i32,
+    channels_heard: [bool; 2],
+}
+
+/// Find units that can't be heard, or are likely to be mixed wrong.
+///
+/// This goes through the events of the song, and checks the state of each unit whenever
+/// it plays a note.
+/// The voices in `ins` need to be prepared (like after [`read_song`](crate::read_song)),
+/// so their peak amplitude can be estimated.
+#[must_use]
+pub fn mix_report(song: &Song, herd: &Herd, ins: &MooInstructions) -> MixReport {
+    let mut stats: Vec<UnitMix> = herd.units.iter().map(|_| UnitMix::default()).collect();
+    for eve in song.events.iter() {
+        let Some(unit) = stats.get_mut(eve.unit.usize()) else {
+            continue;
+        };
+        let st = &mut unit.state;
+        match eve.payload {
+            EventPayload::SetVoice(voice) => st.voice = voice,
+            EventPayload::Volume(vol) => st.volume = vol.into(),
+            EventPayload::Velocity(vel) => st.velocity = vel.into(),
+            EventPayload::PanVol(pan) => {
+                st.pan_vols = ins.pan_law.unit_pan_vols(pan).map(i32::from);
+            }
+            EventPayload::On { .. } => unit.note(ins),
+            _ => {}
+        }
+    }
+    MixReport {
+        units: stats
+            .into_iter()
+            .zip(herd.units.enumerated())
+            .map(|(stats, (idx, unit))| stats.into_report(idx, unit.mute))
+            .collect(),
+    }
+}
+
+impl UnitMix {
+    fn note(&mut self, ins: &MooInstructions) {
+        let st = &self.state;
+        self.notes += 1;
+        let Some(voice) = ins.voices.get(st.voice) else {
+            self.missing_voice.get_or_insert((0, st.voice)).0 += 1;
+            return;
+        };
+        if st.volume == 0 {
+            self.zero_volume += 1;
+        }
+        if st.velocity == 0 {
+            self.zero_velocity += 1;
+        }
+        if st.volume == 0 || st.velocity == 0 || st.pan_vols == [0, 0] {
+            return;
+        }
+        self.audible += 1;
+        let voice_peak = voice
+            .slots()
+            .map(|slot| {
+                slot.inst
+                    .sample_buf
+                    .as_chunks::<2>()
+                    .0
+                    .iter()
+                    .map(|bytes| i32::from(i16::from_le_bytes(*bytes)).abs())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .sum::<i32>();
+        let pan = st.pan_vols[0].max(st.pan_vols[1]);
+        let peak = voice_peak * st.velocity / 128 * st.volume / 128 * pan / 64;
+        self.peak = self.peak.max(peak);
+        for (heard, pan) in self.channels_heard.iter_mut().zip(st.pan_vols) {
+            *heard |= pan != 0;
+        }
+    }
+    fn into_report(self, unit: UnitIdx, muted: bool) -> UnitReport {
+        let mut issues = Vec::new();
+        if muted {
+            issues.push(MixIssue::Muted);
+        }
+        if self.notes == 0 {
+            issues.push(MixIssue::NoNotes);
+        }
+        if let Some((notes, voice)) = self.missing_voice {
+            issues.push(MixIssue::MissingVoice { notes, voice });
+        }
+        if self.zero_volume != 0 {
+            issues.push(MixIssue::ZeroVolume(self.zero_volume));
+        }
+        if self.zero_velocity != 0 {
+            issues.push(MixIssue::ZeroVelocity(self.zero_velocity));
+        }
+        if self.peak > i32::from(i16::MAX) {
+            issues.push(MixIssue::Clipping { peak: self.peak });
+        }
+        match self.channels_heard {
+            [true, false] => issues.push(MixIssue::OneSided { channel: 0 }),
+            [false, true] => issues.push(MixIssue::OneSided { channel: 1 }),
+            _ => {}
+        }
+        UnitReport {
+            unit,
+            notes: self.notes,
+            audible_notes: self.audible,
+            issues,
+        }
+    }
+}
+
+/// Statistics of a song, created by [`analyze`]
+#[derive(Debug, Default)]
+pub struct SongStats {
+    /// Statistics of the units, by index.
+    ///
+    /// Units are only known from their events, so this goes up to the last unit that has any.
+    pub units: Vec<UnitStats>,
+    /// The number of notes played with each voice, by index
+    pub voice_notes: Vec<usize>,
+    /// How many notes sound at the same time, as the ticks where the number changes, and the
+    /// new number.
+    ///
+    /// A unit plays one note at a time, so a note ends when the next note of its unit starts.
+    /// Release is not counted.
+    pub polyphony: Vec<(Tick, usize)>,
+    /// The number of events in each measure
+    pub events_per_meas: Vec<usize>,
+    /// The length of the song in ticks, up to its [end](crate::Master::end_meas)
+    pub len_ticks: Tick,
+    /// The time it takes to play the song once, in seconds
+    pub len_secs: f64,
+}
+
+impl SongStats {
+    /// The number of notes in the song
+    #[must_use]
+    pub fn notes(&self) -> usize {
+        self.units.iter().map(|unit| unit.notes).sum()
+    }
+    /// The most notes that sound at the same time
+    #[must_use]
+    pub fn max_polyphony(&self) -> usize {
+        self.polyphony.iter().map(|&(_, n)| n).max().unwrap_or(0)
+    }
+    /// The voices no note is played with
+    pub fn unused_voices(&self) -> impl Iterator<Item = VoiceIdx> {
+        self.voice_notes
+            .iter()
+            .zip(0..=u8::MAX)
+            .filter(|&(&notes, _)| notes == 0)
+            .map(|(_, idx)| VoiceIdx(idx))
+    }
+}
+
+/// Statistics of a unit, part of [`SongStats`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UnitStats {
+    /// Number of notes ([`On`](EventPayload::On) events) the unit plays
+    pub notes: usize,
+    /// The lowest and the highest key the notes start at, or `None` if there are no notes
+    pub key_range: Option<RangeInclusive<Key>>,
+}
+
+/// Gather statistics about the notes and events of a song, like for studying a collection
+/// of songs.
+///
+/// Only the events are looked at, so `ins` doesn't need to be prepared.
+#[must_use]
+#[expect(clippy::cast_precision_loss)]
+pub fn analyze(song: &Song, ins: &MooInstructions) -> SongStats {
+    let timing = song.master.timing;
+    let len_ticks = meas_to_tick(song.master.end_meas(), timing);
+    let meas_len = meas_to_tick(1, timing);
+    let mut stats = SongStats {
+        voice_notes: vec![0; usize::from(ins.voices.len())],
+        events_per_meas: vec![0; song.master.end_meas() as usize],
+        len_ticks,
+        len_secs: play_length(song, 1, ins.out_sample_rate) as f64 / f64::from(ins.out_sample_rate),
+        ..SongStats::default()
+    };
+    // The voice and key of every unit, and when its current note ends
+    let mut playing: Vec<(VoiceIdx, Key, Option<Tick>)> = Vec::new();
+    // +1 when a note starts, -1 when it ends
+    let mut changes: Vec<(Tick, isize)> = Vec::new();
+    for eve in song.events.iter() {
+        if let Some(count) = eve
+            .tick
+            .checked_div(meas_len)
+            .and_then(|meas| stats.events_per_meas.get_mut(meas as usize))
+        {
+            *count += 1;
+        }
+        let unit = eve.unit.usize();
+        if unit >= playing.len() {
+            playing.resize(unit + 1, (VoiceIdx(0), DEFAULT_KEY, None));
+            stats.units.resize(unit + 1, UnitStats::default());
+        }
+        let (voice, key, note_end) = &mut playing[unit];
+        match eve.payload {
+            EventPayload::SetVoice(new) => (*voice, *key) = (new, DEFAULT_KEY),
+            EventPayload::Key(new) => *key = new,
+            EventPayload::On { duration } => {
+                let unit = &mut stats.units[unit];
+                unit.notes += 1;
+                unit.key_range = Some(unit.key_range.take().map_or(*key..=*key, |range| {
+                    (*range.start()).min(*key)..=(*range.end()).max(*key)
+                }));
+                if let Some(notes) = stats.voice_notes.get_mut(usize::from(voice.0)) {
+                    *notes += 1;
+                }
+                // The new note cuts the previous one short
+                if let Some(end) = note_end.take().filter(|&end| end > eve.tick) {
+                    changes.push((end, 1));
+                    changes.push((eve.tick, -1));
+                }
+                let end = eve.tick.saturating_add(duration);
+                if end > eve.tick {
+                    changes.push((eve.tick, 1));
+                    changes.push((end, -1));
+                    *note_end = Some(end);
+                }
+            }
+            _ => {}
+        }
+    }
+    changes.sort_unstable();
+    let mut sounding: isize = 0;
+    for (tick, change) in changes {
+        sounding += change;
+        let sounding = sounding.unsigned_abs();
+        match stats.polyphony.last_mut() {
+            Some((last, n)) if *last == tick => *n = sounding,
+            _ => stats.polyphony.push((tick, sounding)),
+        }
+    }
+    stats.polyphony.dedup_by_key(|&mut (_, n)| n);
+    stats
+}
+
+#[test]
+fn test_mix_report() {
+    use crate::{Event, NATIVE_SAMPLE_RATE, Unit, rebuild_tones};
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(crate::presets::wave::factory_voices());
+    let mut song = Song::default();
+    rebuild_tones(&mut ins, NATIVE_SAMPLE_RATE, &mut [], &mut [], &song.master);
+    let mut herd = Herd::default();
+    for _ in 0..4 {
+        herd.units.push(Unit::new());
+    }
+    let eve = |unit, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick: 0,
+    };
+    let on = EventPayload::On { duration: 480 };
+    song.events.eves = vec![
+        eve(0, on),
+        eve(1, EventPayload::Volume(0)),
+        eve(1, on),
+        eve(2, EventPayload::SetVoice(VoiceIdx(99))),
+        eve(2, on),
+        eve(3, EventPayload::PanVol(0)),
+        eve(3, EventPayload::Velocity(127)),
+        eve(3, EventPayload::Volume(127)),
+        eve(3, on),
+    ];
+    let report = mix_report(&song, &herd, &ins);
+    assert!(report.units[0].issues.is_empty());
+    assert_eq!(report.units[1].issues, [MixIssue::ZeroVolume(1)]);
+    assert_eq!(
+        report.units[2].issues,
+        [MixIssue::MissingVoice {
+            notes: 1,
+            voice: VoiceIdx(99)
+        }]
+    );
+    assert!(report.units[3].issues.contains(&MixIssue::OneSided { channel: 0 }));
+    assert_eq!(report.problems().count(), 3);
+}
+
+#[test]
+fn test_analyze() {
+    use crate::{Event, NATIVE_SAMPLE_RATE};
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(crate::presets::wave::factory_voices());
+    let mut song = Song::default();
+    let eve = |unit, tick, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick,
+    };
+    let on = |duration| EventPayload::On { duration };
+    song.events.eves = vec![
+        eve(0, 0, on(960)),
+        eve(2, 0, EventPayload::SetVoice(VoiceIdx(3))),
+        eve(2, 0, on(480)),
+        eve(0, 480, EventPayload::Key(DEFAULT_KEY + Key::OCTAVE)),
+        eve(0, 480, on(480)),
+        eve(2, 480, on(480)),
+        eve(0, 1920, on(480)),
+    ];
+    song.events.sort();
+    song.recalculate_length();
+    let stats = analyze(&song, &ins);
+    assert_eq!(stats.notes(), 5);
+    assert_eq!(stats.units.len(), 3);
+    assert_eq!(stats.units[0].notes, 3);
+    assert_eq!(
+        stats.units[0].key_range,
+        Some(DEFAULT_KEY..=DEFAULT_KEY + Key::OCTAVE)
+    );
+    assert_eq!(stats.units[1], UnitStats::default());
+    assert_eq!(stats.voice_notes[..4], [3, 0, 0, 2]);
+    assert_eq!(stats.unused_voices().count(), 6);
+    assert_eq!(stats.polyphony, [(0, 2), (960, 0), (1920, 1), (2400, 0)]);
+    assert_eq!(stats.max_polyphony(), 2);
+    assert_eq!(stats.events_per_meas, [6, 1]);
+    assert_eq!(stats.len_ticks, 3840);
+    assert!((stats.len_secs - 4.0).abs() < 0.01, "{}", stats.len_secs);
+}
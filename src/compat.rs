@@ -0,0 +1,220 @@
+//! Functions named after the methods of the original PxTone library
+//!
+//! If you are porting code that uses `pxtnService` or `pxtnEvelist`, you can look up the
+//! method you used here (the names are snake case versions of the original ones, with
+//! `Evelist` methods prefixed with `evels_`).
+//! Each function is a thin wrapper, and its documentation links to the ptcow API it uses,
+//! which is what new code should use directly.
+//!
+//! | PxTone                                 | ptcow                                    |
+//! |----------------------------------------|------------------------------------------|
+//! | `pxtnService::read`                    | [`read_song`]                            |
+//! | `pxtnService::write`                   | [`serialize_project`]                    |
+//! | `pxtnService::tones_ready`             | [`rebuild_tones`]                        |
+//! | `pxtnService::moo_preparation`         | [`moo_prepare`]                          |
+//! | `pxtnService::Moo`                     | [`Herd::moo`]                            |
+//! | `pxtnService::moo_is_end_vomit`        | [`Herd::moo_end`]                        |
+//! | `pxtnService::moo_set_mute_by_unit`    | [`Unit::mute`](crate::Unit::mute)        |
+//! | `pxtnService::moo_set_loop`            | [`MooPlan::loop_`]                       |
+//! | `pxtnService::moo_get_now_clock`       | [`current_tick`]                         |
+//! | `pxtnService::moo_get_end_clock`       | [`Master::end_meas`](crate::Master::end_meas) |
+//! | `pxtnService::moo_get_sampling_offset` | [`Herd::smp_count`]                      |
+//! | `pxtnService::moo_get_total_sample`    | [`Herd::smp_end`]                        |
+//! | `pxtnEvelist::get_Count`               | [`EveList::eves`](crate::EveList::eves)  |
+//! | `pxtnEvelist::Record_Add_i`            | [`EveList::sort`](crate::EveList::sort)  |
+//! | `pxtnEvelist::Record_Delete`           | [`EveList::eves`](crate::EveList::eves)  |
+//!
+//! Unlike PxTone, ptcow doesn't keep the song, the playback state, and the voices in a single
+//! service object, so the functions take the parts they need.
+//! If you only need playback, [`Player`](crate::Player) is the simplest replacement for
+//! `pxtnService`.
+
+use crate::{
+    Event, EventPayload, Herd, MooInstructions, MooPlan, ReadResult, SampleRate, SampleT, Song,
+    Tick, UnitIdx, current_tick, moo_prepare, read_song, rebuild_tones, result::WriteResult,
+    serialize_project, timing::meas_to_tick,
+};
+
+/// Read a project. Calls [`read_song`], which also readies the tones.
+#[doc(alias = "pxtnService::read")]
+pub fn read(data: &[u8], out_sample_rate: SampleRate) -> ReadResult<(Song, Herd, MooInstructions)> {
+    read_song(data, out_sample_rate)
+}
+
+/// Serialize a project. Calls [`serialize_project`].
+#[doc(alias = "pxtnService::write")]
+pub fn write(song: &Song, herd: &Herd, ins: &MooInstructions) -> WriteResult<Vec<u8>> {
+    serialize_project(song, herd, ins)
+}
+
+/// Prepare the voices and effects for playback. Calls [`rebuild_tones`].
+#[doc(alias = "pxtnService::tones_ready")]
+pub fn tones_ready(song: &Song, herd: &mut Herd, ins: &mut MooInstructions) {
+    let sps = ins.out_sample_rate;
+    rebuild_tones(
+        ins,
+        sps,
+        &mut herd.delays,
+        &mut herd.overdrives,
+        &song.master,
+    );
+}
+
+/// Set the output sample rate, and ready the tones for it.
+///
+/// ptcow always outputs stereo, so there is no channel number argument.
+#[doc(alias = "pxtnService::set_destination_quality")]
+pub fn set_destination_quality(
+    song: &Song,
+    herd: &mut Herd,
+    ins: &mut MooInstructions,
+    sps: SampleRate,
+) {
+    ins.out_sample_rate = sps;
+    tones_ready(song, herd, ins);
+}
+
+/// Prepare playback. Calls [`moo_prepare`].
+///
+/// Returns `false` if the output sample rate is not supported, like PxTone.
+#[doc(alias = "pxtnService::moo_preparation")]
+pub fn moo_preparation(
+    song: &Song,
+    herd: &mut Herd,
+    ins: &mut MooInstructions,
+    plan: &MooPlan,
+) -> bool {
+    moo_prepare(ins, herd, song, plan).is_ok()
+}
+
+/// Render samples into `buf`. Calls [`Herd::moo`].
+#[doc(alias = "pxtnService::Moo")]
+pub fn moo(song: &Song, herd: &mut Herd, ins: &MooInstructions, buf: &mut [i16]) -> bool {
+    herd.moo(ins, song, buf, true)
+}
+
+/// Whether playback has ended. Reads [`Herd::moo_end`].
+#[doc(alias = "pxtnService::moo_is_end_vomit")]
+#[must_use]
+pub const fn moo_is_end_vomit(herd: &Herd) -> bool {
+    herd.moo_end
+}
+
+/// Mute or unmute a unit. Sets [`Unit::mute`](crate::Unit::mute).
+///
+/// Returns false if the unit doesn't exist.
+#[doc(alias = "pxtnService::moo_set_mute_by_unit")]
+pub fn moo_set_mute_by_unit(herd: &mut Herd, unit: UnitIdx, mute: bool) -> bool {
+    let Some(unit) = herd.units.get_mut(unit) else {
+        return false;
+    };
+    unit.mute = mute;
+    true
+}
+
+/// Set whether playback loops. Usually set with [`MooPlan::loop_`].
+#[doc(alias = "pxtnService::moo_set_loop")]
+pub const fn moo_set_loop(herd: &mut Herd, loop_: bool) {
+    herd.set_loop(loop_);
+}
+
+/// The current playback position in ticks. Calls [`current_tick`].
+#[doc(alias = "pxtnService::moo_get_now_clock")]
+#[must_use]
+pub fn moo_get_now_clock(herd: &Herd, ins: &MooInstructions) -> Tick {
+    current_tick(herd, ins)
+}
+
+/// The tick at which playback ends. Uses [`Master::end_meas`](crate::Master::end_meas).
+#[doc(alias = "pxtnService::moo_get_end_clock")]
+#[must_use]
+pub fn moo_get_end_clock(song: &Song) -> Tick {
+    meas_to_tick(song.master.end_meas(), song.master.timing)
+}
+
+/// The current playback position in samples. Reads [`Herd::smp_count`].
+#[doc(alias = "pxtnService::moo_get_sampling_offset")]
+#[must_use]
+pub const fn moo_get_sampling_offset(herd: &Herd) -> SampleT {
+    herd.smp_count
+}
+
+/// The number of samples until the end of the song. Reads [`Herd::smp_end`], so
+/// [`moo_preparation`] needs to be called first.
+#[doc(alias = "pxtnService::moo_get_total_sample")]
+#[must_use]
+pub const fn moo_get_total_sample(herd: &Herd) -> SampleT {
+    herd.smp_end
+}
+
+/// The number of events. Reads the length of [`EveList::eves`](crate::EveList::eves).
+#[doc(alias = "pxtnEvelist::get_Count")]
+#[must_use]
+pub fn evels_get_count(song: &Song) -> usize {
+    song.events.len()
+}
+
+/// Add an event, keeping the events sorted.
+/// Pushes to [`EveList::eves`](crate::EveList::eves), then calls
+/// [`EveList::sort`](crate::EveList::sort) and [`Song::recalculate_length`].
+#[doc(
+    alias = "pxtnEvelist::Record_Add_i",
+    alias = "pxtnEvelist::Record_Add_f"
+)]
+pub fn evels_record_add(song: &mut Song, tick: Tick, unit: UnitIdx, payload: EventPayload) {
+    song.events.push(Event {
+        payload,
+        unit,
+        tick,
+    });
+    song.events.sort();
+    song.recalculate_length();
+}
+
+/// Delete the events of `unit` in the tick range `begin..end`.
+///
+/// Returns the number of deleted events.
+#[doc(alias = "pxtnEvelist::Record_Delete")]
+pub fn evels_record_delete(song: &mut Song, begin: Tick, end: Tick, unit: UnitIdx) -> usize {
+    let len = song.events.len();
+    song.events.retain(|eve| eve.unit != unit || !(begin..end).contains(&eve.tick));
+    len - song.events.len()
+}
+
+#[test]
+fn test_compat() {
+    use crate::{NATIVE_SAMPLE_RATE, SampleRate, StartPosPlan, Unit};
+    let mut song = Song::default();
+    let mut herd = Herd::default();
+    herd.units.push(Unit::new());
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(crate::presets::wave::factory_voices());
+    evels_record_add(
+        &mut song,
+        480,
+        UnitIdx(0),
+        EventPayload::On { duration: 480 },
+    );
+    evels_record_add(&mut song, 0, UnitIdx(0), EventPayload::On { duration: 480 });
+    assert_eq!(evels_get_count(&song), 2);
+    assert_eq!(song.events[0].tick, 0);
+    let (song, mut herd, mut ins) =
+        read(&write(&song, &herd, &ins).unwrap(), NATIVE_SAMPLE_RATE).unwrap();
+    set_destination_quality(&song, &mut herd, &mut ins, SampleRate(22_050));
+    let plan = MooPlan {
+        start_pos: StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: true,
+    };
+    moo_preparation(&song, &mut herd, &mut ins, &plan);
+    moo_set_loop(&mut herd, false);
+    let mut buf = vec![0; 2 * usize::try_from(moo_get_total_sample(&herd)).unwrap() + 2];
+    assert!(moo(&song, &mut herd, &ins, &mut buf));
+    assert!(buf.iter().any(|&s| s != 0));
+    assert!(moo_is_end_vomit(&herd));
+    assert_eq!(moo_get_now_clock(&herd, &ins), moo_get_end_clock(&song));
+    let mut song = song;
+    assert_eq!(evels_record_delete(&mut song, 0, 480, UnitIdx(0)), 1);
+    assert!(!moo_set_mute_by_unit(&mut herd, UnitIdx(1), true));
+}
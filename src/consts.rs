@@ -0,0 +1,96 @@
+//! Constants that external tools need to match to produce compatible voices
+//!
+//! # From key to sample increment
+//!
+//! Pitch is measured in [`Key`]s, [`KEY_PER_SEMITONE`] of them to a semitone.
+//! [`DEFAULT_KEY`] is A4 (440 Hz).
+//!
+//! Every voice has a [basic key](crate::VoiceUnit::basic_key), the key it was recorded at.
+//! A voice whose basic key is [`DEFAULT_BASICKEY`] plays its samples at their own speed
+//! (at [`NATIVE_SAMPLE_RATE`]) when the unit plays [`DEFAULT_KEY`].
+//! Every octave the played key is above that, or the basic key is below [`DEFAULT_BASICKEY`],
+//! doubles the speed.
+//! The speed is then multiplied by the [tuning](crate::VoiceUnit::tuning) of the voice, and
+//! by the ratio of [`NATIVE_SAMPLE_RATE`] to the output sample rate, giving the number of
+//! samples the voice advances by per output sample.
+//! See [`sample_increment`].
+//!
+//! Noise voices are built from oscillator tables that hold a single cycle of
+//! [`BASIC_FREQUENCY`] Hz, and their samples peak at [`SAMPLING_TOP`].
+
+use crate::{DEFAULT_KEY, Key, NATIVE_SAMPLE_RATE, SampleRate, pulse_frequency::PULSE_FREQ};
+
+/// The highest sample value of oscillators, noise and wave voices.
+///
+/// The lowest is `-SAMPLING_TOP`, so waveforms are symmetric.
+pub const SAMPLING_TOP: i16 = 32767;
+
+/// The number of [`Key`] units in a semitone (the same as [`Key::SEMITONE`])
+pub const KEY_PER_SEMITONE: i32 = 256;
+
+/// The frequency in Hz of the oscillator tables noise voices are built from.
+///
+/// A table holds one cycle of the waveform, `NATIVE_SAMPLE_RATE / BASIC_FREQUENCY`
+/// samples long.
+pub const BASIC_FREQUENCY: u16 = 100;
+
+/// The basic key a voice plays at its own speed with, when the unit plays [`DEFAULT_KEY`].
+///
+/// Also the default [`VoiceUnit::basic_key`](crate::VoiceUnit::basic_key).
+pub const DEFAULT_BASICKEY: Key = Key(17664);
+
+/// The speed ratio of a pitch `distance`, `2^(distance / octave)`.
+///
+/// Like `PxTone`, this is looked up from a table in 1/16 semitone steps.
+#[must_use]
+pub fn key_ratio(distance: Key) -> f32 {
+    PULSE_FREQ.get(distance.0)
+}
+
+/// The frequency of `key` in Hz, in concert pitch (A4 is 440 Hz).
+///
+/// ```
+/// use ptcow::{DEFAULT_KEY, Key, consts::key_frequency};
+///
+/// assert_eq!(key_frequency(DEFAULT_KEY), 440.0);
+/// assert_eq!(key_frequency(DEFAULT_KEY - Key::OCTAVE), 220.0);
+/// ```
+#[must_use]
+#[expect(clippy::cast_precision_loss)]
+pub fn key_frequency(key: Key) -> f32 {
+    440.0 * ((key.0 - DEFAULT_KEY.0) as f32 / (KEY_PER_SEMITONE * 12) as f32).exp2()
+}
+
+/// How many samples a voice advances by per output sample, when playing `key`.
+///
+/// `basic_key` and `tuning` are those of the [`VoiceUnit`](crate::VoiceUnit).
+/// This is the speed playback uses, not counting portamento, pitch bend and vibrato, which
+/// change the key over time.
+#[must_use]
+#[expect(clippy::cast_sign_loss)]
+pub fn sample_increment(key: Key, basic_key: Key, tuning: f32, out_sample_rate: SampleRate) -> f32 {
+    let stride = f32::from(NATIVE_SAMPLE_RATE) / f32::from(out_sample_rate);
+    PULSE_FREQ.get2(key.0 as usize)
+        * key_ratio(Key(DEFAULT_BASICKEY.0.wrapping_sub(basic_key.0)))
+        * tuning
+        * stride
+}
+
+#[test]
+#[expect(clippy::float_cmp)]
+fn test_sample_increment() {
+    let inc = |key, basic_key, rate| sample_increment(key, basic_key, 1.0, SampleRate(rate));
+    assert_eq!(inc(DEFAULT_KEY, DEFAULT_BASICKEY, 44_100), 1.0);
+    assert_eq!(
+        inc(DEFAULT_KEY + Key::OCTAVE, DEFAULT_BASICKEY, 44_100),
+        2.0
+    );
+    assert_eq!(
+        inc(DEFAULT_KEY, DEFAULT_BASICKEY + Key::OCTAVE, 44_100),
+        0.5
+    );
+    assert_eq!(inc(DEFAULT_KEY, DEFAULT_BASICKEY, 22_050), 2.0);
+    assert_eq!(key_ratio(Key::OCTAVE * 2), 4.0);
+    let fifth = key_ratio(Key::semitones(7));
+    assert!((fifth - 1.5).abs() < 0.01, "{fifth}");
+}
@@ -5,7 +5,7 @@ use crate::{
 };
 
 /// What unit should the delay frequency be treated as
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DelayUnit {
     /// Number of beats
     ///
@@ -24,8 +24,12 @@ pub struct Delay {
     pub unit: DelayUnit,
     /// Index of the group this delay applies to
     pub group: GroupIdx,
-    /// How much to apply the delay effect to the group
-    pub rate: u8,
+    /// How much to apply the delay effect to the group, in percent.
+    ///
+    /// This is stored as-is in the project file, so fractional rates survive a round-trip.
+    /// The PxTone editor uses the range 0 to 100, see [`Self::rate_percent`] for how
+    /// playback treats other values.
+    pub rate: f32,
     /// What frequency should the reverb effect have.
     pub freq: f32,
     pub(crate) offset: usize,
@@ -45,11 +49,28 @@ impl Default for Delay {
     }
 }
 
+impl Clone for Delay {
+    fn clone(&self) -> Self {
+        Self {
+            bufs: self.bufs.clone(),
+            ..*self
+        }
+    }
+    /// Reuses the allocations of the buffers
+    fn clone_from(&mut self, source: &Self) {
+        let mut bufs = std::mem::take(&mut self.bufs);
+        bufs.clone_from(&source.bufs);
+        *self = Self { bufs, ..*source };
+    }
+}
+
 enum BufLenCalcError {
     /// The resulting length would be too large (unintended huge allocation)
     TooLarge,
     /// The frequency is zero, it would result in division by zero
     ZeroFreq,
+    /// The timing of the song is invalid
+    InvalidTiming,
 }
 
 // 2^24, size in bytes is ~67 megabytes
@@ -61,6 +82,25 @@ impl Delay {
     pub const fn buf_len(&self) -> usize {
         self.bufs[0].len()
     }
+    /// The [rate](Self::rate) used for playback.
+    ///
+    /// Any rate from 0 up is played as is. Above 100, each echo is louder than the one
+    /// before it. Negative rates and not-a-number are treated as 0.
+    #[must_use]
+    pub const fn rate_percent(&self) -> f32 {
+        if self.rate.is_nan() {
+            0.0
+        } else {
+            self.rate.max(0.0)
+        }
+    }
+    /// Silence the echoes that are still in the buffers
+    pub(crate) fn clear(&mut self) {
+        self.offset = 0;
+        for buf in &mut self.bufs {
+            buf.fill(0);
+        }
+    }
     /// Rebuild the internal buffers used for the delay effect
     pub fn rebuild(&mut self, bp_mea: BpMea, beat_tempo: f32, sps: SampleRate) {
         self.offset = 0;
@@ -76,6 +116,9 @@ impl Delay {
             Err(BufLenCalcError::ZeroFreq) => {
                 eprintln!("Can't calc buffer size because frequency is zero.");
             }
+            Err(BufLenCalcError::InvalidTiming) => {
+                eprintln!("Can't calc buffer size because the timing is invalid.");
+            }
         }
     }
     /// Calculate the buffer length to use for the delay.
@@ -89,6 +132,10 @@ impl Delay {
         if self.freq == 0.0 {
             return Err(BufLenCalcError::ZeroFreq);
         }
+        let timing_valid = bp_mea != 0 && beat_tempo.is_finite() && beat_tempo > 0.0;
+        if self.unit != DelayUnit::Second && !timing_valid {
+            return Err(BufLenCalcError::InvalidTiming);
+        }
         let size = match self.unit {
             DelayUnit::Beat => (f32::from(sps) * 60. / beat_tempo / self.freq) as usize,
             DelayUnit::Meas => {
@@ -103,6 +150,7 @@ impl Delay {
         }
     }
 
+    #[expect(clippy::cast_possible_truncation)]
     pub(crate) fn tone_supple(&mut self, ch: u8, group_smps: &mut GroupSamples) {
         // Be resilient against offset overflow (like when configuring delay on the fly)
         let Some(buf_sample) = self.bufs[ch as usize].get(self.offset) else {
@@ -110,8 +158,14 @@ impl Delay {
             self.offset = 0;
             return;
         };
-        group_smps[self.group.usize()] += buf_sample * i32::from(self.rate) / 100;
-        self.bufs[ch as usize][self.offset] = group_smps[self.group.usize()];
+        // Fixed point with 8 fractional bits, which gives the same results as integer
+        // math for whole percentages
+        let rate = (self.rate_percent() * 256.0).round() as i64;
+        let echo = (i64::from(*buf_sample).saturating_mul(rate) / 25_600)
+            .clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+        let smp = &mut group_smps[self.group.usize()];
+        *smp = smp.saturating_add(echo);
+        self.bufs[ch as usize][self.offset] = *smp;
     }
 
     pub(crate) const fn tone_increment(&mut self) {
@@ -121,3 +175,32 @@ impl Delay {
         }
     }
 }
+
+#[test]
+fn test_rate_percent() {
+    let mut delay = Delay::default();
+    for (rate, expected) in [
+        (33.0, 330),
+        (33.5, 335),
+        (150.0, 1501),
+        (-5.0, 0),
+        (1e6, 10_010_000),
+        (f32::NAN, 0),
+    ] {
+        delay.rate = rate;
+        delay.bufs = [vec![1001], vec![-1001]];
+        let mut group_smps = GroupSamples::default();
+        delay.tone_supple(0, &mut group_smps);
+        assert_eq!(group_smps[0], expected, "rate {rate}");
+        let mut group_smps = GroupSamples::default();
+        delay.tone_supple(1, &mut group_smps);
+        assert_eq!(group_smps[0], -expected, "rate {rate}");
+    }
+    // Saturates instead of overflowing
+    delay.rate = f32::INFINITY;
+    delay.bufs = [vec![1001], vec![-1001]];
+    let mut group_smps = GroupSamples::default();
+    group_smps[0] = 1;
+    delay.tone_supple(0, &mut group_smps);
+    assert_eq!(group_smps[0], i32::MAX);
+}
@@ -0,0 +1,509 @@
+//! Compact, tracker-like text notation for songs
+//!
+//! The text is processed line by line. Everything after a `#` at the start of a word is
+//! a comment.
+//!
+//! The header consists of directives that set up the song:
+//!
+//! | Directive          | Meaning                                         |
+//! |--------------------|-------------------------------------------------|
+//! | `title <text>`     | Name of the song                                |
+//! | `comment <text>`   | Comment of the song                             |
+//! | `bpm <number>`     | Beats per minute                                |
+//! | `beats <number>`   | Beats per measure                               |
+//! | `ticks <number>`   | Ticks per beat                                  |
+//! | `repeat <meas>`    | Measure to repeat from when looping             |
+//! | `last <meas>`      | Last measure of the song                        |
+//!
+//! A `track <name>` line starts a new track (unit). It can be followed by `key=value`
+//! settings: `voice`, `volume`, `velocity`, `pan`, `group`.
+//!
+//! The lines after a track line contain whitespace separated tokens for that track:
+//!
+//! - Notes: `c4`, `f#3:8`, `eb5:4.`
+//!   - The note name (`a`-`g`), optionally followed by `#` (sharp) or `b` (flat)
+//!   - The octave. If omitted, the octave of the previous note is used.
+//!   - `:` followed by the length as a fraction of a whole note (`1`, `2`, `4`, `8`, ...),
+//!     optionally dotted (`.`). If omitted, the length of the previous note or rest is used.
+//! - Rests: `r`, `r:2`
+//! - Voice change: `@<voice index>`
+//! - Velocity change: `v<velocity>`
+//! - Bar lines (`|`) are ignored, they are just there to help readability.
+//!
+//! A beat is treated as a quarter note.
+//!
+//! # Example
+//!
+//! ```
+//! let src = "
+//! title Hello
+//! bpm 140
+//!
+//! track lead voice=0
+//! c4:8 d e f | g:4 g | a:8 a a a | g:2
+//!
+//! track bass voice=1 volume=80
+//! c2:2 r | f2:2 g2
+//! ";
+//! let (song, herd) = ptcow::dsl::compile(src).unwrap();
+//! assert_eq!(song.text.name, "Hello");
+//! assert_eq!(herd.units.len(), 2);
+//! ```
+
+use crate::{
+    Event, EventPayload, GroupIdx, Herd, Key, Song, Tick, Unit, UnitIdx, Units, VoiceIdx,
+    timing::NonZeroMeas,
+};
+
+/// Error that can happen when compiling song text
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("line {line}, column {column}: {kind}")]
+pub struct DslError {
+    /// Line number (1-based)
+    pub line: usize,
+    /// Column number (1-based)
+    pub column: usize,
+    /// What went wrong
+    pub kind: DslErrorKind,
+}
+
+/// The kind of a [`DslError`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DslErrorKind {
+    /// Unknown header directive
+    #[error("unknown directive `{0}`")]
+    UnknownDirective(String),
+    /// A directive or setting is missing its value
+    #[error("missing value for `{0}`")]
+    MissingValue(&'static str),
+    /// A value couldn't be parsed, or it's out of range
+    #[error("invalid value `{value}` for `{what}`")]
+    InvalidValue {
+        /// What the value is for
+        what: &'static str,
+        /// The offending value
+        value: String,
+    },
+    /// Unknown track setting
+    #[error("unknown track setting `{0}`")]
+    UnknownSetting(String),
+    /// A token couldn't be parsed as a note, rest, or command
+    #[error("can't understand `{0}`")]
+    InvalidToken(String),
+    /// Notes appeared before any `track` line
+    #[error("notes outside of a track (start a track with `track <name>`)")]
+    NoTrack,
+    /// A note doesn't have an octave, and there is no previous note to take it from
+    #[error("note `{0}` needs an octave (for example `c4`)")]
+    MissingOctave(String),
+    /// The resulting note is outside of the playable key range
+    #[error("note `{0}` is out of range")]
+    NoteOutOfRange(String),
+    /// The length of a note doesn't fit into a whole number of ticks
+    #[error("length `{0}` is too short for the current ticks per beat")]
+    LengthTooShort(String),
+    /// More tracks than PxTone supports
+    #[error("too many tracks (maximum is {})", Units::MAX_STANDARD)]
+    TooManyTracks,
+}
+
+/// Compile song text into a [`Song`] and a [`Herd`] with one unit for each track.
+///
+/// The voices referred to by the tracks need to be supplied separately, in
+/// [`MooInstructions::voices`](crate::MooInstructions::voices).
+///
+/// See the [module level documentation](self) for the format.
+pub fn compile(src: &str) -> Result<(Song, Herd), DslError> {
+    let mut cx = Compiler::default();
+    for (line_idx, line) in src.lines().enumerate() {
+        let line = strip_comment(line);
+        cx.line(line_idx + 1, line)?;
+    }
+    Ok(cx.finish())
+}
+
+#[derive(Default)]
+struct Compiler {
+    song: Song,
+    herd: Herd,
+    events: Vec<Event>,
+    track: Option<Track>,
+    repeat: Option<u32>,
+    last: Option<u32>,
+}
+
+struct Track {
+    unit: UnitIdx,
+    tick: Tick,
+    octave: Option<i32>,
+    length: Tick,
+    key: Option<Key>,
+}
+
+/// Whitespace separated word of a line, with its column
+struct Word<'a> {
+    column: usize,
+    text: &'a str,
+}
+
+fn words(line: &str) -> impl Iterator<Item = Word<'_>> {
+    line.split_whitespace().map(move |text| Word {
+        // `text` is a subslice of `line`, so the offset is always in bounds
+        column: text.as_ptr() as usize - line.as_ptr() as usize + 1,
+        text,
+    })
+}
+
+impl Compiler {
+    fn line(&mut self, line: usize, text: &str) -> Result<(), DslError> {
+        let mut words = words(text);
+        let Some(first) = words.next() else {
+            return Ok(());
+        };
+        let err = |column, kind| DslError { line, column, kind };
+        if first.text == "track" {
+            return self.start_track(words).map_err(|(col, kind)| err(col, kind));
+        }
+        if self.track.is_some() {
+            for word in std::iter::once(first).chain(words) {
+                self.token(word.text).map_err(|kind| err(word.column, kind))?;
+            }
+            return Ok(());
+        }
+        let rest = text[first.column - 1 + first.text.len()..].trim();
+        let value_column = first.column + first.text.len() + 1;
+        self.directive(first.text, rest)
+            .map_err(|kind| err(value_column.min(text.len() + 1), kind))
+    }
+    fn directive(&mut self, name: &str, value: &str) -> Result<(), DslErrorKind> {
+        let timing = &mut self.song.master.timing;
+        match name {
+            "title" => value.clone_into(&mut self.song.text.name),
+            "comment" => value.clone_into(&mut self.song.text.comment),
+            "bpm" => timing.bpm = parse(value, "bpm").and_then(positive("bpm"))?,
+            "beats" => timing.beats_per_meas = parse(value, "beats").and_then(nonzero("beats"))?,
+            "ticks" => timing.ticks_per_beat = parse(value, "ticks").and_then(nonzero("ticks"))?,
+            "repeat" => self.repeat = Some(parse(value, "repeat")?),
+            "last" => self.last = Some(parse(value, "last")?),
+            // Allow a note line to appear directly after the header, with a helpful error
+            _ if parse_note(name).is_some() => return Err(DslErrorKind::NoTrack),
+            _ => return Err(DslErrorKind::UnknownDirective(name.to_owned())),
+        }
+        Ok(())
+    }
+    fn start_track<'a>(
+        &mut self,
+        mut words: impl Iterator<Item = Word<'a>>,
+    ) -> Result<(), (usize, DslErrorKind)> {
+        if self.herd.units.len() >= Units::MAX_STANDARD {
+            return Err((1, DslErrorKind::TooManyTracks));
+        }
+        let mut unit = Unit::new();
+        if let Some(name) = words.next() {
+            name.text.clone_into(&mut unit.name);
+        }
+        let idx = UnitIdx(self.herd.units.len());
+        self.herd.units.0.push(unit);
+        for word in words {
+            self.track_setting(idx, word.text).map_err(|kind| (word.column, kind))?;
+        }
+        self.track = Some(Track {
+            unit: idx,
+            tick: 0,
+            octave: None,
+            length: Tick::from(self.song.master.timing.ticks_per_beat),
+            key: None,
+        });
+        Ok(())
+    }
+    fn track_setting(&mut self, unit: UnitIdx, setting: &str) -> Result<(), DslErrorKind> {
+        let Some((key, value)) = setting.split_once('=') else {
+            return Err(DslErrorKind::UnknownSetting(setting.to_owned()));
+        };
+        let payload = match key {
+            "voice" => EventPayload::SetVoice(VoiceIdx(parse(value, "voice")?)),
+            "volume" => EventPayload::Volume(parse(value, "volume")?),
+            "velocity" => EventPayload::Velocity(parse(value, "velocity")?),
+            "pan" => EventPayload::PanVol(parse(value, "pan")?),
+            "group" => {
+                let group = GroupIdx(parse(value, "group")?);
+                if group.0 > GroupIdx::MAX.0 {
+                    return Err(invalid("group", value));
+                }
+                EventPayload::SetGroup(group)
+            }
+            _ => return Err(DslErrorKind::UnknownSetting(key.to_owned())),
+        };
+        self.events.push(Event {
+            payload,
+            unit,
+            tick: 0,
+        });
+        Ok(())
+    }
+    fn token(&mut self, token: &str) -> Result<(), DslErrorKind> {
+        let Some(track) = &mut self.track else {
+            return Err(DslErrorKind::NoTrack);
+        };
+        let mut push = |payload, tick| {
+            self.events.push(Event {
+                payload,
+                unit: track.unit,
+                tick,
+            });
+        };
+        if token == "|" {
+            return Ok(());
+        }
+        if let Some(voice) = token.strip_prefix('@') {
+            push(
+                EventPayload::SetVoice(VoiceIdx(parse(voice, "voice")?)),
+                track.tick,
+            );
+            return Ok(());
+        }
+        if let Some(vel) = token.strip_prefix('v') {
+            push(EventPayload::Velocity(parse(vel, "velocity")?), track.tick);
+            return Ok(());
+        }
+        let (name, length) = match token.split_once(':') {
+            Some((name, length)) => (name, Some(length)),
+            None => (token, None),
+        };
+        if let Some(length) = length {
+            track.length = parse_length(length, self.song.master.timing.ticks_per_beat)?;
+        }
+        if name != "r" {
+            let Some((semitone, octave)) = parse_note(name) else {
+                return Err(DslErrorKind::InvalidToken(token.to_owned()));
+            };
+            let Some(octave) = octave.or(track.octave) else {
+                return Err(DslErrorKind::MissingOctave(token.to_owned()));
+            };
+            track.octave = Some(octave);
+            let key = note_key(semitone, octave)
+                .ok_or_else(|| DslErrorKind::NoteOutOfRange(token.to_owned()))?;
+            // Only emit key events when the key actually changes
+            if track.key != Some(key) {
+                track.key = Some(key);
+                push(EventPayload::Key(key), track.tick);
+            }
+            push(
+                EventPayload::On {
+                    duration: track.length,
+                },
+                track.tick,
+            );
+        }
+        track.tick += track.length;
+        Ok(())
+    }
+    fn finish(mut self) -> (Song, Herd) {
+        self.song.events.eves = self.events;
+        self.song.events.sort();
+        if let Some(repeat) = self.repeat {
+            self.song.master.loop_points.repeat = repeat;
+        }
+        self.song.master.loop_points.last = self.last.and_then(NonZeroMeas::new);
+        self.song.recalculate_length();
+        (self.song, self.herd)
+    }
+}
+
+/// Cut off the comment of a line.
+///
+/// Comments start with a `#` at the start of a word, so sharps like `f#4` are kept.
+fn strip_comment(line: &str) -> &str {
+    let mut word_start = true;
+    for (i, ch) in line.char_indices() {
+        if ch == '#' && word_start {
+            return &line[..i];
+        }
+        word_start = ch.is_whitespace();
+    }
+    line
+}
+
+fn parse<T: std::str::FromStr>(value: &str, what: &'static str) -> Result<T, DslErrorKind> {
+    if value.is_empty() {
+        return Err(DslErrorKind::MissingValue(what));
+    }
+    value.parse().map_err(|_| invalid(what, value))
+}
+
+fn invalid(what: &'static str, value: impl std::fmt::Display) -> DslErrorKind {
+    DslErrorKind::InvalidValue {
+        what,
+        value: value.to_string(),
+    }
+}
+
+fn positive(what: &'static str) -> impl Fn(f32) -> Result<f32, DslErrorKind> {
+    move |val| {
+        if val > 0.0 && val.is_finite() {
+            Ok(val)
+        } else {
+            Err(invalid(what, val))
+        }
+    }
+}
+
+fn nonzero<T: Default + PartialEq + std::fmt::Display>(
+    what: &'static str,
+) -> impl Fn(T) -> Result<T, DslErrorKind> {
+    move |val| {
+        if val == T::default() {
+            Err(invalid(what, val))
+        } else {
+            Ok(val)
+        }
+    }
+}
+
+/// Parse a note name into a semitone (relative to C) and an optional octave
+fn parse_note(name: &str) -> Option<(i32, Option<i32>)> {
+    let mut chars = name.chars();
+    let semitone = match chars.next()? {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (semitone, rest) = match rest.as_bytes().first() {
+        Some(b'#') => (semitone + 1, &rest[1..]),
+        Some(b'b') => (semitone - 1, &rest[1..]),
+        _ => (semitone, rest),
+    };
+    if rest.is_empty() {
+        return Some((semitone, None));
+    }
+    Some((semitone, Some(rest.parse().ok()?)))
+}
+
+/// Convert a note to a [`Key`].
+///
+/// A4 is the default key of units.
+fn note_key(semitone: i32, octave: i32) -> Option<Key> {
+    let midi = octave.checked_add(1)?.checked_mul(12)?.checked_add(semitone)?;
+    // A4 (midi note 69) is [`DEFAULT_KEY`](crate::DEFAULT_KEY)
+    let key = (midi + 27).checked_mul(256)?;
+    (0..=0xFFFF * 2).contains(&key).then_some(Key(key))
+}
+
+/// Parse a note length given as a fraction of a whole note
+fn parse_length(length: &str, ticks_per_beat: u16) -> Result<Tick, DslErrorKind> {
+    let (length_num, dotted) =
+        length.strip_suffix('.').map_or((length, false), |length| (length, true));
+    let divisor: Tick = parse(length_num, "length").and_then(nonzero("length"))?;
+    let whole = Tick::from(ticks_per_beat) * 4;
+    if !whole.is_multiple_of(divisor) {
+        return Err(DslErrorKind::LengthTooShort(length.to_owned()));
+    }
+    let mut ticks = whole / divisor;
+    if dotted {
+        if !ticks.is_multiple_of(2) {
+            return Err(DslErrorKind::LengthTooShort(length.to_owned()));
+        }
+        ticks += ticks / 2;
+    }
+    Ok(ticks)
+}
+
+#[test]
+fn test_dsl_errors() {
+    let err = |src| compile(src).err().unwrap();
+    let e = err("bpm fast");
+    assert_eq!((e.line, e.column), (1, 5));
+    let e = err("track a\nc4 x");
+    assert_eq!((e.line, e.column), (2, 4));
+    assert_eq!(e.kind, DslErrorKind::InvalidToken("x".into()));
+    let e = err("track a\nc:4");
+    assert_eq!(e.kind, DslErrorKind::MissingOctave("c:4".into()));
+    assert_eq!(err("c4 d4").kind, DslErrorKind::NoTrack);
+}
+
+#[test]
+fn test_dsl_compile() {
+    let (song, herd) = compile(
+        "
+ticks 96
+beats 3
+repeat 1
+last 3
+
+track lead voice=2 volume=80
+c4:8 d e:4. | r:8 @3 v100 f#:2 # comment
+track bass group=1
+a2:1 a
+",
+    )
+    .unwrap();
+    assert_eq!(song.master.timing.ticks_per_beat, 96);
+    assert_eq!(song.master.timing.beats_per_meas, 3);
+    assert_eq!(song.master.loop_points.repeat, 1);
+    assert_eq!(song.master.loop_points.last, NonZeroMeas::new(3));
+    assert_eq!(herd.units.len(), 2);
+    assert_eq!(herd.units.0[1].name, "bass");
+    // (tick, key, duration) of the notes of a unit
+    let notes = |unit| {
+        let mut key = None;
+        let mut notes = Vec::new();
+        for eve in song.events.eves.iter().filter(|eve| eve.unit == UnitIdx(unit)) {
+            match eve.payload {
+                EventPayload::Key(k) => key = Some(k.0),
+                EventPayload::On { duration } => notes.push((eve.tick, key.unwrap(), duration)),
+                _ => {}
+            }
+        }
+        notes
+    };
+    let other = |unit| {
+        song.events
+            .eves
+            .iter()
+            .filter(|eve| eve.unit == UnitIdx(unit))
+            .filter(|eve| !matches!(eve.payload, EventPayload::Key(_) | EventPayload::On { .. }))
+            .map(|eve| (eve.tick, eve.payload))
+            .collect::<Vec<_>>()
+    };
+    // A whole note is 4 beats of 96 ticks
+    assert_eq!(
+        notes(0),
+        [
+            (0, 22272, 48),
+            (48, 22784, 48),
+            (96, 23296, 144),
+            (288, 23808, 192)
+        ]
+    );
+    assert_eq!(
+        other(0),
+        [
+            (0, EventPayload::SetVoice(VoiceIdx(2))),
+            (0, EventPayload::Volume(80)),
+            (288, EventPayload::SetVoice(VoiceIdx(3))),
+            (288, EventPayload::Velocity(100)),
+        ]
+    );
+    // The key only changes once, and A4 is the default key
+    assert_eq!(notes(1), [(0, 18432, 384), (384, 18432, 384)]);
+    let key_events = song
+        .events
+        .eves
+        .iter()
+        .filter(|eve| eve.unit == UnitIdx(1) && matches!(eve.payload, EventPayload::Key(_)));
+    assert_eq!(key_events.count(), 1);
+    assert_eq!(other(1), [(0, EventPayload::SetGroup(GroupIdx(1)))]);
+    let (song, _) = compile("track a\na4").unwrap();
+    assert!(
+        song.events
+            .eves
+            .iter()
+            .any(|eve| eve.payload == EventPayload::Key(crate::DEFAULT_KEY))
+    );
+}
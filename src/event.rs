@@ -1,14 +1,33 @@
-use crate::{
-    io::write_varint,
-    result::{ProjectReadError, ReadResult},
-    timing::Tick,
-    unit::{GroupIdx, PanTime, UnitIdx, VoiceIdx},
+mod clip;
+mod csv;
+mod dynamics;
+mod optimize;
+
+pub use {
+    clip::{ClipMap, EventClip},
+    csv::{CsvError, CsvErrorKind},
+    dynamics::{Curve, Dynamic, DynamicCurve},
+};
+
+use {
+    crate::{
+        Key,
+        io::write_varint,
+        result::{ProjectReadError, ReadResult},
+        timing::Tick,
+        unit::{GroupIdx, LfoParams, PanTime, Tuning, UnitIdx, VoiceIdx},
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        ops::Range,
+    },
 };
 
 /// List of [`Event`]s.
 ///
 /// INVARIANT: ptcow's playback code assumes that events are sorted
-/// by tick value in ascending order.
+/// by tick value in ascending order, and events on the same tick are
+/// ordered like PxTone orders them (see [`Self::sort`]).
 /// Use [`Self::sort`] after you made modifications to the event list,
 /// to ensure correct playback.
 #[derive(Default)]
@@ -52,25 +71,7 @@ impl EveList {
             let unit_no = UnitIdx(rd.next::<u8>()?);
             let kind = rd.next::<u8>()?;
             let value = rd.next_varint()?;
-            let payload = match kind {
-                0 => EventPayload::Null,
-                1 => EventPayload::On { duration: value },
-                2 => EventPayload::Key(value.try_into().unwrap()),
-                3 => EventPayload::PanVol(value.try_into().unwrap()),
-                4 => EventPayload::Velocity(value.cast_signed().try_into().unwrap()),
-                5 => EventPayload::Volume(value.cast_signed().try_into().unwrap()),
-                6 => EventPayload::Portament { duration: value },
-                7 => EventPayload::BeatClock,
-                8 => EventPayload::BeatTempo,
-                9 => EventPayload::BeatNum,
-                10 => EventPayload::Repeat,
-                11 => EventPayload::Last,
-                12 => EventPayload::SetVoice(VoiceIdx(value.try_into().unwrap())),
-                13 => EventPayload::SetGroup(GroupIdx(value.try_into().unwrap())),
-                14 => EventPayload::Tuning(bytemuck::cast(value)),
-                15 => EventPayload::PanTime(PanTime(value.try_into().unwrap())),
-                _ => return Err(ProjectReadError::InvalidData),
-            };
+            let payload = payload_from_raw(kind, value)?;
             absolute += clock;
             eves.push(Event {
                 payload,
@@ -85,14 +86,78 @@ impl EveList {
         })
     }
 
+    /// Read the events of one unit and kind, stored in an `evenUNIT` (V4) or
+    /// `EVENT===` (V1) chunk.
+    ///
+    /// These old formats store a separate list per unit and kind, so the events are
+    /// appended unsorted; call [`Self::finish_old_read`] after all chunks are read.
+    ///
+    /// In V1, the ticks of the events following a note or portamento are relative to
+    /// the end of it (`tail_absolute`).
+    pub(crate) fn read_old_unit_events(
+        &mut self,
+        rd: &mut crate::io::Reader,
+        tail_absolute: bool,
+        check_rrr: bool,
+    ) -> ReadResult {
+        let _size = rd.next::<u32>()?;
+        let unit_index = rd.next::<u16>()?;
+        let kind = rd.next::<u16>()?;
+        let data_num = rd.next::<u16>()?;
+        let rrr = rd.next::<u16>()?;
+        let eve_num = rd.next::<u32>()?;
+        if data_num != 2 || kind > 15 || (check_rrr && rrr != 0) {
+            return Err(ProjectReadError::FmtUnknown);
+        }
+        let unit = UnitIdx(unit_index.try_into().map_err(|_| ProjectReadError::FmtUnknown)?);
+        #[expect(clippy::cast_possible_truncation)]
+        let kind = kind as u8;
+        let mut absolute: u32 = 0;
+        for _ in 0..eve_num {
+            let clock = rd.next_varint()?;
+            let value = rd.next_varint()?;
+            absolute = absolute.wrapping_add(clock);
+            let payload = payload_from_raw(kind, value)?;
+            self.eves.push(Event {
+                payload,
+                unit,
+                tick: absolute,
+            });
+            if tail_absolute && event_duration(payload).is_some() {
+                absolute = absolute.wrapping_add(value);
+            }
+        }
+        Ok(())
+    }
+    /// Sort the events read by [`Self::read_old_unit_events`].
+    ///
+    /// Like in PxTone, an event replaces an earlier one of the same unit and kind on
+    /// the same tick.
+    pub(crate) fn finish_old_read(&mut self) {
+        self.sort();
+        self.eves.dedup_by(|later, earlier| {
+            let same = later.tick == earlier.tick
+                && later.unit == earlier.unit
+                && std::mem::discriminant(&later.payload)
+                    == std::mem::discriminant(&earlier.payload);
+            if same {
+                *earlier = *later;
+            }
+            same
+        });
+    }
     pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        self.write_filtered(out, |_| true);
+    }
+    /// Write only the events for which `keep` returns true
+    pub(crate) fn write_filtered(&self, out: &mut Vec<u8>, keep: impl Fn(&Event) -> bool) {
         out.extend_from_slice(&self.ser_size.to_le_bytes());
         // Write dummy len
         let eve_num_offset = out.len();
         out.extend_from_slice(&[0u8; 4]);
         let mut eve_num: u32 = 0;
         let mut absolute: u32 = 0;
-        for eve in &self.eves {
+        for eve in self.eves.iter().filter(|eve| keep(eve)) {
             if let EventPayload::PtcowDebug(_) = eve.payload {
                 // We ignore debug events
                 continue;
@@ -104,7 +169,7 @@ impl EveList {
             let (kind, value): (u8, u32) = match &eve.payload {
                 EventPayload::Null => (0, 0),
                 EventPayload::On { duration } => (1, *duration),
-                EventPayload::Key(k) => (2, k.cast_unsigned()),
+                EventPayload::Key(k) => (2, k.0.cast_unsigned()),
                 EventPayload::PanVol(vol) => (3, u32::from(*vol)),
                 EventPayload::Velocity(vel) => (4, vel.cast_unsigned().into()),
                 EventPayload::Volume(vol) => (5, vol.cast_unsigned().into()),
@@ -116,8 +181,20 @@ impl EveList {
                 EventPayload::Last => (11, 0),
                 EventPayload::SetVoice(n) => (12, u32::from(n.0)),
                 EventPayload::SetGroup(g) => (13, u32::from(g.0)),
-                EventPayload::Tuning(t) => (14, t.to_bits()),
+                EventPayload::Tuning(t) => (14, t.get().to_bits()),
                 EventPayload::PanTime(t) => (15, u32::from(t.0)),
+                EventPayload::PitchBend(bend) => {
+                    (EXT_KIND_PITCH_BEND, i32::from(*bend).cast_unsigned())
+                }
+                EventPayload::PitchBendRange(range) => {
+                    (EXT_KIND_PITCH_BEND_RANGE, range.0.cast_unsigned())
+                }
+                EventPayload::VibratoRate(rate) => (EXT_KIND_VIBRATO_RATE, rate.to_bits()),
+                EventPayload::VibratoDepth(depth) => {
+                    (EXT_KIND_VIBRATO_DEPTH, depth.0.cast_unsigned())
+                }
+                EventPayload::Tremolo(params) => (EXT_KIND_TREMOLO, params.to_bits()),
+                EventPayload::AutoPan(params) => (EXT_KIND_AUTO_PAN, params.to_bits()),
                 EventPayload::PtcowDebug(_) => {
                     // We ignore debug events
                     continue;
@@ -129,10 +206,348 @@ impl EveList {
         }
         out[eve_num_offset..eve_num_offset + 4].copy_from_slice(&eve_num.to_le_bytes());
     }
+    /// The number of bytes the events take up in a serialized project, including the
+    /// chunk header.
+    ///
+    /// Events are stored as tick deltas and values with variable length encoding, so
+    /// this depends on the values of the events, not just on how many there are.
+    #[must_use]
+    pub fn serialized_size_estimate(&self) -> usize {
+        let mut out = Vec::new();
+        self.write(&mut out);
+        // Tag code
+        8 + out.len()
+    }
     /// Sort the events by their tick values, to ensure correct playback.
+    ///
+    /// Events on the same tick are ordered by their kind, the same way PxTone orders them.
+    /// This matters for playback, for example a [`SetVoice`](EventPayload::SetVoice) resets
+    /// the key of the unit, so it needs to come before [`Key`](EventPayload::Key),
+    /// and a [`Key`](EventPayload::Key) needs to come before [`On`](EventPayload::On) to
+    /// start the note at the new key, instead of sliding to it.
+    ///
+    /// The sort is stable, events of the same kind on the same tick keep their order.
     pub fn sort(&mut self) {
-        self.eves.sort_by_key(|eve| eve.tick);
+        self.eves.sort_by_key(|eve| (eve.tick, eve.payload.priority()));
+    }
+    /// Merge the events of `other` into this list, resolving conflicts according to `policy`.
+    ///
+    /// The result is sorted (see [`Self::sort`]). Of two events of the same kind on the same
+    /// tick, the one from this list comes first, so when both are kept, the one from `other`
+    /// takes effect.
+    pub fn merge(&mut self, other: &Self, policy: MergePolicy) {
+        // Events of the same kind for the same unit on the same tick
+        let slot = |eve: &Event| (eve.tick, eve.unit, std::mem::discriminant(&eve.payload));
+        let mut slots: HashMap<_, Vec<EventPayload>> = HashMap::new();
+        let preferred = if policy == MergePolicy::PreferOther {
+            other
+        } else {
+            &*self
+        };
+        for eve in &preferred.eves {
+            slots.entry(slot(eve)).or_default().push(eve.payload);
+        }
+        let conflicts = |eve: &Event| {
+            slots.get(&slot(eve)).is_some_and(|payloads| match policy {
+                MergePolicy::KeepBoth => false,
+                MergePolicy::DropDuplicates => payloads.contains(&eve.payload),
+                MergePolicy::PreferSelf | MergePolicy::PreferOther => true,
+            })
+        };
+        if policy == MergePolicy::PreferOther {
+            self.eves.retain(|eve| !conflicts(eve));
+            self.eves.extend_from_slice(&other.eves);
+        } else {
+            let kept: Vec<Event> =
+                other.eves.iter().filter(|eve| !conflicts(eve)).copied().collect();
+            self.eves.extend(kept);
+        }
+        self.sort();
+    }
+    /// Make `unit` start the song in `group`, by setting its
+    /// [`SetGroup`](EventPayload::SetGroup) event at tick 0.
+    ///
+    /// The event is added if the unit doesn't have one, and extra `SetGroup` events of the
+    /// unit at tick 0 are removed. Later `SetGroup` events of the unit are left alone.
+    pub fn set_initial_group(&mut self, unit: UnitIdx, group: GroupIdx) {
+        let is_initial = |eve: &Event| {
+            eve.tick == 0 && eve.unit == unit && matches!(eve.payload, EventPayload::SetGroup(_))
+        };
+        let mut found = false;
+        self.eves.retain_mut(|eve| {
+            if !is_initial(eve) {
+                return true;
+            }
+            if found {
+                return false;
+            }
+            found = true;
+            eve.payload = EventPayload::SetGroup(group);
+            true
+        });
+        if !found {
+            self.eves.push(Event {
+                payload: EventPayload::SetGroup(group),
+                unit,
+                tick: 0,
+            });
+            self.sort();
+        }
+    }
+    /// Change the unit of every event to what `map` returns, for when units are removed or
+    /// reordered.
+    ///
+    /// Events of units `map` returns `None` for are removed.
+    /// Events keep their order, so if units of events on the same tick swapped places,
+    /// the list stays sorted.
+    pub fn remap_units(&mut self, mut map: impl FnMut(UnitIdx) -> Option<UnitIdx>) {
+        self.eves.retain_mut(|eve| map(eve.unit).map(|unit| eve.unit = unit).is_some());
+    }
+    /// Change the voice of every [`SetVoice`](EventPayload::SetVoice) event to what `map`
+    /// returns, for when voices are removed or reordered.
+    ///
+    /// `SetVoice` events of voices `map` returns `None` for are removed.
+    pub fn remap_voices(&mut self, mut map: impl FnMut(VoiceIdx) -> Option<VoiceIdx>) {
+        self.eves.retain_mut(|eve| match &mut eve.payload {
+            EventPayload::SetVoice(voice) => map(*voice).map(|new| *voice = new).is_some(),
+            _ => true,
+        });
+    }
+    /// Move the notes starting in `range` towards the nearest multiple of `grid`.
+    ///
+    /// `strength` is how far they move, from `0.0` (not at all) to `1.0` (all the way).
+    /// The end of each note is quantized the same way, so its duration changes with it,
+    /// but a note is never shorter than a tick.
+    /// [`Key`](EventPayload::Key) and [`Velocity`](EventPayload::Velocity) events of the unit
+    /// on the same tick as a note move along with it.
+    ///
+    /// The result is sorted (see [`Self::sort`]).
+    pub fn quantize(&mut self, range: Range<Tick>, grid: Tick, strength: f32) {
+        if grid == 0 {
+            return;
+        }
+        let strength = strength.clamp(0.0, 1.0);
+        #[expect(clippy::cast_possible_truncation)]
+        let snap = |tick: Tick| {
+            let target = tick.saturating_add(grid / 2) / grid * grid;
+            let offset = (f64::from(target) - f64::from(tick)) * f64::from(strength);
+            i64::from(tick) + offset.round() as i64
+        };
+        self.move_notes(&range, |tick, end| (snap(tick), snap(end)));
+    }
+    /// Randomly move the start and the end of the notes starting in `range` by up to `jitter`
+    /// ticks, to make them sound less mechanical.
+    ///
+    /// The same `seed` always moves the notes the same way.
+    /// See [`Song::humanize`](crate::Song::humanize) for using [`Song::seed`](crate::Song::seed).
+    /// Notes are never moved before tick 0, or made shorter than a tick.
+    /// [`Key`](EventPayload::Key) and [`Velocity`](EventPayload::Velocity) events of the unit
+    /// on the same tick as a note move along with it.
+    ///
+    /// The result is sorted (see [`Self::sort`]).
+    pub fn humanize(&mut self, range: Range<Tick>, jitter: Tick, seed: u64) {
+        let mut state = seed;
+        let span = u64::from(jitter) * 2 + 1;
+        #[expect(clippy::cast_possible_wrap, reason = "`span` is at most 2^33")]
+        let mut offset = move || (splitmix64(&mut state) % span) as i64 - i64::from(jitter);
+        self.move_notes(&range, |tick, end| {
+            (i64::from(tick) + offset(), i64::from(end) + offset())
+        });
+    }
+    /// Transpose the notes of `units` in `range` by `semitones`.
+    ///
+    /// The [`Key`](EventPayload::Key) events in `range` are shifted. Notes in `range` that
+    /// play at a key set before `range` (or at [`DEFAULT_KEY`], after a
+    /// [`SetVoice`](EventPayload::SetVoice)) get a new `Key` event, and so do the notes after
+    /// `range` that would play at a transposed key, so the song stays the same outside of
+    /// `range`.
+    ///
+    /// Keys are clamped to `0..=i32::MAX`, the range that can be serialized.
+    /// The list needs to be [sorted](Self::sort), and the result is sorted.
+    pub fn transpose(&mut self, units: &[UnitIdx], range: Range<Tick>, semitones: i32) {
+        let shift = Key::semitones(semitones);
+        for &unit in units {
+            self.map_unit_values(
+                unit,
+                &range,
+                DEFAULT_KEY,
+                |payload| match payload {
+                    EventPayload::Key(key) => Some(*key),
+                    EventPayload::SetVoice(_) => Some(DEFAULT_KEY),
+                    _ => None,
+                },
+                EventPayload::Key,
+                |key| Key(key.0.saturating_add(shift.0).max(0)),
+            );
+        }
+        self.sort();
+    }
+    /// Multiply the velocity of the notes in `range` by `factor`.
+    ///
+    /// Like [`Self::transpose`], notes in `range` that play at a velocity set before
+    /// `range` get a new [`Velocity`](EventPayload::Velocity) event, and the velocity is
+    /// restored after `range`.
+    ///
+    /// Velocities are clamped to `0..=i16::MAX`, the range that can be serialized.
+    /// The list needs to be [sorted](Self::sort), and the result is sorted.
+    pub fn scale_velocity(&mut self, range: Range<Tick>, factor: f32) {
+        let mut units: Vec<UnitIdx> = self.eves.iter().map(|eve| eve.unit).collect();
+        units.sort_unstable_by_key(|unit| unit.0);
+        units.dedup();
+        #[expect(clippy::cast_possible_truncation)]
+        let scale =
+            |vel: i16| (f32::from(vel) * factor).round().clamp(0.0, f32::from(i16::MAX)) as i16;
+        for unit in units {
+            self.map_unit_values(
+                unit,
+                &range,
+                DEFAULT_VELOCITY.cast_signed(),
+                |payload| match payload {
+                    EventPayload::Velocity(vel) => Some(*vel),
+                    _ => None,
+                },
+                EventPayload::Velocity,
+                scale,
+            );
+        }
+        self.sort();
     }
+    /// Apply `map` to a value of `unit` (like its key) in `range`.
+    ///
+    /// `value_of` returns the value an event sets, `to_event` makes an event that sets it.
+    /// Events that set the value in `range` are mapped. Notes that play at a value set
+    /// outside of `range` (or by an event that isn't mapped) get a new event.
+    /// Expects the list to be sorted, and leaves it unsorted.
+    fn map_unit_values<T: Copy>(
+        &mut self,
+        unit: UnitIdx,
+        range: &Range<Tick>,
+        default: T,
+        value_of: impl Fn(&EventPayload) -> Option<T>,
+        to_event: impl Fn(T) -> EventPayload,
+        map: impl Fn(T) -> T,
+    ) {
+        // The unmapped value in effect
+        let mut value = default;
+        // Where the mapped value needs to be set before the next note, if it isn't set yet
+        let mut pending = None;
+        // Whether the value in effect is mapped
+        let mut mapped = false;
+        let mut inserted = Vec::new();
+        let insert = |tick, value| Event {
+            payload: to_event(value),
+            unit,
+            tick,
+        };
+        // Events on the same tick take effect together, no matter their order
+        let (mut set_on, mut reset_on) = (HashSet::new(), HashSet::new());
+        for eve in self.eves.iter().filter(|eve| eve.unit == unit) {
+            match value_of(&eve.payload) {
+                Some(value) if to_event(value) == eve.payload => set_on.insert(eve.tick),
+                Some(_) => reset_on.insert(eve.tick),
+                None => false,
+            };
+        }
+        for eve in self.eves.iter_mut().filter(|eve| eve.unit == unit) {
+            if eve.tick >= range.end {
+                // Restore the value, unless it's set again right away
+                if mapped && !set_on.contains(&eve.tick) && !reset_on.contains(&eve.tick) {
+                    inserted.push(insert(range.end, value));
+                }
+                break;
+            }
+            let in_range = eve.tick >= range.start;
+            if in_range && pending.is_none() && !mapped {
+                pending = Some(range.start);
+            }
+            if let Some(new) = value_of(&eve.payload) {
+                value = new;
+                if !in_range {
+                    continue;
+                }
+                if to_event(new) == eve.payload {
+                    eve.payload = to_event(map(new));
+                    pending = None;
+                    mapped = true;
+                } else {
+                    // Resets the value, like `SetVoice` does with the key
+                    pending = Some(eve.tick);
+                    mapped = false;
+                }
+            } else if in_range
+                && matches!(
+                    eve.payload,
+                    EventPayload::On { .. } | EventPayload::Portament { .. }
+                )
+                && !set_on.contains(&eve.tick)
+                && let Some(tick) = pending.take()
+            {
+                inserted.push(insert(tick, map(value)));
+                mapped = true;
+            }
+        }
+        self.eves.extend(inserted);
+    }
+    /// Move the [`On`](EventPayload::On) events starting in `range`, and their companion
+    /// events, to the start and end ticks returned by `f`.
+    ///
+    /// Notes of the same unit on the same tick are moved by the same amount.
+    fn move_notes(&mut self, range: &Range<Tick>, mut f: impl FnMut(Tick, Tick) -> (i64, i64)) {
+        let clamp = |tick: i64| Tick::try_from(tick.max(0)).unwrap_or(Tick::MAX);
+        // The start tick and the shift of the end tick of the notes, by unit and start tick
+        let mut moves: HashMap<(UnitIdx, Tick), (Tick, i64)> = HashMap::new();
+        for eve in &self.eves {
+            if let EventPayload::On { duration } = eve.payload
+                && range.contains(&eve.tick)
+            {
+                moves.entry((eve.unit, eve.tick)).or_insert_with(|| {
+                    let end = eve.tick.saturating_add(duration);
+                    let (start, new_end) = f(eve.tick, end);
+                    (clamp(start), new_end - i64::from(end))
+                });
+            }
+        }
+        for eve in &mut self.eves {
+            let Some(&(start, end_shift)) = moves.get(&(eve.unit, eve.tick)) else {
+                continue;
+            };
+            match &mut eve.payload {
+                EventPayload::On { duration } => {
+                    let end = clamp(i64::from(eve.tick.saturating_add(*duration)) + end_shift);
+                    *duration = end.saturating_sub(start).max(1);
+                }
+                EventPayload::Key(_) | EventPayload::Velocity(_) => {}
+                _ => continue,
+            }
+            eve.tick = start;
+        }
+        self.sort();
+    }
+}
+
+/// Next number of the `SplitMix64` generator
+const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// How [`EveList::merge`] resolves events of the two lists that are of the same kind, for the
+/// same unit, on the same tick
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep all events of both lists
+    KeepBoth,
+    /// Drop the events of the other list that are exactly the same as an event of this list,
+    /// like the same [`Key`](EventPayload::Key) being set twice
+    DropDuplicates,
+    /// Drop the conflicting events of the other list, so this list's
+    /// [`SetVoice`](EventPayload::SetVoice), [`Volume`](EventPayload::Volume), etc. win
+    PreferSelf,
+    /// Drop the conflicting events of this list
+    PreferOther,
 }
 
 impl std::ops::Deref for EveList {
@@ -149,6 +564,52 @@ impl std::ops::DerefMut for EveList {
     }
 }
 
+/// Convert the kind and value of a serialized event to a payload
+fn payload_from_raw(kind: u8, value: u32) -> ReadResult<EventPayload> {
+    let invalid = |_| ProjectReadError::InvalidData;
+    let payload = match kind {
+        0 => EventPayload::Null,
+        1 => EventPayload::On { duration: value },
+        // Keys are written as the bits of an `i32`
+        2 => EventPayload::Key(Key(value.cast_signed())),
+        3 => EventPayload::PanVol(value.try_into().map_err(invalid)?),
+        4 => EventPayload::Velocity(value.cast_signed().try_into().map_err(invalid)?),
+        5 => EventPayload::Volume(value.cast_signed().try_into().map_err(invalid)?),
+        6 => EventPayload::Portament { duration: value },
+        7 => EventPayload::BeatClock,
+        8 => EventPayload::BeatTempo,
+        9 => EventPayload::BeatNum,
+        10 => EventPayload::Repeat,
+        11 => EventPayload::Last,
+        12 => EventPayload::SetVoice(VoiceIdx(value.try_into().map_err(invalid)?)),
+        13 => EventPayload::SetGroup(GroupIdx(value.try_into().map_err(invalid)?)),
+        14 => EventPayload::Tuning(Tuning::from_raw(f32::from_bits(value))),
+        15 => EventPayload::PanTime(PanTime(value.try_into().map_err(invalid)?)),
+        EXT_KIND_PITCH_BEND => {
+            let max = EventPayload::PITCH_BEND_MAX;
+            let bend: i16 = value.cast_signed().try_into().map_err(invalid)?;
+            if !(-max..=max).contains(&bend) {
+                return Err(ProjectReadError::InvalidData);
+            }
+            EventPayload::PitchBend(bend)
+        }
+        EXT_KIND_PITCH_BEND_RANGE => {
+            let max = EventPayload::PITCH_BEND_RANGE_MAX.0;
+            EventPayload::PitchBendRange(Key(value.cast_signed().clamp(-max, max)))
+        }
+        EXT_KIND_VIBRATO_RATE => EventPayload::VibratoRate(f32::from_bits(value)),
+        EXT_KIND_VIBRATO_DEPTH => EventPayload::VibratoDepth(Key(value.cast_signed())),
+        EXT_KIND_TREMOLO => {
+            EventPayload::Tremolo(LfoParams::from_bits(value).ok_or(ProjectReadError::InvalidData)?)
+        }
+        EXT_KIND_AUTO_PAN => {
+            EventPayload::AutoPan(LfoParams::from_bits(value).ok_or(ProjectReadError::InvalidData)?)
+        }
+        _ => return Err(ProjectReadError::InvalidData),
+    };
+    Ok(payload)
+}
+
 const fn event_duration(payload: EventPayload) -> Option<u32> {
     match payload {
         EventPayload::On { duration } | EventPayload::Portament { duration } => Some(duration),
@@ -159,9 +620,21 @@ const fn event_duration(payload: EventPayload) -> Option<u32> {
 pub const DEFAULT_VOLUME: u16 = 104;
 pub const DEFAULT_VELOCITY: u16 = 104;
 /// The default [`Key`] units start out with
-pub const DEFAULT_KEY: Key = 24576;
-pub const DEFAULT_BASICKEY: u32 = 17664;
+pub const DEFAULT_KEY: Key = Key(24576);
 pub const DEFAULT_TUNING: f32 = 1.0;
+/// The default [`PitchBendRange`](EventPayload::PitchBendRange) units start out with (2 semitones)
+pub const DEFAULT_PITCH_BEND_RANGE: Key = Key(512);
+
+// Event kinds that only ptcow understands.
+//
+// PxTone itself only defines kinds `0..16`, so we start the extension kinds at a
+// comfortable distance from those, in case PxTone ever adds new kinds.
+const EXT_KIND_PITCH_BEND: u8 = 0x40;
+const EXT_KIND_PITCH_BEND_RANGE: u8 = 0x41;
+const EXT_KIND_VIBRATO_RATE: u8 = 0x42;
+const EXT_KIND_VIBRATO_DEPTH: u8 = 0x43;
+const EXT_KIND_TREMOLO: u8 = 0x44;
+const EXT_KIND_AUTO_PAN: u8 = 0x45;
 
 /// Payload of an event
 #[repr(u8)]
@@ -205,12 +678,30 @@ pub enum EventPayload {
     /// Set the group index of the target unit
     SetGroup(GroupIdx),
     /// Set the [`tuning`](crate::Unit::tuning) property of the target unit
-    Tuning(f32),
+    Tuning(Tuning),
     /// Set the [`PanTime`] for the target unit
     PanTime(PanTime),
     /// This event is ignored during playback, but you can insert it into the event stream for
     /// debugging purposes, because it can show in a GUI event viewer for example.
     PtcowDebug(i32),
+    /// (ptcow extension) Bend the pitch of the target unit.
+    ///
+    /// Ranges from `-8192` to `8192`, where the extremes correspond to bending down/up
+    /// by the unit's [`PitchBendRange`](Self::PitchBendRange). 0 means no bend.
+    PitchBend(i16),
+    /// (ptcow extension) Set how far a [`PitchBend`](Self::PitchBend) can bend the pitch, in [`Key`]
+    /// units.
+    PitchBendRange(Key),
+    /// (ptcow extension) Set the rate of the vibrato LFO of the target unit, in Hz
+    VibratoRate(f32),
+    /// (ptcow extension) Set the depth of the vibrato LFO of the target unit, in [`Key`] units.
+    ///
+    /// 0 turns the vibrato off.
+    VibratoDepth(Key),
+    /// (ptcow extension) Set the [`tremolo`](crate::Unit::tremolo) LFO of the target unit
+    Tremolo(LfoParams),
+    /// (ptcow extension) Set the [`auto_pan`](crate::Unit::auto_pan) LFO of the target unit
+    AutoPan(LfoParams),
 }
 
 impl EventPayload {
@@ -219,16 +710,63 @@ impl EventPayload {
     pub const fn discriminant(&self) -> u8 {
         unsafe { *std::ptr::from_ref(self).cast() }
     }
+    /// Maximum magnitude of a [`PitchBend`](Self::PitchBend) value
+    pub const PITCH_BEND_MAX: i16 = 8192;
+    /// Maximum magnitude of a [`PitchBendRange`](Self::PitchBendRange) read from a file
+    /// (4 octaves). Larger ranges are clamped to it.
+    pub const PITCH_BEND_RANGE_MAX: Key = Key(Key::OCTAVE.0 * 4);
+    /// Whether this event can only be understood by ptcow, and not by PxTone itself.
+    ///
+    /// Extension events are serialized in an extension chunk after the end of the project,
+    /// where PxTone doesn't look, so PxTone plays the song without them.
+    #[must_use]
+    pub const fn is_extension(&self) -> bool {
+        matches!(
+            self,
+            Self::PitchBend(_)
+                | Self::PitchBendRange(_)
+                | Self::VibratoRate(_)
+                | Self::VibratoDepth(_)
+                | Self::Tremolo(_)
+                | Self::AutoPan(_)
+        )
+    }
+    /// The order of this event relative to other events on the same tick.
+    ///
+    /// Lower values play first. Taken from PxTone's event list.
+    const fn priority(self) -> u8 {
+        match self {
+            Self::Null
+            | Self::BeatClock
+            | Self::BeatTempo
+            | Self::BeatNum
+            | Self::Repeat
+            | Self::PtcowDebug(_) => 0,
+            Self::SetVoice(_) => 10,
+            Self::SetGroup(_) => 20,
+            Self::Portament { .. } => 30,
+            Self::Key(_) => 40,
+            Self::On { .. } => 50,
+            Self::PanVol(_) => 60,
+            Self::Velocity(_) => 70,
+            Self::Volume(_) => 80,
+            Self::Tuning(_) => 90,
+            Self::PanTime(_) => 100,
+            // Not known to PxTone, they play after everything else
+            Self::PitchBend(_)
+            | Self::PitchBendRange(_)
+            | Self::VibratoRate(_)
+            | Self::VibratoDepth(_)
+            | Self::Tremolo(_)
+            | Self::AutoPan(_) => 110,
+            Self::Last => 255,
+        }
+    }
 }
 
 // We probably don't want the event payload to get too big.
 const _: () = assert!(size_of::<EventPayload>() == 8);
 
-/// 1/256 of a semitone.
-///
-/// A semitone is the smallest distance between keys on a piano.
-pub type Key = i32;
-
 /// Song event
 #[derive(Copy, Clone)]
 pub struct Event {
@@ -239,3 +777,245 @@ pub struct Event {
     /// The clock tick the event place takes at
     pub tick: Tick,
 }
+
+#[test]
+fn test_sort_same_tick() {
+    let eve = |tick, payload| Event {
+        payload,
+        unit: UnitIdx(0),
+        tick,
+    };
+    let mut list = EveList {
+        eves: vec![
+            eve(10, EventPayload::On { duration: 10 }),
+            eve(0, EventPayload::On { duration: 10 }),
+            eve(0, EventPayload::Key(DEFAULT_KEY)),
+            eve(0, EventPayload::Portament { duration: 5 }),
+            eve(0, EventPayload::SetVoice(VoiceIdx(1))),
+        ],
+        ser_size: 0,
+    };
+    list.sort();
+    let payloads: Vec<_> = list.eves.iter().map(|eve| eve.payload).collect();
+    assert_eq!(
+        payloads,
+        [
+            EventPayload::SetVoice(VoiceIdx(1)),
+            EventPayload::Portament { duration: 5 },
+            EventPayload::Key(DEFAULT_KEY),
+            EventPayload::On { duration: 10 },
+            EventPayload::On { duration: 10 },
+        ]
+    );
+}
+
+#[test]
+fn test_merge() {
+    let eve = |unit, tick, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick,
+    };
+    let melody = EveList {
+        eves: vec![
+            eve(0, 0, EventPayload::SetVoice(VoiceIdx(1))),
+            eve(0, 0, EventPayload::Key(DEFAULT_KEY)),
+            eve(0, 0, EventPayload::On { duration: 480 }),
+        ],
+        ser_size: 0,
+    };
+    let generated = EveList {
+        eves: vec![
+            eve(0, 480, EventPayload::On { duration: 480 }),
+            eve(0, 0, EventPayload::Key(DEFAULT_KEY)),
+            eve(0, 0, EventPayload::SetVoice(VoiceIdx(2))),
+            eve(1, 0, EventPayload::SetVoice(VoiceIdx(2))),
+        ],
+        ser_size: 0,
+    };
+    let merged = |policy| {
+        let mut list = EveList {
+            eves: melody.eves.clone(),
+            ser_size: 0,
+        };
+        list.merge(&generated, policy);
+        list.eves
+            .iter()
+            .map(|eve| (eve.unit.0, eve.tick, eve.payload))
+            .collect::<Vec<_>>()
+    };
+    let voice = |unit, idx| (unit, 0, EventPayload::SetVoice(VoiceIdx(idx)));
+    let key = (0, 0, EventPayload::Key(DEFAULT_KEY));
+    let on = |tick| (0, tick, EventPayload::On { duration: 480 });
+    assert_eq!(
+        merged(MergePolicy::KeepBoth),
+        [
+            voice(0, 1),
+            voice(0, 2),
+            voice(1, 2),
+            key,
+            key,
+            on(0),
+            on(480)
+        ]
+    );
+    assert_eq!(
+        merged(MergePolicy::DropDuplicates),
+        [voice(0, 1), voice(0, 2), voice(1, 2), key, on(0), on(480)]
+    );
+    assert_eq!(
+        merged(MergePolicy::PreferSelf),
+        [voice(0, 1), voice(1, 2), key, on(0), on(480)]
+    );
+    assert_eq!(
+        merged(MergePolicy::PreferOther),
+        [voice(0, 2), voice(1, 2), key, on(0), on(480)]
+    );
+}
+
+#[test]
+fn test_quantize_humanize() {
+    let eve = |unit, tick, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick,
+    };
+    let notes = || EveList {
+        eves: vec![
+            eve(0, 0, EventPayload::SetVoice(VoiceIdx(1))),
+            eve(0, 110, EventPayload::Key(DEFAULT_KEY)),
+            eve(0, 110, EventPayload::On { duration: 350 }),
+            eve(1, 110, EventPayload::On { duration: 100 }),
+            eve(0, 110, EventPayload::Velocity(80)),
+            eve(0, 530, EventPayload::On { duration: 10 }),
+        ],
+        ser_size: 0,
+    };
+    let ticks = |list: &EveList| {
+        list.eves
+            .iter()
+            .map(|eve| (eve.unit.0, eve.tick, event_duration(eve.payload)))
+            .collect::<Vec<_>>()
+    };
+
+    let mut list = notes();
+    list.quantize(0..500, 120, 1.0);
+    assert_eq!(
+        ticks(&list),
+        [
+            (0, 0, None),
+            (0, 120, None),
+            (0, 120, Some(360)),
+            (1, 120, Some(120)),
+            (0, 120, None),
+            (0, 530, Some(10)),
+        ]
+    );
+    let mut list = notes();
+    list.quantize(0..1000, 120, 0.5);
+    assert_eq!(
+        ticks(&list)[2..],
+        [
+            (0, 115, Some(355)),
+            (1, 115, Some(110)),
+            (0, 115, None),
+            (0, 505, Some(65))
+        ]
+    );
+
+    let mut list = notes();
+    list.humanize(100..200, 5, 1);
+    let humanized = ticks(&list);
+    let note = |unit| humanized[1..5].iter().find(|eve| eve.0 == unit && eve.2.is_some());
+    for (unit, old_dur) in [(0, 350), (1, 100)] {
+        let &(_, tick, Some(dur)) = note(unit).unwrap() else {
+            unreachable!()
+        };
+        assert!(tick.abs_diff(110) <= 5);
+        assert!((tick + dur).abs_diff(110 + old_dur) <= 5);
+    }
+    // Companion events stay on the tick of their note
+    for &(unit, tick, _) in &humanized[1..5] {
+        assert!(unit == 1 || tick == note(0).unwrap().1);
+    }
+    assert_eq!(humanized[5], (0, 530, Some(10)));
+    let mut again = notes();
+    again.humanize(100..200, 5, 1);
+    assert_eq!(ticks(&again), humanized);
+}
+
+#[test]
+fn test_transpose_scale_velocity() {
+    let eve = |unit, tick, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick,
+    };
+    let on = EventPayload::On { duration: 100 };
+    let key = |semitones| EventPayload::Key(DEFAULT_KEY + Key::semitones(semitones));
+    let mut list = EveList {
+        eves: vec![
+            eve(0, 0, key(2)),
+            eve(0, 0, on),
+            eve(1, 0, on),
+            eve(0, 100, on),
+            eve(0, 200, key(4)),
+            eve(0, 200, on),
+            eve(0, 300, EventPayload::SetVoice(VoiceIdx(1))),
+            eve(0, 300, on),
+            eve(0, 400, on),
+            eve(1, 400, EventPayload::Velocity(100)),
+            eve(1, 400, on),
+        ],
+        ser_size: 0,
+    };
+    list.transpose(&[UnitIdx(0)], 100..400, 12);
+    let keys: Vec<_> = list
+        .eves
+        .iter()
+        .filter_map(|eve| match eve.payload {
+            EventPayload::Key(key) => Some((eve.unit.0, eve.tick, (key - DEFAULT_KEY).0 / 256)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        keys,
+        [
+            (0, 0, 2),
+            (0, 100, 14),
+            (0, 200, 16),
+            (0, 300, 12),
+            (0, 400, 0)
+        ]
+    );
+    // Keys that can't be serialized are clamped
+    list.transpose(&[UnitIdx(0)], 0..1, -1000);
+    assert_eq!(list.eves[0].payload, EventPayload::Key(Key(0)));
+
+    list.scale_velocity(0..400, 0.5);
+    let vels: Vec<_> = list
+        .eves
+        .iter()
+        .filter_map(|eve| match eve.payload {
+            EventPayload::Velocity(vel) => Some((eve.unit.0, eve.tick, vel)),
+            _ => None,
+        })
+        .collect();
+    // Unit 0 plays its whole song at half the velocity, unit 1 only up to tick 400
+    assert_eq!(vels, [(0, 0, 52), (1, 0, 52), (1, 400, 100), (0, 400, 104)]);
+}
+
+#[test]
+fn test_pitch_bend_from_raw() {
+    let max = EventPayload::PITCH_BEND_MAX;
+    let bend = |value: i32| payload_from_raw(EXT_KIND_PITCH_BEND, value.cast_unsigned());
+    assert!(matches!(bend(-i32::from(max)), Ok(EventPayload::PitchBend(b)) if b == -max));
+    for value in [i32::from(max) + 1, 40_000, -40_000, i32::MIN] {
+        assert_eq!(bend(value).err(), Some(ProjectReadError::InvalidData));
+    }
+    let range = payload_from_raw(EXT_KIND_PITCH_BEND_RANGE, i32::MAX.cast_unsigned());
+    assert!(matches!(
+        range,
+        Ok(EventPayload::PitchBendRange(range)) if range == EventPayload::PITCH_BEND_RANGE_MAX
+    ));
+}
@@ -0,0 +1,289 @@
+//! Copying and pasting regions of events
+
+use {
+    super::{EveList, Event, EventPayload},
+    crate::{ReadResult, UnitIdx, VoiceIdx, io::Reader, result::ProjectReadError, timing::Tick},
+    std::{collections::HashMap, mem::discriminant, ops::Range},
+};
+
+const MAGIC: &[u8; 8] = b"PTCOWCLP";
+
+/// A region of events, copied with [`EveList::extract`], to be [pasted](EveList::paste)
+/// into the same or another song.
+///
+/// The clip doesn't depend on the song it was copied from: the events refer to units and
+/// voices by their position in [`Self::units`] and [`Self::voices`], and their ticks are
+/// relative to the start of the region. It can be serialized with [`Self::to_bytes`],
+/// for example to put it on a clipboard.
+#[derive(Clone, Default)]
+pub struct EventClip {
+    /// The events, sorted, with ticks relative to the start of the clip.
+    ///
+    /// [`Event::unit`] is an index into [`Self::units`], and
+    /// [`SetVoice`](EventPayload::SetVoice) events hold an index into [`Self::voices`].
+    pub events: Vec<Event>,
+    /// The length of the clip in ticks
+    pub len: Tick,
+    /// The units the events were copied from
+    pub units: Vec<UnitIdx>,
+    /// The voices the [`SetVoice`](EventPayload::SetVoice) events referred to
+    pub voices: Vec<VoiceIdx>,
+}
+
+/// Where [`EveList::paste`] puts the units and voices of an [`EventClip`]
+#[derive(Clone, Default)]
+pub struct ClipMap {
+    /// The unit each unit of the clip is pasted into, by index.
+    ///
+    /// The events of clip units without an entry are left out.
+    pub units: Vec<UnitIdx>,
+    /// The voice each voice of the clip refers to after pasting, by index.
+    ///
+    /// [`SetVoice`](EventPayload::SetVoice) events of voices without an entry are left out.
+    pub voices: Vec<VoiceIdx>,
+}
+
+impl ClipMap {
+    /// Paste the clip into the units and voices it was copied from
+    #[must_use]
+    pub fn identity(clip: &EventClip) -> Self {
+        Self {
+            units: clip.units.clone(),
+            voices: clip.voices.clone(),
+        }
+    }
+}
+
+impl EveList {
+    /// Copy the events of `units` in `range` into an [`EventClip`].
+    ///
+    /// Notes that go past the end of `range` are cut short. The settings the units have
+    /// at the start of `range` (their voice, key, volume, etc.) are copied to the start of
+    /// the clip, so the clip sounds the same wherever it's pasted.
+    ///
+    /// The list needs to be [sorted](Self::sort).
+    #[must_use]
+    pub fn extract(&self, range: Range<Tick>, units: &[UnitIdx]) -> EventClip {
+        let mut clip = EventClip {
+            len: range.end.saturating_sub(range.start),
+            units: units.to_vec(),
+            ..EventClip::default()
+        };
+        let mut voice_slots = HashMap::new();
+        let mut slot_of = |voice: VoiceIdx| {
+            *voice_slots.entry(voice).or_insert_with(|| {
+                clip.voices.push(voice);
+                // There are at most 100 voices
+                #[expect(clippy::cast_possible_truncation)]
+                VoiceIdx((clip.voices.len() - 1) as u8)
+            })
+        };
+        for (slot, &unit) in units.iter().enumerate() {
+            let Ok(slot) = u8::try_from(slot).map(UnitIdx) else {
+                break;
+            };
+            // The last event of every kind before the region, in order
+            let mut state: Vec<EventPayload> = Vec::new();
+            for eve in self.eves.iter().filter(|eve| eve.unit == unit) {
+                if eve.tick >= range.end {
+                    break;
+                }
+                let mut payload = eve.payload;
+                if eve.tick < range.start {
+                    if is_setting(payload) {
+                        if let EventPayload::SetVoice(_) = payload {
+                            // Changing the voice resets the key
+                            state.retain(|p| !matches!(p, EventPayload::Key(_)));
+                        }
+                        state.retain(|p| discriminant(p) != discriminant(&payload));
+                        state.push(payload);
+                    }
+                    continue;
+                }
+                if let EventPayload::On { duration } | EventPayload::Portament { duration } =
+                    &mut payload
+                {
+                    *duration = (*duration).min(range.end - eve.tick);
+                }
+                clip.events.push(Event {
+                    payload,
+                    unit: slot,
+                    tick: eve.tick - range.start,
+                });
+            }
+            clip.events.extend(state.into_iter().map(|payload| Event {
+                payload,
+                unit: slot,
+                tick: 0,
+            }));
+        }
+        for eve in &mut clip.events {
+            if let EventPayload::SetVoice(voice) = &mut eve.payload {
+                *voice = slot_of(*voice);
+            }
+        }
+        let mut events = Self {
+            eves: clip.events,
+            ser_size: 0,
+        };
+        events.sort();
+        clip.events = events.eves;
+        clip
+    }
+    /// Paste `clip` at `at`, into the units and voices `map` says.
+    ///
+    /// The events of the clip are added on top of the events already in the region.
+    /// Use [`Self::merge`] on an [`EveList`] made from the clip for more control.
+    ///
+    /// The result is sorted (see [`Self::sort`]).
+    pub fn paste(&mut self, clip: &EventClip, at: Tick, map: &ClipMap) {
+        for eve in &clip.events {
+            let Some(&unit) = map.units.get(usize::from(eve.unit.0)) else {
+                continue;
+            };
+            let mut payload = eve.payload;
+            if let EventPayload::SetVoice(voice) = &mut payload {
+                let Some(&mapped) = map.voices.get(usize::from(voice.0)) else {
+                    continue;
+                };
+                *voice = mapped;
+            }
+            self.eves.push(Event {
+                payload,
+                unit,
+                tick: at.saturating_add(eve.tick),
+            });
+        }
+        self.sort();
+    }
+}
+
+/// Whether the event changes a setting of the unit that later notes play with
+const fn is_setting(payload: EventPayload) -> bool {
+    !matches!(
+        payload,
+        EventPayload::Null
+            | EventPayload::On { .. }
+            | EventPayload::Portament { .. }
+            | EventPayload::BeatClock
+            | EventPayload::BeatTempo
+            | EventPayload::BeatNum
+            | EventPayload::Repeat
+            | EventPayload::Last
+            | EventPayload::PtcowDebug(_)
+    )
+}
+
+impl EventClip {
+    /// Serialize the clip
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&self.len.to_le_bytes());
+        for indices in [
+            self.units.iter().map(|unit| unit.0).collect::<Vec<_>>(),
+            self.voices.iter().map(|voice| voice.0).collect(),
+        ] {
+            out.extend_from_slice(&u32::try_from(indices.len()).unwrap_or(0).to_le_bytes());
+            out.extend_from_slice(&indices);
+        }
+        EveList {
+            eves: self.events.clone(),
+            ser_size: 0,
+        }
+        .write(&mut out);
+        out
+    }
+    /// Deserialize a clip serialized with [`Self::to_bytes`]
+    pub fn from_bytes(data: &[u8]) -> ReadResult<Self> {
+        let mut rd = Reader { data, cur: 0 };
+        if rd.next::<[u8; 8]>()? != *MAGIC {
+            return Err(ProjectReadError::FmtUnknown);
+        }
+        let len = rd.next::<u32>()?;
+        let mut indices = [Vec::new(), Vec::new()];
+        for indices in &mut indices {
+            let count = rd.next::<u32>()?;
+            for _ in 0..count {
+                indices.push(rd.next::<u8>()?);
+            }
+        }
+        let [units, voices] = indices;
+        let events = EveList::read(&mut rd)?;
+        Ok(Self {
+            events: events.eves,
+            len,
+            units: units.into_iter().map(UnitIdx).collect(),
+            voices: voices.into_iter().map(VoiceIdx).collect(),
+        })
+    }
+}
+
+#[test]
+fn test_clip() {
+    use crate::{DEFAULT_KEY, Key};
+    let eve = |unit, tick, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick,
+    };
+    let mut list = EveList {
+        eves: vec![
+            eve(0, 0, EventPayload::SetVoice(VoiceIdx(3))),
+            eve(0, 0, EventPayload::Key(DEFAULT_KEY)),
+            eve(1, 0, EventPayload::Key(DEFAULT_KEY)),
+            eve(1, 0, EventPayload::SetVoice(VoiceIdx(5))),
+            eve(0, 0, EventPayload::On { duration: 100 }),
+            eve(0, 100, EventPayload::Volume(50)),
+            eve(0, 480, EventPayload::On { duration: 960 }),
+            eve(1, 480, EventPayload::On { duration: 10 }),
+            eve(0, 960, EventPayload::Key(DEFAULT_KEY + Key::OCTAVE)),
+            eve(0, 1000, EventPayload::On { duration: 10 }),
+        ],
+        ser_size: 0,
+    };
+    list.sort();
+    let clip = list.extract(480..960, &[UnitIdx(0), UnitIdx(1)]);
+    let clip = EventClip::from_bytes(&clip.to_bytes()).unwrap();
+    assert_eq!(clip.len, 480);
+    assert_eq!(clip.voices, [VoiceIdx(3), VoiceIdx(5)]);
+    let events: Vec<_> =
+        clip.events.iter().map(|eve| (eve.unit.0, eve.tick, eve.payload)).collect();
+    assert_eq!(
+        events,
+        [
+            (0, 0, EventPayload::SetVoice(VoiceIdx(0))),
+            (1, 0, EventPayload::SetVoice(VoiceIdx(1))),
+            (0, 0, EventPayload::Key(DEFAULT_KEY)),
+            (1, 0, EventPayload::Key(DEFAULT_KEY)),
+            (0, 0, EventPayload::On { duration: 480 }),
+            (1, 0, EventPayload::On { duration: 10 }),
+            (0, 0, EventPayload::Volume(50)),
+        ]
+    );
+
+    // Paste unit 0 of the clip into unit 2, with voice 3 replaced by voice 7
+    let map = ClipMap {
+        units: vec![UnitIdx(2)],
+        voices: vec![VoiceIdx(7)],
+    };
+    let len = list.eves.len();
+    list.paste(&clip, 2000, &map);
+    let pasted: Vec<_> = list
+        .eves
+        .iter()
+        .filter(|eve| eve.unit == UnitIdx(2))
+        .map(|eve| (eve.tick, eve.payload))
+        .collect();
+    assert_eq!(list.eves.len(), len + 4);
+    assert_eq!(
+        pasted,
+        [
+            (2000, EventPayload::SetVoice(VoiceIdx(7))),
+            (2000, EventPayload::Key(DEFAULT_KEY)),
+            (2000, EventPayload::On { duration: 480 }),
+            (2000, EventPayload::Volume(50)),
+        ]
+    );
+    assert!(EventClip::from_bytes(b"PTCOWCLQ").is_err());
+}
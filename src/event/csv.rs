@@ -0,0 +1,378 @@
+//! Event lists as CSV, for looking at or editing them in a spreadsheet
+
+use {
+    super::{EveList, Event, EventPayload},
+    crate::{
+        GroupIdx, Key, LfoParams, NoiseType, PanTime, Timing, Tuning, UnitIdx, VoiceIdx,
+        timing::Tick,
+    },
+    std::{fmt::Write as _, str::FromStr},
+};
+
+const HEADER: [&str; 5] = ["tick", "time", "unit", "kind", "value"];
+
+/// Error that can happen when importing events from CSV
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("line {line}: {kind}")]
+pub struct CsvError {
+    /// Line number (1-based)
+    pub line: usize,
+    /// What went wrong
+    pub kind: CsvErrorKind,
+}
+
+/// The kind of a [`CsvError`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CsvErrorKind {
+    /// The first line isn't the `tick,time,unit,kind,value` header
+    #[error("expected the header `tick,time,unit,kind,value`")]
+    InvalidHeader,
+    /// A row doesn't have the same number of columns as the header
+    #[error("expected 5 columns, found {0}")]
+    ColumnCount(usize),
+    /// The event kind isn't known
+    #[error("unknown event kind `{0}`")]
+    UnknownKind(String),
+    /// A value couldn't be parsed, or it's out of range
+    #[error("invalid value `{value}` for `{what}`")]
+    InvalidValue {
+        /// What the value is for
+        what: &'static str,
+        /// The offending value
+        value: String,
+    },
+}
+
+impl EveList {
+    /// Write the events as CSV, one event per row.
+    ///
+    /// The columns are:
+    ///
+    /// - `tick`: The [`Tick`] of the event
+    /// - `time`: When the event plays, in seconds, according to `timing`
+    /// - `unit`: The index of the unit
+    /// - `kind`: The name of the [`EventPayload`] variant, like `On` or `Key`
+    /// - `value`: The value of the payload. [`Tremolo`](EventPayload::Tremolo) and
+    ///   [`AutoPan`](EventPayload::AutoPan) have three space separated values: rate, depth,
+    ///   and shape. Kinds without a value leave it empty.
+    ///
+    /// Read it back with [`Self::from_csv`].
+    #[must_use]
+    pub fn to_csv(&self, timing: &Timing) -> String {
+        let mut out = HEADER.join(",");
+        out.push('\n');
+        for eve in &self.eves {
+            let _ = writeln!(
+                out,
+                "{},{:.4},{},{},{}",
+                eve.tick,
+                tick_secs(eve.tick, *timing),
+                eve.unit.0,
+                kind_name(eve.payload),
+                payload_value(eve.payload)
+            );
+        }
+        out
+    }
+    /// Read events written by [`Self::to_csv`].
+    ///
+    /// Columns can be separated by commas or tabs, whichever the header uses, so rows
+    /// copied out of a spreadsheet work too. The `time` column is ignored, the `tick` column
+    /// decides when events play. Empty lines are skipped.
+    ///
+    /// The events are sorted (see [`Self::sort`]).
+    pub fn from_csv(text: &str) -> Result<Self, CsvError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let Some((header_line, header)) = lines.next() else {
+            return Ok(Self::default());
+        };
+        let delim = if header.contains('\t') { '\t' } else { ',' };
+        let header_ok = header.split(delim).map(unquote).map(str::to_ascii_lowercase).eq(HEADER);
+        if !header_ok {
+            return Err(CsvError {
+                line: header_line,
+                kind: CsvErrorKind::InvalidHeader,
+            });
+        }
+        let mut eves = Vec::new();
+        for (line, row) in lines {
+            let err = |kind| CsvError { line, kind };
+            let cols: Vec<&str> = row.split(delim).map(unquote).collect();
+            let &[tick, _time, unit, kind, value] = cols.as_slice() else {
+                return Err(err(CsvErrorKind::ColumnCount(cols.len())));
+            };
+            eves.push(Event {
+                tick: parse("tick", tick).map_err(err)?,
+                unit: UnitIdx(parse("unit", unit).map_err(err)?),
+                payload: parse_payload(kind, value).map_err(err)?,
+            });
+        }
+        let mut list = Self { eves, ser_size: 0 };
+        list.sort();
+        Ok(list)
+    }
+}
+
+fn tick_secs(tick: Tick, timing: Timing) -> f64 {
+    f64::from(tick) / f64::from(timing.ticks_per_beat) * 60.0 / f64::from(timing.bpm)
+}
+
+/// Spreadsheets quote fields when they feel like it
+fn unquote(field: &str) -> &str {
+    let field = field.trim();
+    field
+        .strip_prefix('"')
+        .and_then(|field| field.strip_suffix('"'))
+        .unwrap_or(field)
+}
+
+const fn kind_name(payload: EventPayload) -> &'static str {
+    match payload {
+        EventPayload::Null => "Null",
+        EventPayload::On { .. } => "On",
+        EventPayload::Key(_) => "Key",
+        EventPayload::PanVol(_) => "PanVol",
+        EventPayload::Velocity(_) => "Velocity",
+        EventPayload::Volume(_) => "Volume",
+        EventPayload::Portament { .. } => "Portament",
+        EventPayload::BeatClock => "BeatClock",
+        EventPayload::BeatTempo => "BeatTempo",
+        EventPayload::BeatNum => "BeatNum",
+        EventPayload::Repeat => "Repeat",
+        EventPayload::Last => "Last",
+        EventPayload::SetVoice(_) => "SetVoice",
+        EventPayload::SetGroup(_) => "SetGroup",
+        EventPayload::Tuning(_) => "Tuning",
+        EventPayload::PanTime(_) => "PanTime",
+        EventPayload::PtcowDebug(_) => "PtcowDebug",
+        EventPayload::PitchBend(_) => "PitchBend",
+        EventPayload::PitchBendRange(_) => "PitchBendRange",
+        EventPayload::VibratoRate(_) => "VibratoRate",
+        EventPayload::VibratoDepth(_) => "VibratoDepth",
+        EventPayload::Tremolo(_) => "Tremolo",
+        EventPayload::AutoPan(_) => "AutoPan",
+    }
+}
+
+fn payload_value(payload: EventPayload) -> String {
+    match payload {
+        EventPayload::Null
+        | EventPayload::BeatClock
+        | EventPayload::BeatTempo
+        | EventPayload::BeatNum
+        | EventPayload::Repeat
+        | EventPayload::Last => String::new(),
+        EventPayload::On { duration } | EventPayload::Portament { duration } => {
+            duration.to_string()
+        }
+        EventPayload::Key(key)
+        | EventPayload::PitchBendRange(key)
+        | EventPayload::VibratoDepth(key) => key.0.to_string(),
+        EventPayload::PanVol(vol) => vol.to_string(),
+        EventPayload::Velocity(val) | EventPayload::Volume(val) | EventPayload::PitchBend(val) => {
+            val.to_string()
+        }
+        EventPayload::SetVoice(idx) => idx.0.to_string(),
+        EventPayload::SetGroup(idx) => idx.0.to_string(),
+        EventPayload::Tuning(tuning) => tuning.get().to_string(),
+        EventPayload::PanTime(time) => time.0.to_string(),
+        EventPayload::PtcowDebug(val) => val.to_string(),
+        EventPayload::VibratoRate(rate) => rate.to_string(),
+        EventPayload::Tremolo(params) | EventPayload::AutoPan(params) => {
+            format!("{} {} {:?}", params.rate, params.depth, params.shape)
+        }
+    }
+}
+
+fn parse<T: FromStr>(what: &'static str, value: &str) -> Result<T, CsvErrorKind> {
+    value.parse().map_err(|_| invalid(what, value))
+}
+
+fn invalid(what: &'static str, value: &str) -> CsvErrorKind {
+    CsvErrorKind::InvalidValue {
+        what,
+        value: value.to_owned(),
+    }
+}
+
+fn parse_payload(kind: &str, value: &str) -> Result<EventPayload, CsvErrorKind> {
+    // Kinds without a value also accept the 0 PxTone stores for them
+    let no_value = |payload| {
+        if value.is_empty() || value == "0" {
+            Ok(payload)
+        } else {
+            Err(invalid(kind_name(payload), value))
+        }
+    };
+    let payload = match kind {
+        "Null" => no_value(EventPayload::Null)?,
+        "On" => EventPayload::On {
+            duration: parse("On", value)?,
+        },
+        "Key" => EventPayload::Key(Key(parse("Key", value)?)),
+        "PanVol" => EventPayload::PanVol(parse("PanVol", value)?),
+        "Velocity" => EventPayload::Velocity(parse("Velocity", value)?),
+        "Volume" => EventPayload::Volume(parse("Volume", value)?),
+        "Portament" => EventPayload::Portament {
+            duration: parse("Portament", value)?,
+        },
+        "BeatClock" => no_value(EventPayload::BeatClock)?,
+        "BeatTempo" => no_value(EventPayload::BeatTempo)?,
+        "BeatNum" => no_value(EventPayload::BeatNum)?,
+        "Repeat" => no_value(EventPayload::Repeat)?,
+        "Last" => no_value(EventPayload::Last)?,
+        "SetVoice" => EventPayload::SetVoice(VoiceIdx(parse("SetVoice", value)?)),
+        "SetGroup" => {
+            let group = GroupIdx(parse("SetGroup", value)?);
+            if group.0 > GroupIdx::MAX.0 {
+                return Err(invalid("SetGroup", value));
+            }
+            EventPayload::SetGroup(group)
+        }
+        "Tuning" => EventPayload::Tuning(
+            Tuning::new(parse("Tuning", value)?).ok_or_else(|| invalid("Tuning", value))?,
+        ),
+        "PanTime" => {
+            let time = parse("PanTime", value)?;
+            if !PanTime::RANGE.contains(&time) {
+                return Err(invalid("PanTime", value));
+            }
+            EventPayload::PanTime(PanTime(time))
+        }
+        "PtcowDebug" => EventPayload::PtcowDebug(parse("PtcowDebug", value)?),
+        "PitchBend" => {
+            let bend: i16 = parse("PitchBend", value)?;
+            if bend.abs() > EventPayload::PITCH_BEND_MAX {
+                return Err(invalid("PitchBend", value));
+            }
+            EventPayload::PitchBend(bend)
+        }
+        "PitchBendRange" => EventPayload::PitchBendRange(Key(parse("PitchBendRange", value)?)),
+        "VibratoRate" => {
+            let rate: f32 = parse("VibratoRate", value)?;
+            if !rate.is_finite() {
+                return Err(invalid("VibratoRate", value));
+            }
+            EventPayload::VibratoRate(rate)
+        }
+        "VibratoDepth" => EventPayload::VibratoDepth(Key(parse("VibratoDepth", value)?)),
+        "Tremolo" => EventPayload::Tremolo(parse_lfo("Tremolo", value)?),
+        "AutoPan" => EventPayload::AutoPan(parse_lfo("AutoPan", value)?),
+        _ => return Err(CsvErrorKind::UnknownKind(kind.to_owned())),
+    };
+    Ok(payload)
+}
+
+/// Parse `rate depth shape`, like `500 64 Sine`
+fn parse_lfo(what: &'static str, value: &str) -> Result<LfoParams, CsvErrorKind> {
+    let mut parts = value.split_whitespace();
+    let (Some(rate), Some(depth), Some(shape), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid(what, value));
+    };
+    let shape = (0..=u8::MAX)
+        .map_while(NoiseType::from_index)
+        .find(|ty| format!("{ty:?}") == shape)
+        .ok_or_else(|| invalid(what, value))?;
+    Ok(LfoParams {
+        rate: parse(what, rate)?,
+        depth: parse(what, depth)?,
+        shape,
+    })
+}
+
+#[test]
+fn test_csv_roundtrip() {
+    let eve = |tick, unit, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick,
+    };
+    let mut list = EveList {
+        eves: vec![
+            eve(0, 0, EventPayload::SetVoice(VoiceIdx(2))),
+            eve(0, 0, EventPayload::Key(crate::DEFAULT_KEY)),
+            eve(0, 0, EventPayload::On { duration: 480 }),
+            eve(0, 1, EventPayload::Tuning(Tuning::from_cents(-33.0))),
+            eve(0, 1, EventPayload::BeatClock),
+            eve(
+                480,
+                1,
+                EventPayload::Tremolo(LfoParams {
+                    rate: 500,
+                    depth: 64,
+                    shape: NoiseType::Tri,
+                }),
+            ),
+            eve(960, 1, EventPayload::PitchBend(-8192)),
+        ],
+        ser_size: 0,
+    };
+    list.sort();
+    let timing = Timing::default();
+    let csv = list.to_csv(&timing);
+    assert!(csv.starts_with("tick,time,unit,kind,value\n"));
+    assert!(csv.contains("480,0.5000,1,Tremolo,500 64 Tri\n"), "{csv}");
+    let read = EveList::from_csv(&csv).unwrap();
+    let payloads = |list: &EveList| {
+        list.eves
+            .iter()
+            .map(|eve| (eve.tick, eve.unit, eve.payload))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(payloads(&read), payloads(&list));
+    // Tab separated, quoted, and out of order
+    let tsv = "tick\ttime\tunit\tkind\tvalue\n\n10\t\t0\t\"On\"\t5\n0\t\t0\tKey\t256\n";
+    let read = EveList::from_csv(tsv).unwrap();
+    assert_eq!(
+        payloads(&read),
+        [
+            (0, UnitIdx(0), EventPayload::Key(Key(256))),
+            (10, UnitIdx(0), EventPayload::On { duration: 5 })
+        ]
+    );
+}
+
+#[test]
+fn test_csv_errors() {
+    let err = |text: &str| match EveList::from_csv(text) {
+        Ok(_) => panic!("`{text}` should fail"),
+        Err(err) => err,
+    };
+    assert_eq!(err("tick,unit\n").kind, CsvErrorKind::InvalidHeader);
+    let header = "tick,time,unit,kind,value\n";
+    assert_eq!(
+        err(&format!("{header}0,0,0,On\n")),
+        CsvError {
+            line: 2,
+            kind: CsvErrorKind::ColumnCount(4)
+        }
+    );
+    assert_eq!(
+        err(&format!("{header}0,0,0,Boop,1\n")).kind,
+        CsvErrorKind::UnknownKind("Boop".into())
+    );
+    for row in [
+        "-1,0,0,On,1",
+        "0,0,50000,On,1",
+        "0,0,0,SetGroup,7",
+        "0,0,0,Tuning,-1",
+        "0,0,0,PanTime,128",
+        "0,0,0,PitchBend,9000",
+        "0,0,0,Repeat,3",
+        "0,0,0,AutoPan,1 2 Wobble",
+    ] {
+        assert!(
+            matches!(
+                err(&format!("{header}{row}\n")).kind,
+                CsvErrorKind::InvalidValue { .. }
+            ),
+            "{row}"
+        );
+    }
+}
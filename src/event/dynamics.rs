@@ -0,0 +1,283 @@
+//! Crescendos and decrescendos drawn with curves
+
+use {
+    super::{EveList, Event, EventPayload},
+    crate::{UnitIdx, timing::Tick},
+    std::ops::Range,
+};
+
+/// The shape of a [`DynamicCurve`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Curve {
+    /// Changes at the same rate all the way
+    #[default]
+    Linear,
+    /// Changes slowly at first, then faster and faster
+    Exp,
+    /// Changes slowly at the start and at the end, and faster in the middle
+    SCurve,
+}
+
+impl Curve {
+    /// How far along the curve is at `t`, where `t` and the result go from 0 to 1
+    #[must_use]
+    pub fn at(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::Exp => ((t * 4.0).exp2() - 1.0) / 15.0,
+            Self::SCurve => t * t * 2.0f32.mul_add(-t, 3.0),
+        }
+    }
+}
+
+/// Which events a [`DynamicCurve`] is made of
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dynamic {
+    /// [`Velocity`](EventPayload::Velocity) events, which set the velocity of the notes that
+    /// start after them
+    Velocity,
+    /// [`Volume`](EventPayload::Volume) events, which change the volume of the unit right away,
+    /// even in the middle of a note
+    Volume,
+}
+
+/// A change of velocity or volume over time, for [`EveList::apply_dynamic_curve`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DynamicCurve {
+    /// The kind of events to place
+    pub dynamic: Dynamic,
+    /// The shape of the change
+    pub curve: Curve,
+    /// The value at the start of the range
+    pub from: i16,
+    /// The value at the end of the range
+    pub to: i16,
+    /// How far the placed values may be from the curve, rounded to whole values.
+    ///
+    /// The larger it is, the fewer events are needed.
+    pub max_error: i16,
+}
+
+impl DynamicCurve {
+    /// The value of the curve `tick` ticks into a range `len` ticks long
+    #[expect(clippy::cast_precision_loss)]
+    fn value_at(self, tick: Tick, len: Tick) -> f32 {
+        let t = if len == 0 {
+            1.0
+        } else {
+            tick as f32 / len as f32
+        };
+        let (from, to) = (f32::from(self.from), f32::from(self.to));
+        (to - from).mul_add(self.curve.at(t), from)
+    }
+    /// The value to place at a point the curve has value `value`.
+    ///
+    /// It's as far towards [`Self::to`] as the error allows, so it stays close to the curve
+    /// for as long as possible.
+    #[expect(clippy::cast_possible_truncation)]
+    fn step_value(self, value: f32) -> i16 {
+        let toward = if self.to >= self.from {
+            (value + f32::from(self.max_error)).min(f32::from(self.to))
+        } else {
+            (value - f32::from(self.max_error)).max(f32::from(self.to))
+        };
+        toward.round().clamp(0.0, f32::from(i16::MAX)) as i16
+    }
+    /// Whether `value` is too far from the curve value `curve`
+    fn too_far(self, value: i16, curve: f32) -> bool {
+        (curve.round() - f32::from(value)).abs() > f32::from(self.max_error)
+    }
+}
+
+impl EveList {
+    /// Replace the velocity or volume of `units` in `range` with a curve, like a crescendo.
+    ///
+    /// The [`Velocity`](EventPayload::Velocity) or [`Volume`](EventPayload::Volume) events of
+    /// the units in `range` are removed, and as few new ones are placed as needed to stay within
+    /// [`DynamicCurve::max_error`] of the curve. Velocity only matters when a note starts,
+    /// so velocity events are only placed for notes.
+    /// The value reaches [`DynamicCurve::to`] at the end of `range`, and stays there until the
+    /// next event that changes it.
+    ///
+    /// Values are clamped to `0..=i16::MAX`, the range that can be serialized.
+    /// The list needs to be [sorted](Self::sort), and the result is sorted.
+    pub fn apply_dynamic_curve(
+        &mut self,
+        range: Range<Tick>,
+        units: &[UnitIdx],
+        curve: DynamicCurve,
+    ) {
+        let is_dynamic = |payload: &EventPayload| match curve.dynamic {
+            Dynamic::Velocity => matches!(payload, EventPayload::Velocity(_)),
+            Dynamic::Volume => matches!(payload, EventPayload::Volume(_)),
+        };
+        let to_event = |value| match curve.dynamic {
+            Dynamic::Velocity => EventPayload::Velocity(value),
+            Dynamic::Volume => EventPayload::Volume(value),
+        };
+        let len = range.end.saturating_sub(range.start);
+        let mut placed = Vec::new();
+        for &unit in units {
+            let steps = match curve.dynamic {
+                Dynamic::Velocity => {
+                    let notes = self.eves.iter().filter(|eve| {
+                        eve.unit == unit
+                            && range.contains(&eve.tick)
+                            && matches!(eve.payload, EventPayload::On { .. })
+                    });
+                    velocity_steps(curve, notes.map(|eve| eve.tick - range.start), len)
+                }
+                Dynamic::Volume => volume_steps(curve, len),
+            };
+            let last = steps.last().map(|&(_, value)| value);
+            placed.extend(steps.into_iter().map(|(tick, value)| Event {
+                payload: to_event(value),
+                unit,
+                tick: range.start + tick,
+            }));
+            if last.is_some_and(|last| last != curve.to)
+                && self.needs_end_value(unit, range.end, curve.dynamic, is_dynamic)
+            {
+                placed.push(Event {
+                    payload: to_event(curve.to.max(0)),
+                    unit,
+                    tick: range.end,
+                });
+            }
+        }
+        self.eves.retain(|eve| {
+            !(units.contains(&eve.unit) && range.contains(&eve.tick) && is_dynamic(&eve.payload))
+        });
+        self.eves.extend(placed);
+        self.sort();
+    }
+    /// Whether the value at the end of a curve affects anything after `end`
+    fn needs_end_value(
+        &self,
+        unit: UnitIdx,
+        end: Tick,
+        dynamic: Dynamic,
+        is_dynamic: impl Fn(&EventPayload) -> bool,
+    ) -> bool {
+        let mut after = self.eves.iter().filter(|eve| eve.unit == unit && eve.tick >= end);
+        match dynamic {
+            // Only notes that start before the velocity is set again
+            Dynamic::Velocity => after
+                .find(|eve| {
+                    is_dynamic(&eve.payload) || matches!(eve.payload, EventPayload::On { .. })
+                })
+                .is_some_and(|eve| !is_dynamic(&eve.payload)),
+            // Unless it's set again right away
+            Dynamic::Volume => !after.any(|eve| eve.tick == end && is_dynamic(&eve.payload)),
+        }
+    }
+}
+
+/// The velocities to set for the notes starting at `notes` (sorted, relative to the start of
+/// a curve `len` ticks long)
+fn velocity_steps(
+    curve: DynamicCurve,
+    notes: impl Iterator<Item = Tick>,
+    len: Tick,
+) -> Vec<(Tick, i16)> {
+    let mut steps: Vec<(Tick, i16)> = Vec::new();
+    for tick in notes {
+        let value = curve.value_at(tick, len);
+        if steps.last().is_none_or(|&(_, last)| curve.too_far(last, value)) {
+            steps.push((tick, curve.step_value(value)));
+        }
+    }
+    steps
+}
+
+/// The volumes to set over a curve `len` ticks long, relative to its start
+fn volume_steps(curve: DynamicCurve, len: Tick) -> Vec<(Tick, i16)> {
+    let mut steps = Vec::new();
+    let mut tick = 0;
+    while tick < len {
+        let value = curve.step_value(curve.value_at(tick, len));
+        steps.push((tick, value));
+        // The curve is monotonic, so once it's too far, it stays too far
+        let (mut lo, mut hi) = (tick + 1, len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if curve.too_far(value, curve.value_at(mid, len)) {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        tick = lo;
+    }
+    steps
+}
+
+#[test]
+fn test_dynamic_curve() {
+    let eve = |tick, payload| Event {
+        payload,
+        unit: UnitIdx(0),
+        tick,
+    };
+    let mut list = EveList {
+        eves: (0..10)
+            .map(|i| eve(i * 100, EventPayload::On { duration: 100 }))
+            .chain([
+                eve(500, EventPayload::Volume(10)),
+                eve(500, EventPayload::Velocity(10)),
+                eve(1200, EventPayload::Volume(64)),
+            ])
+            .collect(),
+        ser_size: 0,
+    };
+    list.sort();
+    let mut curve = DynamicCurve {
+        dynamic: Dynamic::Volume,
+        curve: Curve::Linear,
+        from: 0,
+        to: 100,
+        max_error: 5,
+    };
+    list.apply_dynamic_curve(0..1000, &[UnitIdx(0)], curve);
+    let volumes: Vec<_> = list
+        .eves
+        .iter()
+        .filter_map(|eve| match eve.payload {
+            EventPayload::Volume(vol) => Some((eve.tick, vol)),
+            _ => None,
+        })
+        .collect();
+    // Every tick of the range is within the error of the curve, rounded
+    for tick in 0..1000 {
+        let (_, vol) = volumes.iter().rev().find(|(t, _)| *t <= tick).unwrap();
+        assert!(
+            (i32::from(*vol) - (tick.cast_signed() + 5) / 10).abs() <= 5,
+            "{tick}: {vol}"
+        );
+    }
+    assert_eq!(volumes.len(), 11, "{volumes:?}");
+    assert_eq!(volumes[9], (985, 100));
+    assert_eq!(volumes[10], (1200, 64));
+
+    // Velocity is only set for notes
+    curve.dynamic = Dynamic::Velocity;
+    curve.curve = Curve::SCurve;
+    (curve.from, curve.to) = (100, 20);
+    list.apply_dynamic_curve(0..1000, &[UnitIdx(0)], curve);
+    let velocities: Vec<_> = list
+        .eves
+        .iter()
+        .filter_map(|eve| match eve.payload {
+            EventPayload::Velocity(vel) => Some((eve.tick, vel)),
+            _ => None,
+        })
+        .collect();
+    assert!(velocities.iter().all(|(tick, _)| tick % 100 == 0));
+    assert!(
+        velocities.windows(2).all(|w| w[0].1 > w[1].1),
+        "{velocities:?}"
+    );
+    assert_eq!(velocities[0], (0, 95));
+    assert!(!velocities.iter().any(|&(tick, _)| tick >= 1000));
+}
@@ -0,0 +1,152 @@
+//! Removing events that don't change how the song plays
+
+use {
+    super::{DEFAULT_PITCH_BEND_RANGE, DEFAULT_VELOCITY, DEFAULT_VOLUME, EveList, EventPayload},
+    crate::{
+        Key,
+        unit::{GroupIdx, LfoParams, Tuning},
+    },
+    std::collections::HashMap,
+};
+
+impl EveList {
+    /// Remove events that don't change how the song plays, and return how many were removed.
+    ///
+    /// PxTone Collage writes lots of these, so this makes files smaller, and playback has
+    /// fewer events to go through.
+    ///
+    /// Events that set a value of a unit (like [`Volume`](EventPayload::Volume) or
+    /// [`PanVol`](EventPayload::PanVol)) are removed if
+    /// - a later event of the same kind sets the value of the unit on the same tick, or
+    /// - the unit already has that value, because an earlier event set it, or because units
+    ///   start out with it.
+    ///
+    /// Notes, keys and voice changes are always kept.
+    /// This assumes the units are only changed by the events of this list, so values set
+    /// by an [`EventSource`](crate::EventSource) or directly on a [`Unit`](crate::Unit)
+    /// aren't taken into account.
+    ///
+    /// The list needs to be [sorted](Self::sort), and the order of the events is kept.
+    pub fn optimize(&mut self) -> usize {
+        let before = self.eves.len();
+        let slot = |eve: &super::Event| (eve.tick, eve.unit, eve.payload.discriminant());
+        // Only the last value set on a tick is heard, values are only used when rendering
+        let mut last = HashMap::new();
+        for (i, eve) in self.eves.iter().enumerate() {
+            if sets_value(eve.payload) {
+                last.insert(slot(eve), i);
+            }
+        }
+        let mut i = 0;
+        self.eves.retain(|eve| {
+            let keep = !sets_value(eve.payload) || last.get(&slot(eve)) == Some(&i);
+            i += 1;
+            keep
+        });
+        // Playback always starts (and loops) with every unit at its initial values, followed
+        // by the events in order, so the current values are known at every event
+        let mut current = HashMap::new();
+        self.eves.retain(|eve| {
+            if !sets_value(eve.payload) {
+                return true;
+            }
+            let value = current
+                .entry((eve.unit, eve.payload.discriminant()))
+                .or_insert_with(|| initial_value(eve.payload));
+            if *value == Some(eve.payload) {
+                return false;
+            }
+            *value = Some(eve.payload);
+            true
+        });
+        before - self.eves.len()
+    }
+}
+
+/// Whether `payload` only sets a value of the unit, and setting it again to the same value
+/// does nothing
+const fn sets_value(payload: EventPayload) -> bool {
+    matches!(
+        payload,
+        EventPayload::PanVol(_)
+            | EventPayload::Velocity(_)
+            | EventPayload::Volume(_)
+            | EventPayload::Portament { .. }
+            | EventPayload::SetGroup(_)
+            | EventPayload::Tuning(_)
+            | EventPayload::PanTime(_)
+            | EventPayload::PitchBend(_)
+            | EventPayload::PitchBendRange(_)
+            | EventPayload::VibratoRate(_)
+            | EventPayload::VibratoDepth(_)
+            | EventPayload::Tremolo(_)
+            | EventPayload::AutoPan(_)
+    )
+}
+
+/// The value that units start out with for the kind of `payload`, if it's known
+fn initial_value(payload: EventPayload) -> Option<EventPayload> {
+    Some(match payload {
+        EventPayload::Velocity(_) => EventPayload::Velocity(DEFAULT_VELOCITY.cast_signed()),
+        EventPayload::Volume(_) => EventPayload::Volume(DEFAULT_VOLUME.cast_signed()),
+        EventPayload::Portament { .. } => EventPayload::Portament { duration: 0 },
+        EventPayload::SetGroup(_) => EventPayload::SetGroup(GroupIdx(0)),
+        EventPayload::Tuning(_) => EventPayload::Tuning(Tuning::default()),
+        EventPayload::PitchBend(_) => EventPayload::PitchBend(0),
+        EventPayload::PitchBendRange(_) => EventPayload::PitchBendRange(DEFAULT_PITCH_BEND_RANGE),
+        EventPayload::VibratoRate(_) => EventPayload::VibratoRate(0.0),
+        EventPayload::VibratoDepth(_) => EventPayload::VibratoDepth(Key(0)),
+        EventPayload::Tremolo(_) => EventPayload::Tremolo(LfoParams::default()),
+        EventPayload::AutoPan(_) => EventPayload::AutoPan(LfoParams::default()),
+        // The initial pan depends on the pan law, and the initial pan time on the sample rate
+        _ => return None,
+    })
+}
+
+#[test]
+fn test_optimize() {
+    use crate::{Event, UnitIdx};
+    let eve = |unit, tick, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick,
+    };
+    let on = EventPayload::On { duration: 100 };
+    let mut list = EveList {
+        eves: vec![
+            // Units start out at the default volume
+            eve(0, 0, EventPayload::Volume(104)),
+            eve(0, 0, EventPayload::PanVol(64)),
+            eve(0, 0, on),
+            eve(0, 0, EventPayload::Velocity(80)),
+            eve(0, 0, EventPayload::Velocity(90)),
+            eve(1, 0, EventPayload::Velocity(90)),
+            eve(0, 100, EventPayload::PanVol(64)),
+            eve(0, 100, on),
+            eve(0, 100, EventPayload::Velocity(90)),
+            eve(0, 200, EventPayload::SetVoice(crate::VoiceIdx(1))),
+            eve(0, 200, EventPayload::Velocity(90)),
+            eve(0, 200, on),
+            eve(0, 200, on),
+            eve(0, 300, EventPayload::Volume(50)),
+            eve(0, 300, EventPayload::Volume(104)),
+        ],
+        ser_size: 0,
+    };
+    assert_eq!(list.optimize(), 7);
+    let left: Vec<_> = list.eves.iter().map(|eve| (eve.unit.0, eve.tick, eve.payload)).collect();
+    assert_eq!(
+        left,
+        [
+            (0, 0, EventPayload::PanVol(64)),
+            (0, 0, on),
+            (0, 0, EventPayload::Velocity(90)),
+            (1, 0, EventPayload::Velocity(90)),
+            (0, 100, on),
+            (0, 200, EventPayload::SetVoice(crate::VoiceIdx(1))),
+            (0, 200, on),
+            (0, 200, on),
+        ]
+    );
+    assert_eq!(list.optimize(), 0);
+}
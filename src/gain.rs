@@ -0,0 +1,102 @@
+//! Conversion of volume related event values into gains, decibels and percentages
+//!
+//! The gains follow the math the [`Unit`]s use when mixing: the samples of a voice are
+//! multiplied by `velocity / 128 * volume / 128 * pan / 64`, where `pan` is the volume of
+//! the channel set by [`PanVol`](crate::EventPayload::PanVol) (64 for both channels at
+//! the center).
+//! A gain of 1.0 plays the voice at its original level.
+//!
+//! The mixer does the math with integers, so the actual output can be a tiny bit quieter.
+
+use crate::{PanLaw, Unit};
+
+/// Gain of a [`Velocity`](crate::EventPayload::Velocity) value
+#[must_use]
+pub fn velocity(velocity: i16) -> f64 {
+    f64::from(velocity) / 128.0
+}
+
+/// Gain of a [`Volume`](crate::EventPayload::Volume) value
+#[must_use]
+pub fn volume(volume: i16) -> f64 {
+    f64::from(volume) / 128.0
+}
+
+/// Left and right gains of a [`PanVol`](crate::EventPayload::PanVol) value
+/// (0 is left, 64 is the center, 128 is right)
+#[must_use]
+pub fn pan_vol(pan_vol: u8, law: PanLaw) -> [f64; 2] {
+    law.unit_pan_vols(pan_vol).map(|vol| f64::from(vol) / 64.0)
+}
+
+/// Left and right gains of a unit playing with these event values
+#[must_use]
+pub fn combined(velocity: i16, volume: i16, pan_vol: u8, law: PanLaw) -> [f64; 2] {
+    let gain = self::velocity(velocity) * self::volume(volume);
+    self::pan_vol(pan_vol, law).map(|pan| gain * pan)
+}
+
+/// Convert a gain into decibels. A gain of 0 is negative infinity.
+#[must_use]
+pub fn to_db(gain: f64) -> f64 {
+    20.0 * gain.log10()
+}
+
+/// Convert decibels into a gain
+#[must_use]
+pub fn from_db(db: f64) -> f64 {
+    10f64.powf(db / 20.0)
+}
+
+/// Convert a gain into a percentage (1.0 is 100%)
+#[must_use]
+pub fn to_percent(gain: f64) -> f64 {
+    gain * 100.0
+}
+
+/// The [`Volume`](crate::EventPayload::Volume) value closest to `gain`
+#[must_use]
+#[expect(clippy::cast_possible_truncation)]
+pub fn volume_for_gain(gain: f64) -> i16 {
+    (gain * 128.0).round().clamp(0.0, f64::from(i16::MAX)) as i16
+}
+
+impl Unit {
+    /// Left and right gains the unit currently plays its voice with.
+    ///
+    /// This includes the velocity, volume, and pan volume, but not the
+    /// [tremolo](Self::tremolo), [auto-pan](Self::auto_pan), or the envelope of the voice.
+    #[must_use]
+    pub fn output_gains(&self) -> [f64; 2] {
+        let gain = velocity(self.velocity) * volume(self.volume);
+        self.pan_vols.map(|pan| gain * f64::from(pan) / 64.0)
+    }
+}
+
+#[test]
+fn test_gains() {
+    use crate::event::{DEFAULT_VELOCITY, DEFAULT_VOLUME};
+    let bits = |gains: [f64; 2]| gains.map(f64::to_bits);
+    assert_eq!(bits(pan_vol(64, PanLaw::Linear)), bits([1.0, 1.0]));
+    assert_eq!(bits(pan_vol(0, PanLaw::Linear)), bits([1.0, 0.0]));
+    assert_eq!(bits(pan_vol(96, PanLaw::Linear)), bits([0.5, 1.0]));
+    assert_eq!(
+        bits(combined(128, 64, 128, PanLaw::Linear)),
+        bits([0.0, 0.5])
+    );
+    assert_eq!(to_percent(volume(32)).to_bits(), 25f64.to_bits());
+    assert!((to_db(0.5) + 6.0206).abs() < 1e-4);
+    assert!((from_db(to_db(0.3)) - 0.3).abs() < 1e-12);
+    assert_eq!(to_db(0.0).to_bits(), f64::NEG_INFINITY.to_bits());
+    assert_eq!(volume_for_gain(volume(104)), 104);
+    assert_eq!(volume_for_gain(-1.0), 0);
+    // A fresh unit plays at the default velocity and volume
+    let vel = i16::try_from(DEFAULT_VELOCITY).unwrap();
+    let vol = i16::try_from(DEFAULT_VOLUME).unwrap();
+    let mut unit = Unit::new();
+    unit.tone_init();
+    assert_eq!(
+        bits(unit.output_gains()),
+        bits(combined(vel, vol, 64, PanLaw::Linear))
+    );
+}
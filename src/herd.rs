@@ -1,22 +1,38 @@
 use crate::{
-    ReadResult, SampleRate, SamplesPerTick, Timing, UnitIdx,
+    NATIVE_SAMPLE_RATE, NearestResampler, ReadResult, Resampler, SampleRate, SamplesPerTick,
+    Timing, UnitIdx,
     delay::Delay,
-    event::EveList,
-    master::Master,
+    event::{EveList, Event, EventPayload},
+    master::{LoopPoints, Master},
     noise_builder::NoiseTable,
-    overdrive::Overdrive,
-    result::WriteResult,
-    timing::SampleT,
-    unit::{Unit, VoiceIdx},
-    voice::Voice,
+    overdrive::{Overdrive, OverdriveSettings},
+    result::{
+        OverdriveError, ProjectReadError, ReadWarning, StreamReadError, StreamWriteError,
+        WriteResult,
+    },
+    states::{MusicStates, Transition},
+    timing::{SampleT, Tick},
+    unit::{
+        GroupIdx, GroupSamples, Interpolation, LevelMeter, PanLaw, PanTimeScale, Unit, UnitLevel,
+        UnitTap, VoiceIdx,
+    },
+    util::ArrayLenExt as _,
+    voice::{Voice, VoiceData},
 };
 
+mod checkpoint;
+pub use checkpoint::checkpoint_sample;
 mod io;
-use arrayvec::ArrayVec;
 pub use io::Tag;
+mod routing;
+pub use routing::{GroupEffect, GroupRouting};
+use {
+    arrayvec::ArrayVec,
+    moo::{LoopPolicy, MixPolicy, Tail},
+    std::{num::NonZeroU32, ops::Range},
+};
 pub mod moo;
 
-const MAX_UNITS: u16 = 50;
 const MAX_TUNE_VOICE_NAME: u32 = 16;
 pub const MAX_TUNE_UNIT_NAME: usize = 16;
 
@@ -29,6 +45,94 @@ pub struct Text {
     pub comment: String,
 }
 
+/// Changes to the [`Text`] of a song, for [`rewrite_metadata`]
+#[derive(Default, Clone, Debug)]
+pub struct TextPatch {
+    /// The new name. `None` keeps the current name, an empty string removes it.
+    pub name: Option<String>,
+    /// The new comment. `None` keeps the current comment, an empty string removes it.
+    pub comment: Option<String>,
+}
+
+/// A difference between two serialized project files, found by [`diff_chunks`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkDiff {
+    /// The version headers differ
+    Header,
+    /// The `nth` chunk with tag `code` differs between the files
+    Changed {
+        /// The tag code of the chunk (for example `b"matePCM "`)
+        code: [u8; 8],
+        /// Which occurrence of the chunk differs (0 is the first chunk with this code)
+        nth: usize,
+    },
+    /// The `nth` chunk with tag `code` is only present in the left file
+    OnlyLeft {
+        /// The tag code of the chunk
+        code: [u8; 8],
+        /// Which occurrence of the chunk is missing from the right file
+        nth: usize,
+    },
+    /// The `nth` chunk with tag `code` is only present in the right file
+    OnlyRight {
+        /// The tag code of the chunk
+        code: [u8; 8],
+        /// Which occurrence of the chunk is missing from the left file
+        nth: usize,
+    },
+    /// The chunks both files have appear in a different order
+    Order,
+    /// The bytes after the end chunk differ
+    Trailing,
+}
+
+impl ChunkDiff {
+    /// The tag code of the chunk this difference is about, if any
+    #[must_use]
+    pub const fn code(&self) -> Option<&[u8; 8]> {
+        match self {
+            Self::Changed { code, .. }
+            | Self::OnlyLeft { code, .. }
+            | Self::OnlyRight { code, .. } => Some(code),
+            Self::Header | Self::Order | Self::Trailing => None,
+        }
+    }
+}
+
+/// How many bytes each part of a serialized project takes up,
+/// created by [`project_size_breakdown`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SizeBreakdown {
+    /// The version header and the end chunk
+    pub header: usize,
+    /// Timing and loop points
+    pub master: usize,
+    /// The events (see [`EveList::serialized_size_estimate`])
+    pub events: usize,
+    /// The name and comment of the song
+    pub text: usize,
+    /// Delays and overdrives
+    pub effects: usize,
+    /// Every voice, including its name, in order
+    pub voices: Vec<usize>,
+    /// The number of units, and their names
+    pub units: usize,
+}
+
+impl SizeBreakdown {
+    /// The size of the whole project
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.header
+            + self.master
+            + self.events
+            + self.text
+            + self.effects
+            + self.voices.iter().sum::<usize>()
+            + self.units
+    }
+}
+
 /// PxTone format version
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum FmtVer {
@@ -45,7 +149,7 @@ pub enum FmtVer {
 }
 
 /// Kind of PxTone format we are dealing with
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum FmtKind {
     /// PxTone collage (.ptcop)
     Collage,
@@ -60,8 +164,79 @@ pub struct FmtInfo {
     pub ver: FmtVer,
     /// Are we a project or a tune?
     pub kind: FmtKind,
-    pub(crate) exe_ver: u16,
-    pub(crate) dummy: u16,
+    /// The version of the program that saved the song
+    pub exe_ver: ExeVer,
+    /// The unused value after [`Self::exe_ver`] in the header.
+    ///
+    /// PxTone doesn't look at it, but it's kept, so files can be written back byte-exact.
+    pub dummy: u16,
+}
+
+/// The version of the program that saved a song, as written in its header.
+///
+/// PxTone Collage writes its own version there, as the decimal digits of the version number
+/// (`925` for 0.9.2.5). Other programs write whatever they like, usually 0.
+/// Values that aren't known are kept as they are, so they survive being read and written again.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[expect(non_camel_case_types)]
+pub enum ExeVer {
+    /// No version, written by the PxTone library, ptcow, and most other tools
+    #[default]
+    Unset,
+    /// PxTone Collage 0.8.3.4
+    Pxtone_0_8_3_4,
+    /// PxTone Collage 0.9.1.4
+    Pxtone_0_9_1_4,
+    /// PxTone Collage 0.9.2.3
+    Pxtone_0_9_2_3,
+    /// PxTone Collage 0.9.2.5, the last release
+    Pxtone_0_9_2_5,
+    /// A version ptcow doesn't know about
+    Unknown(u16),
+}
+
+impl ExeVer {
+    /// The version a raw header value stands for
+    #[must_use]
+    pub const fn from_raw(raw: u16) -> Self {
+        match raw {
+            0 => Self::Unset,
+            834 => Self::Pxtone_0_8_3_4,
+            914 => Self::Pxtone_0_9_1_4,
+            923 => Self::Pxtone_0_9_2_3,
+            925 => Self::Pxtone_0_9_2_5,
+            _ => Self::Unknown(raw),
+        }
+    }
+    /// The raw value written to the header
+    #[must_use]
+    pub const fn raw(self) -> u16 {
+        match self {
+            Self::Unset => 0,
+            Self::Pxtone_0_8_3_4 => 834,
+            Self::Pxtone_0_9_1_4 => 914,
+            Self::Pxtone_0_9_2_3 => 923,
+            Self::Pxtone_0_9_2_5 => 925,
+            Self::Unknown(raw) => raw,
+        }
+    }
+}
+
+/// Something in a song that keeps it from being written with an older format version,
+/// see [`Song::can_downgrade_to`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DowngradeBlocker {
+    /// The song is a tune, and V1 only has collages
+    TuneKind,
+    /// V1 only has PCM voices
+    NonPcmVoice(VoiceIdx),
+    /// Ogg/Vorbis voices were added in V5
+    OggVoice(VoiceIdx),
+    /// The song has this many [extension events](EventPayload::is_extension).
+    ///
+    /// No version of PxTone understands them, so they block every version, even V5.
+    /// The song still loads, but plays without them.
+    ExtensionEvents(usize),
 }
 
 impl Default for FmtInfo {
@@ -69,7 +244,7 @@ impl Default for FmtInfo {
         Self {
             ver: FmtVer::V5,
             kind: FmtKind::Collage,
-            exe_ver: 0,
+            exe_ver: ExeVer::Unset,
             dummy: 0,
         }
     }
@@ -86,6 +261,12 @@ pub struct Song {
     pub events: EveList,
     /// Information about the pxtone file format this song has
     pub fmt: FmtInfo,
+    /// Random seed for generative features, so renders of generative songs are reproducible.
+    ///
+    /// Randomized utilities use this seed when they aren't given one explicitly.
+    /// `PxTone` has no place for it, so it's saved in an extension chunk after the end of
+    /// the file, which `PxTone` ignores. `None` saves nothing.
+    pub seed: Option<u64>,
 }
 
 impl Song {
@@ -99,6 +280,164 @@ impl Song {
             self.events.get_max_tick(),
         ));
     }
+    /// [Humanize](EveList::humanize) the notes starting in `range`, with [`Self::seed`]
+    /// (or 0, if there is none).
+    pub fn humanize(&mut self, range: Range<Tick>, jitter: Tick) {
+        self.events.humanize(range, jitter, self.seed.unwrap_or_default());
+    }
+    /// What keeps the song (with the voices of `ins`) from being written for the version of
+    /// PxTone that reads `ver`.
+    ///
+    /// Serializing with an older [`FmtInfo::ver`] works either way, but that PxTone
+    /// version won't be able to play the song right, unless this is empty.
+    #[must_use]
+    pub fn can_downgrade_to(&self, ver: FmtVer, ins: &MooInstructions) -> Vec<DowngradeBlocker> {
+        let mut blockers = Vec::new();
+        if ver == FmtVer::V1 && self.fmt.kind == FmtKind::Tune {
+            blockers.push(DowngradeBlocker::TuneKind);
+        }
+        for (idx, voice) in ins.voices.enumerated() {
+            match voice.base.data {
+                VoiceData::Pcm(_) => {}
+                VoiceData::OggV(_) if ver < FmtVer::V5 => {
+                    blockers.push(DowngradeBlocker::OggVoice(idx));
+                }
+                _ if ver == FmtVer::V1 => blockers.push(DowngradeBlocker::NonPcmVoice(idx)),
+                _ => {}
+            }
+        }
+        let extensions = self.events.iter().filter(|eve| eve.payload.is_extension()).count();
+        if extensions != 0 {
+            blockers.push(DowngradeBlocker::ExtensionEvents(extensions));
+        }
+        blockers
+    }
+    /// Convert the project into a tune meant for playback only, like a game would ship it.
+    ///
+    /// - The song name and comment, and the names of units and voices are dropped.
+    /// - Voices that are exactly the same are merged into one.
+    /// - Events are canonicalized: events that playback ignores (like
+    ///   [`BeatClock`](EventPayload::BeatClock)) are dropped, and the rest are
+    ///   [sorted](EveList::sort).
+    ///
+    /// The tune plays the same as the project. Pair it with [`serialize_tune`] to write it.
+    #[must_use]
+    pub fn to_tune(&self, herd: &Herd, ins: &MooInstructions) -> (Self, Herd, MooInstructions) {
+        let mut tune_ins = MooInstructions::new(ins.out_sample_rate);
+        tune_ins.samples_per_tick = ins.samples_per_tick;
+        tune_ins.pan_law = ins.pan_law;
+        tune_ins.event_budget = ins.event_budget;
+        tune_ins.pan_time_scale = ins.pan_time_scale;
+        tune_ins.interpolation = ins.interpolation;
+        tune_ins.loop_policy = ins.loop_policy;
+        tune_ins.tail = ins.tail;
+        tune_ins.mix_policy = ins.mix_policy;
+        tune_ins.render = ins.render;
+        tune_ins.speed = ins.speed;
+        tune_ins.pitch = ins.pitch;
+        // Where each voice ended up after merging
+        let mut voice_map = Vec::new();
+        for voice in &ins.voices {
+            let same = tune_ins.voices.enumerated().find(|(_, kept)| same_voice_data(kept, voice));
+            let idx = if let Some((idx, _)) = same {
+                idx
+            } else {
+                let mut voice = voice.clone();
+                voice.name.clear();
+                // Can't fail, there are never more voices than in `ins`
+                tune_ins.add_voice(voice).unwrap_or(VoiceIdx(0))
+            };
+            voice_map.push(idx);
+        }
+        let mut tune_herd = Herd::default();
+        for _ in herd.units.iter() {
+            tune_herd.add_unit(String::new());
+        }
+        for delay in &herd.delays {
+            tune_herd.delays.push(Delay {
+                unit: delay.unit,
+                group: delay.group,
+                rate: delay.rate,
+                freq: delay.freq,
+                ..Delay::default()
+            });
+        }
+        for ovr in &herd.overdrives {
+            tune_herd.overdrives.push(Overdrive {
+                on: ovr.on,
+                group: ovr.group,
+                cut_percent: ovr.cut_percent,
+                amp_mul: ovr.amp_mul,
+                ..Overdrive::default()
+            });
+        }
+        let mut events = EveList::default();
+        events.eves = self
+            .events
+            .iter()
+            .filter(|eve| {
+                !matches!(
+                    eve.payload,
+                    EventPayload::BeatClock
+                        | EventPayload::BeatTempo
+                        | EventPayload::BeatNum
+                        | EventPayload::Repeat
+                        | EventPayload::Last
+                        | EventPayload::PtcowDebug(_)
+                )
+            })
+            .map(|&eve| match eve.payload {
+                EventPayload::SetVoice(idx) => Event {
+                    payload: EventPayload::SetVoice(
+                        voice_map.get(idx.usize()).copied().unwrap_or(idx),
+                    ),
+                    ..eve
+                },
+                _ => eve,
+            })
+            .collect();
+        events.sort();
+        let song = Self {
+            text: Text::default(),
+            master: Master {
+                timing: self.master.timing,
+                loop_points: LoopPoints {
+                    repeat: self.master.loop_points.repeat,
+                    last: self.master.loop_points.last,
+                },
+                meas_num: self.master.meas_num,
+            },
+            events,
+            fmt: FmtInfo {
+                kind: FmtKind::Tune,
+                ..FmtInfo::default()
+            },
+            seed: self.seed,
+        };
+        (song, tune_herd, tune_ins)
+    }
+}
+
+/// How voices are rendered before they are played
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RenderConfig {
+    /// The sample rate PCM, noise and Ogg/Vorbis voices are rendered at.
+    ///
+    /// [`NATIVE_SAMPLE_RATE`] by default, like `PxTone`. Setting it to the output sample rate
+    /// (like 48000 Hz) spares those voices a conversion to [`NATIVE_SAMPLE_RATE`] and back,
+    /// so a voice played at its basic key comes out sample for sample.
+    ///
+    /// Wave voices hold a single cycle of their waveform, so they are always rendered at
+    /// [`NATIVE_SAMPLE_RATE`]. Their pitch is the same either way.
+    pub voice_sample_rate: SampleRate,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            voice_sample_rate: NATIVE_SAMPLE_RATE,
+        }
+    }
 }
 
 /// How to moo the song
@@ -109,26 +448,60 @@ pub struct MooInstructions {
     pub voices: Voices,
     /// How many samples constitute a tick.
     pub samples_per_tick: SamplesPerTick,
+    /// Wave tables used for noise voices and unit LFOs
+    pub noise_table: NoiseTable,
+    /// The pan law used for units, and noise voices.
+    ///
+    /// Noise voices pick up changes on the next [`rebuild_tones`].
+    pub pan_law: PanLaw,
+    /// The most events played per sample, or `None` for no limit.
+    ///
+    /// Events over the limit are deferred to the following samples, so a song with a huge
+    /// number of events on the same tick can't stall playback. They still play in order,
+    /// just slightly late. See also [`Herd::budget_exhausted`].
+    pub event_budget: Option<NonZeroU32>,
+    /// How pan time offsets scale with the output sample rate.
+    ///
+    /// Takes effect on the next [`moo_prepare`](crate::moo_prepare).
+    pub pan_time_scale: PanTimeScale,
+    /// How voice samples are fetched between their recorded samples
+    pub interpolation: Interpolation,
+    /// What happens to the effect buffers when the song loops
+    pub loop_policy: LoopPolicy,
+    /// How long playback continues after the end of a song that doesn't loop
+    pub tail: Tail,
+    /// How the mix is kept within the 16 bit sample range.
+    ///
+    /// Takes effect on the next [`moo_prepare`](crate::moo_prepare).
+    pub mix_policy: MixPolicy,
+    /// Converts PCM and Ogg/Vorbis voices to the sample rate voices are played at.
+    ///
+    /// Voices pick up changes on the next [`rebuild_tones`].
+    pub resampler: Box<dyn Resampler>,
+    /// How voices are rendered.
+    ///
+    /// Voices pick up changes on the next [`rebuild_tones`].
+    pub render: RenderConfig,
+    /// Playback speed multiplier. 1.0 plays at the tempo of the song.
+    ///
+    /// Only the tempo changes, not the pitch of the voices.
+    /// Takes effect on the next [`moo_prepare`](crate::moo_prepare), or right away with
+    /// [`Herd::set_speed`].
+    pub speed: f32,
+    /// Pitch multiplier. 2.0 plays an octave higher.
+    ///
+    /// Only the pitch of the voices changes, not the tempo.
+    /// Takes effect on the next [`moo_prepare`](crate::moo_prepare), or right away with
+    /// [`Herd::set_pitch`].
+    pub pitch: f32,
 }
 
 /// The vocal cords of the cows
+///
+/// Indexed by [`VoiceIdx`]. The maximum number of voices is 100.
 #[derive(Default)]
 pub struct Voices(ArrayVec<Voice, 100>);
 
-impl std::ops::Deref for Voices {
-    type Target = ArrayVec<Voice, 100>;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl std::ops::DerefMut for Voices {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
 impl std::ops::Index<VoiceIdx> for Voices {
     type Output = Voice;
 
@@ -149,7 +522,7 @@ impl Voices {
     pub const fn len(&self) -> u8 {
         #[expect(
             clippy::cast_possible_truncation,
-            reason = "50 is the max unit number, so this always succeeds"
+            reason = "100 is the max voice number, so this always succeeds"
         )]
         (self.0.len() as u8)
     }
@@ -158,11 +531,36 @@ impl Voices {
     pub const fn is_empty(&self) -> bool {
         self.len() == 0
     }
+    /// Add a voice at the end, without building it (see [`MooInstructions::add_voice`]).
+    ///
+    /// Returns the index of the new voice, or `None` if there is no room for more voices.
+    pub fn push(&mut self, voice: Voice) -> Option<VoiceIdx> {
+        let idx = VoiceIdx(self.len());
+        self.0.try_push(voice).ok()?;
+        Some(idx)
+    }
+    /// Remove the last voice, and return it
+    pub fn pop(&mut self) -> Option<Voice> {
+        self.0.pop()
+    }
+    /// The voices as a slice, in [`VoiceIdx`] order
+    #[must_use]
+    pub fn as_slice(&self) -> &[Voice] {
+        &self.0
+    }
+    /// Immutably iterate over the voices
+    pub fn iter(&self) -> std::slice::Iter<'_, Voice> {
+        self.0.iter()
+    }
+    /// Mutably iterate over the voices
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Voice> {
+        self.0.iter_mut()
+    }
     /// Immutably iterate over the voices, along with their indices
     pub fn enumerated(&self) -> impl Iterator<Item = (VoiceIdx, &Voice)> {
         #[expect(
             clippy::cast_possible_truncation,
-            reason = "50 is the max unit number, so this always succeeds"
+            reason = "100 is the max voice number, so this always succeeds"
         )]
         self.iter().enumerate().map(|(idx, item)| (VoiceIdx(idx as u8), item))
     }
@@ -170,7 +568,7 @@ impl Voices {
     pub fn enumerated_mut(&mut self) -> impl Iterator<Item = (VoiceIdx, &mut Voice)> {
         #[expect(
             clippy::cast_possible_truncation,
-            reason = "50 is the max unit number, so this always succeeds"
+            reason = "100 is the max voice number, so this always succeeds"
         )]
         self.iter_mut().enumerate().map(|(idx, item)| (VoiceIdx(idx as u8), item))
     }
@@ -184,6 +582,43 @@ impl Voices {
     pub fn get_mut(&mut self, idx: VoiceIdx) -> Option<&mut Voice> {
         self.0.get_mut(idx.usize())
     }
+    /// Keep only the voices `keep` returns true for
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(VoiceIdx) -> bool) {
+        let mut idx = 0;
+        self.0.retain(|_| {
+            idx += 1;
+            keep(VoiceIdx(idx - 1))
+        });
+    }
+}
+
+/// Voices over the limit of 100 are dropped.
+impl Extend<Voice> for Voices {
+    fn extend<T: IntoIterator<Item = Voice>>(&mut self, iter: T) {
+        for voice in iter {
+            if self.push(voice).is_none() {
+                break;
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Voices {
+    type Item = &'a Voice;
+    type IntoIter = std::slice::Iter<'a, Voice>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Voices {
+    type Item = &'a mut Voice;
+    type IntoIter = std::slice::IterMut<'a, Voice>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
 }
 
 impl MooInstructions {
@@ -194,8 +629,78 @@ impl MooInstructions {
             out_sample_rate,
             voices: Voices::default(),
             samples_per_tick: 1.0,
+            noise_table: NoiseTable::generate(),
+            pan_law: PanLaw::default(),
+            event_budget: None,
+            pan_time_scale: PanTimeScale::default(),
+            interpolation: Interpolation::default(),
+            loop_policy: LoopPolicy::default(),
+            tail: Tail::default(),
+            mix_policy: MixPolicy::default(),
+            resampler: Box::new(NearestResampler),
+            render: RenderConfig::default(),
+            speed: 1.0,
+            pitch: 1.0,
         }
     }
+    /// Add a voice, ready to be played.
+    ///
+    /// Returns the index of the new voice, or `None` if there is no room for more voices.
+    pub fn add_voice(&mut self, mut voice: Voice) -> Option<VoiceIdx> {
+        voice.recalculate_at(
+            &self.noise_table,
+            self.out_sample_rate,
+            self.render.voice_sample_rate,
+            &*self.resampler,
+        );
+        self.voices.push(voice)
+    }
+    /// Rebuild the voice at `idx` after editing it, so it plays the edits.
+    ///
+    /// Unlike [`rebuild_tones`], only this voice is rebuilt, so this is cheap enough to
+    /// call after every edit. Cows that are already playing the voice pick up a changed key
+    /// or tuning the next time their voice is set, or on [`Herd::tune_cow_voices`].
+    ///
+    /// Returns false if there is no voice at `idx`.
+    pub fn rebuild_voice(&mut self, idx: VoiceIdx) -> bool {
+        self.noise_table.pan_law = self.pan_law;
+        let Some(voice) = self.voices.get_mut(idx) else {
+            return false;
+        };
+        voice.recalculate_at(
+            &self.noise_table,
+            self.out_sample_rate,
+            self.render.voice_sample_rate,
+            &*self.resampler,
+        );
+        true
+    }
+    /// Like [`Self::add_voice`], but if the name of the voice is already taken,
+    /// a number is added to it to make it unique (`name (2)`, `name (3)`, ...).
+    pub fn add_voice_unique(&mut self, mut voice: Voice) -> Option<VoiceIdx> {
+        voice.name = unique_name(&voice.name, |name| self.voice_by_name(name).is_some());
+        self.add_voice(voice)
+    }
+    /// The index of the first voice called `name`
+    #[must_use]
+    pub fn voice_by_name(&self, name: &str) -> Option<VoiceIdx> {
+        self.voices
+            .enumerated()
+            .find(|(_, voice)| voice.name == name)
+            .map(|(idx, _)| idx)
+    }
+}
+
+/// `name`, or if it's `taken`, `name` with the first number that makes it free
+fn unique_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_owned();
+    }
+    // There are at most 255 units (and 100 voices), so one of these is always free
+    (2..=256)
+        .map(|n| format!("{name} ({n})"))
+        .find(|name| !taken(name))
+        .unwrap_or_else(|| name.to_owned())
 }
 
 /// Adjust voice and effect tones to output sample rate
@@ -206,20 +711,156 @@ pub fn rebuild_tones(
     overdrives: &mut [Overdrive],
     master: &Master,
 ) {
-    for delay in delays {
-        delay.rebuild(
-            master.timing.beats_per_meas,
-            master.timing.bpm,
-            ins.out_sample_rate,
+    // Without effects processing, the delay buffers would never be read
+    if cfg!(feature = "effects") {
+        for delay in delays {
+            delay.rebuild(
+                master.timing.beats_per_meas,
+                master.timing.bpm,
+                ins.out_sample_rate,
+            );
+        }
+        for ovr in overdrives {
+            ovr.rebuild();
+        }
+    }
+    ins.noise_table.pan_law = ins.pan_law;
+    for voice in &mut ins.voices {
+        voice.recalculate_at(
+            &ins.noise_table,
+            out_sample_rate,
+            ins.render.voice_sample_rate,
+            &*ins.resampler,
         );
     }
-    for ovr in overdrives {
-        ovr.rebuild();
+}
+
+/// What [`reload_song`] changed
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+    /// Whether the tempo, beats per measure, or ticks per beat changed
+    pub timing_changed: bool,
+    /// The voices that changed, and had to be rebuilt
+    pub rebuilt_voices: Vec<VoiceIdx>,
+}
+
+/// Replace the song being played with the serialized project in `data`, keeping the
+/// playback position.
+///
+/// This is meant for hot reloading: when a composer saves the song in an editor, a player
+/// can swap it in without playback starting over.
+///
+/// - Playback continues at the same measure and beat in the new song, even if the tempo
+///   changed. Notes playing there are picked up like with [`SilentMode::Tones`].
+///   If the position is past the end of the new song, playback continues from the repeat
+///   point (or ends, if the song doesn't loop).
+/// - Voices that are the same as before are kept, only changed voices are rebuilt.
+/// - Mutes are kept for units with the same name. Group gains, music states, and whether
+///   the song loops are kept as well. The end and repeat points come from the new song.
+///
+/// `herd` and `ins` should be prepared for playing `song` with [`moo_prepare`].
+/// If `data` can't be read, nothing is changed.
+///
+/// [`SilentMode::Tones`]: moo::SilentMode::Tones
+/// [`moo_prepare`]: moo::moo_prepare
+pub fn reload_song(
+    data: &[u8],
+    song: &mut Song,
+    herd: &mut Herd,
+    ins: &mut MooInstructions,
+) -> ReadResult<ReloadReport> {
+    let mut new_song = Song::default();
+    let mut new_herd = Herd::default();
+    let old_voices = std::mem::take(&mut ins.voices);
+    if let Err(e) = io::read(&mut new_song, &mut new_herd, ins, data, None) {
+        ins.voices = old_voices;
+        return Err(e);
+    }
+    new_song.recalculate_length();
+    let (meas, beat) = meas_beat(herd.smp_count, ins.samples_per_tick, song.master.timing);
+    let old = song.master.timing;
+    let new = new_song.master.timing;
+    let mut report = ReloadReport {
+        timing_changed: old.bpm.to_bits() != new.bpm.to_bits()
+            || old.beats_per_meas != new.beats_per_meas
+            || old.ticks_per_beat != new.ticks_per_beat,
+        rebuilt_voices: Vec::new(),
+    };
+    let mut old_voices: Vec<Option<Voice>> = old_voices.0.into_iter().map(Some).collect();
+    ins.noise_table.pan_law = ins.pan_law;
+    for (idx, voice) in ins.voices.enumerated_mut() {
+        let unchanged = old_voices
+            .get_mut(idx.usize())
+            .and_then(|old| old.take_if(|old| same_voice_data(old, voice)));
+        if let Some(mut old) = unchanged {
+            old.name = std::mem::take(&mut voice.name);
+            *voice = old;
+        } else {
+            voice.recalculate_at(
+                &ins.noise_table,
+                ins.out_sample_rate,
+                ins.render.voice_sample_rate,
+                &*ins.resampler,
+            );
+            report.rebuilt_voices.push(idx);
+        }
+    }
+    if cfg!(feature = "effects") {
+        for delay in &mut new_herd.delays {
+            delay.rebuild(new.beats_per_meas, new.bpm, ins.out_sample_rate);
+        }
+        for ovr in &mut new_herd.overdrives {
+            ovr.rebuild();
+        }
     }
-    let builder = NoiseTable::generate();
-    for voice in ins.voices.iter_mut() {
-        voice.recalculate(&builder, out_sample_rate);
+    for unit in new_herd.units.iter_mut() {
+        if let Some(old) = herd.units.iter().find(|old| old.name == unit.name) {
+            unit.mute = old.mute;
+        }
+    }
+    herd.units = new_herd.units;
+    herd.delays = new_herd.delays;
+    herd.overdrives = new_herd.overdrives;
+    *song = new_song;
+    let plan = moo::MooPlan {
+        start_pos: moo::StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: herd.loop_,
+    };
+    moo::moo_prepare(ins, herd, song, &plan)?;
+    herd.moo_end = false;
+    let beats_per_meas = f64::from(new.beats_per_meas);
+    let beats = meas.mul_add(beats_per_meas, beat.min(beats_per_meas));
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let mut to_sample =
+        (beats * f64::from(new.ticks_per_beat) * f64::from(ins.samples_per_tick)) as SampleT;
+    if to_sample >= herd.smp_end && herd.loop_ {
+        to_sample = herd.smp_repeat;
     }
+    herd.advance_silent(ins, song, to_sample, moo::SilentMode::Tones);
+    Ok(report)
+}
+
+/// The measure at `smp`, and the beat within it (both counted from 0, the beat with fractions)
+fn meas_beat(smp: SampleT, samples_per_tick: SamplesPerTick, timing: Timing) -> (f64, f64) {
+    if samples_per_tick <= 0.0 || !timing.is_valid() {
+        return (0.0, 0.0);
+    }
+    #[expect(clippy::cast_precision_loss)]
+    let beats = smp as f64 / f64::from(samples_per_tick) / f64::from(timing.ticks_per_beat);
+    let meas = (beats / f64::from(timing.beats_per_meas)).floor();
+    (meas, meas.mul_add(-f64::from(timing.beats_per_meas), beats))
+}
+
+/// Whether two voices would be serialized the same way, so they build the same
+fn same_voice_data(a: &Voice, b: &Voice) -> bool {
+    let (mut a_bytes, mut b_bytes) = (Vec::new(), Vec::new());
+    io::write_voice_chunk(a, &mut a_bytes).is_ok()
+        && io::write_layer_entry(a, &mut a_bytes).is_ok()
+        && io::write_voice_chunk(b, &mut b_bytes).is_ok()
+        && io::write_layer_entry(b, &mut b_bytes).is_ok()
+        && a_bytes == b_bytes
 }
 
 /// The glorious cows that are going to moo your song
@@ -230,7 +871,7 @@ pub struct Herd {
     /// Usually set after a song without `loop` finished playing.
     pub moo_end: bool,
     loop_: bool,
-    smp_smooth: SampleRate,
+    smp_smooth: u16,
     /// Counter variable for what sample we are at
     pub smp_count: SampleT,
     smp_start: SampleT,
@@ -240,6 +881,7 @@ pub struct Herd {
     pub smp_repeat: SampleT,
     smp_stride: f32,
     time_pan_index: usize,
+    pan_time_len: PanTimeLen,
     /// What event to play next
     pub evt_idx: usize,
     /// The 🐄 cow units that drive music synthesis. Each one outputs a PCM stream that's mixed
@@ -249,23 +891,69 @@ pub struct Herd {
     pub delays: Delays,
     /// Overdrive (amplify + clip) effects
     pub overdrives: Overdrives,
+    group_gains: GroupGains,
+    /// How many samples ran out of [`MooInstructions::event_budget`], and deferred events
+    ///
+    /// If this keeps growing, the song is likely malformed or hostile.
+    pub budget_exhausted: u64,
+    /// Music states for interactive music, see [`Self::trigger_state`]
+    pub states: MusicStates,
+    /// Samples left of the [`Tail`], once the song has ended
+    tail_left: Option<SampleT>,
+    /// How many samples the output has been silent for, see [`Tail::UntilSilent`]
+    tail_silent: SampleT,
+    meter: LevelMeter,
+    /// The gain applied by [`MixPolicy::AutoGain`]
+    auto_gain: Option<f32>,
+    /// See [`Self::set_event_observer`]
+    event_observer: Option<Box<EventObserver>>,
+    /// See [`Self::seek_with_crossfade`]
+    seek_fade: Option<Box<moo::SeekFade>>,
+}
+
+/// Called with every event that's played, see [`Herd::set_event_observer`]
+pub type EventObserver = dyn FnMut(&Event, SampleT) + Send + Sync;
+
+/// Length of the pan time ring buffers of the units, see [`PanTimeScale::buf_len`]
+#[derive(Clone, Copy)]
+struct PanTimeLen(usize);
+
+impl Default for PanTimeLen {
+    fn default() -> Self {
+        Self(PanTimeScale::Reference.buf_len(NATIVE_SAMPLE_RATE))
+    }
+}
+
+/// Gain multipliers applied to each sample group when mixing them together
+struct GroupGains([f32; GroupSamples::LEN]);
+
+impl Default for GroupGains {
+    fn default() -> Self {
+        Self([1.0; _])
+    }
 }
 
 pub type Delays = ArrayVec<Delay, 4>;
 pub type Overdrives = ArrayVec<Overdrive, 2>;
 /// The 🐄[cow](Unit)s that moo the song.
 ///
-/// The maximum number of them is 50.
+/// PxTone supports up to [`Self::MAX_STANDARD`] (50) of them. A herd can hold up to
+/// [`Self::MAX`] (use [`Herd::add_unit`] to stay within the limit), but songs with more than
+/// 50 units can only be saved with [`serialize_project_extended`].
 #[derive(Default)]
-pub struct Units(pub(crate) ArrayVec<Unit, 50>);
+pub struct Units(pub(crate) Vec<Unit>);
 
 impl Units {
+    /// The most units the PxTone format supports
+    pub const MAX_STANDARD: u8 = 50;
+    /// The most units a herd can hold, limited by [`UnitIdx`]
+    pub const MAX: u8 = u8::MAX;
     /// The current number of cows
     #[must_use]
     pub const fn len(&self) -> u8 {
         #[expect(
             clippy::cast_possible_truncation,
-            reason = "50 is the max unit number, so this always succeeds"
+            reason = "255 is the max unit number, so this always succeeds"
         )]
         (self.0.len() as u8)
     }
@@ -278,7 +966,7 @@ impl Units {
     pub fn enumerated(&self) -> impl Iterator<Item = (UnitIdx, &Unit)> {
         #[expect(
             clippy::cast_possible_truncation,
-            reason = "50 is the max unit number, so this always succeeds"
+            reason = "255 is the max unit number, so this always succeeds"
         )]
         self.iter().enumerate().map(|(idx, item)| (UnitIdx(idx as u8), item))
     }
@@ -286,7 +974,7 @@ impl Units {
     pub fn enumerated_mut(&mut self) -> impl Iterator<Item = (UnitIdx, &mut Unit)> {
         #[expect(
             clippy::cast_possible_truncation,
-            reason = "50 is the max unit number, so this always succeeds"
+            reason = "255 is the max unit number, so this always succeeds"
         )]
         self.iter_mut().enumerate().map(|(idx, item)| (UnitIdx(idx as u8), item))
     }
@@ -303,7 +991,7 @@ impl Units {
 }
 
 impl std::ops::Deref for Units {
-    type Target = ArrayVec<Unit, 50>;
+    type Target = Vec<Unit>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -331,12 +1019,152 @@ impl<T: Into<UnitIdx>> std::ops::IndexMut<T> for Units {
 }
 
 impl Herd {
+    /// Add a new unit with `name`.
+    ///
+    /// Returns the index of the new unit, or `None` if there is no room for more units
+    /// (see [`Units::MAX`]).
+    /// The unit starts out with the first voice, and is ready to play after [`moo_prepare`].
+    ///
+    /// [`moo_prepare`]: crate::moo_prepare
+    pub fn add_unit(&mut self, name: impl Into<String>) -> Option<UnitIdx> {
+        if self.units.len() == Units::MAX {
+            return None;
+        }
+        let idx = UnitIdx(self.units.len());
+        let mut unit = Unit::new();
+        unit.name = name.into();
+        self.units.0.push(unit);
+        Some(idx)
+    }
+    /// Like [`Self::add_unit`], but if `name` is already taken, a number is added to it
+    /// to make it unique (`name (2)`, `name (3)`, ...).
+    pub fn add_unit_unique(&mut self, name: &str) -> Option<UnitIdx> {
+        let name = unique_name(name, |name| self.unit_by_name(name).is_some());
+        self.add_unit(name)
+    }
+    /// The index of the first unit called `name`
+    #[must_use]
+    pub fn unit_by_name(&self, name: &str) -> Option<UnitIdx> {
+        self.units.enumerated().find(|(_, unit)| unit.name == name).map(|(idx, _)| idx)
+    }
+    pub(crate) const fn set_loop(&mut self, loop_: bool) {
+        self.loop_ = loop_;
+    }
+    pub(crate) const fn loops(&self) -> bool {
+        self.loop_
+    }
     /// Seek to sample count
     pub const fn seek_to_sample(&mut self, sample: SampleT) {
         self.smp_count = sample;
         // If we set the event index to zero, the correct event index will be found when we moo
         self.evt_idx = 0;
     }
+    /// Switch to the [`MusicState`](crate::states::MusicState) called `name`.
+    ///
+    /// The switch happens while playing with [`Self::moo_states`].
+    /// A transition that is still waiting for its measure is replaced.
+    ///
+    /// Returns false if there is no such state in [`Self::states`].
+    pub fn trigger_state(&mut self, name: &str, transition: Transition) -> bool {
+        self.states.trigger(name, transition)
+    }
+    /// Move a unit to another sample group while playing, without clicks.
+    ///
+    /// Setting [`Unit::group`] directly (or a [`SetGroup`](EventPayload::SetGroup) event)
+    /// switches instantly, so a note that's playing jumps from one set of effects to another.
+    /// This crossfades the output of the unit from the old group to the new one over a few
+    /// milliseconds instead, rendering into both groups while the fade lasts.
+    ///
+    /// Returns false if there is no such unit, or `group` is out of range.
+    pub fn set_unit_group(&mut self, unit: UnitIdx, group: GroupIdx) -> bool {
+        if group.0 > GroupIdx::MAX.0 {
+            return false;
+        }
+        let Some(unit) = self.units.get_mut(unit) else {
+            return false;
+        };
+        unit.fade_to_group(group, self.smp_smooth);
+        true
+    }
+    /// Set the gain multiplier of a sample group.
+    ///
+    /// The gain is applied to the output of the group (after effects) when the groups are
+    /// mixed together for the final output. 1.0 is the normal volume, 0.0 silences the group.
+    ///
+    /// Out of range group indices are ignored.
+    pub fn set_group_gain(&mut self, group: GroupIdx, gain: f32) {
+        if let Some(g) = self.group_gains.0.get_mut(group.usize()) {
+            *g = gain;
+        }
+    }
+    /// Change the settings of an overdrive in [`Self::overdrives`] while playing.
+    ///
+    /// The settings are validated, and the overdrive is [rebuilt](Overdrive::rebuild), so
+    /// the change is heard from the next sample [mooed](Self::moo).
+    ///
+    /// # Errors
+    ///
+    /// If there is no overdrive at `idx`, or the settings are
+    /// [invalid](OverdriveSettings::validate). Nothing is changed then.
+    pub fn update_overdrive(
+        &mut self,
+        idx: usize,
+        settings: OverdriveSettings,
+    ) -> Result<(), OverdriveError> {
+        self.overdrives
+            .get_mut(idx)
+            .ok_or(OverdriveError::NoSuchOverdrive(idx))?
+            .update(settings)
+    }
+    /// Get the gain multiplier of a sample group (see [`Self::set_group_gain`]).
+    ///
+    /// Returns 1.0 for out of range group indices.
+    #[must_use]
+    pub fn group_gain(&self, group: GroupIdx) -> f32 {
+        self.group_gains.0.get(group.usize()).copied().unwrap_or(1.0)
+    }
+    /// How far the play position of a voice advances per output sample, for a note played at
+    /// the rate of the voice.
+    ///
+    /// This is the ratio of [`NATIVE_SAMPLE_RATE`] to the output sample rate, times
+    /// [`MooInstructions::pitch`], set by [`moo_prepare`](crate::moo_prepare). See [`Interpolation`] for how samples are picked
+    /// at the fractional positions this results in.
+    #[must_use]
+    pub const fn smp_stride(&self) -> f32 {
+        self.smp_stride
+    }
+    /// Reset the gain of every sample group to 1.0
+    pub fn reset_group_gains(&mut self) {
+        self.group_gains = GroupGains::default();
+    }
+    /// The gain the mix is turned down by with [`MixPolicy::AutoGain`], or `None` with
+    /// other policies.
+    ///
+    /// 1.0 if the song doesn't clip. Set by [`moo_prepare`](crate::moo_prepare).
+    #[must_use]
+    pub const fn auto_gain(&self) -> Option<f32> {
+        self.auto_gain
+    }
+    /// The most recent samples the unit at `idx` rendered, see [`UnitTap`].
+    ///
+    /// Returns `None` if there is no unit at `idx`.
+    #[must_use]
+    pub fn unit_tap(&self, idx: UnitIdx) -> Option<UnitTap<'_>> {
+        Some(self.units.get(idx)?.tap(self.time_pan_index, self.pan_time_len.0))
+    }
+    /// The output levels of the units, indexed by [`UnitIdx`].
+    ///
+    /// Updated by [`Self::moo`] and friends. The RMS covers the last call, so it's as
+    /// responsive as the buffer size, while the peaks are kept until
+    /// [`Self::reset_unit_peaks`].
+    #[must_use]
+    pub fn unit_levels(&self) -> &[UnitLevel] {
+        self.meter.levels()
+    }
+    /// Reset the peaks of [`Self::unit_levels`], for example after drawing them
+    pub fn reset_unit_peaks(&mut self) {
+        self.meter.reset_peaks();
+    }
     /// Make sure all the cows' voices are ready for playback
     pub fn tune_cow_voices(&mut self, ins: &MooInstructions, timing: Timing) {
         for unit in self.units.iter_mut() {
@@ -356,6 +1184,8 @@ impl Herd {
 ///
 /// The current organization structure is a bit arbitrary, reached after a lot of refactoring
 /// of various parts of the codebase. It will probably change in future releases to a cleaner API.
+/// [`Project`](crate::Project) bundles the three together, for when you don't need to
+/// keep them apart.
 ///
 /// ## Playback
 ///
@@ -366,6 +1196,69 @@ pub fn read_song(
     data: &[u8],
     out_sample_rate: SampleRate,
 ) -> ReadResult<(Song, Herd, MooInstructions)> {
+    read_song_with_warnings(data, out_sample_rate).map(|(song, herd, ins, _)| (song, herd, ins))
+}
+
+/// Like [`read_song`], but also returns a list of [`ReadWarning`]s about unusual things
+/// in the song (like not having any units), which don't prevent it from being read.
+#[expect(clippy::missing_errors_doc)]
+pub fn read_song_with_warnings(
+    data: &[u8],
+    out_sample_rate: SampleRate,
+) -> ReadResult<(Song, Herd, MooInstructions, Vec<ReadWarning>)> {
+    read_song_impl(out_sample_rate, false, |song, herd, ins, issues| {
+        io::read(song, herd, ins, data, issues)
+    })
+}
+
+/// Like [`read_song_with_warnings`], but tolerates some damage in the song, like the
+/// original PxTone player does.
+///
+/// Voices that can't be read are replaced with silent voices, effects that can't be read
+/// are left out, and invalid timing (like a tempo of 0) is replaced by the defaults.
+/// These are reported as [`ReadWarning`]s too, so a player can still play
+/// slightly corrupted songs, and tell the user about what's missing.
+///
+/// Damage that leaves nothing playable (like a broken header or event list) is still an error.
+#[expect(clippy::missing_errors_doc)]
+pub fn read_song_lenient(
+    data: &[u8],
+    out_sample_rate: SampleRate,
+) -> ReadResult<(Song, Herd, MooInstructions, Vec<ReadWarning>)> {
+    read_song_impl(out_sample_rate, true, |song, herd, ins, issues| {
+        io::read(song, herd, ins, data, issues)
+    })
+}
+
+/// Like [`read_song`], but reads the project from a stream, like a file or a network
+/// connection, instead of needing all of it in memory first.
+///
+/// The stream is read one chunk at a time, so only the chunk being read (like a single
+/// Ogg/Vorbis voice) is buffered on top of the project itself.
+/// If the whole project is already in memory, [`read_song`] is faster.
+///
+/// The stream doesn't need to be buffered, as chunks are read with few large reads.
+#[expect(clippy::missing_errors_doc)]
+pub fn read_song_from(
+    src: impl std::io::Read,
+    out_sample_rate: SampleRate,
+) -> Result<(Song, Herd, MooInstructions), StreamReadError> {
+    read_song_impl(out_sample_rate, false, |song, herd, ins, issues| {
+        io::read_from(song, herd, ins, src, issues)
+    })
+    .map(|(song, herd, ins, _)| (song, herd, ins))
+}
+
+fn read_song_impl<E: From<ProjectReadError>>(
+    out_sample_rate: SampleRate,
+    lenient: bool,
+    read: impl FnOnce(
+        &mut Song,
+        &mut Herd,
+        &mut MooInstructions,
+        Option<&mut Vec<ReadWarning>>,
+    ) -> Result<(), E>,
+) -> Result<(Song, Herd, MooInstructions, Vec<ReadWarning>), E> {
     let mut song = Song {
         text: Text::default(),
         master: Master::default(),
@@ -373,18 +1266,37 @@ pub fn read_song(
         fmt: FmtInfo {
             ver: FmtVer::V5,
             kind: FmtKind::Collage,
-            exe_ver: 0,
+            exe_ver: ExeVer::Unset,
             dummy: 0,
         },
+        seed: None,
     };
     let mut ins = MooInstructions {
         out_sample_rate,
         voices: Voices::default(),
         samples_per_tick: 0.0,
+        noise_table: NoiseTable::generate(),
+        pan_law: PanLaw::default(),
+        event_budget: None,
+        pan_time_scale: PanTimeScale::default(),
+        interpolation: Interpolation::default(),
+        loop_policy: LoopPolicy::default(),
+        tail: Tail::default(),
+        mix_policy: MixPolicy::default(),
+        resampler: Box::new(NearestResampler),
+        render: RenderConfig::default(),
+        speed: 1.0,
+        pitch: 1.0,
     };
     let mut herd = Herd::default();
 
-    io::read(&mut song, &mut herd, &mut ins, data)?;
+    let mut issues = Vec::new();
+    read(
+        &mut song,
+        &mut herd,
+        &mut ins,
+        lenient.then_some(&mut issues),
+    )?;
     song.recalculate_length();
     rebuild_tones(
         &mut ins,
@@ -393,10 +1305,579 @@ pub fn read_song(
         &mut herd.overdrives,
         &song.master,
     );
-    Ok((song, herd, ins))
+    issues.extend(read_warnings(&song, &herd, &ins));
+    Ok((song, herd, ins, issues))
+}
+
+fn read_warnings(song: &Song, herd: &Herd, ins: &MooInstructions) -> Vec<ReadWarning> {
+    let mut warnings = Vec::new();
+    if herd.units.is_empty() {
+        warnings.push(ReadWarning::NoUnits);
+    }
+    if ins.voices.is_empty() {
+        warnings.push(ReadWarning::NoVoices);
+    }
+    let missing_units = song.events.iter().filter(|eve| eve.unit.0 >= herd.units.len()).count();
+    if missing_units != 0 {
+        warnings.push(ReadWarning::MissingUnits(missing_units));
+    }
+    let missing_voices = song
+        .events
+        .iter()
+        .filter(|eve| {
+            matches!(eve.payload, EventPayload::SetVoice(voice) if ins.voices.get(voice).is_none())
+        })
+        .count();
+    if missing_voices != 0 {
+        warnings.push(ReadWarning::MissingVoices(missing_voices));
+    }
+    if let ExeVer::Unknown(raw) = song.fmt.exe_ver {
+        warnings.push(ReadWarning::UnknownExeVer(raw));
+    }
+    warnings
+}
+
+/// Change the name and/or comment of a song in serialized project `data`, without touching
+/// any other bytes.
+///
+/// Unlike reading the song and serializing it again, this can't change how the song sounds,
+/// so it's safe to use on large collections of songs.
+///
+/// # Errors
+///
+/// Returns an error if `data` isn't a valid project file.
+pub fn rewrite_metadata(data: &[u8], patch: &TextPatch) -> ReadResult<Vec<u8>> {
+    io::rewrite_metadata(data, patch)
+}
+
+/// Compare two serialized project files chunk by chunk.
+///
+/// Chunks are matched by their tag code and their order among the chunks with the same code,
+/// so an inserted or removed chunk doesn't make every following chunk differ.
+/// Useful for finding out which part of a project doesn't survive a round-trip.
+///
+/// Returns an empty list if the files are identical.
+///
+/// # Errors
+///
+/// Returns an error if either file isn't a valid project file.
+pub fn diff_chunks(left: &[u8], right: &[u8]) -> ReadResult<Vec<ChunkDiff>> {
+    io::diff_chunks(left, right)
+}
+
+/// The tag code of the chunk of a serialized project file that `offset` falls into.
+///
+/// Useful for finding out which chunk a [`ProjectReadError::Data`](crate::ProjectReadError::Data) error happened in.
+/// Returns `None` if `offset` is in the version header, or after the end chunk.
+#[must_use]
+pub fn chunk_at_offset(data: &[u8], offset: usize) -> Option<[u8; 8]> {
+    io::chunk_at_offset(data, offset)
+}
+
+/// Find out how many bytes each part of the project takes up when serialized
+/// with [`serialize_project`].
+///
+/// Useful for finding what to trim when a project needs to fit in a size budget.
+pub fn project_size_breakdown(
+    song: &Song,
+    herd: &Herd,
+    ins: &MooInstructions,
+) -> WriteResult<SizeBreakdown> {
+    io::size_breakdown(song, herd, ins)
 }
 
 /// Serialize the project into the PxTone file format
+///
+/// Fails with [`ProjectWriteError::TooManyUnits`](crate::ProjectWriteError::TooManyUnits)
+/// if the herd has more than [`Units::MAX_STANDARD`] units, see
+/// [`serialize_project_extended`].
 pub fn serialize_project(song: &Song, herd: &Herd, ins: &MooInstructions) -> WriteResult<Vec<u8>> {
     io::write(song, herd, ins)
 }
+
+/// Serialize the project like [`serialize_project`], but write it to a stream,
+/// like a file, instead of returning it.
+///
+/// The sample data of PCM and Ogg/Vorbis voices is written straight from the voices,
+/// so exporting projects with many sampled voices doesn't need a copy of all of them
+/// in memory. The stream is flushed at the end.
+#[expect(clippy::missing_errors_doc)]
+pub fn serialize_project_to(
+    dst: impl std::io::Write,
+    song: &Song,
+    herd: &Herd,
+    ins: &MooInstructions,
+) -> Result<(), StreamWriteError> {
+    io::write_to(dst, song, herd, ins)
+}
+
+/// Serialize the project like [`serialize_project`], but with support for more than
+/// [`Units::MAX_STANDARD`] units.
+///
+/// The first 50 units and their events are written the normal way, so PxTone can still open
+/// the song, without the extra units.
+/// The rest of the units and their events go into a ptcow specific extension chunk after the
+/// end of the file, which [`read_song`] reads back.
+pub fn serialize_project_extended(
+    song: &Song,
+    herd: &Herd,
+    ins: &MooInstructions,
+) -> WriteResult<Vec<u8>> {
+    io::write_extended(song, herd, ins)
+}
+
+/// Serialize the project as a minimal tune (`.pttune`), like PxTone's "save as tune" does.
+///
+/// Tunes are meant for embedding into games, so the names of units and voices, which are
+/// only used by the editor, are left out. The song name and comment are kept.
+/// The tune is always written in the latest format version, regardless of [`Song::fmt`].
+pub fn serialize_tune(song: &Song, herd: &Herd, ins: &MooInstructions) -> WriteResult<Vec<u8>> {
+    io::write_tune(song, herd, ins)
+}
+
+#[test]
+fn test_empty_song() {
+    use crate::moo_prepare;
+    let mut song = Song::default();
+    song.master.meas_num = 1;
+    let data = serialize_project(
+        &song,
+        &Herd::default(),
+        &MooInstructions::new(NATIVE_SAMPLE_RATE),
+    )
+    .unwrap();
+    let (song, mut herd, mut ins, warnings) =
+        read_song_with_warnings(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(warnings, [ReadWarning::NoUnits, ReadWarning::NoVoices]);
+    let plan = moo::MooPlan {
+        start_pos: moo::StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: false,
+    };
+    moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+    let mut buf = vec![0i16; 4096];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert!(buf.iter().all(|&s| s == 0));
+    // Add the first voice and unit
+    let mut song = song;
+    let voice = crate::presets::wave::factory_voices().swap_remove(0);
+    assert_eq!(ins.add_voice(voice), Some(VoiceIdx(0)));
+    assert_eq!(herd.add_unit("first"), Some(UnitIdx(0)));
+    song.events.eves.push(Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(0),
+        tick: 0,
+    });
+    song.recalculate_length();
+    moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+    herd.moo(&ins, &song, &mut buf, true);
+    assert!(buf.iter().any(|&s| s != 0));
+}
+
+#[test]
+fn test_exe_ver() {
+    for raw in [0, 925, 1234] {
+        assert_eq!(ExeVer::from_raw(raw).raw(), raw);
+    }
+    assert_eq!(ExeVer::from_raw(925), ExeVer::Pxtone_0_9_2_5);
+    let herd = Herd::default();
+    let ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    let mut song = Song::default();
+    song.master.meas_num = 1;
+    for (exe_ver, warning) in [
+        (ExeVer::Pxtone_0_9_2_5, None),
+        (
+            ExeVer::Unknown(1234),
+            Some(ReadWarning::UnknownExeVer(1234)),
+        ),
+    ] {
+        song.fmt.exe_ver = exe_ver;
+        let data = serialize_project(&song, &herd, &ins).unwrap();
+        let (read, _, _, warnings) = read_song_with_warnings(&data, NATIVE_SAMPLE_RATE).unwrap();
+        assert_eq!(read.fmt.exe_ver, exe_ver);
+        // Other warnings (like the song being empty) don't matter here
+        let exe_warning =
+            warnings.iter().find(|warning| matches!(warning, ReadWarning::UnknownExeVer(_)));
+        assert_eq!(exe_warning.copied(), warning);
+    }
+}
+
+#[test]
+fn test_update_overdrive() {
+    let mut herd = Herd::default();
+    let settings = OverdriveSettings {
+        on: true,
+        group: GroupIdx(1),
+        cut_percent: 90.0,
+        amp_mul: 2.0,
+    };
+    assert_eq!(
+        herd.update_overdrive(0, settings),
+        Err(OverdriveError::NoSuchOverdrive(0))
+    );
+    herd.overdrives.push(Overdrive::default());
+    herd.update_overdrive(0, settings).unwrap();
+    assert_eq!(herd.overdrives[0].settings(), settings);
+    assert_eq!(herd.overdrives[0].cut_16bit_top, 3276);
+    for (bad, err) in [
+        (
+            OverdriveSettings {
+                group: GroupIdx(7),
+                ..settings
+            },
+            OverdriveError::GroupOutOfRange(GroupIdx(7)),
+        ),
+        (
+            OverdriveSettings {
+                cut_percent: 100.0,
+                ..settings
+            },
+            OverdriveError::CutOutOfRange(100.0),
+        ),
+        (
+            OverdriveSettings {
+                amp_mul: 0.0,
+                ..settings
+            },
+            OverdriveError::AmpOutOfRange(0.0),
+        ),
+    ] {
+        assert_eq!(herd.update_overdrive(0, bad), Err(err));
+        assert_eq!(herd.overdrives[0].settings(), settings);
+    }
+    // The on flag isn't saved, so like in PxTone, the overdrive is read back on
+    herd.update_overdrive(
+        0,
+        OverdriveSettings {
+            on: false,
+            ..settings
+        },
+    )
+    .unwrap();
+    let mut song = Song::default();
+    song.master.meas_num = 1;
+    let ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    let data = serialize_project(&song, &herd, &ins).unwrap();
+    let (_, read, _) = read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(read.overdrives[0].settings(), settings);
+}
+
+#[test]
+fn test_by_name() {
+    let mut herd = Herd::default();
+    let drums = herd.add_unit("drums");
+    let lead = herd.add_unit_unique("lead");
+    assert_eq!(herd.add_unit_unique("drums"), Some(UnitIdx(2)));
+    assert_eq!(herd.add_unit_unique("drums"), Some(UnitIdx(3)));
+    assert_eq!(herd.unit_by_name("drums"), drums);
+    assert_eq!(herd.unit_by_name("lead"), lead);
+    assert_eq!(herd.unit_by_name("drums (3)"), Some(UnitIdx(3)));
+    assert_eq!(herd.unit_by_name("bass"), None);
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    for voice in crate::presets::wave::factory_voices().into_iter().take(2) {
+        let name = voice.name.clone();
+        let idx = ins.add_voice_unique(voice);
+        assert_eq!(ins.voice_by_name(&name), idx);
+    }
+    let voice = ins.voices[VoiceIdx(0)].clone();
+    let name = voice.name.clone();
+    let idx = ins.add_voice_unique(voice);
+    assert_eq!(ins.voice_by_name(&format!("{name} (2)")), idx);
+    assert_eq!(ins.voice_by_name(&name), Some(VoiceIdx(0)));
+}
+
+#[test]
+fn test_reload_song() {
+    use crate::{current_tick, moo_prepare};
+    let mut song = Song::default();
+    song.events.eves.push(Event {
+        payload: EventPayload::On { duration: 480 * 16 },
+        unit: UnitIdx(0),
+        tick: 0,
+    });
+    song.recalculate_length();
+    let mut herd = Herd::default();
+    herd.add_unit("lead");
+    herd.add_unit("bass");
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    for voice in crate::presets::wave::factory_voices().into_iter().take(2) {
+        ins.add_voice(voice);
+    }
+    let data = serialize_project(&song, &herd, &ins).unwrap();
+    let plan = moo::MooPlan {
+        start_pos: moo::StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: true,
+    };
+    moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+    herd.units[UnitIdx(1)].mute = true;
+    // Measure 1, beat 2
+    let tick = (4 + 2) * 480;
+    herd.advance_silent(&ins, &song, 6 * 22050, moo::SilentMode::Tones);
+    assert_eq!(current_tick(&herd, &ins), tick);
+    // Unreadable data changes nothing
+    assert!(reload_song(b"garbage", &mut song, &mut herd, &mut ins).is_err());
+    assert_eq!(ins.voices.len(), 2);
+    assert_eq!(current_tick(&herd, &ins), tick);
+    // Double the tempo, and change the second voice
+    let (mut song2, herd2, mut ins2) = read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    song2.master.timing.bpm = 240.0;
+    ins2.voices[VoiceIdx(1)].base.unit.tuning = 2.0;
+    let data2 = serialize_project(&song2, &herd2, &ins2).unwrap();
+    let report = reload_song(&data2, &mut song, &mut herd, &mut ins).unwrap();
+    assert!(report.timing_changed);
+    assert_eq!(report.rebuilt_voices, [VoiceIdx(1)]);
+    assert_eq!(song.master.timing.bpm.to_bits(), 240.0f32.to_bits());
+    assert_eq!(herd.smp_count, 6 * 22050 / 2);
+    assert_eq!(current_tick(&herd, &ins), tick);
+    assert!(herd.units[UnitIdx(1)].mute);
+    assert!(!herd.units[UnitIdx(0)].mute);
+    let mut buf = vec![0i16; 4096];
+    assert!(herd.moo(&ins, &song, &mut buf, true));
+    assert!(buf.iter().any(|&s| s != 0));
+}
+
+#[test]
+fn test_chunk_at_offset() {
+    let data = serialize_project(
+        &Song::default(),
+        &Herd::default(),
+        &MooInstructions::new(NATIVE_SAMPLE_RATE),
+    )
+    .unwrap();
+    assert_eq!(chunk_at_offset(&data, 0), None);
+    assert_eq!(chunk_at_offset(&data, 20), Some(*b"MasterV5"));
+    // The event chunk comes right after the master chunk
+    let events = 20 + 12 + 15;
+    assert_eq!(chunk_at_offset(&data, events), Some(*b"Event V5"));
+    assert_eq!(chunk_at_offset(&data, data.len()), None);
+    // A cut off chunk still has its code
+    assert_eq!(
+        chunk_at_offset(&data[..events + 10], events + 9),
+        Some(*b"Event V5")
+    );
+}
+
+#[test]
+fn test_serialize_project_to() {
+    /// Records every write separately
+    struct Writes(Vec<Vec<u8>>);
+    impl std::io::Write for Writes {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.push(buf.to_vec());
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    let mut pcm = crate::PcmData::new();
+    pcm.create(
+        crate::ChNum::Mono,
+        NATIVE_SAMPLE_RATE.into(),
+        crate::Bps::B16,
+        4096,
+    );
+    for (i, smp) in pcm.smp.iter_mut().enumerate() {
+        *smp = i.to_le_bytes()[0];
+    }
+    ins.add_voice(Voice::from_data(VoiceData::Pcm(pcm.clone())));
+    ins.voices.extend(crate::presets::wave::factory_voices().into_iter().take(1));
+    let mut herd = Herd::default();
+    herd.add_unit("sampled");
+    let song = Song::default();
+    let mut writes = Writes(Vec::new());
+    serialize_project_to(&mut writes, &song, &herd, &ins).unwrap();
+    assert_eq!(
+        writes.0.concat(),
+        serialize_project(&song, &herd, &ins).unwrap()
+    );
+    // The samples went out without being copied into the buffer
+    assert!(writes.0.contains(&pcm.smp));
+    // Errors of the stream are passed on
+    let mut full = [0u8; 100];
+    assert!(matches!(
+        serialize_project_to(&mut full[..], &song, &herd, &ins),
+        Err(StreamWriteError::Io(_))
+    ));
+}
+
+#[test]
+fn test_can_downgrade_to() {
+    let mut song = Song::default();
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(crate::presets::wave::factory_voices().into_iter().take(1));
+    ins.add_voice(Voice::from_data(VoiceData::OggV(crate::OggVData {
+        raw_bytes: Vec::new(),
+        ch: 1,
+        sps2: 44100,
+        smp_num: 0,
+    })));
+    assert_eq!(song.can_downgrade_to(FmtVer::V5, &ins), []);
+    assert_eq!(
+        song.can_downgrade_to(FmtVer::V4, &ins),
+        [DowngradeBlocker::OggVoice(VoiceIdx(1))]
+    );
+    song.fmt.kind = FmtKind::Tune;
+    assert_eq!(
+        song.can_downgrade_to(FmtVer::V1, &ins),
+        [
+            DowngradeBlocker::TuneKind,
+            DowngradeBlocker::NonPcmVoice(VoiceIdx(0)),
+            DowngradeBlocker::OggVoice(VoiceIdx(1)),
+        ]
+    );
+    song.events.eves = vec![Event {
+        payload: EventPayload::PitchBend(100),
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    assert_eq!(
+        song.can_downgrade_to(FmtVer::V5, &ins),
+        [DowngradeBlocker::ExtensionEvents(1)]
+    );
+}
+
+#[test]
+fn test_to_tune() {
+    let mut song = Song::default();
+    song.text.name = "song".into();
+    song.text.comment = "comment".into();
+    let eve = |unit, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick: 0,
+    };
+    song.events.eves = vec![
+        eve(0, EventPayload::On { duration: 480 }),
+        eve(0, EventPayload::SetVoice(VoiceIdx(2))),
+        eve(1, EventPayload::BeatClock),
+        eve(1, EventPayload::SetVoice(VoiceIdx(1))),
+        eve(1, EventPayload::On { duration: 480 }),
+        eve(1, EventPayload::Repeat),
+    ];
+    song.events.sort();
+    song.recalculate_length();
+    let mut herd = Herd::default();
+    herd.add_unit("lead");
+    herd.add_unit("bass");
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    let voices = crate::presets::wave::factory_voices();
+    for voice in [&voices[0], &voices[1], &voices[0]] {
+        let mut voice = voice.clone();
+        voice.name = "named".into();
+        ins.add_voice(voice);
+    }
+    let (tune, tune_herd, tune_ins) = song.to_tune(&herd, &ins);
+    assert!(tune.text.name.is_empty() && tune.text.comment.is_empty());
+    assert_eq!(tune.fmt.kind, FmtKind::Tune);
+    assert!(tune_herd.units.iter().all(|unit| unit.name.is_empty()));
+    assert_eq!(tune_ins.voices.len(), 2);
+    assert!(tune_ins.voices.iter().all(|voice| voice.name.is_empty()));
+    let payloads: Vec<_> = tune.events.iter().map(|eve| (eve.unit.0, eve.payload)).collect();
+    assert_eq!(
+        payloads,
+        [
+            (0, EventPayload::SetVoice(VoiceIdx(0))),
+            (1, EventPayload::SetVoice(VoiceIdx(1))),
+            (0, EventPayload::On { duration: 480 }),
+            (1, EventPayload::On { duration: 480 }),
+        ]
+    );
+    let project = serialize_project(&song, &herd, &ins).unwrap();
+    let tune_data = serialize_tune(&tune, &tune_herd, &tune_ins).unwrap();
+    assert!(tune_data.len() < project.len());
+    // Plays the same
+    let render = |song: &Song, herd: &mut Herd, ins: &mut MooInstructions| {
+        let plan = moo::MooPlan {
+            start_pos: moo::StartPosPlan::Sample(0),
+            meas_end: None,
+            meas_repeat: None,
+            loop_: false,
+        };
+        moo::moo_prepare(ins, herd, song, &plan).unwrap();
+        let mut buf = vec![0i16; 8192];
+        herd.moo(ins, song, &mut buf, true);
+        buf
+    };
+    let (mut tune_herd, mut tune_ins) = (tune_herd, tune_ins);
+    let expected = render(&song, &mut herd, &mut ins);
+    assert!(expected.iter().any(|&s| s != 0));
+    assert_eq!(render(&tune, &mut tune_herd, &mut tune_ins), expected);
+}
+
+#[test]
+fn test_voices() {
+    let mut voices = Voices::default();
+    let voice = crate::presets::wave::factory_voices().swap_remove(0);
+    assert_eq!(voices.push(voice.clone()), Some(VoiceIdx(0)));
+    voices.extend(std::iter::repeat_n(voice.clone(), 200));
+    assert_eq!(voices.len(), 100);
+    assert_eq!(voices.push(voice), None);
+    assert!(voices.get(VoiceIdx(99)).is_some());
+    assert!(voices.get(VoiceIdx(100)).is_none());
+    assert_eq!(
+        voices.enumerated().last().map(|(idx, _)| idx),
+        Some(VoiceIdx(99))
+    );
+}
+
+#[test]
+fn test_rebuild_voice() {
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    for voice in crate::presets::wave::factory_voices().into_iter().take(2) {
+        ins.add_voice(voice);
+    }
+    let first = ins.voices[VoiceIdx(0)].base.inst.sample_buf.clone();
+    let noise = VoiceData::Noise(crate::presets::noise::snare());
+    ins.voices[VoiceIdx(1)].base.data = noise.clone();
+    assert!(ins.rebuild_voice(VoiceIdx(1)));
+    let mut expected = Voice::from_data(noise);
+    expected.recalculate(&ins.noise_table, NATIVE_SAMPLE_RATE);
+    assert_eq!(
+        ins.voices[VoiceIdx(1)].base.inst.sample_buf,
+        expected.base.inst.sample_buf
+    );
+    assert_eq!(ins.voices[VoiceIdx(0)].base.inst.sample_buf, first);
+    assert!(!ins.rebuild_voice(VoiceIdx(2)));
+}
+
+#[test]
+fn test_render_config() {
+    use crate::{Bps, ChNum, PcmData, Unit};
+    let rate = SampleRate(48_000);
+    let mut pcm = PcmData::new();
+    pcm.create(ChNum::Mono, rate.into(), Bps::B16, 4800);
+    let noise = VoiceData::Noise(crate::presets::noise::snare());
+    let build = |render| {
+        let mut ins = MooInstructions::new(rate);
+        ins.render = render;
+        ins.add_voice(Voice::from_data(VoiceData::Pcm(pcm.clone())));
+        ins.add_voice(Voice::from_data(noise.clone()));
+        ins
+    };
+    let native = build(RenderConfig::default());
+    let at_rate = build(RenderConfig {
+        voice_sample_rate: rate,
+    });
+    // PCM at the voice rate isn't converted, and noise lasts just as long
+    let pcm_inst = &at_rate.voices[VoiceIdx(0)].base.inst;
+    assert_eq!(pcm_inst.sample_rate, rate);
+    assert_eq!(pcm_inst.num_samples, 4800);
+    assert_eq!(native.voices[VoiceIdx(0)].base.inst.num_samples, 4410);
+    let noise_len = |ins: &MooInstructions| ins.voices[VoiceIdx(1)].base.inst.num_samples;
+    assert_eq!(noise_len(&native) * 480 / 441, noise_len(&at_rate));
+    // Either way, the PCM voice plays at its own speed at its basic key, which is a sample
+    // per output sample only when it's rendered at the output rate
+    let stride = f32::from(NATIVE_SAMPLE_RATE) / f32::from(rate);
+    let speed = |ins: &MooInstructions| {
+        let mut unit = Unit::new();
+        unit.reset_voice(ins, VoiceIdx(0), Master::default().timing);
+        unit.tones[0].offset_freq * stride
+    };
+    assert!((speed(&at_rate) - 1.0).abs() < 1e-6);
+    assert!((speed(&native) - 44_100.0 / 48_000.0).abs() < 1e-6);
+}
@@ -0,0 +1,161 @@
+//! Saving and restoring the playback state of a [`Herd`] for [`Checkpoint`]s
+//!
+//! [`Checkpoint`]: crate::render::Checkpoint
+
+use {
+    super::{Herd, PanTimeLen},
+    crate::{
+        io::{Reader, write_pod},
+        result::CheckpointError,
+        timing::SampleT,
+        unit::PanTimeBuf,
+        util::ArrayLenExt as _,
+    },
+    std::iter::zip,
+};
+
+const MAGIC: &[u8; 8] = b"PTCOWCHK";
+const VERSION: u16 = 1;
+
+impl Herd {
+    /// Write everything that affects the rendered output from here on
+    #[expect(clippy::cast_possible_truncation)]
+    pub(crate) fn write_checkpoint(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(MAGIC);
+        write_pod(VERSION, out);
+        write_pod(u8::from(self.moo_end), out);
+        write_pod(u8::from(self.loop_), out);
+        write_pod(self.smp_smooth, out);
+        write_pod(self.smp_count, out);
+        write_pod(self.smp_start, out);
+        write_pod(self.smp_end, out);
+        write_pod(self.smp_repeat, out);
+        write_pod(self.smp_stride, out);
+        write_pod(self.time_pan_index as u64, out);
+        write_pod(self.pan_time_len.0 as u64, out);
+        write_pod(self.evt_idx as u64, out);
+        write_pod(self.budget_exhausted, out);
+        write_pod(self.group_gains.0, out);
+        write_pod(u8::from(self.auto_gain.is_some()), out);
+        write_pod(self.auto_gain.unwrap_or(1.0), out);
+        write_pod(u8::from(self.tail_left.is_some()), out);
+        write_pod(self.tail_left.unwrap_or(0), out);
+        write_pod(self.tail_silent, out);
+        write_pod(self.units.len(), out);
+        for unit in self.units.iter() {
+            unit.write_state(out);
+        }
+        // There can be at most 4 delays
+        write_pod(self.delays.len() as u8, out);
+        for delay in &self.delays {
+            write_pod(delay.offset as u64, out);
+            write_pod(delay.buf_len() as u64, out);
+            for buf in &delay.bufs {
+                out.extend_from_slice(bytemuck::cast_slice(buf));
+            }
+        }
+    }
+    /// Restore the state written by [`Self::write_checkpoint`].
+    ///
+    /// Nothing is changed if an error is returned.
+    pub(crate) fn read_checkpoint(&mut self, data: &[u8]) -> Result<(), CheckpointError> {
+        let mut rd = Reader { data, cur: 0 };
+        read_header(&mut rd)?;
+        let moo_end = rd.next::<u8>()? != 0;
+        let loop_ = rd.next::<u8>()? != 0;
+        let smp_smooth = rd.next()?;
+        let smp_count = rd.next()?;
+        let smp_start = rd.next()?;
+        let smp_end = rd.next()?;
+        let smp_repeat = rd.next()?;
+        let smp_stride = rd.next()?;
+        let time_pan_index = read_usize(&mut rd)?;
+        let pan_time_len = read_usize(&mut rd)?;
+        if !pan_time_len.is_power_of_two()
+            || pan_time_len > PanTimeBuf::LEN
+            || time_pan_index >= pan_time_len
+        {
+            return Err(CheckpointError::InvalidData);
+        }
+        let evt_idx = read_usize(&mut rd)?;
+        let budget_exhausted = rd.next()?;
+        let group_gains = rd.next()?;
+        let auto_gain = (rd.next::<u8>()? != 0, rd.next::<f32>()?);
+        let tail_left = (rd.next::<u8>()? != 0, rd.next()?);
+        let tail_silent = rd.next()?;
+        if rd.next::<u8>()? != self.units.len() {
+            return Err(CheckpointError::LayoutMismatch);
+        }
+        let mut units = self.units.to_vec();
+        for unit in &mut units {
+            unit.read_state(&mut rd)?;
+        }
+        if usize::from(rd.next::<u8>()?) != self.delays.len() {
+            return Err(CheckpointError::LayoutMismatch);
+        }
+        let mut delays = self.delays.clone();
+        for delay in &mut delays {
+            let offset = read_usize(&mut rd)?;
+            if read_usize(&mut rd)? != delay.buf_len() {
+                return Err(CheckpointError::LayoutMismatch);
+            }
+            if offset != 0 && offset >= delay.buf_len() {
+                return Err(CheckpointError::InvalidData);
+            }
+            delay.offset = offset;
+            for buf in &mut delay.bufs {
+                rd.fill_slice(bytemuck::cast_slice_mut(buf))?;
+            }
+        }
+        if rd.cur != data.len() {
+            return Err(CheckpointError::InvalidData);
+        }
+        self.moo_end = moo_end;
+        self.loop_ = loop_;
+        self.smp_smooth = smp_smooth;
+        self.smp_count = smp_count;
+        self.smp_start = smp_start;
+        self.smp_end = smp_end;
+        self.smp_repeat = smp_repeat;
+        self.smp_stride = smp_stride;
+        self.time_pan_index = time_pan_index;
+        self.pan_time_len = PanTimeLen(pan_time_len);
+        self.evt_idx = evt_idx;
+        self.budget_exhausted = budget_exhausted;
+        self.group_gains.0 = group_gains;
+        self.auto_gain = auto_gain.0.then_some(auto_gain.1);
+        self.tail_left = tail_left.0.then_some(tail_left.1);
+        self.tail_silent = tail_silent;
+        for (unit, restored) in zip(self.units.iter_mut(), units) {
+            *unit = restored;
+        }
+        self.delays = delays;
+        Ok(())
+    }
+}
+
+/// The playback position a checkpoint was taken at
+pub fn checkpoint_sample(data: &[u8]) -> Result<SampleT, CheckpointError> {
+    let mut rd = Reader { data, cur: 0 };
+    read_header(&mut rd)?;
+    // Skip `moo_end`, `loop_` and `smp_smooth`
+    rd.next::<[u8; 4]>()?;
+    Ok(rd.next()?)
+}
+
+fn read_header(rd: &mut Reader) -> Result<(), CheckpointError> {
+    let mut magic = [0; MAGIC.len()];
+    rd.fill_slice(&mut magic).map_err(|_| CheckpointError::NotACheckpoint)?;
+    if &magic != MAGIC {
+        return Err(CheckpointError::NotACheckpoint);
+    }
+    let version = rd.next::<u16>()?;
+    if version != VERSION {
+        return Err(CheckpointError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+fn read_usize(rd: &mut Reader) -> Result<usize, CheckpointError> {
+    rd.next::<u64>()?.try_into().map_err(|_| CheckpointError::InvalidData)
+}
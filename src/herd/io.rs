@@ -1,18 +1,28 @@
-use encoding_rs::SHIFT_JIS;
+use {
+    encoding_rs::SHIFT_JIS,
+    std::io::{BufReader, Read, Write},
+};
 
 use crate::{
+    Bps, ChNum, Key, NATIVE_SAMPLE_RATE, PcmData, VoiceData,
+    consts::DEFAULT_BASICKEY,
     delay::{Delay, DelayUnit},
-    event::EveList,
+    event::{DEFAULT_KEY, EveList, Event, EventPayload},
     herd::{
-        Delays, FmtInfo, FmtKind, FmtVer, Herd, MAX_TUNE_UNIT_NAME, MAX_TUNE_VOICE_NAME, MAX_UNITS,
-        MooInstructions, Song,
+        ChunkDiff, Delays, ExeVer, FmtInfo, FmtKind, FmtVer, Herd, MAX_TUNE_UNIT_NAME,
+        MAX_TUNE_VOICE_NAME, MooInstructions, SizeBreakdown, Song, Text, TextPatch, Units,
     },
-    io::{ReadError, Reader},
+    io::{LeField as _, ReadError, Reader, io_struct},
     master::Master,
     overdrive::Overdrive,
-    result::{ProjectReadError, ProjectWriteError, ReadResult, WriteResult},
-    unit::{GroupIdx, Unit},
-    voice::Voice,
+    result::{
+        ProjectReadError, ProjectWriteError, ReadResult, ReadWarning, StreamReadError,
+        StreamWriteError, WriteResult,
+    },
+    timing::Timing,
+    unit::{GroupIdx, GroupSamples, Tuning, Unit, UnitIdx, VoiceIdx},
+    util::ArrayLenExt,
+    voice::{OriginalChunk, Voice, VoiceSlot, X3xPitch},
 };
 
 type Code = [u8; CODESIZE];
@@ -51,15 +61,40 @@ fn read_tune_items(
     herd: &mut Herd,
     ins: &mut MooInstructions,
     rd: &mut Reader,
+    mut issues: Option<&mut Vec<ReadWarning>>,
 ) -> ReadResult {
-    let mut end = false;
-
-    while !end {
+    let mut items = TuneItems::default();
+    while !items.end {
         let code = rd.next::<Code>()?;
-
         let Some(tag) = Tag::from_code(code) else {
             return Err(ProjectReadError::FmtUnknown);
         };
+        items.read(&tag, song, herd, ins, rd, issues.as_deref_mut())?;
+    }
+    items.finish(song, herd, ins)
+}
+
+/// State of reading the chunks of a project, which carries over from one chunk to the next
+#[derive(Default)]
+struct TuneItems {
+    end: bool,
+    // Units and events stored the way V4 and earlier did
+    old_units: bool,
+    old_events: bool,
+    x3x_pitches: Vec<X3xPitch>,
+}
+
+impl TuneItems {
+    /// Read the chunk tagged `tag`, with `rd` right after the tag
+    fn read(
+        &mut self,
+        tag: &Tag,
+        song: &mut Song,
+        herd: &mut Herd,
+        ins: &mut MooInstructions,
+        rd: &mut Reader,
+        issues: Option<&mut Vec<ReadWarning>>,
+    ) -> ReadResult {
         match tag {
             Tag::AntiOPER => {
                 return Err(ProjectReadError::AntiOpreation);
@@ -72,31 +107,31 @@ fn read_tune_items(
             }
 
             Tag::MasterV5 => {
-                song.master = Master::read_v5(rd)?;
+                song.master = Master::read_v5(rd, issues)?;
             }
             Tag::EventV5 => {
                 song.events = EveList::read(rd)?;
             }
 
-            Tag::MatePCM | Tag::V1Pcm => {
-                read_voice(ins, rd, IoVoiceType::Pcm)?;
-            }
-            Tag::MatePTV => {
-                read_voice(ins, rd, IoVoiceType::Ptv)?;
-            }
-            Tag::MatePTN => {
-                read_voice(ins, rd, IoVoiceType::Ptn)?;
-            }
-
-            Tag::MateOGGV => {
-                read_voice(ins, rd, IoVoiceType::Oggv)?;
+            Tag::MatePCM | Tag::V1Pcm | Tag::MatePTV | Tag::MatePTN | Tag::MateOGGV => {
+                let kind = IoVoiceType::of_tag(tag).unwrap_or(IoVoiceType::Pcm);
+                self.x3x_pitches.push(read_voice_or_placeholder(ins, rd, kind, issues)?);
             }
 
             Tag::EffeDELA => {
-                read_delay(rd, &mut herd.delays)?;
+                recoverable(
+                    rd,
+                    issues,
+                    |rd| read_delay(rd, &mut herd.delays),
+                    ReadWarning::DelaySkipped,
+                )?;
             }
             Tag::EffeOVER => {
-                herd.overdrives.push(read_overdrive(rd)?);
+                if let Some(ovr) =
+                    recoverable(rd, issues, read_overdrive, ReadWarning::OverdriveSkipped)?
+                {
+                    herd.overdrives.push(ovr);
+                }
             }
             Tag::TextNAME => {
                 song.text.name_r(rd)?;
@@ -111,65 +146,265 @@ fn read_tune_items(
                 read_unit(herd, rd)?;
             }
             Tag::PxtoneND | Tag::V1End => {
-                end = true;
+                self.end = true;
+            }
+            Tag::V4EvenMast => {
+                song.master = Master::read_x4x(rd, issues)?;
+            }
+            Tag::V4EvenUnit => {
+                song.events.read_old_unit_events(rd, false, true)?;
+                self.old_events = true;
             }
-            Tag::V4EvenMast
-            | Tag::V4EvenUnit
-            | Tag::V3Unit
-            | Tag::V1Proj
-            | Tag::V1Unit
-            | Tag::V1Event => {
-                return Err(ProjectReadError::OldUnsupported);
+            Tag::V1Event => {
+                song.events.read_old_unit_events(rd, true, false)?;
+                self.old_events = true;
             }
+            Tag::V3Unit | Tag::V1Unit => {
+                read_old_unit(herd, &mut song.events, rd, matches!(tag, Tag::V1Unit))?;
+                self.old_units = true;
+            }
+            Tag::V1Proj => {
+                read_x1x_project(song, rd, issues)?;
+            }
+        }
+        Ok(())
+    }
+    /// Convert what was stored the old way, after the last chunk
+    fn finish(self, song: &mut Song, herd: &Herd, ins: &mut MooInstructions) -> ReadResult {
+        if self.old_units {
+            apply_x3x_pitches(song, herd, ins, &self.x3x_pitches)?;
         }
+        if self.old_units || self.old_events {
+            song.events.finish_old_read();
+        }
+        Ok(())
+    }
+}
+
+/// Read a chunk with `read`.
+///
+/// When reading leniently (`issues` is `Some`), a chunk that fails to read is skipped,
+/// and the error is recorded with `issue`, instead of failing the whole read.
+/// The chunk size is trusted for skipping, so a chunk can only be skipped if it's complete.
+fn recoverable<T>(
+    rd: &mut Reader,
+    issues: Option<&mut Vec<ReadWarning>>,
+    read: impl FnOnce(&mut Reader) -> ReadResult<T>,
+    issue: impl FnOnce(ProjectReadError) -> ReadWarning,
+) -> ReadResult<Option<T>> {
+    let size_pos = rd.cur;
+    let err = match read(rd) {
+        Ok(value) => return Ok(Some(value)),
+        Err(err) => err,
+    };
+    let Some(issues) = issues else {
+        return Err(err);
+    };
+    let mut size_rd = Reader {
+        data: rd.data,
+        cur: size_pos,
+    };
+    let size = size_rd.next::<u32>().map_err(|_| err)?;
+    let end = size_rd.cur.saturating_add(size as usize);
+    if end > rd.data.len() {
+        return Err(err);
+    }
+    rd.cur = end;
+    issues.push(issue(err));
+    Ok(None)
+}
+
+/// [`read_voice`], which replaces the voice with a [`placeholder_voice`] if it can't be
+/// [`recoverable`]-ly read
+fn read_voice_or_placeholder(
+    ins: &mut MooInstructions,
+    rd: &mut Reader,
+    kind: IoVoiceType,
+    issues: Option<&mut Vec<ReadWarning>>,
+) -> ReadResult<X3xPitch> {
+    let voice = VoiceIdx(ins.voices.len());
+    let pitch = recoverable(
+        rd,
+        issues,
+        |rd| read_voice(ins, rd, kind),
+        |error| ReadWarning::VoiceReplaced { voice, error },
+    )?;
+    Ok(pitch.unwrap_or_else(|| {
+        ins.voices.push(placeholder_voice());
+        X3xPitch {
+            basic_key: DEFAULT_BASICKEY,
+            tuning: 0.0,
+        }
+    }))
+}
+
+/// Silent voice that stands in for a voice that couldn't be read
+fn placeholder_voice() -> Voice {
+    let mut pcm = PcmData::new();
+    pcm.create(ChNum::Mono, NATIVE_SAMPLE_RATE.into(), Bps::B16, 1);
+    Voice::from_data(VoiceData::Pcm(pcm))
+}
+
+/// Read the `PROJECT=` chunk of V1 projects, which holds the name and the timing
+fn read_x1x_project(
+    song: &mut Song,
+    rd: &mut Reader,
+    issues: Option<&mut Vec<ReadWarning>>,
+) -> ReadResult {
+    let _size = rd.next::<u32>()?;
+    let name = rd.next::<[u8; 16]>()?;
+    let bpm = rd.next::<f32>()?;
+    let ticks_per_beat = rd.next::<u16>()?;
+    let beats_per_meas = rd.next::<u16>()?;
+    let _beat_note = rd.next::<u16>()?;
+    let _meas_num = rd.next::<u16>()?;
+    let _ch_num = rd.next::<u16>()?;
+    let _bps = rd.next::<u16>()?;
+    let _sps = rd.next::<u32>()?;
+    let timing = Timing {
+        ticks_per_beat,
+        bpm,
+        // Values that don't fit are invalid like 0
+        beats_per_meas: beats_per_meas.try_into().unwrap_or(0),
     }
+    .checked_read(issues)?;
+    song.master.timing = timing;
+    let len = strlen(&name) as usize;
+    song.text.name = SHIFT_JIS.decode(&name[..len]).0.into_owned();
+    Ok(())
+}
 
+/// Read a `pxtnUNIT` (V3) or `UNIT====` (V1) chunk.
+///
+/// Back then, a unit had a fixed group, and played the voice with the same index as
+/// the unit. These are turned into events at the start of the song.
+fn read_old_unit(herd: &mut Herd, events: &mut EveList, rd: &mut Reader, v1: bool) -> ReadResult {
+    let _size = rd.next::<u32>()?;
+    let mut unit = Unit::new();
+    if v1 {
+        let name = rd.next::<[u8; MAX_TUNE_UNIT_NAME]>()?;
+        let len = strlen(&name) as usize;
+        unit.name = SHIFT_JIS.decode(&name[..len]).0.into_owned();
+    }
+    let voice_kind = rd.next::<u16>()?;
+    let group = rd.next::<u16>()?;
+    // PCM, PTV or PTN. V1 only had PCM voices.
+    let max_voice_kind = if v1 { 0 } else { 2 };
+    if voice_kind > max_voice_kind {
+        return Err(ProjectReadError::FmtUnknown);
+    }
+    if herd.units.len() >= Units::MAX_STANDARD {
+        return Err(ProjectReadError::FmtUnknown);
+    }
+    let unit_idx = UnitIdx(herd.units.len());
+    herd.units.push(unit);
+    #[expect(clippy::cast_possible_truncation)]
+    let group = GroupIdx(usize::from(group).min(GroupSamples::LEN - 1) as u8);
+    for payload in [
+        EventPayload::SetGroup(group),
+        EventPayload::SetVoice(VoiceIdx(unit_idx.0)),
+    ] {
+        events.push(Event {
+            payload,
+            unit: unit_idx,
+            tick: 0,
+        });
+    }
     Ok(())
 }
 
+/// In V3 and earlier, the pitch of a voice was applied to the key events of the unit
+/// with the same index.
+///
+/// Also names the voices, because these versions didn't store voice names.
+fn apply_x3x_pitches(
+    song: &mut Song,
+    herd: &Herd,
+    ins: &mut MooInstructions,
+    pitches: &[X3xPitch],
+) -> ReadResult {
+    if usize::from(herd.units.len()) > pitches.len() {
+        return Err(ProjectReadError::InvalidData);
+    }
+    for (unit, pitch) in (0..herd.units.len()).map(UnitIdx).zip(pitches) {
+        let shift = pitch.basic_key - DEFAULT_BASICKEY;
+        let mut has_key = false;
+        for eve in song.events.iter_mut().filter(|eve| eve.unit == unit) {
+            if let EventPayload::Key(key) = &mut eve.payload {
+                *key = Key(key.0.saturating_add(shift.0));
+                has_key = true;
+            }
+        }
+        let mut add = |payload| {
+            song.events.push(Event {
+                payload,
+                unit,
+                tick: 0,
+            });
+        };
+        if !has_key {
+            add(EventPayload::Key(DEFAULT_KEY + shift));
+        }
+        if pitch.tuning != 0.0 {
+            add(EventPayload::Tuning(Tuning::from_raw(pitch.tuning)));
+        }
+    }
+    for (idx, voice) in ins.voices.enumerated_mut() {
+        voice.name = format!("voice_{:02}", idx.0);
+    }
+    Ok(())
+}
+
+/// Write the chunks of the project. Without `assist`, the editor-only names of voices and units
+/// are left out.
 fn write_tune_items(
-    out: &mut Vec<u8>,
+    out: &mut ChunkWriter<impl Write>,
     song: &Song,
     herd: &Herd,
     ins: &MooInstructions,
-) -> WriteResult<()> {
-    out.extend_from_slice(Tag::MasterV5.to_code());
-    song.master.write_v5(out);
-    out.extend_from_slice(Tag::EventV5.to_code());
-    song.events.write(out);
-    song.text.name_w(out);
-    song.text.comment_w(out);
+    assist: bool,
+) -> Result<(), StreamWriteError> {
+    out.buf.extend_from_slice(Tag::MasterV5.to_code());
+    song.master.write_v5(&mut out.buf);
+    out.buf.extend_from_slice(Tag::EventV5.to_code());
+    song.events.write_filtered(&mut out.buf, |eve| {
+        eve.unit.0 < Units::MAX_STANDARD && !eve.payload.is_extension()
+    });
+    song.text.name_w(&mut out.buf);
+    song.text.comment_w(&mut out.buf);
     for delay in &herd.delays {
-        out.extend_from_slice(Tag::EffeDELA.to_code());
-        write_delay(delay, out);
+        out.buf.extend_from_slice(Tag::EffeDELA.to_code());
+        write_delay(delay, &mut out.buf);
     }
     for ovr in &herd.overdrives {
-        out.extend_from_slice(Tag::EffeOVER.to_code());
-        write_overdrive(ovr, out);
+        out.buf.extend_from_slice(Tag::EffeOVER.to_code());
+        write_overdrive(ovr, &mut out.buf);
+    }
+    for (idx, voice) in ins.voices.enumerated() {
+        write_voice(voice, idx, out, assist)?;
     }
-    for (i, voice) in ins.voices.iter().enumerate() {
-        write_voice(voice, i, out)?;
+    write_unit_num(&mut out.buf, herd);
+    if assist {
+        write_units(&mut out.buf, herd);
     }
-    write_unit_num(out, herd);
-    write_units(out, herd);
     Ok(())
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::AnyBitPattern, bytemuck::NoUninit)]
-struct IoDelay {
-    unit: u16,
-    group: u16,
-    rate: f32,
-    freq: f32,
+io_struct! {
+    struct IoDelay {
+        unit: u16,
+        group: u16,
+        rate: f32,
+        freq: f32,
+    }
 }
 
 fn read_delay(rd: &mut Reader, delays: &mut Delays) -> ReadResult {
     let size: u32 = rd.next()?;
-    if size as usize != size_of::<IoDelay>() {
+    if size as usize != IoDelay::SIZE {
         return Err(ProjectReadError::FmtUnknown);
     }
-    let io_delay: IoDelay = rd.next()?;
+    let io_delay: IoDelay = rd.next_le()?;
     let unit = match io_delay.unit {
         0 => DelayUnit::Beat,
         1 => DelayUnit::Meas,
@@ -178,20 +413,18 @@ fn read_delay(rd: &mut Reader, delays: &mut Delays) -> ReadResult {
     };
     let delay = Delay {
         unit,
-        group: GroupIdx(io_delay.group.try_into().unwrap()),
-        // The rate is effectively an integer, but stored as float in the PxTone format
-        #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-        rate: io_delay.rate as u8,
+        group: GroupIdx(io_delay.group.try_into().map_err(|_| ProjectReadError::InvalidData)?),
+        rate: io_delay.rate,
         freq: io_delay.freq,
         offset: 0,
         bufs: [Vec::new(), Vec::new()],
     };
-    delays.push(delay);
+    delays.try_push(delay).map_err(|_| ProjectReadError::InvalidData)?;
     Ok(())
 }
 
 fn write_delay(delay: &Delay, out: &mut Vec<u8>) {
-    let size: u32 = size_of::<IoDelay>().try_into().unwrap();
+    let size: u32 = IoDelay::SIZE.try_into().unwrap();
     out.extend_from_slice(&size.to_le_bytes());
     let unit = match delay.unit {
         DelayUnit::Beat => 0,
@@ -201,29 +434,29 @@ fn write_delay(delay: &Delay, out: &mut Vec<u8>) {
     let io_delay = IoDelay {
         unit,
         group: u16::from(delay.group.0),
-        rate: f32::from(delay.rate),
+        rate: delay.rate,
         freq: delay.freq,
     };
-    out.extend_from_slice(bytemuck::bytes_of(&io_delay));
+    io_delay.write_le(out);
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, bytemuck::AnyBitPattern, bytemuck::NoUninit)]
-struct IoOverDrv {
-    xxx: u16,
-    group: u16,
-    cut: f32,
-    amp: f32,
-    yyy: f32,
+io_struct! {
+    struct IoOverDrv {
+        xxx: u16,
+        group: u16,
+        cut: f32,
+        amp: f32,
+        yyy: f32,
+    }
 }
 
 fn read_overdrive(rd: &mut Reader) -> ReadResult<Overdrive> {
-    let _size: u32 = rd.next().unwrap();
-    let ovr: IoOverDrv = rd.next().unwrap();
+    let _size: u32 = rd.next()?;
+    let ovr: IoOverDrv = rd.next_le()?;
     if ovr.xxx != 0 {
         return Err(ProjectReadError::FmtUnknown);
     }
-    if ovr.xxx != 0 {
+    if ovr.yyy != 0.0 {
         return Err(ProjectReadError::FmtUnknown);
     }
     if !Overdrive::CUT_VALID_RANGE.contains(&ovr.cut) {
@@ -242,7 +475,7 @@ fn read_overdrive(rd: &mut Reader) -> ReadResult<Overdrive> {
 }
 
 fn write_overdrive(ovr: &Overdrive, out: &mut Vec<u8>) {
-    let size: u32 = size_of::<IoOverDrv>().try_into().unwrap();
+    let size: u32 = IoOverDrv::SIZE.try_into().unwrap();
     out.extend_from_slice(&size.to_le_bytes());
     let io_ovr = IoOverDrv {
         xxx: 0,
@@ -251,7 +484,7 @@ fn write_overdrive(ovr: &Overdrive, out: &mut Vec<u8>) {
         amp: ovr.amp_mul,
         yyy: 0.0,
     };
-    out.extend_from_slice(bytemuck::bytes_of(&io_ovr));
+    io_ovr.write_le(out);
 }
 
 #[derive(Clone, Copy)]
@@ -262,6 +495,19 @@ enum IoVoiceType {
     Oggv,
 }
 
+impl IoVoiceType {
+    /// The kind of voice a chunk with `tag` holds
+    const fn of_tag(tag: &Tag) -> Option<Self> {
+        match tag {
+            Tag::MatePCM | Tag::V1Pcm => Some(Self::Pcm),
+            Tag::MatePTV => Some(Self::Ptv),
+            Tag::MatePTN => Some(Self::Ptn),
+            Tag::MateOGGV => Some(Self::Oggv),
+            _ => None,
+        }
+    }
+}
+
 impl Tag {
     const fn from_code(code: Code) -> Option<Self> {
         Some(match &code {
@@ -320,22 +566,22 @@ impl Tag {
     }
 }
 
-#[derive(bytemuck::AnyBitPattern, bytemuck::NoUninit, Clone, Copy)]
-#[repr(C)]
-struct IoUnit {
-    unit_index: u16,
-    rrr: u16,
-    name: [u8; MAX_TUNE_UNIT_NAME],
+io_struct! {
+    struct IoUnit {
+        unit_index: u16,
+        rrr: u16,
+        name: [u8; MAX_TUNE_UNIT_NAME],
+    }
 }
 
 fn read_unit(herd: &mut Herd, rd: &mut Reader) -> ReadResult {
     let size = rd.next::<u32>()?;
 
-    if size as usize != size_of::<IoUnit>() {
+    if size as usize != IoUnit::SIZE {
         return Err(ProjectReadError::FmtUnknown);
     }
 
-    let io_unit = rd.next::<IoUnit>()?;
+    let io_unit = rd.next_le::<IoUnit>()?;
     if io_unit.rrr != 0 {
         return Err(ProjectReadError::FmtUnknown);
     }
@@ -356,13 +602,13 @@ fn read_unit(herd: &mut Herd, rd: &mut Reader) -> ReadResult {
 }
 
 fn write_units(out: &mut Vec<u8>, herd: &Herd) {
-    for (i, unit) in herd.units.iter().take(MAX_UNITS as usize).enumerate() {
+    for (i, unit) in herd.units.iter().take(usize::from(Units::MAX_STANDARD)).enumerate() {
         // TODO: Fix this no name thingy? Maybe Option?
         if unit.name == "<no name>" {
             continue;
         }
         out.extend_from_slice(Tag::AssiUNIT.to_code());
-        let size: u32 = size_of::<IoUnit>().try_into().unwrap();
+        let size: u32 = IoUnit::SIZE.try_into().unwrap();
         out.extend_from_slice(&size.to_le_bytes());
         let shift_jis = SHIFT_JIS.encode(&unit.name);
         let mut name: [u8; MAX_TUNE_UNIT_NAME] = [0; _];
@@ -373,7 +619,7 @@ fn write_units(out: &mut Vec<u8>, herd: &Herd) {
             rrr: 0,
             name,
         };
-        out.extend_from_slice(bytemuck::bytes_of(&io_unit));
+        io_unit.write_le(out);
     }
 }
 
@@ -410,7 +656,7 @@ fn read_version(rd: &mut Reader) -> ReadResult<FmtInfo> {
         }
     };
 
-    let exe_ver = rd.next::<u16>()?;
+    let exe_ver = ExeVer::from_raw(rd.next::<u16>()?);
     let dummy = rd.next::<u16>()?;
 
     Ok(FmtInfo {
@@ -421,48 +667,109 @@ fn read_version(rd: &mut Reader) -> ReadResult<FmtInfo> {
     })
 }
 
-fn read_voice(ins: &mut MooInstructions, rd: &mut Reader, kind: IoVoiceType) -> ReadResult {
+/// Returns the pitch of the voice as V3 and earlier understood it
+fn read_voice(
+    ins: &mut MooInstructions,
+    rd: &mut Reader,
+    kind: IoVoiceType,
+) -> ReadResult<X3xPitch> {
+    // Include the tag that was already read
+    let start = rd.cur - 8;
+    let (mut voice, x3x_pitch) = read_voice_chunk(rd, kind)?;
+    // Remember the bytes of voices we generate from a model, so untouched voices can
+    // be written back byte-exact, even if the model misses some detail.
+    if matches!(kind, IoVoiceType::Ptv | IoVoiceType::Ptn) {
+        let mut regenerated = Vec::new();
+        if write_voice_chunk(&voice, &mut regenerated).is_ok() {
+            voice.original = Some(Box::new(OriginalChunk {
+                bytes: rd.data[start..rd.cur].to_vec(),
+                regenerated,
+            }));
+        }
+    }
+    ins.voices.push(voice);
+    Ok(x3x_pitch)
+}
+
+/// Read the voice chunk of `kind` that starts at `rd`, after its tag
+fn read_voice_chunk(rd: &mut Reader, kind: IoVoiceType) -> ReadResult<(Voice, X3xPitch)> {
     let voice = match kind {
+        IoVoiceType::Ptv => return Voice::read_mate_ptv(rd),
         IoVoiceType::Pcm => Voice::read_mate_pcm(rd)?,
-        IoVoiceType::Ptv => Voice::read_mate_ptv(rd)?,
         IoVoiceType::Ptn => Voice::read_mate_ptn(rd)?,
         IoVoiceType::Oggv => Voice::read_ogg(rd)?,
     };
-    ins.voices.push(voice);
+    let pitch = X3xPitch {
+        basic_key: voice.base.unit.basic_key,
+        tuning: 0.0,
+    };
+    Ok((voice, pitch))
+}
+
+pub(super) fn write_voice_chunk(voice: &Voice, out: &mut Vec<u8>) -> WriteResult {
+    match &voice.base.data {
+        VoiceData::Noise(noise_data) => voice.write_mate_ptn(out, noise_data),
+        VoiceData::Pcm(pcm_data) => voice.write_mate_pcm(out, pcm_data),
+        VoiceData::Wave { .. } => voice.write_mate_ptv(out)?,
+        VoiceData::OggV(oggv_data) => voice.write_mate_oggv(out, oggv_data),
+    }
     Ok(())
 }
 
-fn write_voice(voice: &Voice, idx: usize, out: &mut Vec<u8>) -> WriteResult {
+fn write_voice(
+    voice: &Voice,
+    idx: VoiceIdx,
+    out: &mut ChunkWriter<impl Write>,
+    assist: bool,
+) -> Result<(), StreamWriteError> {
     match &voice.base.data {
-        crate::VoiceData::Noise(noise_data) => voice.write_mate_ptn(out, noise_data),
-        // TODO: Ogg/vorbis is being serialized as PCM (because we also deserialize it as such)
-        crate::VoiceData::Pcm(pcm_data) => voice.write_mate_pcm(out, pcm_data),
-        crate::VoiceData::Wave { .. } => voice.write_mate_ptv(out)?,
-        crate::VoiceData::OggV(oggv_data) => voice.write_mate_oggv(out, oggv_data),
+        // Sample data is written straight from the voice, so big sampled voices
+        // don't have to be copied.
+        // Only voices generated from a model have original bytes, so there's nothing
+        // to compare these with.
+        VoiceData::Pcm(pcm_data) if voice.original.is_none() => {
+            voice.write_mate_pcm_head(&mut out.buf, pcm_data);
+            out.write_direct(&pcm_data.smp)?;
+        }
+        VoiceData::OggV(oggv_data) if voice.original.is_none() => {
+            voice.write_mate_oggv_head(&mut out.buf, oggv_data);
+            out.write_direct(&oggv_data.raw_bytes)?;
+        }
+        _ => {
+            let start = out.buf.len();
+            write_voice_chunk(voice, &mut out.buf)?;
+            if let Some(original) = &voice.original
+                && out.buf[start..] == original.regenerated
+            {
+                out.buf.truncate(start);
+                out.buf.extend_from_slice(&original.bytes);
+            }
+        }
     }
     // TODO: Fix this no name thingy?
-    if voice.name != "<no name>" {
-        write_assist_voice(voice, idx, out);
+    if assist && voice.name != "<no name>" {
+        write_assist_voice(voice, idx, &mut out.buf);
     }
     Ok(())
 }
 
-#[derive(Default, bytemuck::AnyBitPattern, Clone, Copy)]
-struct NumUnit {
-    num: u16,
-    rrr: u16,
+io_struct! {
+    struct NumUnit {
+        num: u16,
+        rrr: u16,
+    }
 }
 
 fn read_unit_num(rd: &mut Reader) -> ReadResult<i32> {
     let size = rd.next::<u32>()?;
-    if size as usize != size_of::<NumUnit>() {
+    if size as usize != NumUnit::SIZE {
         return Err(ProjectReadError::FmtUnknown);
     }
-    let data = rd.next::<NumUnit>()?;
+    let data = rd.next_le::<NumUnit>()?;
     if data.rrr != 0 {
         return Err(ProjectReadError::FmtUnknown);
     }
-    if data.num > MAX_UNITS {
+    if data.num > u16::from(Units::MAX_STANDARD) {
         return Err(ProjectReadError::FmtNewer);
     }
 
@@ -471,32 +778,31 @@ fn read_unit_num(rd: &mut Reader) -> ReadResult<i32> {
 
 fn write_unit_num(out: &mut Vec<u8>, herd: &Herd) {
     out.extend_from_slice(Tag::NumUNIT.to_code());
-    let size: u32 = size_of::<NumUnit>().try_into().unwrap();
+    let size: u32 = NumUnit::SIZE.try_into().unwrap();
     out.extend_from_slice(&size.to_le_bytes());
-    let mut n_units: u16 = herd.units.len().into();
-    // Only 50 units are supported by the serialization format
-    if n_units > MAX_UNITS {
-        n_units = MAX_UNITS;
-    }
-    out.extend_from_slice(&n_units.to_le_bytes());
-    let rrr: u16 = 0;
-    out.extend_from_slice(&rrr.to_le_bytes());
+    // Only 50 units are supported by the serialization format,
+    // the rest are written to an extension chunk by `write_extended`
+    let num_unit = NumUnit {
+        num: u16::from(herd.units.len().min(Units::MAX_STANDARD)),
+        rrr: 0,
+    };
+    num_unit.write_le(out);
 }
 
-#[derive(bytemuck::AnyBitPattern, bytemuck::NoUninit, Clone, Copy)]
-#[repr(C)]
-struct AssistVoice {
-    voice_idx: u16,
-    rrr: u16,
-    name: [u8; MAX_TUNE_VOICE_NAME as usize],
+io_struct! {
+    struct AssistVoice {
+        voice_idx: u16,
+        rrr: u16,
+        name: [u8; MAX_TUNE_VOICE_NAME as usize],
+    }
 }
 
 fn read_assist_voice(rd: &mut Reader, ins: &mut MooInstructions) -> ReadResult {
     let size = rd.next::<u32>()?;
-    if size as usize != size_of::<AssistVoice>() {
+    if size as usize != AssistVoice::SIZE {
         return Err(ProjectReadError::FmtUnknown);
     }
-    let assi = rd.next::<AssistVoice>()?;
+    let assi = rd.next_le::<AssistVoice>()?;
 
     if assi.rrr != 0 {
         eprintln!("Warning: rrr is not 0. Possibly invalid.");
@@ -507,51 +813,780 @@ fn read_assist_voice(rd: &mut Reader, ins: &mut MooInstructions) -> ReadResult {
         return Err(ProjectReadError::FmtUnknown);
     };
 
-    let voice = &mut ins.voices[crate::VoiceIdx(idx)];
+    let voice = ins.voices.get_mut(VoiceIdx(idx)).ok_or(ProjectReadError::InvalidData)?;
     let len = strlen(&assi.name);
     voice.name = SHIFT_JIS.decode(&assi.name[..len as usize]).0.into_owned();
 
     Ok(())
 }
 
-fn write_assist_voice(voice: &Voice, idx: usize, out: &mut Vec<u8>) {
+fn write_assist_voice(voice: &Voice, idx: VoiceIdx, out: &mut Vec<u8>) {
     out.extend_from_slice(Tag::AssiWOIC.to_code());
-    let size: u32 = size_of::<AssistVoice>().try_into().unwrap();
+    let size: u32 = AssistVoice::SIZE.try_into().unwrap();
     out.extend_from_slice(&size.to_le_bytes());
     let mut name: [u8; MAX_TUNE_VOICE_NAME as usize] = [0; _];
     let shift_jis = SHIFT_JIS.encode(&voice.name).0;
     name[..shift_jis.len()].copy_from_slice(&shift_jis);
     let assi = AssistVoice {
-        voice_idx: idx.try_into().unwrap(),
+        voice_idx: idx.0.into(),
         rrr: 0,
         name,
     };
-    out.extend_from_slice(bytemuck::bytes_of(&assi));
+    assi.write_le(out);
 }
 
+/// If `issues` is `Some`, the read is lenient, see [`recoverable`]
 pub(super) fn read(
     song: &mut Song,
     herd: &mut Herd,
     ins: &mut MooInstructions,
     data: &[u8],
+    issues: Option<&mut Vec<ReadWarning>>,
 ) -> ReadResult {
     let mut reader = Reader { data, cur: 0 };
     reader.cur = 0;
     song.fmt = read_version(&mut reader)?;
-    read_tune_items(song, herd, ins, &mut reader)?;
+    read_tune_items(song, herd, ins, &mut reader, issues)?;
+    read_extensions(song, herd, ins, &mut reader);
+    Ok(())
+}
+
+/// Reads a project from an [`io::Read`](Read) one chunk at a time,
+/// so only the chunk being read has to be in memory
+struct ChunkReader<R> {
+    src: R,
+    /// The current chunk, starting with its code
+    buf: Vec<u8>,
+    /// Position of the current chunk in the stream
+    start: usize,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Start the next chunk, dropping the previous one
+    fn next_chunk(&mut self) {
+        self.start += self.buf.len();
+        self.buf.clear();
+    }
+    /// Append the next `len` bytes of the stream to the current chunk
+    fn fill(&mut self, len: usize, what: &'static str) -> Result<(), StreamReadError> {
+        let before = self.buf.len();
+        // `take` makes the buffer grow with what's actually there, so a bogus chunk size
+        // can't make it allocate much more than the stream has
+        (&mut self.src).take(len as u64).read_to_end(&mut self.buf)?;
+        if self.buf.len() - before != len {
+            return Err(self
+                .relocate(ProjectReadError::Data {
+                    what,
+                    cursor: before,
+                    len: self.buf.len(),
+                })
+                .into());
+        }
+        Ok(())
+    }
+    /// Append a varint to the current chunk
+    fn fill_varint(&mut self) -> Result<(), StreamReadError> {
+        for _ in 0..5 {
+            self.fill(1, "varint")?;
+            if self.buf.last().is_some_and(|byte| byte & 0x80 == 0) {
+                break;
+            }
+        }
+        Ok(())
+    }
+    /// Append the events of an [`Tag::EventV5`] chunk.
+    ///
+    /// The size written for the chunk is kept from whatever the song was read from, so it
+    /// doesn't always match the events, which have to be scanned to know where the chunk ends.
+    fn fill_events(&mut self) -> Result<(), StreamReadError> {
+        self.fill(4, "u32")?;
+        let eve_num = u32::from_le_bytes(self.buf[self.buf.len() - 4..].try_into().unwrap());
+        for _ in 0..eve_num {
+            // Clock, unit, kind, value
+            self.fill_varint()?;
+            self.fill(2, "u8")?;
+            self.fill_varint()?;
+        }
+        Ok(())
+    }
+    /// Append the rest of the stream to the current chunk
+    fn fill_to_end(&mut self) -> Result<(), StreamReadError> {
+        self.src.read_to_end(&mut self.buf)?;
+        Ok(())
+    }
+    /// The current chunk, right after its code
+    fn reader(&self) -> Reader<'_> {
+        Reader {
+            data: &self.buf,
+            cur: CODESIZE,
+        }
+    }
+    /// Make the positions in a [`ProjectReadError::Data`] relative to the stream,
+    /// instead of the current chunk
+    const fn relocate(&self, err: ProjectReadError) -> ProjectReadError {
+        match err {
+            ProjectReadError::Data { what, cursor, len } => ProjectReadError::Data {
+                what,
+                cursor: self.start + cursor,
+                len: self.start + len,
+            },
+            err => err,
+        }
+    }
+}
+
+/// [`read`], from a stream
+pub(super) fn read_from(
+    song: &mut Song,
+    herd: &mut Herd,
+    ins: &mut MooInstructions,
+    src: impl Read,
+    mut issues: Option<&mut Vec<ReadWarning>>,
+) -> Result<(), StreamReadError> {
+    let mut chunks = ChunkReader {
+        // Codes and events are read a few bytes at a time
+        src: BufReader::new(src),
+        buf: Vec::new(),
+        start: 0,
+    };
+    chunks.fill(VERSIONSIZE + 4, "version")?;
+    song.fmt = read_version(&mut Reader {
+        data: &chunks.buf,
+        cur: 0,
+    })?;
+    let mut items = TuneItems::default();
+    while !items.end {
+        chunks.next_chunk();
+        // The code, and the size of the chunk (or the value of the end chunk)
+        chunks.fill(CODESIZE + 4, "chunk header")?;
+        let code: Code = chunks.buf[..CODESIZE].try_into().unwrap();
+        let size: [u8; 4] = chunks.buf[CODESIZE..].try_into().unwrap();
+        let Some(tag) = Tag::from_code(code) else {
+            return Err(ProjectReadError::FmtUnknown.into());
+        };
+        if matches!(tag, Tag::PxtoneND | Tag::V1End) {
+            // The extensions after the end chunk are small, and don't have a chunk layout
+            // PxTone knows about, so they're read in one go
+            chunks.fill_to_end()?;
+        } else if matches!(tag, Tag::EventV5) {
+            chunks.fill_events()?;
+        } else {
+            chunks.fill(u32::from_le_bytes(size) as usize, "chunk")?;
+        }
+        items
+            .read(
+                &tag,
+                song,
+                herd,
+                ins,
+                &mut chunks.reader(),
+                issues.as_deref_mut(),
+            )
+            .map_err(|err| chunks.relocate(err))?;
+    }
+    items.finish(song, herd, ins)?;
+    read_extensions(song, herd, ins, &mut chunks.reader());
+    Ok(())
+}
+
+/// Code of the extension chunk that stores [`Song::seed`]
+const EXT_SEED: &Code = b"ptcowSED";
+/// Code of the extension chunk that stores the units past [`Units::MAX_STANDARD`],
+/// and their events
+const EXT_UNITS: &Code = b"ptcowUNT";
+/// Code of the extension chunk that stores what `PxTone` doesn't know about the slots of
+/// voices: their volume and pan, and extra slots other than a wave layer on a wave voice
+const EXT_LAYERS: &Code = b"ptcowLYR";
+/// Code of the extension chunk that stores the [extension events](EventPayload::is_extension)
+/// of all units
+const EXT_EVENTS: &Code = b"ptcowEVE";
+
+/// How the extra slot of a voice is stored in an [`EXT_LAYERS`] chunk
+const LAYER_NONE: u8 = 0;
+const LAYER_IN_VOICE: u8 = 1;
+const LAYER_FOLLOWS: u8 = 2;
+
+/// Read the extension chunks ptcow writes after the end chunk, where `PxTone` doesn't look.
+///
+/// Other programs can leave anything there, so whatever isn't understood is ignored.
+fn read_extensions(song: &mut Song, herd: &mut Herd, ins: &mut MooInstructions, rd: &mut Reader) {
+    // The value of the end chunk
+    if rd.next::<u32>().is_err() {
+        return;
+    }
+    while let Ok(code) = rd.next::<Code>() {
+        let Ok(size) = rd.next::<u32>() else {
+            return;
+        };
+        let Some(body) = rd.data.get(rd.cur..rd.cur.saturating_add(size as usize)) else {
+            return;
+        };
+        rd.cur += body.len();
+        if &code == EXT_SEED
+            && let Ok(bytes) = body.try_into()
+        {
+            song.seed = Some(u64::from_le_bytes(bytes));
+        }
+        if &code == EXT_UNITS
+            && let Some((names, events)) = read_extended_units(body, herd)
+        {
+            for name in names {
+                let mut unit = Unit::new();
+                unit.name = name;
+                herd.units.push(unit);
+            }
+            // Keep the order of the events on the same tick, extra units after the others
+            song.events.eves.extend(events);
+            song.events.eves.sort_by_key(|eve| eve.tick);
+        }
+        if &code == EXT_LAYERS {
+            read_layers(body, ins);
+        }
+        if &code == EXT_EVENTS
+            && let Some(events) = read_extension_events(body)
+        {
+            // After the other events on the same tick, like PxTone would sort them
+            song.events.eves.extend(events);
+            song.events.eves.sort_by_key(|eve| eve.tick);
+        }
+    }
+}
+
+/// Apply the entries of an [`EXT_LAYERS`] chunk to the voices.
+///
+/// Reading stops at the first entry that doesn't make sense.
+fn read_layers(body: &[u8], ins: &mut MooInstructions) {
+    let mut rd = Reader { data: body, cur: 0 };
+    while let Ok(idx) = rd.next_le::<u8>() {
+        let Some(voice) = ins.voices.get_mut(VoiceIdx(idx)) else {
+            return;
+        };
+        if read_layer_entry(&mut rd, voice).is_err() {
+            return;
+        }
+    }
+}
+
+fn read_layer_entry(rd: &mut Reader, voice: &mut Voice) -> ReadResult {
+    voice.base.volume = rd.next_le()?;
+    voice.base.pan = rd.next_le()?;
+    let kind: u8 = rd.next_le()?;
+    if kind == LAYER_NONE {
+        return Ok(());
+    }
+    let volume = rd.next_le()?;
+    let pan = rd.next_le()?;
+    match kind {
+        LAYER_IN_VOICE => {
+            let extra = voice.extra.as_mut().ok_or(ProjectReadError::InvalidData)?;
+            extra.volume = volume;
+            extra.pan = pan;
+        }
+        LAYER_FOLLOWS => {
+            let kind = Tag::from_code(rd.next()?)
+                .as_ref()
+                .and_then(IoVoiceType::of_tag)
+                .ok_or(ProjectReadError::InvalidTag)?;
+            let (layer, _) = read_voice_chunk(rd, kind)?;
+            voice.extra = Some(VoiceSlot {
+                volume,
+                pan,
+                ..layer.base
+            });
+        }
+        _ => return Err(ProjectReadError::FmtUnknown),
+    }
+    Ok(())
+}
+
+/// The [`EXT_LAYERS`] entry of `voice`, without the voice index,
+/// or nothing if `PxTone`'s voice chunk has it all.
+pub(super) fn write_layer_entry(voice: &Voice, out: &mut Vec<u8>) -> WriteResult {
+    let in_voice = |extra: &VoiceSlot| {
+        matches!(
+            (&voice.base.data, &extra.data),
+            (VoiceData::Wave(_), VoiceData::Wave(_))
+        )
+    };
+    let needed = !voice.base.has_default_mix()
+        || voice
+            .extra
+            .as_ref()
+            .is_some_and(|extra| !in_voice(extra) || !extra.has_default_mix());
+    if !needed {
+        return Ok(());
+    }
+    voice.base.volume.write_le(out);
+    voice.base.pan.write_le(out);
+    let Some(extra) = &voice.extra else {
+        out.push(LAYER_NONE);
+        return Ok(());
+    };
+    out.push(if in_voice(extra) {
+        LAYER_IN_VOICE
+    } else {
+        LAYER_FOLLOWS
+    });
+    extra.volume.write_le(out);
+    extra.pan.write_le(out);
+    if !in_voice(extra) {
+        write_voice_chunk(
+            &Voice::from_unit_and_data(extra.unit.clone(), extra.data.clone()),
+            out,
+        )?;
+    }
     Ok(())
 }
 
+/// Read the names and the events of the units in an [`EXT_UNITS`] chunk.
+///
+/// Returns `None` if the chunk doesn't fit the units that were read.
+fn read_extended_units(body: &[u8], herd: &Herd) -> Option<(Vec<String>, Vec<Event>)> {
+    if herd.units.len() != Units::MAX_STANDARD {
+        return None;
+    }
+    let mut rd = Reader { data: body, cur: 0 };
+    let count = rd.next::<u8>().ok()?;
+    let total = Units::MAX_STANDARD.checked_add(count)?;
+    let mut names = Vec::new();
+    for _ in 0..count {
+        let len = rd.next::<u16>().ok()?;
+        let bytes = body.get(rd.cur..rd.cur + usize::from(len))?;
+        rd.cur += bytes.len();
+        names.push(String::from_utf8(bytes.to_vec()).ok()?);
+    }
+    let events = EveList::read(&mut rd).ok()?.eves;
+    let in_range = |eve: &Event| (Units::MAX_STANDARD..total).contains(&eve.unit.0);
+    events.iter().all(in_range).then_some((names, events))
+}
+
+/// Read the events of an [`EXT_EVENTS`] chunk.
+///
+/// Returns `None` if the chunk has anything other than extension events.
+fn read_extension_events(body: &[u8]) -> Option<Vec<Event>> {
+    let mut rd = Reader { data: body, cur: 0 };
+    let events = EveList::read(&mut rd).ok()?.eves;
+    events.iter().all(|eve| eve.payload.is_extension()).then_some(events)
+}
+
+fn write_extensions(
+    song: &Song,
+    herd: &Herd,
+    ins: &MooInstructions,
+    out: &mut Vec<u8>,
+) -> WriteResult {
+    if let Some(seed) = song.seed {
+        out.extend_from_slice(EXT_SEED);
+        out.extend_from_slice(&8u32.to_le_bytes());
+        out.extend_from_slice(&seed.to_le_bytes());
+    }
+    if herd.units.len() > Units::MAX_STANDARD {
+        let mut body = Vec::new();
+        body.push(herd.units.len() - Units::MAX_STANDARD);
+        for unit in herd.units.iter().skip(usize::from(Units::MAX_STANDARD)) {
+            // Names longer than this are cut off, at a character boundary
+            let mut len = unit.name.len().min(usize::from(u16::MAX));
+            while !unit.name.is_char_boundary(len) {
+                len -= 1;
+            }
+            #[expect(clippy::cast_possible_truncation)]
+            body.extend_from_slice(&(len as u16).to_le_bytes());
+            body.extend_from_slice(&unit.name.as_bytes()[..len]);
+        }
+        song.events.write_filtered(&mut body, |eve| {
+            eve.unit.0 >= Units::MAX_STANDARD && !eve.payload.is_extension()
+        });
+        write_extension(*EXT_UNITS, &body, out);
+    }
+    if song.events.iter().any(|eve| eve.payload.is_extension()) {
+        let mut body = Vec::new();
+        song.events.write_filtered(&mut body, |eve| eve.payload.is_extension());
+        write_extension(*EXT_EVENTS, &body, out);
+    }
+    let mut body = Vec::new();
+    for (idx, voice) in ins.voices.enumerated() {
+        let mut entry = Vec::new();
+        write_layer_entry(voice, &mut entry)?;
+        if !entry.is_empty() {
+            body.push(idx.0);
+            body.extend_from_slice(&entry);
+        }
+    }
+    if !body.is_empty() {
+        write_extension(*EXT_LAYERS, &body, out);
+    }
+    Ok(())
+}
+
+fn write_extension(code: Code, body: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(&code);
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "projects are far smaller than 4 GiB"
+    )]
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(body);
+}
+
 pub(super) fn write(song: &Song, herd: &Herd, ins: &MooInstructions) -> WriteResult<Vec<u8>> {
+    check_unit_count(herd)?;
+    write_with(song.fmt, song, herd, ins, true)
+}
+
+/// [`write`], to a stream
+pub(super) fn write_to(
+    dst: impl Write,
+    song: &Song,
+    herd: &Herd,
+    ins: &MooInstructions,
+) -> Result<(), StreamWriteError> {
+    check_unit_count(herd)?;
+    write_stream(dst, song.fmt, song, herd, ins, true)
+}
+
+/// Write the project, with the units past [`Units::MAX_STANDARD`] in an extension chunk
+pub(super) fn write_extended(
+    song: &Song,
+    herd: &Herd,
+    ins: &MooInstructions,
+) -> WriteResult<Vec<u8>> {
+    write_with(song.fmt, song, herd, ins, true)
+}
+
+fn check_unit_count(herd: &Herd) -> WriteResult {
+    if herd.units.len() > Units::MAX_STANDARD {
+        return Err(ProjectWriteError::TooManyUnits(herd.units.len()));
+    }
+    Ok(())
+}
+
+/// Write a V5 tune, without the editor-only chunks (like PxTone's "save as tune")
+pub(super) fn write_tune(song: &Song, herd: &Herd, ins: &MooInstructions) -> WriteResult<Vec<u8>> {
+    let fmt = FmtInfo {
+        ver: FmtVer::V5,
+        kind: FmtKind::Tune,
+        ..song.fmt
+    };
+    check_unit_count(herd)?;
+    write_with(fmt, song, herd, ins, false)
+}
+
+fn write_with(
+    fmt: FmtInfo,
+    song: &Song,
+    herd: &Herd,
+    ins: &MooInstructions,
+    assist: bool,
+) -> WriteResult<Vec<u8>> {
     let mut out = Vec::new();
-    write_version(&mut out, song.fmt)?;
-    write_tune_items(&mut out, song, herd, ins)?;
-    out.extend_from_slice(Tag::PxtoneND.to_code());
+    write_stream(&mut out, fmt, song, herd, ins, assist).map_err(vec_write_error)?;
+    Ok(out)
+}
+
+fn write_stream(
+    dst: impl Write,
+    fmt: FmtInfo,
+    song: &Song,
+    herd: &Herd,
+    ins: &MooInstructions,
+    assist: bool,
+) -> Result<(), StreamWriteError> {
+    let mut out = ChunkWriter {
+        dst,
+        buf: Vec::new(),
+    };
+    write_version(&mut out.buf, fmt)?;
+    write_tune_items(&mut out, song, herd, ins, assist)?;
+    out.buf.extend_from_slice(Tag::PxtoneND.to_code());
     // Tail zero bytes (dummy tag value?)
-    out.extend_from_slice(&[0; 4]);
+    out.buf.extend_from_slice(&[0; 4]);
+    write_extensions(song, herd, ins, &mut out.buf)?;
+    out.flush()?;
+    out.dst.flush()?;
+    Ok(())
+}
+
+/// Writes a project to an [`io::Write`](Write).
+///
+/// The chunks are collected in a buffer, except for the sample data of voices,
+/// which is written straight from the voices.
+struct ChunkWriter<W> {
+    dst: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    /// Write out the buffer
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.dst.write_all(&self.buf)?;
+        self.buf.clear();
+        Ok(())
+    }
+    /// Write out the buffer, then `bytes`
+    fn write_direct(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.flush()?;
+        self.dst.write_all(bytes)
+    }
+}
+
+/// The error of writing to a `Vec`, which can only be a [`ProjectWriteError`]
+fn vec_write_error(err: StreamWriteError) -> ProjectWriteError {
+    match err {
+        StreamWriteError::Project(err) => err,
+        StreamWriteError::Io(err) => unreachable!("writing to a Vec failed: {err}"),
+    }
+}
+
+pub(super) fn size_breakdown(
+    song: &Song,
+    herd: &Herd,
+    ins: &MooInstructions,
+) -> WriteResult<SizeBreakdown> {
+    let mut voices = Vec::new();
+    for (idx, voice) in ins.voices.enumerated() {
+        let mut out = ChunkWriter {
+            dst: Vec::new(),
+            buf: Vec::new(),
+        };
+        write_voice(voice, idx, &mut out, true).map_err(vec_write_error)?;
+        out.flush().map_err(|err| vec_write_error(err.into()))?;
+        voices.push(out.dst.len());
+    }
+    Ok(SizeBreakdown {
+        header: VERSIONSIZE + 4 + CODESIZE + 4,
+        master: written_len(|out| {
+            out.extend_from_slice(Tag::MasterV5.to_code());
+            song.master.write_v5(out);
+            Ok(())
+        })?,
+        events: song.events.serialized_size_estimate(),
+        text: written_len(|out| {
+            song.text.name_w(out);
+            song.text.comment_w(out);
+            Ok(())
+        })?,
+        effects: written_len(|out| {
+            for delay in &herd.delays {
+                out.extend_from_slice(Tag::EffeDELA.to_code());
+                write_delay(delay, out);
+            }
+            for ovr in &herd.overdrives {
+                out.extend_from_slice(Tag::EffeOVER.to_code());
+                write_overdrive(ovr, out);
+            }
+            Ok(())
+        })?,
+        voices,
+        units: written_len(|out| {
+            write_unit_num(out, herd);
+            write_units(out, herd);
+            Ok(())
+        })?,
+    })
+}
+
+fn written_len(write: impl FnOnce(&mut Vec<u8>) -> WriteResult) -> WriteResult<usize> {
+    let mut out = Vec::new();
+    write(&mut out)?;
+    Ok(out.len())
+}
+
+/// A chunk of a project file, as raw bytes
+pub struct RawChunk<'a> {
+    /// The tag code of the chunk
+    pub code: Code,
+    /// The whole chunk, including the code and the size
+    pub bytes: &'a [u8],
+}
+
+/// Iterator over the [`RawChunk`]s of a project file, up to and including the end chunk
+pub struct RawChunks<'a> {
+    rd: Reader<'a>,
+    done: bool,
+}
+
+impl<'a> RawChunks<'a> {
+    /// Read the version header, and return it along with an iterator over the chunks
+    pub fn new(data: &'a [u8]) -> ReadResult<(FmtInfo, Self)> {
+        let mut rd = Reader { data, cur: 0 };
+        let fmt = read_version(&mut rd)?;
+        Ok((fmt, Self { rd, done: false }))
+    }
+    /// The header of the file (everything before the first chunk)
+    pub fn header(data: &[u8]) -> &[u8] {
+        &data[..VERSIONSIZE + 4]
+    }
+    /// The bytes after the end chunk, if any
+    pub fn rest(&self) -> &'a [u8] {
+        &self.rd.data[self.rd.cur..]
+    }
+    fn next_chunk(&mut self) -> ReadResult<RawChunk<'a>> {
+        let start = self.rd.cur;
+        let code = self.rd.next::<Code>()?;
+        if code == *Tag::EventV5.to_code() {
+            // The size of the event chunk can't be trusted, so we have to parse it
+            EveList::read(&mut self.rd)?;
+            return Ok(RawChunk {
+                code,
+                bytes: &self.rd.data[start..self.rd.cur],
+            });
+        }
+        let size = self.rd.next::<u32>()?;
+        let end = self.rd.cur.checked_add(size as usize).ok_or(ProjectReadError::InvalidData)?;
+        if end > self.rd.data.len() {
+            return Err(ProjectReadError::Data {
+                what: "chunk",
+                cursor: self.rd.cur,
+                len: self.rd.data.len(),
+            });
+        }
+        self.rd.cur = end;
+        if code == *Tag::PxtoneND.to_code() {
+            self.done = true;
+        }
+        Ok(RawChunk {
+            code,
+            bytes: &self.rd.data[start..end],
+        })
+    }
+}
+
+impl<'a> Iterator for RawChunks<'a> {
+    type Item = ReadResult<RawChunk<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_chunk();
+        if result.is_err() {
+            self.done = true;
+        }
+        Some(result)
+    }
+}
+
+/// See [`rewrite_metadata`](crate::rewrite_metadata)
+pub(super) fn rewrite_metadata(data: &[u8], patch: &TextPatch) -> ReadResult<Vec<u8>> {
+    let (_, mut iter) = RawChunks::new(data)?;
+    let chunks = iter.by_ref().collect::<ReadResult<Vec<_>>>()?;
+    let has = |tag: &Tag| chunks.iter().any(|chunk| chunk.code == *tag.to_code());
+    // Text chunks that are not in the file yet, and need to be inserted
+    let mut insert_name = patch.name.is_some() && !has(&Tag::TextNAME);
+    let mut insert_comment = patch.comment.is_some() && !has(&Tag::TextCOMM);
+    let text = Text {
+        name: patch.name.clone().unwrap_or_default(),
+        comment: patch.comment.clone().unwrap_or_default(),
+    };
+    let mut insert_missing = |out: &mut Vec<u8>| {
+        if std::mem::take(&mut insert_name) {
+            text.name_w(out);
+        }
+        if std::mem::take(&mut insert_comment) {
+            text.comment_w(out);
+        }
+    };
+    let mut out = RawChunks::header(data).to_vec();
+    for chunk in &chunks {
+        match Tag::from_code(chunk.code) {
+            Some(Tag::TextNAME) if patch.name.is_some() => text.name_w(&mut out),
+            Some(Tag::TextCOMM) if patch.comment.is_some() => text.comment_w(&mut out),
+            // Insert the missing text chunks where we would write them, or at the end
+            Some(Tag::EventV5) => {
+                out.extend_from_slice(chunk.bytes);
+                insert_missing(&mut out);
+            }
+            Some(Tag::PxtoneND) => {
+                insert_missing(&mut out);
+                out.extend_from_slice(chunk.bytes);
+            }
+            _ => out.extend_from_slice(chunk.bytes),
+        }
+    }
+    out.extend_from_slice(iter.rest());
     Ok(out)
 }
 
+/// See [`chunk_at_offset`](crate::chunk_at_offset)
+pub(super) fn chunk_at_offset(data: &[u8], offset: usize) -> Option<Code> {
+    let (_, mut iter) = RawChunks::new(data).ok()?;
+    if offset < iter.rd.cur {
+        return None;
+    }
+    loop {
+        let start = iter.rd.cur;
+        match iter.next()? {
+            Ok(chunk) if offset < start + chunk.bytes.len() => return Some(chunk.code),
+            Ok(_) => {}
+            // The chunk is cut off or malformed, but its code might still be there
+            Err(_) => return data.get(start..start + CODESIZE)?.try_into().ok(),
+        }
+    }
+}
+
+/// See [`diff_chunks`](crate::diff_chunks)
+pub(super) fn diff_chunks(left: &[u8], right: &[u8]) -> ReadResult<Vec<ChunkDiff>> {
+    let (_, mut left_iter) = RawChunks::new(left)?;
+    let (_, mut right_iter) = RawChunks::new(right)?;
+    let left_chunks = left_iter.by_ref().collect::<ReadResult<Vec<_>>>()?;
+    let right_chunks = right_iter.by_ref().collect::<ReadResult<Vec<_>>>()?;
+    let mut diffs = Vec::new();
+    if RawChunks::header(left) != RawChunks::header(right) {
+        diffs.push(ChunkDiff::Header);
+    }
+    // Codes in order of first appearance, so the diffs are in file order
+    let mut codes: Vec<Code> = Vec::new();
+    for chunk in left_chunks.iter().chain(&right_chunks) {
+        if !codes.contains(&chunk.code) {
+            codes.push(chunk.code);
+        }
+    }
+    for code in codes {
+        let (l, r) = (
+            chunks_with_code(&left_chunks, code),
+            chunks_with_code(&right_chunks, code),
+        );
+        for nth in 0..l.len().max(r.len()) {
+            match (l.get(nth), r.get(nth)) {
+                (Some(l), Some(r)) if l != r => diffs.push(ChunkDiff::Changed { code, nth }),
+                (Some(_), None) => diffs.push(ChunkDiff::OnlyLeft { code, nth }),
+                (None, Some(_)) => diffs.push(ChunkDiff::OnlyRight { code, nth }),
+                _ => {}
+            }
+        }
+    }
+    // Only chunks in both files count, so added or removed chunks aren't reordering
+    let (left_keys, right_keys) = (chunk_keys(&left_chunks), chunk_keys(&right_chunks));
+    let order = |keys: &[(Code, usize)], other: &[(Code, usize)]| {
+        keys.iter().filter(|key| other.contains(key)).copied().collect::<Vec<_>>()
+    };
+    if order(&left_keys, &right_keys) != order(&right_keys, &left_keys) {
+        diffs.push(ChunkDiff::Order);
+    }
+    if left_iter.rest() != right_iter.rest() {
+        diffs.push(ChunkDiff::Trailing);
+    }
+    Ok(diffs)
+}
+
+/// The code of every chunk, and its order among the chunks with the same code
+fn chunk_keys(chunks: &[RawChunk<'_>]) -> Vec<(Code, usize)> {
+    let mut keys: Vec<(Code, usize)> = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        let nth = keys.iter().filter(|(code, _)| *code == chunk.code).count();
+        keys.push((chunk.code, nth));
+    }
+    keys
+}
+
+fn chunks_with_code<'a>(chunks: &[RawChunk<'a>], code: Code) -> Vec<&'a [u8]> {
+    chunks
+        .iter()
+        .filter(|chunk| chunk.code == code)
+        .map(|chunk| chunk.bytes)
+        .collect()
+}
+
 fn write_version(out: &mut Vec<u8>, info: FmtInfo) -> WriteResult {
     let bytes = match (info.ver, info.kind) {
         (FmtVer::V1, FmtKind::Collage) => V1_COLLAGE,
@@ -566,12 +1601,12 @@ fn write_version(out: &mut Vec<u8>, info: FmtInfo) -> WriteResult {
         (FmtVer::V5, FmtKind::Tune) => V5_TUNE,
     };
     out.extend_from_slice(bytes);
-    out.extend_from_slice(&info.exe_ver.to_le_bytes());
+    out.extend_from_slice(&info.exe_ver.raw().to_le_bytes());
     out.extend_from_slice(&info.dummy.to_le_bytes());
     Ok(())
 }
 
-impl super::Text {
+impl Text {
     pub(crate) fn comment_r(&mut self, rd: &mut Reader) -> ReadResult {
         self.comment = SHIFT_JIS.decode(&read_vec(rd)?).0.into_owned();
         Ok(())
@@ -612,3 +1647,393 @@ fn read_vec(rd: &mut Reader) -> Result<Vec<u8>, ReadError> {
     rd.fill_slice(&mut v)?;
     Ok(v)
 }
+
+#[test]
+fn test_rewrite_metadata() {
+    let mut song = Song::default();
+    song.text.comment = "old comment".into();
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(crate::presets::wave::factory_voices());
+    let herd = Herd::default();
+    let data = write(&song, &herd, &ins).unwrap();
+    let patch = TextPatch {
+        name: Some("New name".into()),
+        comment: Some(String::new()),
+    };
+    let rewritten = rewrite_metadata(&data, &patch).unwrap();
+    // Same result as changing the text and serializing again
+    song.text.name = "New name".into();
+    song.text.comment.clear();
+    assert!(rewritten == write(&song, &herd, &ins).unwrap());
+    // Keeping everything is a no-op
+    assert!(rewrite_metadata(&data, &TextPatch::default()).unwrap() == data);
+}
+
+#[test]
+fn test_diff_chunks() {
+    let mut song = Song::default();
+    song.text.name = "name".into();
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(crate::presets::wave::factory_voices().into_iter().take(2));
+    let herd = Herd::default();
+    let data = write(&song, &herd, &ins).unwrap();
+    assert_eq!(diff_chunks(&data, &data).unwrap(), []);
+    song.text.name = "other".into();
+    song.text.comment = "comment".into();
+    ins.voices.pop();
+    let mut other = write(&song, &herd, &ins).unwrap();
+    other.push(0);
+    let code = |tag: Tag| *tag.to_code();
+    assert_eq!(
+        diff_chunks(&data, &other).unwrap(),
+        [
+            ChunkDiff::Changed {
+                code: code(Tag::TextNAME),
+                nth: 0
+            },
+            ChunkDiff::OnlyLeft {
+                code: code(Tag::MatePTV),
+                nth: 1
+            },
+            ChunkDiff::OnlyLeft {
+                code: code(Tag::AssiWOIC),
+                nth: 1
+            },
+            // Chunks only in the right file come last
+            ChunkDiff::OnlyRight {
+                code: code(Tag::TextCOMM),
+                nth: 0
+            },
+            ChunkDiff::Trailing,
+        ]
+    );
+    // Swapping two chunks is a reordering
+    let (_, chunks) = RawChunks::new(&data).unwrap();
+    let chunks: Vec<_> = chunks.map(Result::unwrap).collect();
+    let mut swapped = RawChunks::header(&data).to_vec();
+    for idx in [1, 0].into_iter().chain(2..chunks.len()) {
+        swapped.extend_from_slice(chunks[idx].bytes);
+    }
+    assert_eq!(diff_chunks(&data, &swapped).unwrap(), [ChunkDiff::Order]);
+}
+
+#[test]
+fn test_size_breakdown() {
+    let mut song = Song::default();
+    song.text.name = "size".into();
+    song.events.eves.push(Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(0),
+        tick: 100_000,
+    });
+    let mut herd = Herd::default();
+    herd.units.push(Unit::new());
+    herd.delays.push(Delay::default());
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(crate::presets::wave::factory_voices().into_iter().take(2));
+    ins.voices.push(Voice::from_data(VoiceData::Noise(
+        crate::presets::noise::crash(),
+    )));
+    let sizes = super::project_size_breakdown(&song, &herd, &ins).unwrap();
+    assert_eq!(sizes.total(), write(&song, &herd, &ins).unwrap().len());
+    assert_eq!(sizes.voices.len(), 3);
+    // Tag, size, event count, and a 3 byte tick, unit, kind, and a 2 byte duration
+    assert_eq!(sizes.events, 8 + 4 + 4 + 3 + 1 + 1 + 2);
+}
+
+#[test]
+fn test_original_voice_bytes() {
+    let song = Song::default();
+    let herd = Herd::default();
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.push(Voice::from_data(VoiceData::Noise(
+        crate::presets::noise::snare(),
+    )));
+    let mut data = write(&song, &herd, &ins).unwrap();
+    // Set the unused unit number field of the noise voice, which ptcow doesn't model
+    let pos = data.windows(8).position(|w| w == Tag::MatePTN.to_code()).unwrap() + 12;
+    data[pos] = 5;
+    let (song, herd, mut ins) = crate::read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert!(write(&song, &herd, &ins).unwrap() == data);
+    // Edited voices are written from their current state
+    ins.voices[VoiceIdx(0)].base.unit.tuning = 2.0;
+    let edited = write(&song, &herd, &ins).unwrap();
+    assert_eq!(edited[pos], 0);
+    ins.voices[VoiceIdx(0)].base.unit.tuning = 1.0;
+    assert!(write(&song, &herd, &ins).unwrap() == data);
+    ins.voices[VoiceIdx(0)].forget_original_bytes();
+    assert_eq!(write(&song, &herd, &ins).unwrap()[pos], 0);
+}
+
+#[test]
+fn test_lenient_read_timing() {
+    let song = Song::default();
+    let mut herd = Herd::default();
+    herd.units.push(Unit::new());
+    let ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    let mut data = write(&song, &herd, &ins).unwrap();
+    // Zero ticks per beat
+    let mast = data.windows(8).position(|w| w == Tag::MasterV5.to_code()).unwrap();
+    data[mast + 12..mast + 14].fill(0);
+    assert_eq!(
+        crate::read_song(&data, NATIVE_SAMPLE_RATE).err(),
+        Some(ProjectReadError::InvalidTiming)
+    );
+    let (song, mut herd, mut ins, warnings) =
+        crate::read_song_lenient(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(
+        warnings,
+        [ReadWarning::TimingClamped, ReadWarning::NoVoices]
+    );
+    assert!(song.master.timing.is_valid());
+    assert_eq!(song.master.timing.ticks_per_beat, 480);
+    let plan = crate::MooPlan {
+        start_pos: crate::StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: false,
+    };
+    crate::moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+    assert!(herd.smp_end > 0);
+}
+
+#[test]
+fn test_lenient_read() {
+    let mut song = Song::default();
+    song.events.eves.push(Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(0),
+        tick: 0,
+    });
+    let mut herd = Herd::default();
+    herd.units.push(Unit::new());
+    herd.delays.push(Delay::default());
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.push(Voice::from_data(VoiceData::Noise(
+        crate::presets::noise::snare(),
+    )));
+    let mut data = write(&song, &herd, &ins).unwrap();
+    // Reserved field of the noise voice, and the unit of the delay
+    let ptn = data.windows(8).position(|w| w == Tag::MatePTN.to_code()).unwrap();
+    data[ptn + 24] = 5;
+    let dela = data.windows(8).position(|w| w == Tag::EffeDELA.to_code()).unwrap();
+    data[dela + 12] = 7;
+    assert_eq!(
+        crate::read_song(&data, NATIVE_SAMPLE_RATE).err(),
+        Some(ProjectReadError::FmtUnknown)
+    );
+    let (song, mut herd, mut ins, warnings) =
+        crate::read_song_lenient(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(
+        warnings,
+        [
+            ReadWarning::DelaySkipped(ProjectReadError::FmtUnknown),
+            ReadWarning::VoiceReplaced {
+                voice: VoiceIdx(0),
+                error: ProjectReadError::FmtUnknown
+            },
+        ]
+    );
+    assert_eq!(ins.voices.len(), 1);
+    assert!(herd.delays.is_empty());
+    let plan = crate::MooPlan {
+        start_pos: crate::StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: false,
+    };
+    crate::moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+    let mut buf = [1; 1024];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert!(buf.iter().all(|&smp| smp == 0));
+}
+
+#[test]
+fn test_oggv_roundtrip() {
+    let song = Song::default();
+    let herd = Herd::default();
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    // Not valid Ogg/Vorbis data, so the voice can't be decoded and stays silent
+    ins.voices.push(Voice::from_data(VoiceData::OggV(crate::OggVData {
+        raw_bytes: b"OggS, but not really".to_vec(),
+        ch: 1,
+        sps2: 44_100,
+        smp_num: 1000,
+    })));
+    let data = write(&song, &herd, &ins).unwrap();
+    let (song, herd, ins) = crate::read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    let voice = &ins.voices[VoiceIdx(0)];
+    let VoiceData::OggV(oggv) = &voice.base.data else {
+        panic!("Ogg/Vorbis voice should be read as such");
+    };
+    assert_eq!(oggv.raw_bytes, b"OggS, but not really");
+    assert!(voice.base.inst.sample_buf.is_empty());
+    assert!(write(&song, &herd, &ins).unwrap() == data);
+}
+
+#[test]
+fn test_write_tune() {
+    let mut song = Song::default();
+    song.text.name = "tune".into();
+    song.fmt.ver = FmtVer::V3;
+    let mut herd = Herd::default();
+    herd.add_unit("lead");
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(crate::presets::wave::factory_voices().into_iter().take(2));
+    let data = write_tune(&song, &herd, &ins).unwrap();
+    assert!(data.starts_with(V5_TUNE));
+    let codes: Vec<Code> =
+        RawChunks::new(&data).unwrap().1.map(|chunk| chunk.unwrap().code).collect();
+    assert!(!codes.contains(Tag::AssiUNIT.to_code()));
+    assert!(!codes.contains(Tag::AssiWOIC.to_code()));
+    assert!(data.len() < write(&song, &herd, &ins).unwrap().len());
+    let (song, herd, ins) = crate::read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(song.fmt.kind, FmtKind::Tune);
+    assert_eq!(song.text.name, "tune");
+    assert_eq!(herd.units.len(), 1);
+    assert_eq!(ins.voices.len(), 2);
+}
+
+#[test]
+fn test_seed_extension() {
+    let mut song = Song::default();
+    let herd = Herd::default();
+    let ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    let plain = write(&song, &herd, &ins).unwrap();
+    assert!(plain.ends_with(b"pxtoneND\0\0\0\0"));
+    song.seed = Some(0x00C0_FFEE);
+    let seeded = write(&song, &herd, &ins).unwrap();
+    assert!(seeded.starts_with(&plain));
+    let (song, _, _) = crate::read_song(&seeded, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(song.seed, Some(0x00C0_FFEE));
+    // Unknown trailing bytes are ignored
+    let mut data = plain;
+    data.extend_from_slice(b"some junk that isn't a chunk");
+    let (song, _, _) = crate::read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(song.seed, None);
+}
+
+#[test]
+fn test_extended_units() {
+    let mut song = Song::default();
+    let mut herd = Herd::default();
+    for i in 0..60 {
+        herd.add_unit(format!("unit {i}"));
+    }
+    let eve = |tick, unit| Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(unit),
+        tick,
+    };
+    song.events.eves = vec![eve(0, 0), eve(0, 55), eve(0, 1), eve(480, 59), eve(960, 49)];
+    song.recalculate_length();
+    let ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    assert_eq!(
+        write(&song, &herd, &ins).err(),
+        Some(ProjectWriteError::TooManyUnits(60))
+    );
+    let data = write_extended(&song, &herd, &ins).unwrap();
+    let (read_song, read_herd, _) = crate::read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(read_herd.units.len(), 60);
+    assert_eq!(read_herd.units[UnitIdx(57)].name, "unit 57");
+    let ticks_units: Vec<_> = read_song.events.eves.iter().map(|e| (e.tick, e.unit.0)).collect();
+    assert_eq!(ticks_units, [(0, 0), (0, 1), (0, 55), (480, 59), (960, 49)]);
+    // Without the extension chunk, the file is a valid song with the first 50 units
+    let end = data.windows(8).position(|w| w == b"pxtoneND").unwrap() + 12;
+    let (std_song, std_herd, _) = crate::read_song(&data[..end], NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(std_herd.units.len(), 50);
+    assert!(std_song.events.eves.iter().all(|e| e.unit.0 < 50));
+    assert_eq!(std_song.events.eves.len(), 3);
+    // Songs that fit the standard format are written the same way
+    herd.units.truncate(50);
+    song.events.eves.retain(|e| e.unit.0 < 50);
+    assert_eq!(
+        write_extended(&song, &herd, &ins),
+        write(&song, &herd, &ins)
+    );
+}
+
+#[test]
+fn test_extension_events() {
+    use crate::{Key, LfoParams};
+    let mut song = Song::default();
+    let mut herd = Herd::default();
+    for i in 0..60 {
+        herd.add_unit(format!("unit {i}"));
+    }
+    let eve = |tick, unit, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick,
+    };
+    song.events.eves = vec![
+        eve(0, 0, EventPayload::On { duration: 480 }),
+        eve(0, 55, EventPayload::On { duration: 480 }),
+        // Read back after the standard events of the same tick
+        eve(0, 0, EventPayload::PitchBendRange(Key::OCTAVE)),
+        eve(0, 55, EventPayload::Tremolo(LfoParams::default())),
+        eve(240, 0, EventPayload::PitchBend(-100)),
+        eve(480, 59, EventPayload::VibratoRate(4.0)),
+    ];
+    song.recalculate_length();
+    let ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    let data = write_extended(&song, &herd, &ins).unwrap();
+    let (read_song, _, _) = crate::read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    let events = |song: &Song| {
+        song.events
+            .eves
+            .iter()
+            .map(|e| (e.tick, e.unit.0, e.payload))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(events(&read_song), events(&song));
+    // PxTone only sees the standard events
+    let end = data.windows(8).position(|w| w == b"pxtoneND").unwrap() + 12;
+    let (std_song, _, _) = crate::read_song(&data[..end], NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(events(&std_song), events(&song)[..1]);
+    assert!(data[..end].windows(8).all(|w| w != EXT_EVENTS));
+    // Without extension events, there is no chunk for them
+    song.events.eves.retain(|e| !e.payload.is_extension());
+    let data = write_extended(&song, &herd, &ins).unwrap();
+    assert!(data.windows(8).all(|w| w != EXT_EVENTS));
+}
+
+#[test]
+fn test_layered_voice_roundtrip() {
+    use crate::{VoiceUnit, voice::VoiceSlot};
+    let song = Song::default();
+    let herd = Herd::default();
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    let mut pcm = PcmData::new();
+    pcm.create(ChNum::Mono, NATIVE_SAMPLE_RATE.into(), Bps::B8, 1000);
+    pcm.smp.fill(200);
+    let noise = VoiceSlot {
+        volume: 64,
+        pan: 32,
+        ..VoiceSlot::new(
+            VoiceUnit::default(),
+            VoiceData::Noise(crate::presets::noise::snare()),
+        )
+    };
+    ins.voices.push(Voice::layered(
+        VoiceSlot::new(VoiceUnit::default(), VoiceData::Pcm(pcm)),
+        noise,
+    ));
+    ins.voices.extend(crate::presets::wave::factory_voices().into_iter().take(1));
+    let data = write(&song, &herd, &ins).unwrap();
+    let (song, herd, ins) = crate::read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    let voice = &ins.voices[VoiceIdx(0)];
+    assert!(matches!(voice.base.data, VoiceData::Pcm(_)));
+    assert!(voice.base.has_default_mix());
+    let extra = voice.extra.as_ref().unwrap();
+    assert!(matches!(extra.data, VoiceData::Noise(_)));
+    assert_eq!((extra.volume, extra.pan), (64, 32));
+    assert!(!extra.inst.sample_buf.is_empty());
+    // Plain wave voices don't need an entry
+    assert!(ins.voices[VoiceIdx(1)].extra.is_none());
+    assert!(write(&song, &herd, &ins).unwrap() == data);
+    // Without the extension chunk, the voice is a plain PCM voice
+    let end = data.windows(8).position(|w| w == b"pxtoneND").unwrap() + 12;
+    let (_, _, ins) = crate::read_song(&data[..end], NATIVE_SAMPLE_RATE).unwrap();
+    assert!(ins.voices[VoiceIdx(0)].extra.is_none());
+}
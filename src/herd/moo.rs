@@ -1,18 +1,26 @@
 use {
     crate::{
-        Meas, NATIVE_SAMPLE_RATE, SampleRate, SampleT,
+        Delay, Meas, NATIVE_SAMPLE_RATE, SampleRate, SampleT,
         event::{EveList, Event, EventPayload},
-        herd::{Herd, MooInstructions, Song},
+        herd::{GroupGains, Herd, MooInstructions, PanTimeLen, Song},
         master::Master,
         pulse_frequency::PULSE_FREQ,
+        result::{RenderError, UnsupportedSampleRate},
+        states::Fade,
         timing::{self, Tick, meas_to_sample},
-        unit::{MAX_CHANNEL, PanTimeBuf, UnitIdx},
-        util::ArrayLenExt as _,
+        unit::{GroupSamples, MAX_CHANNEL, Unit, UnitIdx},
+    },
+    std::{
+        iter::{Peekable, zip},
+        num::NonZeroU32,
+        ops::ControlFlow,
+        time::Duration,
     },
-    std::{iter::zip, ops::ControlFlow},
 };
 
 /// Get the current [`Tick`] the playback is at.
+///
+/// Saturates at [`Tick::MAX`], which endless playback reaches after about 50 days.
 #[expect(
     clippy::cast_sign_loss,
     clippy::cast_precision_loss,
@@ -20,10 +28,21 @@ use {
 )]
 #[must_use]
 pub fn current_tick(herd: &Herd, ins: &MooInstructions) -> Tick {
-    (herd.smp_count as f32 / ins.samples_per_tick) as u32
+    if ins.samples_per_tick <= 0.0 {
+        return 0;
+    }
+    // PxTone divides in f32, which is exact up to 2^24 samples (about 6 minutes at 44.1 kHz).
+    // Past that, f32 gets too coarse for the sample count, and events would play late.
+    if herd.smp_count < 1 << f32::MANTISSA_DIGITS {
+        (herd.smp_count as f32 / ins.samples_per_tick) as Tick
+    } else {
+        (herd.smp_count as f64 / f64::from(ins.samples_per_tick)) as Tick
+    }
 }
 
+/// A sample type [`Herd::moo`] can render into
 pub trait OutSample {
+    /// Convert from the internal sample value, where the 16 bit range is full scale
     fn from_moo_samp(moo_samp: i32) -> Self;
 }
 
@@ -34,81 +53,285 @@ impl OutSample for i16 {
     }
 }
 
+/// Full scale is -1.0 to 1.0, but louder samples aren't clipped, so the headroom is kept
+impl OutSample for f32 {
+    #[expect(clippy::cast_precision_loss)]
+    fn from_moo_samp(moo_samp: i32) -> Self {
+        moo_samp as Self / 32768.0
+    }
+}
+
 pub(super) fn next_sample<T: OutSample>(
     herd: &mut Herd,
     ins: &MooInstructions,
-    events: &EveList,
-    master: &Master,
-    dst_sps: SampleRate,
+    song: &Song,
     out: &mut [T; 2],
+    mut group_out: Option<&mut [GroupSamples; 2]>,
     advance: bool,
+    source: &mut Option<&mut dyn EventSource>,
 ) -> bool {
     for unit in herd.units.iter_mut() {
         unit.tone_envelope(&ins.voices);
     }
 
-    if advance {
-        let clock = current_tick(herd, ins);
-
-        while herd.evt_idx < events.eves.len() && (events.eves[herd.evt_idx]).tick <= clock {
-            if do_next_event(herd, ins, events, master, clock, dst_sps).is_break() {
-                break;
-            }
-        }
+    // The tail after the end of the song only lets the sound die out
+    if advance && herd.tail_left.is_none() {
+        do_due_events(herd, ins, &song.events, &song.master, source);
     }
 
     for unit in herd.units.iter_mut() {
-        unit.tone_sample(herd.time_pan_index, herd.smp_smooth, &ins.voices);
+        unit.tone_sample(
+            herd.time_pan_index,
+            herd.smp_smooth,
+            &ins.voices,
+            &ins.noise_table,
+            ins.interpolation,
+        );
     }
 
+    let mut loud = false;
+    herd.meter.fit(usize::from(herd.units.len()));
     for ch in 0..MAX_CHANNEL {
         let mut group_smps = [0; _];
-        for unit in herd.units.iter_mut() {
+        for (i, unit) in herd.units.iter_mut().enumerate() {
             if !unit.mute {
-                unit.tone_supple(&mut group_smps, ch, herd.time_pan_index);
+                let smp = unit.tone_supple(
+                    &mut group_smps,
+                    ch,
+                    herd.time_pan_index,
+                    herd.pan_time_len.0,
+                );
+                herd.meter.add(i, ch, smp);
             }
         }
-        for ovr in &mut herd.overdrives {
-            ovr.tone_supple(&mut group_smps);
+        if cfg!(feature = "effects") {
+            for ovr in &mut herd.overdrives {
+                ovr.tone_supple(&mut group_smps);
+            }
+            for delay in &mut herd.delays {
+                delay.tone_supple(ch, &mut group_smps);
+            }
         }
-        for delay in &mut herd.delays {
-            delay.tone_supple(ch, &mut group_smps);
+        if let Some(group_out) = &mut group_out {
+            group_out[ch as usize] = group_smps;
         }
 
         let mut out_samp: i32 = 0;
 
-        for group_smp in group_smps {
-            out_samp += group_smp;
+        for (group_smp, gain) in zip(group_smps, herd.group_gains.0) {
+            out_samp += apply_gain(group_smp, gain);
+        }
+        if let Some(gain) = herd.auto_gain {
+            out_samp = apply_gain(out_samp, gain);
         }
 
+        loud |= out_samp.unsigned_abs() > ins.tail.threshold();
         out[ch as usize] = T::from_moo_samp(out_samp);
     }
+    herd.meter.end_frame();
+    // Also counted before the tail, so it can end at once if the song ends in silence
+    herd.tail_silent = if loud {
+        0
+    } else {
+        herd.tail_silent.saturating_add(1)
+    };
     if advance {
         herd.smp_count += 1;
     }
-    herd.time_pan_index = (herd.time_pan_index + 1) & (PanTimeBuf::LEN - 1);
+    increment_tones(herd, ins);
+
+    if cfg!(feature = "effects") {
+        for delay in &mut herd.delays {
+            delay.tone_increment();
+        }
+    }
+
+    handle_song_end(herd, ins, &song.master, source)
+}
 
+/// An output sample that is kept at the internal sample value, for mixing it some more
+#[derive(Clone, Copy, Default)]
+struct MooSamp(i32);
+
+impl OutSample for MooSamp {
+    fn from_moo_samp(moo_samp: i32) -> Self {
+        Self(moo_samp)
+    }
+}
+
+/// The playback from before a [seek](Herd::seek_with_crossfade), fading out
+///
+/// Kept after the fade is done, so the next one can reuse its allocations.
+#[derive(Default)]
+pub struct SeekFade {
+    old: Herd,
+    /// Frames played, and total length of the fade
+    pos: u32,
+    len: u32,
+}
+
+/// Like [`next_sample`], but blends in the playback from before the seek
+#[expect(clippy::too_many_arguments)]
+fn next_sample_fading(
+    herd: &mut Herd,
+    fade: &mut SeekFade,
+    ins: &MooInstructions,
+    song: &Song,
+    out: &mut [MooSamp; 2],
+    group_out: Option<&mut [GroupSamples; 2]>,
+    advance: bool,
+    source: &mut Option<&mut dyn EventSource>,
+) -> bool {
+    let mut new = [MooSamp::default(); 2];
+    let more = next_sample(herd, ins, song, &mut new, group_out, advance, source);
+    let mut old = [MooSamp::default(); 2];
+    if !fade.old.moo_end
+        && !next_sample(&mut fade.old, ins, song, &mut old, None, advance, &mut None)
+    {
+        fade.old.moo_end = true;
+    }
+    #[expect(clippy::cast_precision_loss)]
+    let t = fade.pos as f32 / fade.len as f32;
+    for (out, (new, old)) in zip(out, zip(new, old)) {
+        #[expect(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+        let smp = ((new.0 - old.0) as f32).mul_add(t, old.0 as f32) as i32;
+        *out = MooSamp(smp);
+    }
+    fade.pos += 1;
+    more
+}
+
+/// Like [`next_sample`], but without rendering anything
+fn next_silent_sample(
+    herd: &mut Herd,
+    ins: &MooInstructions,
+    song: &Song,
+    source: &mut Option<&mut dyn EventSource>,
+) -> bool {
     for unit in herd.units.iter_mut() {
-        #[expect(clippy::cast_sign_loss)]
-        let key_now = unit.tone_increment_key() as usize;
-        unit.tone_increment_sample(PULSE_FREQ.get2(key_now) * herd.smp_stride, &ins.voices);
+        unit.tone_envelope(&ins.voices);
+    }
+    if herd.tail_left.is_none() {
+        do_due_events(herd, ins, &song.events, &song.master, source);
+    }
+    herd.smp_count += 1;
+    increment_tones(herd, ins);
+    handle_song_end(herd, ins, &song.master, source)
+}
+
+/// Do the events that are due at the current tick
+fn do_due_events(
+    herd: &mut Herd,
+    ins: &MooInstructions,
+    events: &EveList,
+    master: &Master,
+    source: &mut Option<&mut dyn EventSource>,
+) {
+    let dst_sps = ins.out_sample_rate;
+    let clock = current_tick(herd, ins);
+    let mut budget = ins.event_budget.map_or(u32::MAX, NonZeroU32::get);
+
+    while herd.evt_idx < events.eves.len() && (events.eves[herd.evt_idx]).tick <= clock {
+        if budget == 0 {
+            herd.budget_exhausted += 1;
+            return;
+        }
+        budget -= 1;
+        if do_next_event(herd, ins, events, master, clock, dst_sps).is_break() {
+            break;
+        }
     }
+    if let Some(source) = source {
+        while budget != 0
+            && let Some(evt) = source.next_event(clock)
+        {
+            budget -= 1;
+            if do_event(herd, ins, events, master, clock, dst_sps, &evt).is_break() {
+                break;
+            }
+            herd.observe(&evt);
+        }
+    }
+}
+
+/// Advance the keys, sample positions and LFOs of the units
+fn increment_tones(herd: &mut Herd, ins: &MooInstructions) {
+    herd.time_pan_index = (herd.time_pan_index + 1) & (herd.pan_time_len.0 - 1);
 
-    for delay in &mut herd.delays {
-        delay.tone_increment();
+    for unit in herd.units.iter_mut() {
+        // Pitch bend and vibrato can bend below the lowest key
+        #[expect(clippy::cast_sign_loss)]
+        let key_now = unit.tone_increment_key(ins.out_sample_rate).0.max(0) as usize;
+        unit.tone_increment_sample(PULSE_FREQ.get2(key_now) * herd.smp_stride, &ins.voices);
+        unit.tone_increment_lfos(ins.out_sample_rate);
+        unit.tone_increment_group_fade();
     }
+}
 
+/// Loop back to the repeat point if the song ended and looping is enabled.
+///
+/// Returns false if the song ended, and isn't looping.
+fn handle_song_end(
+    herd: &mut Herd,
+    ins: &MooInstructions,
+    master: &Master,
+    source: &mut Option<&mut dyn EventSource>,
+) -> bool {
     if herd.smp_count >= herd.smp_end {
         if !herd.loop_ {
-            return false;
+            return continue_tail(herd, ins);
         }
         herd.smp_count = herd.smp_repeat;
         herd.evt_idx = 0;
         herd.tune_cow_voices(ins, master.timing);
+        if ins.loop_policy == LoopPolicy::Clear {
+            for unit in herd.units.iter_mut() {
+                unit.clear_pan_time_bufs();
+            }
+            for delay in &mut herd.delays {
+                delay.clear();
+            }
+        }
+        if let Some(source) = source {
+            source.repeat(current_tick(herd, ins));
+        }
     }
     true
 }
 
+/// Count down the [`Tail`] after the end of a song that doesn't loop.
+///
+/// Returns false once the tail is over.
+fn continue_tail(herd: &mut Herd, ins: &MooInstructions) -> bool {
+    let left = herd.tail_left.get_or_insert_with(|| ins.tail.max_len());
+    if *left == 0 {
+        return false;
+    }
+    *left -= 1;
+    match ins.tail {
+        Tail::UntilSilent { .. } => {
+            // Quiet gaps between echoes are shorter than the longest delay
+            let window = herd.delays.iter().map(Delay::buf_len).max().unwrap_or(0);
+            usize::try_from(herd.tail_silent).unwrap_or(usize::MAX) <= window
+        }
+        Tail::None | Tail::Samples(_) => true,
+    }
+}
+
+#[expect(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::float_cmp
+)]
+fn apply_gain(smp: i32, gain: f32) -> i32 {
+    // Leave the samples untouched at unity gain, so the default output stays exact
+    if gain == 1.0 {
+        smp
+    } else {
+        (smp as f32 * gain) as i32
+    }
+}
+
 fn do_next_event(
     herd: &mut Herd,
     ins: &MooInstructions,
@@ -119,6 +342,7 @@ fn do_next_event(
 ) -> ControlFlow<()> {
     let evt = &events.eves[herd.evt_idx];
     do_event(herd, ins, events, master, clock, dst_sps, evt)?;
+    herd.observe(evt);
     herd.evt_idx += 1;
     ControlFlow::Continue(())
 }
@@ -130,7 +354,7 @@ pub fn do_event(
     events: &EveList,
     master: &Master,
     clock: u32,
-    dst_sps: u16,
+    dst_sps: SampleRate,
     evt: &Event,
 ) -> ControlFlow<()> {
     let u = evt.unit;
@@ -143,8 +367,8 @@ pub fn do_event(
             do_on_event(herd, ins, events, clock, duration, evt.unit, evt.tick);
         }
         EventPayload::Key(key) => unit.tone_key(key),
-        EventPayload::PanVol(vol) => unit.tone_pan_volume(vol),
-        EventPayload::PanTime(pan) => unit.tone_pan_time(pan, dst_sps),
+        EventPayload::PanVol(vol) => unit.tone_pan_volume(vol, ins.pan_law),
+        EventPayload::PanTime(pan) => unit.tone_pan_time(pan, dst_sps, ins.pan_time_scale),
         EventPayload::Velocity(vel) => unit.velocity = vel,
         EventPayload::Volume(vol) => unit.volume = vol,
         EventPayload::Portament { duration } => {
@@ -157,8 +381,14 @@ pub fn do_event(
         | EventPayload::Last
         | EventPayload::PtcowDebug(_) => {}
         EventPayload::SetVoice(num) => unit.reset_voice(ins, num, master.timing),
-        EventPayload::SetGroup(num) => unit.group = num,
-        EventPayload::Tuning(tuning) => unit.tuning = tuning,
+        EventPayload::SetGroup(num) => unit.fade_to_group(num, 0),
+        EventPayload::Tuning(tuning) => unit.tuning = tuning.get(),
+        EventPayload::PitchBend(bend) => unit.pitch_bend = bend,
+        EventPayload::PitchBendRange(range) => unit.pitch_bend_range = range,
+        EventPayload::VibratoRate(rate) => unit.vibrato.rate = rate,
+        EventPayload::VibratoDepth(depth) => unit.vibrato.depth = depth,
+        EventPayload::Tremolo(params) => unit.tremolo.params = params,
+        EventPayload::AutoPan(params) => unit.auto_pan.params = params,
         EventPayload::Null => return ControlFlow::Break(()),
     }
     ControlFlow::Continue(())
@@ -177,12 +407,13 @@ fn do_on_event(
     let Some(unit) = herd.units.get_mut(u) else {
         return;
     };
-    // We need a signed clock here for various calculations that can go below zero
-    let clock: i32 = clock.try_into().unwrap();
-    // Same for duration
-    let duration: i32 = duration.try_into().unwrap();
-    let on_count: i32 = ((i32::try_from(evt_tick).unwrap() + duration.saturating_sub(clock)) as f32
-        * ins.samples_per_tick) as i32;
+    // We need signed ticks here for various calculations that can go below zero.
+    // i64 fits any tick and duration, even from a broken file, and the casts to the
+    // tone counters saturate.
+    let clock = i64::from(clock);
+    let duration = i64::from(duration);
+    let evt_tick = i64::from(evt_tick);
+    let on_count = ((evt_tick + duration - clock) as f32 * ins.samples_per_tick) as i32;
     if on_count <= 0 {
         unit.tone_zero_lives();
         return;
@@ -195,16 +426,14 @@ fn do_on_event(
     for (slot, tone) in zip(voice.slots(), &mut unit.tones) {
         let inst = &slot.inst;
         if inst.env_release != 0 {
-            let max_life_count1: i32 =
-                ((duration - (clock - i32::try_from(evt_tick).unwrap())) as f32)
-                    .mul_add(ins.samples_per_tick, inst.env_release as f32) as i32;
-            let c = i32::try_from(evt_tick).unwrap()
-                + duration
-                + i32::try_from(tone.env_release_clock).unwrap();
+            let max_life_count1 = ((duration - (clock - evt_tick)) as f32)
+                .mul_add(ins.samples_per_tick, inst.env_release as f32)
+                as i32;
+            let c = evt_tick + duration + i64::from(tone.env_release_clock);
             let mut next: Option<&Event> = None;
             for i in herd.evt_idx + 1..events.eves.len() {
                 let eve = &events.eves[i];
-                if i32::try_from(eve.tick).unwrap() > c {
+                if i64::from(eve.tick) > c {
                     break;
                 }
                 if eve.unit == u && matches!((eve).payload, EventPayload::On { .. }) {
@@ -212,12 +441,13 @@ fn do_on_event(
                     break;
                 }
             }
-            let max_life_count2 = match next {
-                Some(next) => {
-                    ((i32::try_from(next.tick).unwrap() - clock) as f32 * ins.samples_per_tick)
-                        as i32
-                }
-                None => herd.smp_end.cast_signed() - (clock as f32 * ins.samples_per_tick) as i32,
+            let max_life_count2 = if let Some(next) = next {
+                ((i64::from(next.tick) - clock) as f32 * ins.samples_per_tick) as i32
+            } else {
+                // Saturating, so notes still sound after hours of endless playback
+                let until_end = i64::try_from(herd.smp_end).unwrap_or(i64::MAX)
+                    - (clock as f32 * ins.samples_per_tick) as i64;
+                i32::try_from(until_end).unwrap_or(i32::MAX)
             };
             if max_life_count1 < max_life_count2 {
                 tone.life_count = max_life_count1;
@@ -225,9 +455,8 @@ fn do_on_event(
                 tone.life_count = max_life_count2;
             }
         } else {
-            tone.life_count = ((duration.saturating_sub(clock - i32::try_from(evt_tick).unwrap()))
-                as f32
-                * ins.samples_per_tick) as i32;
+            tone.life_count =
+                ((duration - (clock - evt_tick)) as f32 * ins.samples_per_tick) as i32;
         }
 
         if tone.life_count > 0 {
@@ -245,7 +474,7 @@ fn do_on_event(
     }
 }
 
-fn get_total_sample(master: &Master, out_sample_rate: SampleRate) -> u32 {
+fn get_total_sample(master: &Master, out_sample_rate: SampleRate) -> SampleT {
     calc_sample_num(
         master.meas_num,
         master.timing.beats_per_meas.into(),
@@ -255,36 +484,47 @@ fn get_total_sample(master: &Master, out_sample_rate: SampleRate) -> u32 {
 }
 
 #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-fn calc_sample_num(meas_num: u32, beat_num: u32, sps: SampleRate, beat_tempo: f32) -> u32 {
-    if beat_tempo == 0. {
+fn calc_sample_num(meas_num: u32, beat_num: u32, sps: SampleRate, beat_tempo: f32) -> SampleT {
+    if !(beat_tempo.is_finite() && beat_tempo > 0.) {
         return 0;
     }
-    let total_beat_num: u32 = meas_num * beat_num;
-    (f64::from(sps) * 60. * f64::from(total_beat_num) / f64::from(beat_tempo)) as u32
+    let total_beat_num = f64::from(meas_num) * f64::from(beat_num);
+    (f64::from(sps) * 60. * total_beat_num / f64::from(beat_tempo)) as SampleT
 }
 
 /// Prepare to [`moo`](Herd::moo).
 ///
-/// # Panics
+/// # Errors
 ///
-/// - If `ins.out_sample_rate` is 0
+/// If `ins.out_sample_rate` isn't [supported](SampleRate::is_supported).
+/// Nothing is changed then.
 #[expect(
     clippy::cast_possible_truncation,
     clippy::cast_precision_loss,
     clippy::cast_sign_loss
 )]
-pub fn moo_prepare(ins: &mut MooInstructions, herd: &mut Herd, song: &Song, plan: &MooPlan) {
-    assert_ne!(ins.out_sample_rate, 0);
+pub fn moo_prepare(
+    ins: &mut MooInstructions,
+    herd: &mut Herd,
+    song: &Song,
+    plan: &MooPlan,
+) -> Result<(), UnsupportedSampleRate> {
+    if !ins.out_sample_rate.is_supported() {
+        return Err(UnsupportedSampleRate(ins.out_sample_rate));
+    }
 
     let meas_end = plan.meas_end.unwrap_or_else(|| song.master.end_meas());
     let meas_repeat = plan.meas_repeat.unwrap_or(song.master.loop_points.repeat);
 
     herd.loop_ = plan.loop_;
 
-    ins.samples_per_tick = timing::samples_per_tick(ins.out_sample_rate, song.master.timing);
-    herd.smp_stride = f32::from(NATIVE_SAMPLE_RATE) / f32::from(ins.out_sample_rate);
+    let tempo = herd.states.tempo() * ins.speed;
+    ins.samples_per_tick =
+        timing::samples_per_tick(ins.out_sample_rate, song.master.timing) / tempo;
+    herd.smp_stride = stride(ins);
 
     herd.time_pan_index = 0;
+    herd.pan_time_len = PanTimeLen(ins.pan_time_scale.buf_len(ins.out_sample_rate));
 
     herd.smp_end = meas_to_sample(meas_end, ins.samples_per_tick, song.master.timing);
     herd.smp_repeat = meas_to_sample(meas_repeat, ins.samples_per_tick, song.master.timing);
@@ -293,19 +533,143 @@ pub fn moo_prepare(ins: &mut MooInstructions, herd: &mut Herd, song: &Song, plan
         StartPosPlan::Meas(val) => meas_to_sample(val, ins.samples_per_tick, song.master.timing),
         StartPosPlan::Sample(val) => val,
         StartPosPlan::F32(val) => {
-            (get_total_sample(&song.master, ins.out_sample_rate) as f32 * val) as u32
+            (get_total_sample(&song.master, ins.out_sample_rate) as f32 / tempo * val) as SampleT
         }
     };
 
     herd.smp_count = herd.smp_start;
-    herd.smp_smooth = ins.out_sample_rate / 250;
+    herd.smp_smooth = u16::try_from(ins.out_sample_rate.get() / 250).unwrap_or(u16::MAX);
 
     herd.evt_idx = 0;
+    herd.tail_left = None;
+    herd.tail_silent = 0;
     herd.tune_cow_voices(ins, song.master.timing);
+
+    herd.auto_gain = match ins.mix_policy {
+        MixPolicy::Clip => None,
+        MixPolicy::AutoGain => Some(gain_for_peak(scan_peak(herd, ins, song))),
+    };
+    Ok(())
+}
+
+/// How far voices advance per output sample, for a note played at the rate of the voice
+fn stride(ins: &MooInstructions) -> f32 {
+    f32::from(NATIVE_SAMPLE_RATE) / f32::from(ins.out_sample_rate) * ins.pitch
+}
+
+/// The loudest sample of the mix, from the prepared start position to the end of the song.
+///
+/// Plays the song on a copy of `herd`, once, without looping.
+fn scan_peak(herd: &Herd, ins: &MooInstructions, song: &Song) -> u32 {
+    let mut scratch = Herd {
+        smp_smooth: herd.smp_smooth,
+        smp_count: herd.smp_start,
+        smp_start: herd.smp_start,
+        smp_end: herd.smp_end,
+        smp_repeat: herd.smp_repeat,
+        smp_stride: herd.smp_stride,
+        pan_time_len: herd.pan_time_len,
+        units: Box::default(),
+        delays: herd.delays.clone(),
+        overdrives: herd.overdrives.clone(),
+        group_gains: GroupGains(herd.group_gains.0),
+        ..Herd::default()
+    };
+    scratch.units.clone_from(&herd.units.0);
+    scratch.tune_cow_voices(ins, song.master.timing);
+    for delay in &mut scratch.delays {
+        delay.clear();
+    }
+    let mut buf = vec![RawSample(0); 8192];
+    let mut peak = 0;
+    while scratch.moo(ins, song, &mut buf, true) {
+        for smp in &buf {
+            peak = peak.max(smp.0.unsigned_abs());
+        }
+    }
+    peak
+}
+
+/// The gain that brings `peak` down to the 16 bit sample range
+#[expect(clippy::cast_precision_loss)]
+fn gain_for_peak(peak: u32) -> f32 {
+    let top = i16::MAX.unsigned_abs();
+    if peak <= u32::from(top) {
+        1.0
+    } else {
+        f32::from(top) / peak as f32
+    }
+}
+
+/// The internal sample value as is, for measuring the mix
+#[derive(Clone, Copy)]
+struct RawSample(i32);
+
+impl OutSample for RawSample {
+    fn from_moo_samp(moo_samp: i32) -> Self {
+        Self(moo_samp)
+    }
+}
+
+/// Render `song` with `loops` loops, then keep playing while fading out over `fade`.
+///
+/// This is the usual format of game soundtrack rips. The fade starts where playback would
+/// jump back to the repeat point for the last time (see [`timing::play_length`]), so it
+/// overlaps the beginning of the next loop.
+///
+/// Playback is prepared from the start of the song with looping enabled, like
+/// [`moo_prepare`] does, so `herd` and `ins` can be fresh from [`read_song`](crate::read_song).
+///
+/// Returns stereo samples, interleaved like [`Herd::moo`] renders them.
+///
+/// # Errors
+///
+/// - [`RenderError::UnsupportedSampleRate`] if `ins.out_sample_rate` isn't
+///   [supported](SampleRate::is_supported)
+/// - [`RenderError::TooLong`] if the rendered samples wouldn't fit in memory, for example
+///   with a huge number of `loops`
+pub fn render_looped(
+    song: &Song,
+    herd: &mut Herd,
+    ins: &mut MooInstructions,
+    loops: u32,
+    fade: Duration,
+) -> Result<Vec<i16>, RenderError> {
+    let plan = MooPlan {
+        start_pos: StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: true,
+    };
+    moo_prepare(ins, herd, song, &plan)?;
+    herd.moo_end = false;
+    let body = usize::try_from(timing::play_length(song, loops, ins.out_sample_rate))
+        .map_err(|_| RenderError::TooLong)?;
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let fade_len = (fade.as_secs_f64() * f64::from(ins.out_sample_rate)) as usize;
+    let len = body
+        .checked_add(fade_len)
+        .and_then(|len| len.checked_mul(2))
+        .ok_or(RenderError::TooLong)?;
+    let mut buf = Vec::new();
+    buf.try_reserve_exact(len).map_err(|_| RenderError::TooLong)?;
+    buf.resize(len, 0i16);
+    herd.moo(ins, song, &mut buf, true);
+    #[expect(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    for (i, frame) in buf[body * 2..].as_chunks_mut::<2>().0.iter_mut().enumerate() {
+        let gain = 1.0 - i as f32 / fade_len as f32;
+        for smp in frame {
+            *smp = (f32::from(*smp) * gain) as i16;
+        }
+    }
+    Ok(buf)
 }
 
 impl Herd {
-    /// Moo the song into a stereo signed 16 bit little endian PCM buffer.
+    /// Moo the song into an interleaved stereo buffer.
+    ///
+    /// With `i16` samples, this renders 16 bit PCM, clipping loud samples.
+    /// With `f32` samples, nothing is clipped (see [`OutSample`]).
     ///
     /// If `advance` is true, the playback proceeds to the next event.
     /// Setting it to false can be useful for pausing playback, while still allowing
@@ -316,30 +680,634 @@ impl Herd {
         song: &Song,
         buf: &mut [T],
         advance: bool,
+    ) -> bool {
+        self.moo_inner(ins, song, buf, &mut [], advance, None)
+    }
+    /// Like [`Self::moo`], but also plays the events produced by `source` on the fly,
+    /// in addition to the events of the song.
+    ///
+    /// See [`EventSource`] for more information.
+    pub fn moo_with_source<T: OutSample>(
+        &mut self,
+        ins: &MooInstructions,
+        song: &Song,
+        source: &mut dyn EventSource,
+        buf: &mut [T],
+        advance: bool,
+    ) -> bool {
+        self.moo_inner(ins, song, buf, &mut [], advance, Some(source))
+    }
+    /// Like [`Self::moo`], but downmixes to mono, one sample per frame.
+    ///
+    /// The sample is the average of the left and right channels.
+    pub fn moo_mono<T: OutSample>(
+        &mut self,
+        ins: &MooInstructions,
+        song: &Song,
+        buf: &mut [T],
+        advance: bool,
+    ) -> bool {
+        self.moo_frames(ins, song, buf.len(), &mut [], advance, None, |i, [l, r]| {
+            buf[i] = T::from_moo_samp(i32::midpoint(l, r));
+        })
+    }
+    /// Like [`Self::moo`], but into one buffer per channel, instead of an interleaved one.
+    ///
+    /// With one channel, it's downmixed like [`Self::moo_mono`]. With more, the first one gets
+    /// the left channel, and the second one the right channel, the rest are left untouched.
+    /// As many frames are rendered as fit into the shortest channel.
+    pub fn moo_planar<T: OutSample>(
+        &mut self,
+        ins: &MooInstructions,
+        song: &Song,
+        channels: &mut [&mut [T]],
+        advance: bool,
+    ) -> bool {
+        match channels {
+            [] => !self.moo_end,
+            [mono] => self.moo_mono(ins, song, mono, advance),
+            [left, right, ..] => {
+                let len = left.len().min(right.len());
+                self.moo_frames(ins, song, len, &mut [], advance, None, |i, [l, r]| {
+                    left[i] = T::from_moo_samp(l);
+                    right[i] = T::from_moo_samp(r);
+                })
+            }
+        }
+    }
+    /// Call `observer` with every event that's played while mooing, and the
+    /// [sample](Self::smp_count) it's played at, for example to sync visuals to the notes.
+    ///
+    /// This includes the events of an [`EventSource`], and the events that are played
+    /// on the way when [advancing silently](Self::advance_silent).
+    /// Replaces the previous observer.
+    pub fn set_event_observer(
+        &mut self,
+        observer: impl FnMut(&Event, SampleT) + Send + Sync + 'static,
+    ) {
+        self.event_observer = Some(Box::new(observer));
+    }
+    /// Stop calling the observer set with [`Self::set_event_observer`]
+    pub fn clear_event_observer(&mut self) {
+        self.event_observer = None;
+    }
+    fn observe(&mut self, evt: &Event) {
+        let smp = self.smp_count;
+        if let Some(observer) = &mut self.event_observer {
+            observer(evt, smp);
+        }
+    }
+    /// Seek to `target`, crossfading from the current position to it over `fade_samples`
+    /// samples, so the jump doesn't click.
+    ///
+    /// While the fade lasts, the playback from before the seek keeps going and is mixed in,
+    /// fading out while the new position fades in. Like [`Self::seek_to_sample`], notes that
+    /// started before `target` don't play.
+    /// A seek also restarts a song that has already ended.
+    ///
+    /// With `fade_samples` of 0, this is a hard seek.
+    ///
+    /// The first crossfade allocates a copy of the playback state, later ones reuse it.
+    pub fn seek_with_crossfade(&mut self, target: SampleT, fade_samples: u32) {
+        let mut fade = self.seek_fade.take().unwrap_or_default();
+        if fade_samples != 0 {
+            self.copy_playback_to(&mut fade.old);
+        }
+        fade.pos = 0;
+        fade.len = fade_samples;
+        self.seek_fade = Some(fade);
+        self.seek_to_sample(target);
+        self.moo_end = false;
+        self.tail_left = None;
+        self.tail_silent = 0;
+    }
+    /// Copy the playback state into `dst`, to keep playing the old position while fading.
+    ///
+    /// The allocations of `dst` are reused.
+    fn copy_playback_to(&self, dst: &mut Self) {
+        // Destructured, so a new field can't be forgotten
+        let Self {
+            moo_end,
+            loop_,
+            smp_smooth,
+            smp_count,
+            smp_start,
+            smp_end,
+            smp_repeat,
+            smp_stride,
+            time_pan_index,
+            pan_time_len,
+            evt_idx,
+            units,
+            delays,
+            overdrives,
+            group_gains,
+            budget_exhausted,
+            states,
+            tail_left,
+            tail_silent,
+            auto_gain,
+            // The old position isn't metered or observed, and doesn't fade itself
+            meter: _,
+            event_observer: _,
+            seek_fade: _,
+        } = self;
+        dst.moo_end = *moo_end;
+        dst.loop_ = *loop_;
+        dst.smp_smooth = *smp_smooth;
+        dst.smp_count = *smp_count;
+        dst.smp_start = *smp_start;
+        dst.smp_end = *smp_end;
+        dst.smp_repeat = *smp_repeat;
+        dst.smp_stride = *smp_stride;
+        dst.time_pan_index = *time_pan_index;
+        dst.pan_time_len = *pan_time_len;
+        dst.evt_idx = *evt_idx;
+        dst.units.0.clone_from(&units.0);
+        dst.delays.clone_from(delays);
+        dst.overdrives.clone_from(overdrives);
+        dst.group_gains.0 = group_gains.0;
+        dst.budget_exhausted = *budget_exhausted;
+        dst.states.clone_from(states);
+        dst.tail_left = *tail_left;
+        dst.tail_silent = *tail_silent;
+        dst.auto_gain = *auto_gain;
+    }
+    /// Set [`MooInstructions::speed`] while playing, keeping the playback position.
+    ///
+    /// Voices keep their pitch, use [`Self::set_pitch`] for that.
+    pub fn set_speed(&mut self, ins: &mut MooInstructions, song: &Song, speed: f32) {
+        ins.speed = speed;
+        self.scale_tempo(ins, song, self.states.tempo());
+    }
+    /// Set [`MooInstructions::pitch`] while playing.
+    ///
+    /// The tempo stays the same, use [`Self::set_speed`] for that.
+    pub fn set_pitch(&mut self, ins: &mut MooInstructions, pitch: f32) {
+        ins.pitch = pitch;
+        self.smp_stride = stride(ins);
+    }
+    /// Like [`Self::moo`], but also reports the samples of each group before they are mixed
+    /// together.
+    ///
+    /// `group_out` is laid out like `buf`, every sample of `buf` gets the group samples it
+    /// was mixed from.
+    /// They include the effects (delays, overdrives), but not the
+    /// [group gains](Self::set_group_gain).
+    /// If `group_out` is shorter than `buf`, the rest of the group samples aren't reported.
+    pub fn moo_with_groups<T: OutSample>(
+        &mut self,
+        ins: &MooInstructions,
+        song: &Song,
+        buf: &mut [T],
+        group_out: &mut [GroupSamples],
+        advance: bool,
+    ) -> bool {
+        self.moo_inner(ins, song, buf, group_out, advance, None)
+    }
+    /// Like [`Self::moo`], but also switches between [music states](crate::states) as they
+    /// are triggered with [`Self::trigger_state`].
+    ///
+    /// This needs mutable access to `ins`, because states can change the tempo.
+    pub fn moo_states<T: OutSample>(
+        &mut self,
+        ins: &mut MooInstructions,
+        song: &Song,
+        mut buf: &mut [T],
+    ) -> bool {
+        // Group gains are updated in blocks of this many frames while fading
+        const FADE_BLOCK: usize = 64;
+        if self.moo_end {
+            return false;
+        }
+        while buf.len() >= 2 && !self.moo_end {
+            let mut frames = buf.len() / 2;
+            if let Some((_, transition)) = self.states.pending {
+                let until = if transition.on_meas {
+                    self.frames_until_meas(ins, song)
+                } else {
+                    0
+                };
+                if until == 0 {
+                    self.apply_pending_state(ins, song);
+                } else {
+                    frames = frames.min(until);
+                }
+            }
+            if self.states.fade.is_some() {
+                frames = frames.min(FADE_BLOCK);
+            }
+            let (chunk, rest) = buf.split_at_mut(frames * 2);
+            self.moo(ins, song, chunk, true);
+            self.advance_fade(frames);
+            buf = rest;
+        }
+        true
+    }
+    /// How many frames it takes to reach the start of the next measure
+    pub(crate) fn frames_until_meas(&self, ins: &MooInstructions, song: &Song) -> usize {
+        let timing = song.master.timing;
+        let ticks_per_meas = timing::meas_to_tick(1, timing);
+        if ticks_per_meas == 0 {
+            return 0;
+        }
+        let meas = current_tick(self, ins) / ticks_per_meas;
+        let meas_start = meas_to_sample(meas, ins.samples_per_tick, timing);
+        let next = if meas_start >= self.smp_count {
+            meas_start
+        } else {
+            meas_to_sample(meas + 1, ins.samples_per_tick, timing)
+        };
+        usize::try_from(next.min(self.smp_end) - self.smp_count).unwrap_or(usize::MAX)
+    }
+    fn apply_pending_state(&mut self, ins: &mut MooInstructions, song: &Song) {
+        let old_tempo = self.states.tempo();
+        let Some((state, transition)) = self.states.take_pending() else {
+            return;
+        };
+        if state.tempo.to_bits() != old_tempo.to_bits() {
+            self.scale_tempo(ins, song, state.tempo);
+        }
+        #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let fade_len = (transition.fade.as_secs_f64() * f64::from(ins.out_sample_rate)) as u32;
+        let mut mute_at_end = Vec::new();
+        for (idx, unit) in self.units.enumerated_mut() {
+            let mute = state.muted.contains(&idx);
+            if mute && !unit.mute && fade_len != 0 {
+                mute_at_end.push(idx);
+            } else {
+                unit.mute = mute;
+            }
+        }
+        self.states.fade = Some(Fade {
+            from: self.group_gains.0,
+            to: state.group_gains,
+            pos: 0,
+            len: fade_len,
+            mute_at_end,
+        });
+        self.advance_fade(0);
+    }
+    /// Change the tempo to `tempo` times the tempo of the song (and [`MooInstructions::speed`]),
+    /// keeping the playback position
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn scale_tempo(&mut self, ins: &mut MooInstructions, song: &Song, tempo: f32) {
+        let old = ins.samples_per_tick;
+        ins.samples_per_tick =
+            timing::samples_per_tick(ins.out_sample_rate, song.master.timing) / (tempo * ins.speed);
+        if old <= 0.0 {
+            return;
+        }
+        let factor = f64::from(ins.samples_per_tick) / f64::from(old);
+        for smp in [
+            &mut self.smp_count,
+            &mut self.smp_start,
+            &mut self.smp_end,
+            &mut self.smp_repeat,
+        ] {
+            if *smp != SampleT::MAX {
+                *smp = (*smp as f64 * factor) as SampleT;
+            }
+        }
+    }
+    fn advance_fade(&mut self, frames: usize) {
+        let Some(fade) = &mut self.states.fade else {
+            return;
+        };
+        fade.pos = fade.pos.saturating_add(frames.try_into().unwrap_or(u32::MAX));
+        if fade.is_done() {
+            self.group_gains.0 = fade.to;
+            for &idx in &fade.mute_at_end {
+                if let Some(unit) = self.units.get_mut(idx) {
+                    unit.mute = true;
+                }
+            }
+            self.states.fade = None;
+        } else {
+            self.group_gains.0 = fade.gains();
+        }
+    }
+    /// Advance playback to `to_sample` without rendering any audio.
+    ///
+    /// This is much faster than mooing into a throwaway buffer, which makes it useful
+    /// for seeking and for scanning through songs.
+    /// The phases of the [tremolo](crate::Unit::tremolo) and [auto-pan](crate::Unit::auto_pan)
+    /// LFOs advance as they would during playback, but effect buffers (delays, overdrives)
+    /// aren't touched.
+    ///
+    /// See [`SilentMode`] for the available modes.
+    ///
+    /// If `to_sample` is behind the current position, this does nothing.
+    ///
+    /// Returns false if the song ended before reaching `to_sample` (and isn't looping).
+    pub fn advance_silent(
+        &mut self,
+        ins: &MooInstructions,
+        song: &Song,
+        to_sample: SampleT,
+        mode: SilentMode,
+    ) -> bool {
+        if self.moo_end {
+            return false;
+        }
+        match mode {
+            SilentMode::Tones => {
+                while self.smp_count < to_sample {
+                    if !next_silent_sample(self, ins, song, &mut None) {
+                        self.moo_end = true;
+                        return false;
+                    }
+                }
+            }
+            SilentMode::Events => {
+                if self.smp_count >= to_sample {
+                    return true;
+                }
+                if to_sample >= self.smp_end {
+                    if !self.loop_ || self.smp_end <= self.smp_repeat {
+                        self.moo_end = true;
+                        return false;
+                    }
+                    let loop_len = self.smp_end - self.smp_repeat;
+                    self.smp_count = self.smp_repeat + (to_sample - self.smp_end) % loop_len;
+                    self.evt_idx = 0;
+                    self.tune_cow_voices(ins, song.master.timing);
+                } else {
+                    self.smp_count = to_sample;
+                }
+                do_due_events(self, ins, &song.events, &song.master, &mut None);
+            }
+        }
+        true
+    }
+    /// Build a [`SeekIndex`] for `song`, with a seek point every `granularity` measures.
+    ///
+    /// Should be called after [`moo_prepare`], as it uses the same end position and
+    /// sample rate as the playback.
+    /// The current playback state is left untouched.
+    ///
+    /// A granularity of 0 is treated as 1.
+    #[must_use]
+    pub fn build_seek_index(
+        &self,
+        ins: &MooInstructions,
+        song: &Song,
+        granularity: Meas,
+    ) -> SeekIndex {
+        let mut scratch = Self {
+            smp_smooth: self.smp_smooth,
+            smp_end: self.smp_end,
+            smp_repeat: self.smp_repeat,
+            smp_stride: self.smp_stride,
+            pan_time_len: self.pan_time_len,
+            units: Box::default(),
+            ..Self::default()
+        };
+        scratch.units.clone_from(&self.units.0);
+        scratch.tune_cow_voices(ins, song.master.timing);
+        let mut points = Vec::new();
+        let mut meas: Meas = 0;
+        loop {
+            let sample = meas_to_sample(meas, ins.samples_per_tick, song.master.timing);
+            if sample >= scratch.smp_end
+                || !scratch.advance_silent(ins, song, sample, SilentMode::Tones)
+            {
+                break;
+            }
+            points.push(SeekPoint {
+                sample,
+                evt_idx: scratch.evt_idx,
+                time_pan_index: scratch.time_pan_index,
+                units: scratch.units.to_vec(),
+            });
+            let Some(next) = meas.checked_add(granularity.max(1)) else {
+                break;
+            };
+            meas = next;
+        }
+        SeekIndex { points }
+    }
+    /// Seek to `to_sample` using a [`SeekIndex`] built by [`Self::build_seek_index`].
+    ///
+    /// The state of the units is restored from the closest seek point before `to_sample`,
+    /// and then advanced like [`SilentMode::Tones`], so playback continues seamlessly.
+    /// This takes at most `granularity` measures worth of silent advancing, no matter
+    /// how far into the song `to_sample` is.
+    ///
+    /// Positions past the end of a looping song fall back to [`Self::advance_silent`].
+    ///
+    /// Returns false if `to_sample` is past the end of the song (and it isn't looping).
+    pub fn seek_indexed(
+        &mut self,
+        ins: &MooInstructions,
+        song: &Song,
+        index: &SeekIndex,
+        to_sample: SampleT,
+    ) -> bool {
+        self.moo_end = false;
+        self.tail_left = None;
+        self.tail_silent = 0;
+        let point = if to_sample < self.smp_end {
+            index.point_before(to_sample)
+        } else {
+            None
+        };
+        if let Some(point) = point {
+            self.smp_count = point.sample;
+            self.evt_idx = point.evt_idx;
+            self.time_pan_index = point.time_pan_index;
+            for (unit, snapshot) in zip(self.units.iter_mut(), &point.units) {
+                let mute = unit.mute;
+                unit.clone_from(snapshot);
+                unit.mute = mute;
+            }
+        } else {
+            self.smp_count = self.smp_start;
+            self.evt_idx = 0;
+            self.tune_cow_voices(ins, song.master.timing);
+        }
+        self.advance_silent(ins, song, to_sample, SilentMode::Tones)
+    }
+    fn moo_inner<T: OutSample>(
+        &mut self,
+        ins: &MooInstructions,
+        song: &Song,
+        buf: &mut [T],
+        group_out: &mut [GroupSamples],
+        advance: bool,
+        source: Option<&mut dyn EventSource>,
+    ) -> bool {
+        let frames = buf.as_chunks_mut().0;
+        let len = frames.len();
+        self.moo_frames(ins, song, len, group_out, advance, source, |i, out| {
+            frames[i] = out.map(T::from_moo_samp);
+        })
+    }
+    /// Moo `len` frames, handing each of them to `write` with its index
+    #[expect(clippy::too_many_arguments)]
+    fn moo_frames(
+        &mut self,
+        ins: &MooInstructions,
+        song: &Song,
+        len: usize,
+        group_out: &mut [GroupSamples],
+        advance: bool,
+        mut source: Option<&mut dyn EventSource>,
+        mut write: impl FnMut(usize, [i32; 2]),
     ) -> bool {
         if self.moo_end {
             return false;
         }
 
-        for out_samp in buf.as_chunks_mut().0 {
-            if !next_sample(
-                self,
-                ins,
-                &song.events,
-                &song.master,
-                ins.out_sample_rate,
-                out_samp,
-                advance,
-            ) {
+        let group_frames = group_out.as_chunks_mut().0;
+        let mut seek_fade = self.seek_fade.take();
+        for i in 0..len {
+            let mut out = [MooSamp::default(); 2];
+            let more = if let Some(fade) = seek_fade.as_mut().filter(|fade| fade.pos < fade.len) {
+                next_sample_fading(
+                    self,
+                    fade,
+                    ins,
+                    song,
+                    &mut out,
+                    group_frames.get_mut(i),
+                    advance,
+                    &mut source,
+                )
+            } else {
+                next_sample(
+                    self,
+                    ins,
+                    song,
+                    &mut out,
+                    group_frames.get_mut(i),
+                    advance,
+                    &mut source,
+                )
+            };
+            write(i, out.map(|smp| smp.0));
+            if !more {
                 self.moo_end = true;
                 break;
             }
         }
+        self.seek_fade = seek_fade;
+        self.meter.end_block();
 
         true
     }
 }
 
+/// Produces events on the fly while mooing, for example for procedurally generated music.
+///
+/// The events are played with the units, voices, and effects of the [`Herd`], just like
+/// the events of the [`Song`].
+///
+/// The clock the source receives is the [`Tick`] of the song, which jumps back to the repeat
+/// position when the song loops (see [`EventSource::repeat`]).
+/// For endless playback, set [`Herd::smp_end`] to [`SampleT::MAX`]
+/// after [`moo_prepare`].
+///
+/// Closures of the form `FnMut(Tick) -> Option<Event>` implement this trait.
+pub trait EventSource {
+    /// Return the next event that should be played at or before `clock`.
+    ///
+    /// This is called repeatedly until it returns `None`, so it should only return events
+    /// that are due, and leave the rest for later calls.
+    fn next_event(&mut self, clock: Tick) -> Option<Event>;
+    /// Called when the song loops, and the clock jumps back to `clock`.
+    fn repeat(&mut self, clock: Tick) {
+        let _ = clock;
+    }
+}
+
+impl<F: FnMut(Tick) -> Option<Event>> EventSource for F {
+    fn next_event(&mut self, clock: Tick) -> Option<Event> {
+        self(clock)
+    }
+}
+
+/// [`EventSource`] that plays the events of an iterator.
+///
+/// The iterator should yield the events in tick order.
+pub struct IterSource<I: Iterator<Item = Event>> {
+    iter: Peekable<I>,
+}
+
+impl<I: Iterator<Item = Event>> IterSource<I> {
+    /// Create a source from an iterator of events
+    pub fn new(iter: impl IntoIterator<IntoIter = I>) -> Self {
+        Self {
+            iter: iter.into_iter().peekable(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Event>> EventSource for IterSource<I> {
+    fn next_event(&mut self, clock: Tick) -> Option<Event> {
+        self.iter.next_if(|eve| eve.tick <= clock)
+    }
+}
+
+/// How [`Herd::advance_silent`] advances the playback
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SilentMode {
+    /// Jump straight to the target position, only doing the events on the way.
+    ///
+    /// Notes that are playing at the target position will start from their beginning,
+    /// like after [`Herd::seek_to_sample`].
+    Events,
+    /// Advance sample by sample, keeping track of the envelopes, keys, and sample positions
+    /// of the playing notes.
+    ///
+    /// Slower than [`SilentMode::Events`], but playback continues seamlessly
+    /// from the target position.
+    Tones,
+}
+
+/// Snapshots of the playback state at regular intervals, for fast seeking.
+///
+/// Built with [`Herd::build_seek_index`], and used by [`Herd::seek_indexed`].
+///
+/// The index is only valid for the song, voices, and units it was built with.
+/// It needs to be rebuilt when any of them change (muting units is fine).
+#[derive(Default)]
+pub struct SeekIndex {
+    points: Vec<SeekPoint>,
+}
+
+impl SeekIndex {
+    /// The sample positions of the seek points, in order
+    pub fn samples(&self) -> impl Iterator<Item = SampleT> {
+        self.points.iter().map(|point| point.sample)
+    }
+    /// The number of seek points
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.points.len()
+    }
+    /// Whether the index doesn't have any seek points
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+    fn point_before(&self, sample: SampleT) -> Option<&SeekPoint> {
+        let idx = self.points.partition_point(|point| point.sample <= sample);
+        idx.checked_sub(1).map(|idx| &self.points[idx])
+    }
+}
+
+struct SeekPoint {
+    sample: SampleT,
+    evt_idx: usize,
+    time_pan_index: usize,
+    units: Vec<Unit>,
+}
+
 /// Plan for the cows on how to moo the song
 #[derive(Copy, Clone)]
 pub struct MooPlan {
@@ -353,6 +1321,80 @@ pub struct MooPlan {
     pub loop_: bool,
 }
 
+/// How the mix is kept within the 16 bit sample range
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MixPolicy {
+    /// Loud samples are clipped when rendering `i16` samples, like `PxTone` does
+    #[default]
+    Clip,
+    /// Turn the whole mix down by a single gain, just enough that it never clips.
+    ///
+    /// [`moo_prepare`] finds the gain by playing the song through once, from the start
+    /// position to the end, which takes about as long as rendering it.
+    /// Unlike a compressor or a limiter, this doesn't change the dynamics of the song.
+    /// The gain is reported by [`Herd::auto_gain`].
+    ///
+    /// Events from an [`EventSource`] and [music states](crate::states) can't be known in
+    /// advance, so they can still make the mix clip.
+    AutoGain,
+}
+
+/// What happens to the state of effects when the song loops back to its repeat position.
+///
+/// The jump itself is sample-exact: the sample after the last sample of the song is the sample
+/// at the repeat position, even in the middle of a [`Herd::moo`] buffer.
+/// The units always stop their notes and start over at the repeat position, but the delay
+/// and [`PanTime`](crate::PanTime) buffers still hold audio from the end of the song.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoopPolicy {
+    /// Keep the buffers, so the echoes from the end of the song ring into the repeat.
+    ///
+    /// This is what `PxTone` does.
+    #[default]
+    Preserve,
+    /// Clear the buffers, so the repeat sounds like playback starting there
+    Clear,
+}
+
+/// How long playback continues after the end of a song that doesn't loop.
+///
+/// When a song ends, the delays still hold echoes, and notes may still be fading out.
+/// During the tail, no more events are played, but the units and effects keep sounding,
+/// so exported songs don't end with an abrupt cutoff.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tail {
+    /// Stop right at the end of the song
+    #[default]
+    None,
+    /// Keep rendering for this many samples
+    Samples(SampleT),
+    /// Keep rendering until the output is silent.
+    ///
+    /// The output counts as silent when no sample is louder than `threshold` (on the 16 bit
+    /// scale) for longer than the longest delay. The tail is at most `max` samples long.
+    UntilSilent {
+        /// The loudest sample that counts as silence
+        threshold: u16,
+        /// The longest the tail can be
+        max: SampleT,
+    },
+}
+
+impl Tail {
+    const fn max_len(self) -> SampleT {
+        match self {
+            Self::None => 0,
+            Self::Samples(len) | Self::UntilSilent { max: len, .. } => len,
+        }
+    }
+    const fn threshold(self) -> u32 {
+        match self {
+            Self::UntilSilent { threshold, .. } => threshold as u32,
+            Self::None | Self::Samples(_) => 0,
+        }
+    }
+}
+
 /// Start position that can be given in different units
 #[derive(Copy, Clone)]
 pub enum StartPosPlan {
@@ -363,3 +1405,1113 @@ pub enum StartPosPlan {
     /// Start position as [`f32`]
     F32(f32),
 }
+
+#[cfg(test)]
+fn test_setup(song: &Song) -> (MooInstructions, Herd) {
+    use crate::rebuild_tones;
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(crate::presets::wave::factory_voices());
+    let mut herd = Herd::default();
+    herd.units.0.push(Unit::new());
+    rebuild_tones(&mut ins, NATIVE_SAMPLE_RATE, &mut [], &mut [], &song.master);
+    let plan = MooPlan {
+        start_pos: StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: false,
+    };
+    moo_prepare(&mut ins, &mut herd, song, &plan).unwrap();
+    (ins, herd)
+}
+
+#[test]
+#[expect(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn test_endless_playback_counters() {
+    let song = Song::default();
+    let (ins, mut herd) = test_setup(&song);
+    herd.smp_end = SampleT::MAX;
+    // Notes with a release go through the math that used to overflow
+    assert!(ins.voices[crate::VoiceIdx(0)].slots().any(|slot| slot.inst.env_release != 0));
+    // Jump ahead instead of playing for days: past 2^24 samples (f32 precision), 2^31 (i32),
+    // 2^32 (27 hours at 44.1 kHz), and 2^36 (18 days)
+    for start in [1 << 24, 1 << 31, (1 << 32) - 1024, 1 << 36] {
+        herd.smp_count = start;
+        let clock = current_tick(&herd, &ins);
+        assert_eq!(
+            SampleT::from(clock),
+            (start as f64 / f64::from(ins.samples_per_tick)) as SampleT
+        );
+        let mut source = IterSource::new([Event {
+            payload: EventPayload::On { duration: 480 },
+            unit: UnitIdx(0),
+            tick: clock,
+        }]);
+        let mut buf = [0i16; 4096];
+        assert!(herd.moo_with_source(&ins, &song, &mut source, &mut buf, true));
+        assert!(buf.iter().any(|&s| s != 0), "{start}");
+        assert_eq!(herd.smp_count, start + 2048);
+    }
+}
+
+#[test]
+fn test_event_source() {
+    let song = Song::default();
+    let (ins, mut herd) = test_setup(&song);
+    herd.smp_end = SampleT::MAX;
+    let mut buf = [0i16; 2048];
+    // Without a source, the empty song is silent
+    herd.moo(&ins, &song, &mut buf, true);
+    assert!(buf.iter().all(|&s| s == 0));
+    let mut source = IterSource::new([Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }]);
+    herd.moo_with_source(&ins, &song, &mut source, &mut buf, true);
+    assert!(buf.iter().any(|&s| s != 0));
+}
+
+#[test]
+fn test_event_observer() {
+    use std::sync::{Arc, Mutex};
+    let mut song = Song::default();
+    let eve = |tick, payload| Event {
+        payload,
+        unit: UnitIdx(0),
+        tick,
+    };
+    song.events.eves = vec![
+        eve(0, EventPayload::On { duration: 240 }),
+        eve(480, EventPayload::On { duration: 240 }),
+    ];
+    song.recalculate_length();
+    let (ins, mut herd) = test_setup(&song);
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&seen);
+    herd.set_event_observer(move |eve, smp| sink.lock().unwrap().push((eve.tick, smp)));
+    let mut source = IterSource::new([eve(240, EventPayload::Velocity(64))]);
+    let mut buf = vec![0i16; 60_000];
+    herd.moo_with_source(&ins, &song, &mut source, &mut buf, true);
+    let seen = seen.lock().unwrap().clone();
+    let ticks: Vec<Tick> = seen.iter().map(|&(tick, _)| tick).collect();
+    assert_eq!(ticks, [0, 240, 480]);
+    // Each event is reported on the first sample of its tick
+    let spt = f64::from(ins.samples_per_tick);
+    for (tick, smp) in seen {
+        #[expect(clippy::cast_precision_loss)]
+        let smp = smp as f64;
+        assert!(smp / spt >= f64::from(tick), "{tick} {smp}");
+        assert!(
+            tick == 0 || (smp - 1.0) / spt < f64::from(tick),
+            "{tick} {smp}"
+        );
+    }
+    herd.clear_event_observer();
+}
+
+#[test]
+fn test_seek_with_crossfade() {
+    let mut song = Song::default();
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 4800 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    let setup = || {
+        let (ins, mut herd) = test_setup(&song);
+        let mut buf = vec![0i16; 20_000];
+        herd.moo(&ins, &song, &mut buf, true);
+        (ins, herd)
+    };
+    let (ins, mut fading) = setup();
+    let (_, mut old) = setup();
+    let (_, mut hard) = setup();
+    fading.seek_with_crossfade(1000, 500);
+    hard.seek_to_sample(1000);
+    let mut buf = vec![0i16; 2000];
+    let mut old_buf = buf.clone();
+    let mut hard_buf = buf.clone();
+    fading.moo(&ins, &song, &mut buf, true);
+    old.moo(&ins, &song, &mut old_buf, true);
+    hard.moo(&ins, &song, &mut hard_buf, true);
+    // Starts out at the old position, and ends up at the new one
+    assert_eq!(buf[..2], old_buf[..2]);
+    assert_ne!(buf[..1000], hard_buf[..1000]);
+    assert_eq!(buf[1000..], hard_buf[1000..]);
+    assert_eq!(fading.smp_count, hard.smp_count);
+}
+
+#[test]
+fn test_seek_fade_copy() {
+    use crate::states::{MusicState, Transition};
+    let mut song = Song::default();
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 4800 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    let (_, mut herd) = test_setup(&song);
+    herd.states.add(MusicState::new("calm"));
+    let transition = Transition {
+        on_meas: true,
+        fade: Duration::from_millis(100),
+    };
+    assert!(herd.trigger_state("calm", transition));
+    herd.seek_with_crossfade(1000, 500);
+    // The old position keeps its pending transition
+    let old = &herd.seek_fade.as_ref().unwrap().old;
+    assert_eq!(old.states.states().len(), 1);
+    assert!(old.states.is_transitioning());
+    assert_eq!(old.units.0.len(), herd.units.0.len());
+    // The next crossfade reuses the copy instead of allocating a new one
+    let units = old.units.0.as_ptr();
+    let states = old.states.states().as_ptr();
+    herd.seek_with_crossfade(2000, 500);
+    let old = &herd.seek_fade.as_ref().unwrap().old;
+    assert_eq!(old.units.0.as_ptr(), units);
+    assert_eq!(old.states.states().as_ptr(), states);
+    assert_eq!(old.smp_count, 1000);
+}
+
+#[test]
+fn test_moo_mono_and_planar() {
+    let mut song = Song::default();
+    song.events.eves = vec![
+        Event {
+            payload: EventPayload::PanVol(0),
+            unit: UnitIdx(0),
+            tick: 0,
+        },
+        Event {
+            payload: EventPayload::On { duration: 480 },
+            unit: UnitIdx(0),
+            tick: 0,
+        },
+    ];
+    song.recalculate_length();
+    let (ins, mut herd) = test_setup(&song);
+    let mut interleaved = vec![0i16; 8000];
+    herd.moo(&ins, &song, &mut interleaved, true);
+
+    let (ins, mut herd) = test_setup(&song);
+    let mut left = vec![0i16; 4000];
+    let mut right = vec![0i16; 4100];
+    herd.moo_planar(&ins, &song, &mut [&mut left, &mut right], true);
+    let (l, r): (Vec<i16>, Vec<i16>) =
+        interleaved.as_chunks().0.iter().map(|&frame| frame.into()).unzip();
+    assert_eq!(left, l);
+    assert_eq!(right[..4000], r);
+    assert!(
+        right[4000..].iter().all(|&s| s == 0),
+        "only full frames are rendered"
+    );
+    assert_ne!(l, r);
+
+    let (ins, mut herd) = test_setup(&song);
+    let mut mono = vec![0i16; 4000];
+    herd.moo_planar(&ins, &song, &mut [&mut mono], true);
+    let expected: Vec<i16> = zip(l, r).map(|(l, r)| l.midpoint(r)).collect();
+    assert_eq!(mono, expected);
+}
+
+#[test]
+fn test_speed_and_pitch() {
+    let mut song = Song::default();
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    let (mut ins, mut herd) = test_setup(&song);
+    let spt = ins.samples_per_tick;
+    let stride = herd.smp_stride();
+    let end = herd.smp_end;
+
+    // Changing the speed halfway keeps the position in the song
+    herd.advance_silent(&ins, &song, end / 2, SilentMode::Events);
+    herd.set_speed(&mut ins, &song, 2.0);
+    assert!((ins.samples_per_tick - spt / 2.0).abs() < 0.001);
+    assert_eq!(herd.smp_count, end / 4);
+    assert_eq!(herd.smp_end, end / 2);
+    assert!((herd.smp_stride() - stride).abs() < f32::EPSILON);
+
+    herd.set_pitch(&mut ins, 2.0);
+    assert!((herd.smp_stride() / stride - 2.0).abs() < f32::EPSILON);
+    assert!((ins.samples_per_tick - spt / 2.0).abs() < 0.001);
+
+    // Both are picked up when preparing again
+    let plan = MooPlan {
+        start_pos: StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: false,
+    };
+    moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+    assert!((ins.samples_per_tick - spt / 2.0).abs() < 0.001);
+    assert!((herd.smp_stride() / stride - 2.0).abs() < f32::EPSILON);
+}
+
+#[test]
+fn test_advance_silent() {
+    let mut song = Song::default();
+    let eve = |tick, payload| Event {
+        payload,
+        unit: UnitIdx(0),
+        tick,
+    };
+    let lfo = |rate, depth| crate::LfoParams {
+        rate,
+        depth,
+        shape: crate::NoiseType::Sine,
+    };
+    song.events.eves = vec![
+        eve(0, EventPayload::Portament { duration: 100 }),
+        eve(0, EventPayload::Tremolo(lfo(700, 100))),
+        eve(0, EventPayload::AutoPan(lfo(300, 64))),
+        eve(0, EventPayload::On { duration: 960 }),
+        eve(
+            240,
+            EventPayload::Key(crate::DEFAULT_KEY + crate::Key::SEMITONE),
+        ),
+    ];
+    song.recalculate_length();
+    let (ins, mut herd) = test_setup(&song);
+    let mut expected = vec![0i16; 20_000];
+    herd.moo(&ins, &song, &mut expected, true);
+    let (ins, mut herd) = test_setup(&song);
+    assert!(herd.advance_silent(&ins, &song, 5_000, SilentMode::Tones));
+    assert_eq!(herd.smp_count, 5_000);
+    let mut buf = vec![0i16; 10_000];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert!(buf.iter().any(|&s| s != 0));
+    assert_eq!(buf, expected[10_000..]);
+}
+
+#[test]
+fn test_seek_index() {
+    let mut song = Song::default();
+    let eve = |tick, payload| Event {
+        payload,
+        unit: UnitIdx(0),
+        tick,
+    };
+    song.events.eves = (0..8)
+        .flat_map(|i| {
+            [
+                eve(
+                    i * 960,
+                    EventPayload::Key(crate::DEFAULT_KEY + crate::Key::semitones(i.cast_signed())),
+                ),
+                eve(i * 960, EventPayload::On { duration: 1200 }),
+            ]
+        })
+        .collect();
+    song.recalculate_length();
+    let (ins, herd) = test_setup(&song);
+    let index = herd.build_seek_index(&ins, &song, 1);
+    assert_eq!(index.len(), song.master.meas_num as usize);
+    let target = meas_to_sample(3, ins.samples_per_tick, song.master.timing) + 1234;
+    let (ins, mut herd) = test_setup(&song);
+    let mut expected = vec![0i16; 2 * usize::try_from(target).unwrap() + 8192];
+    herd.moo(&ins, &song, &mut expected, true);
+    let (ins, mut herd) = test_setup(&song);
+    assert!(herd.seek_indexed(&ins, &song, &index, target));
+    assert_eq!(herd.smp_count, target);
+    let mut buf = vec![0i16; 8192];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert!(buf.iter().any(|&s| s != 0));
+    assert_eq!(buf, expected[2 * usize::try_from(target).unwrap()..]);
+}
+
+#[test]
+fn test_checkpoint() {
+    use crate::{
+        CheckpointError, GroupIdx,
+        render::{Checkpoint, moo_checkpointed},
+    };
+    let mut song = Song::default();
+    let eve = |tick, payload| Event {
+        payload,
+        unit: UnitIdx(0),
+        tick,
+    };
+    let lfo = |rate, depth| crate::LfoParams {
+        rate,
+        depth,
+        shape: crate::NoiseType::Sine,
+    };
+    song.events.eves = vec![
+        eve(0, EventPayload::Portament { duration: 100 }),
+        eve(0, EventPayload::Tremolo(lfo(700, 100))),
+        eve(0, EventPayload::AutoPan(lfo(300, 64))),
+        eve(0, EventPayload::On { duration: 960 }),
+        eve(
+            240,
+            EventPayload::Key(crate::DEFAULT_KEY + crate::Key::SEMITONE),
+        ),
+    ];
+    song.recalculate_length();
+    let setup = || {
+        let (ins, mut herd) = test_setup(&song);
+        let timing = song.master.timing;
+        let mut delay = Delay {
+            group: GroupIdx(0),
+            rate: 50.0,
+            freq: 8.0,
+            ..Delay::default()
+        };
+        delay.rebuild(timing.beats_per_meas, timing.bpm, NATIVE_SAMPLE_RATE);
+        herd.delays.push(delay);
+        (ins, herd)
+    };
+    let (ins, mut herd) = setup();
+    let mut expected = vec![0i16; 40_000];
+    herd.moo(&ins, &song, &mut expected, true);
+    let (ins, mut herd) = setup();
+    let mut checkpoints = Vec::new();
+    let mut buf = vec![0i16; 20_000];
+    assert!(moo_checkpointed(
+        &mut herd,
+        &ins,
+        &song,
+        &mut buf,
+        3_000,
+        |chk| {
+            checkpoints.push(chk.as_bytes().to_vec());
+        }
+    ));
+    assert_eq!(buf, expected[..20_000]);
+    assert_eq!(checkpoints.len(), 4);
+    let checkpoint = Checkpoint::from_bytes(checkpoints[1].clone()).unwrap();
+    assert_eq!(checkpoint.sample(), 6_000);
+    // Resume in a fresh herd, with the delay buffer half full
+    let (ins, mut herd) = setup();
+    checkpoint.restore(&mut herd).unwrap();
+    let mut buf = vec![0i16; 40_000 - 12_000];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert!(buf.iter().any(|&s| s != 0));
+    assert_eq!(buf, expected[12_000..]);
+    // A herd with different units is left alone
+    let (_, mut herd) = setup();
+    herd.units.0.push(Unit::new());
+    assert_eq!(
+        checkpoint.restore(&mut herd),
+        Err(CheckpointError::LayoutMismatch)
+    );
+    assert_eq!(herd.smp_count, 0);
+    assert_eq!(
+        Checkpoint::from_bytes(b"not a checkpoint".to_vec()).unwrap_err(),
+        CheckpointError::NotACheckpoint
+    );
+    let truncated = Checkpoint::from_bytes(checkpoints[1][..100].to_vec()).unwrap();
+    let (_, mut herd) = setup();
+    assert!(matches!(
+        truncated.restore(&mut herd),
+        Err(CheckpointError::Data { .. })
+    ));
+}
+
+#[test]
+fn test_unit_tap() {
+    let mut song = Song::default();
+    song.events.eves.push(Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(0),
+        tick: 0,
+    });
+    song.recalculate_length();
+    let (ins, mut herd) = test_setup(&song);
+    // Render enough to wrap around the ring buffer
+    let mut buf = [0i16; 200];
+    herd.moo(&ins, &song, &mut buf, true);
+    let tap = herd.unit_tap(UnitIdx(0)).unwrap();
+    // With a single unit without effects or pan time, the output is what the unit rendered
+    let frames = buf.as_chunks::<2>().0;
+    assert_eq!(tap.depth(), 64);
+    for (age, frame) in frames.iter().rev().take(tap.depth()).enumerate() {
+        for ch in 0..2 {
+            assert_eq!(tap.sample(ch, age), Some(i32::from(frame[usize::from(ch)])));
+        }
+    }
+    assert!(tap.recent(0).any(|smp| smp != 0));
+    assert_eq!(tap.recent(0).count(), tap.depth());
+    assert_eq!(tap.sample(0, tap.depth()), None);
+    assert_eq!(tap.recent(2).count(), 0);
+    assert!(herd.unit_tap(UnitIdx(1)).is_none());
+}
+
+#[test]
+fn test_play_length() {
+    use crate::master::LoopPoints;
+    let mut song = Song::default();
+    song.master.timing.bpm = 480.0;
+    song.master.loop_points = LoopPoints {
+        repeat: 1,
+        last: NonZeroU32::new(3),
+    };
+    let (mut ins, mut herd) = test_setup(&song);
+    let plan = MooPlan {
+        start_pos: StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: true,
+    };
+    moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+    let len = timing::play_length(&song, 2, ins.out_sample_rate);
+    assert_eq!(len, herd.smp_repeat + 2 * (herd.smp_end - herd.smp_repeat));
+    let mut buf = vec![0i16; (usize::try_from(len).unwrap() - 1) * 2];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert_eq!(herd.smp_count, herd.smp_end - 1);
+    // The last sample of the second loop jumps back to the repeat point
+    herd.moo(&ins, &song, &mut [0i16; 2], true);
+    assert_eq!(herd.smp_count, herd.smp_repeat);
+    assert_eq!(
+        timing::play_length(&song, 1, ins.out_sample_rate),
+        herd.smp_end
+    );
+}
+
+#[test]
+fn test_render_looped() {
+    let mut song = Song::default();
+    song.master.timing.bpm = 480.0;
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 1920 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    let (mut ins, mut herd) = test_setup(&song);
+    let fade = Duration::from_millis(100);
+    let buf = render_looped(&song, &mut herd, &mut ins, 2, fade).unwrap();
+    let body = usize::try_from(timing::play_length(&song, 2, ins.out_sample_rate)).unwrap();
+    assert_eq!(buf.len(), (body + 4410) * 2);
+    // The fade starts at the start of the third loop, which sounds like the first one
+    let fade_part = &buf[body * 2..];
+    assert_eq!(fade_part[..2], buf[..2]);
+    let (fade_start, fade_end) = fade_part.split_at(fade_part.len() / 2);
+    let peak = |smps: &[i16]| smps.iter().map(|s| s.unsigned_abs()).max().unwrap();
+    assert!(peak(fade_start) > peak(fade_end));
+    assert!(peak(&buf[buf.len() - 20..]) < peak(&buf[..body * 2]) / 50);
+    // Lengths that overflow are an error instead of a panic
+    assert_eq!(
+        render_looped(&song, &mut herd, &mut ins, 2, Duration::MAX),
+        Err(RenderError::TooLong)
+    );
+    song.master.timing.bpm = f32::MIN_POSITIVE;
+    assert_eq!(
+        render_looped(&song, &mut herd, &mut ins, u32::MAX, fade),
+        Err(RenderError::TooLong)
+    );
+}
+
+#[test]
+fn test_sample_rate_range() {
+    use crate::SourceSampleRate;
+    let mut song = Song::default();
+    song.events.eves = [
+        EventPayload::PanTime(crate::PanTime(0)),
+        EventPayload::On { duration: 480 },
+    ]
+    .map(|payload| Event {
+        payload,
+        unit: UnitIdx(0),
+        tick: 0,
+    })
+    .to_vec();
+    song.recalculate_length();
+    assert_eq!(SampleRate::new(384_001), None);
+    assert_eq!(
+        SampleRate::try_from(SourceSampleRate(384_000)),
+        Ok(SampleRate::MAX_SUPPORTED)
+    );
+    assert!(SampleRate::try_from(SourceSampleRate(u32::MAX)).is_err());
+    for rate in [0, 7_999].map(SampleRate) {
+        let (mut ins, mut herd) = test_setup(&song);
+        let smp_end = herd.smp_end;
+        ins.out_sample_rate = rate;
+        assert_eq!(
+            render_looped(&song, &mut herd, &mut ins, 1, Duration::ZERO),
+            Err(RenderError::UnsupportedSampleRate(UnsupportedSampleRate(
+                rate
+            )))
+        );
+        assert_eq!(herd.smp_end, smp_end);
+    }
+    // Pan time wraps around like PxTone at low rates, instead of panicking
+    for rate in [
+        SampleRate::MIN_SUPPORTED,
+        SampleRate(11_025),
+        SampleRate(96_000),
+        SampleRate::MAX_SUPPORTED,
+    ] {
+        let (mut ins, mut herd) = test_setup(&song);
+        ins.out_sample_rate = rate;
+        crate::rebuild_tones(&mut ins, rate, &mut [], &mut [], &song.master);
+        let buf = render_looped(&song, &mut herd, &mut ins, 1, Duration::ZERO).unwrap();
+        assert!(buf.iter().any(|&smp| smp != 0), "{rate:?}");
+    }
+}
+
+#[test]
+#[cfg(feature = "effects")]
+fn test_loop_policy() {
+    use crate::{Delay, DelayUnit};
+    let mut song = Song::default();
+    song.master.timing.bpm = 480.0;
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 1920 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    let render = |policy| {
+        let (mut ins, mut herd) = test_setup(&song);
+        ins.loop_policy = policy;
+        let mut delay = Delay {
+            unit: DelayUnit::Beat,
+            freq: 2.0,
+            rate: 50.0,
+            ..Delay::default()
+        };
+        delay.rebuild(
+            song.master.timing.beats_per_meas,
+            song.master.timing.bpm,
+            ins.out_sample_rate,
+        );
+        herd.delays.push(delay);
+        herd.set_loop(true);
+        let len = usize::try_from(herd.smp_end).unwrap();
+        let mut buf = vec![0i16; len * 4];
+        // Buffers that don't line up with the loop point
+        for chunk in buf.chunks_mut(1002) {
+            herd.moo(&ins, &song, chunk, true);
+        }
+        let (first, second) = buf.split_at(len * 2);
+        (first.to_vec(), second.to_vec())
+    };
+    let (first, second) = render(LoopPolicy::Clear);
+    assert!(first.iter().any(|&s| s != 0));
+    assert_eq!(first, second);
+    // The echo of the end of the song is heard after the loop
+    let (first, second) = render(LoopPolicy::Preserve);
+    assert_ne!(first, second);
+}
+
+#[test]
+#[cfg(feature = "effects")]
+fn test_tail() {
+    use crate::{Delay, DelayUnit};
+    let mut song = Song::default();
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 1920 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    // Returns the rendered song, and how long the tail was
+    let render = |tail| {
+        let (mut ins, mut herd) = test_setup(&song);
+        ins.tail = tail;
+        let mut delay = Delay {
+            unit: DelayUnit::Beat,
+            freq: 1.0,
+            rate: 50.0,
+            ..Delay::default()
+        };
+        delay.rebuild(
+            song.master.timing.beats_per_meas,
+            song.master.timing.bpm,
+            ins.out_sample_rate,
+        );
+        herd.delays.push(delay);
+        let end = usize::try_from(herd.smp_end).unwrap();
+        let mut buf = vec![0i16; end * 8];
+        herd.moo(&ins, &song, &mut buf, true);
+        (buf, usize::try_from(herd.smp_count).unwrap() - end)
+    };
+    let peak = |smps: &[i16]| smps.iter().map(|s| s.unsigned_abs()).max().unwrap();
+    let (cut, len) = render(Tail::None);
+    assert_eq!(len, 0);
+    let end = cut.iter().rposition(|&s| s != 0).unwrap() / 2 + 1;
+    let (long, len) = render(Tail::Samples(20_000));
+    assert_eq!(len, 20_000);
+    assert_eq!(long[..end * 2], cut[..end * 2]);
+    assert!(peak(&long[end * 2..]) > 1000);
+    assert!(long[(end + len) * 2..].iter().all(|&s| s == 0));
+    let (faded, len) = render(Tail::UntilSilent {
+        threshold: 16,
+        max: SampleT::MAX,
+    });
+    assert!(len > 20_000);
+    assert!(len < end * 3, "{len}");
+    assert!(peak(&faded[(end + len) * 2 - 2000..]) <= 16);
+}
+
+#[test]
+fn test_tail_without_delay() {
+    let mut song = Song::default();
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    // Without a delay, nothing rings past the end of the song, so the tail stops at once
+    for tail in [
+        Tail::UntilSilent {
+            threshold: 16,
+            max: SampleT::MAX,
+        },
+        Tail::None,
+    ] {
+        let (mut ins, mut herd) = test_setup(&song);
+        ins.tail = tail;
+        let end = herd.smp_end;
+        let mut buf = vec![0i16; usize::try_from(end).unwrap() * 4];
+        herd.moo(&ins, &song, &mut buf, true);
+        assert!(herd.moo_end);
+        assert_eq!(herd.smp_count, end);
+    }
+}
+
+#[test]
+fn test_moo_f32() {
+    let mut song = Song::default();
+    song.events.eves = vec![
+        Event {
+            payload: EventPayload::Volume(127),
+            unit: UnitIdx(0),
+            tick: 0,
+        },
+        Event {
+            payload: EventPayload::On { duration: 480 },
+            unit: UnitIdx(0),
+            tick: 0,
+        },
+    ];
+    song.recalculate_length();
+    let (ins, mut herd) = test_setup(&song);
+    herd.set_group_gain(crate::GroupIdx(0), 4.0);
+    let mut pcm16 = vec![0i16; 8192];
+    herd.moo(&ins, &song, &mut pcm16, true);
+    let (ins, mut herd) = test_setup(&song);
+    herd.set_group_gain(crate::GroupIdx(0), 4.0);
+    let mut floats = vec![0f32; 8192];
+    herd.moo(&ins, &song, &mut floats, true);
+    for (&pcm, &float) in zip(&pcm16, &floats) {
+        #[expect(clippy::cast_possible_truncation)]
+        let scaled = (float * 32768.0) as i32;
+        assert_eq!(i32::from(pcm), scaled.clamp(-32768, 32767));
+    }
+    // The i16 output clips, the f32 output keeps the headroom
+    assert!(floats.iter().any(|s| s.abs() > 1.0));
+}
+
+#[test]
+fn test_group_gain() {
+    use crate::GroupIdx;
+    let mut song = Song::default();
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    let render = |gains: &[(GroupIdx, f32)]| {
+        let (ins, mut herd) = test_setup(&song);
+        for &(group, gain) in gains {
+            herd.set_group_gain(group, gain);
+        }
+        let mut buf = vec![0i16; 8192];
+        herd.moo(&ins, &song, &mut buf, true);
+        buf
+    };
+    let plain = render(&[]);
+    assert!(plain.iter().any(|&smp| smp != 0));
+    // Unity gain, and the gain of a group that's not playing, leave the output bit-exact
+    assert_eq!(render(&[(GroupIdx(0), 0.5), (GroupIdx(0), 1.0)]), plain);
+    assert_eq!(render(&[(GroupIdx(1), 0.0)]), plain);
+    #[expect(clippy::cast_possible_truncation)]
+    let halved: Vec<i16> = plain.iter().map(|&smp| (f32::from(smp) * 0.5) as i16).collect();
+    assert_eq!(render(&[(GroupIdx(0), 0.5)]), halved);
+    assert!(render(&[(GroupIdx(0), 0.0)]).iter().all(|&smp| smp == 0));
+}
+
+#[test]
+fn test_auto_gain() {
+    let mut song = Song::default();
+    song.events.eves = vec![
+        Event {
+            payload: EventPayload::Volume(127),
+            unit: UnitIdx(0),
+            tick: 0,
+        },
+        Event {
+            payload: EventPayload::On { duration: 480 },
+            unit: UnitIdx(0),
+            tick: 0,
+        },
+    ];
+    song.recalculate_length();
+    let plan = MooPlan {
+        start_pos: StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: true,
+    };
+    let render = |policy| {
+        let (mut ins, mut herd) = test_setup(&song);
+        herd.set_group_gain(crate::GroupIdx(0), 4.0);
+        ins.mix_policy = policy;
+        moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+        let mut buf = vec![0f32; usize::try_from(herd.smp_end).unwrap() * 2];
+        herd.moo(&ins, &song, &mut buf, true);
+        (buf, herd.auto_gain())
+    };
+    let (clipping, gain) = render(MixPolicy::Clip);
+    assert_eq!(gain, None);
+    let (fixed, gain) = render(MixPolicy::AutoGain);
+    let gain = gain.unwrap();
+    let peak = |buf: &[f32]| buf.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+    assert!(peak(&clipping) > 1.0);
+    assert!(peak(&fixed) <= 1.0);
+    assert!(peak(&clipping).mul_add(gain, -peak(&fixed)).abs() < 0.001);
+    // Songs that don't clip are left alone
+    song.events.eves[0].payload = EventPayload::Volume(32);
+    let (mut ins, mut herd) = test_setup(&song);
+    ins.mix_policy = MixPolicy::AutoGain;
+    moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+    assert_eq!(herd.auto_gain(), Some(1.0));
+}
+
+#[test]
+fn test_event_budget() {
+    let mut song = Song::default();
+    song.events.eves = vec![
+        Event {
+            payload: EventPayload::Volume(1),
+            unit: UnitIdx(0),
+            tick: 0,
+        };
+        1000
+    ];
+    song.recalculate_length();
+    let (mut ins, mut herd) = test_setup(&song);
+    ins.event_budget = NonZeroU32::new(300);
+    herd.moo(&ins, &song, &mut [0i16; 6], true);
+    assert_eq!(herd.evt_idx, 900);
+    assert_eq!(herd.budget_exhausted, 3);
+    herd.moo(&ins, &song, &mut [0i16; 2], true);
+    assert_eq!(herd.evt_idx, 1000);
+    assert_eq!(herd.budget_exhausted, 3);
+}
+
+#[test]
+fn test_moo_with_groups() {
+    let mut song = Song::default();
+    song.events.eves = vec![
+        Event {
+            payload: EventPayload::SetGroup(crate::GroupIdx(2)),
+            unit: UnitIdx(0),
+            tick: 0,
+        },
+        Event {
+            payload: EventPayload::On { duration: 480 },
+            unit: UnitIdx(0),
+            tick: 0,
+        },
+    ];
+    song.recalculate_length();
+    let (ins, mut herd) = test_setup(&song);
+    let mut buf = [0i16; 2048];
+    let mut groups = vec![[0; 7]; 2048];
+    herd.moo_with_groups(&ins, &song, &mut buf, &mut groups, true);
+    assert!(buf.iter().any(|&s| s != 0));
+    for (&smp, group_smps) in zip(&buf, &groups) {
+        assert_eq!(i32::from(smp), group_smps[2]);
+        assert_eq!(group_smps.iter().sum::<i32>(), group_smps[2]);
+    }
+}
+
+#[test]
+fn test_set_unit_group() {
+    use crate::GroupIdx;
+    let mut song = Song::default();
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    let (ins, mut herd) = test_setup(&song);
+    let mut buf = [0i16; 2048];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert!(!herd.set_unit_group(UnitIdx(1), GroupIdx(3)));
+    assert!(!herd.set_unit_group(UnitIdx(0), GroupIdx(7)));
+    assert!(herd.set_unit_group(UnitIdx(0), GroupIdx(3)));
+    let mut groups = vec![[0; 7]; 1024];
+    herd.moo_with_groups(&ins, &song, &mut buf, &mut groups, true);
+    let fade_len = usize::from(herd.smp_smooth) * 2;
+    for (&smp, group_smps) in zip(&buf, &groups) {
+        assert_eq!(i32::from(smp), group_smps[0] + group_smps[3]);
+    }
+    // Both groups play during the fade, then only the new one
+    let (fade, after) = groups.split_at(fade_len);
+    assert!(fade.iter().any(|smps| smps[0] != 0 && smps[3] != 0));
+    assert!(fade[..2].iter().all(|smps| smps[3].abs() <= 1));
+    assert!(after.iter().all(|smps| smps[0] == 0));
+    assert!(after.iter().any(|smps| smps[3] != 0));
+}
+
+#[test]
+fn test_unit_levels() {
+    let mut song = Song::default();
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 1920 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    let (ins, mut herd) = test_setup(&song);
+    assert!(herd.unit_levels().is_empty());
+    let mut buf = [0i16; 2048];
+    herd.moo(&ins, &song, &mut buf, true);
+    let level = herd.unit_levels()[0];
+    let loudest = buf.iter().map(|s| s.unsigned_abs()).max().unwrap();
+    assert_eq!(level.peak[0].max(level.peak[1]), u32::from(loudest));
+    assert!(level.rms[0] > 0.0 && level.rms[0] < f32::from(loudest));
+    herd.units[0].mute = true;
+    herd.moo(&ins, &song, &mut buf, true);
+    let level = herd.unit_levels()[0];
+    assert!(level.rms.iter().all(|&rms| rms == 0.0));
+    assert_ne!(level.peak, [0; 2], "peaks are held");
+    herd.reset_unit_peaks();
+    assert_eq!(herd.unit_levels()[0].peak, [0; 2]);
+}
+
+#[test]
+fn test_music_states() {
+    use crate::states::{MusicState, Transition};
+    let mut song = Song::default();
+    // 22050 samples per measure
+    song.master.timing.bpm = 480.0;
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 1920 * 4 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    let (mut ins, mut herd) = test_setup(&song);
+    let mut quiet = MusicState::new("quiet");
+    quiet.muted.push(UnitIdx(0));
+    quiet.group_gains[0] = 0.5;
+    quiet.tempo = 2.0;
+    herd.states.add(quiet);
+    assert!(!herd.trigger_state("loud", Transition::default()));
+    let transition = Transition {
+        on_meas: true,
+        fade: Duration::from_millis(100),
+    };
+    let spt = ins.samples_per_tick;
+    let mut buf = vec![0i16; 2 * 22050];
+    assert!(herd.moo_states(&mut ins, &song, &mut buf[..1000]));
+    assert!(herd.trigger_state("quiet", transition));
+    // Waits for the next measure
+    assert!(herd.moo_states(&mut ins, &song, &mut buf[..1000]));
+    assert!(herd.states.pending.is_some());
+    assert!(herd.moo_states(&mut ins, &song, &mut buf[..2 * 22050 - 2000]));
+    assert_eq!(herd.smp_count, 22050);
+    assert!(herd.states.pending.is_some());
+    // Fading
+    assert!(herd.moo_states(&mut ins, &song, &mut buf[..200]));
+    assert!(herd.states.pending.is_none());
+    assert_eq!(herd.states.current().unwrap().name, "quiet");
+    assert_eq!((ins.samples_per_tick * 2.0).to_bits(), spt.to_bits());
+    assert_eq!(herd.smp_count, 22050 / 2 + 100);
+    assert!(!herd.units[UnitIdx(0)].mute);
+    let gain = herd.group_gain(crate::GroupIdx(0));
+    assert!(gain < 1.0 && gain > 0.5);
+    // Done
+    assert!(herd.moo_states(&mut ins, &song, &mut buf[..2 * 4410]));
+    assert!(!herd.states.is_transitioning());
+    assert!(herd.units[UnitIdx(0)].mute);
+    assert_eq!(
+        herd.group_gain(crate::GroupIdx(0)).to_bits(),
+        0.5f32.to_bits()
+    );
+}
+
+#[test]
+fn test_env_stage() {
+    use crate::EnvStage;
+    let mut song = Song::default();
+    song.events.eves = vec![Event {
+        payload: EventPayload::On { duration: 480 },
+        unit: UnitIdx(0),
+        tick: 0,
+    }];
+    song.recalculate_length();
+    let (ins, mut herd) = test_setup(&song);
+    herd.smp_end = SampleT::MAX;
+    let stage = |herd: &Herd| herd.units[UnitIdx(0)].env_stage(0, &ins.voices).unwrap();
+    let volume = |herd: &Herd| herd.units[UnitIdx(0)].current_env_volume(0).unwrap();
+    assert_eq!(stage(&herd), EnvStage::Silent);
+    assert!(herd.units[UnitIdx(0)].current_env_volume(2).is_none());
+    // The factory voices have a 5 ms attack
+    let mut buf = vec![0i16; 2 * 100];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert_eq!(stage(&herd), EnvStage::Attack);
+    assert!(volume(&herd) > 0.0 && volume(&herd) < 1.0);
+    herd.moo(&ins, &song, &mut buf, true);
+    herd.moo(&ins, &song, &mut buf, true);
+    assert_eq!(stage(&herd), EnvStage::Hold);
+    let hold = volume(&herd);
+    assert!(hold > 0.99);
+    // 480 ticks at 120 bpm are half a second, and the release is 30 ms
+    let note_len = 22050 - 400;
+    let mut buf = vec![0i16; 2 * note_len];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert_eq!(stage(&herd), EnvStage::Hold);
+    let mut buf = vec![0i16; 2 * 500];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert_eq!(stage(&herd), EnvStage::Release);
+    assert!(volume(&herd) < hold);
+    let mut buf = vec![0i16; 2 * 2000];
+    herd.moo(&ins, &song, &mut buf, true);
+    assert_eq!(stage(&herd), EnvStage::Silent);
+    assert_eq!(volume(&herd).to_bits(), 0.0f32.to_bits());
+}
+
+/// The number of times the left channel of `buf` goes from negative to non-negative
+#[cfg(test)]
+fn rising_crossings(buf: &[i16]) -> usize {
+    let left: Vec<i16> = buf.iter().step_by(2).copied().collect();
+    left.windows(2).filter(|w| w[0] < 0 && w[1] >= 0).count()
+}
+
+/// Render a note of the sine voice that starts with `effects`, for 0.5 seconds
+#[cfg(test)]
+fn render_sine_note(effects: &[EventPayload]) -> Vec<i16> {
+    let mut song = Song::default();
+    song.events.eves = effects
+        .iter()
+        .copied()
+        .chain([EventPayload::On { duration: 4800 }])
+        .map(|payload| Event {
+            payload,
+            unit: UnitIdx(0),
+            tick: 0,
+        })
+        .collect();
+    song.recalculate_length();
+    let (ins, mut herd) = test_setup(&song);
+    let mut buf = vec![0i16; 44_100];
+    herd.moo(&ins, &song, &mut buf, true);
+    buf
+}
+
+#[test]
+fn test_pitch_bend_playback() {
+    use crate::Key;
+    let plain = rising_crossings(&render_sine_note(&[]));
+    let bent = |bend| {
+        rising_crossings(&render_sine_note(&[
+            EventPayload::PitchBendRange(Key::OCTAVE),
+            EventPayload::PitchBend(bend),
+        ]))
+    };
+    let max = EventPayload::PITCH_BEND_MAX;
+    // A full bend is an octave, so twice or half as many cycles
+    assert!(bent(max).abs_diff(plain * 2) <= 2, "{plain} {}", bent(max));
+    assert!(
+        bent(-max).abs_diff(plain / 2) <= 2,
+        "{plain} {}",
+        bent(-max)
+    );
+    // Bending below the lowest key plays the lowest key, not the highest
+    let low = render_sine_note(&[
+        EventPayload::Key(Key(0)),
+        EventPayload::PitchBendRange(EventPayload::PITCH_BEND_RANGE_MAX),
+        EventPayload::PitchBend(-max),
+    ]);
+    assert!(rising_crossings(&low) < plain);
+    // Huge ranges set through the API don't overflow
+    let mut unit = Unit::new();
+    unit.pitch_bend_range = Key(i32::MAX);
+    unit.pitch_bend = max;
+    unit.tone_increment_key(NATIVE_SAMPLE_RATE);
+}
+
+#[test]
+fn test_vibrato_playback() {
+    use crate::Key;
+    let plain = render_sine_note(&[]);
+    let vibrato = render_sine_note(&[
+        EventPayload::VibratoRate(4.0),
+        EventPayload::VibratoDepth(Key::semitones(12)),
+    ]);
+    // Over whole vibrato cycles, the pitch averages out to about the same number of cycles,
+    // but within a cycle the first quarter is higher and the third one lower
+    let quarter = 44_100 / 4 / 4 * 2;
+    let crossings = |buf: &[i16], n: usize| rising_crossings(&buf[quarter * n..quarter * (n + 1)]);
+    assert!(crossings(&vibrato, 0) > crossings(&plain, 0) + 5);
+    assert!(crossings(&vibrato, 2) + 5 < crossings(&plain, 2));
+}
+
+/// The loudest sample of channel `ch` within 100 frames of `frame`
+#[cfg(test)]
+fn peak_near(buf: &[i16], ch: usize, frame: usize) -> u32 {
+    buf[(frame - 100) * 2..(frame + 100) * 2]
+        .iter()
+        .skip(ch)
+        .step_by(2)
+        .map(|smp| u32::from(smp.unsigned_abs()))
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn test_tremolo_playback() {
+    use crate::{LfoParams, NoiseType};
+    let plain = render_sine_note(&[]);
+    // 4 Hz, so a cycle is 11025 frames
+    let tremolo = render_sine_note(&[EventPayload::Tremolo(LfoParams {
+        rate: 400,
+        depth: 128,
+        shape: NoiseType::Sine,
+    })]);
+    let full = peak_near(&plain, 0, 2756);
+    // Loudest a quarter into each cycle, and silent three quarters into it
+    for cycle in [0, 11_025] {
+        assert!(peak_near(&tremolo, 0, cycle + 2756) > full * 9 / 10);
+        assert!(peak_near(&tremolo, 0, cycle + 8269) < full / 20);
+    }
+}
+
+#[test]
+fn test_auto_pan_playback() {
+    use crate::{LfoParams, NoiseType};
+    let plain = render_sine_note(&[]);
+    // 4 Hz, so a cycle is 11025 frames
+    let auto_pan = render_sine_note(&[EventPayload::AutoPan(LfoParams {
+        rate: 400,
+        depth: 64,
+        shape: NoiseType::Sine,
+    })]);
+    let full = peak_near(&plain, 0, 2756);
+    // All the way right a quarter into each cycle, and all the way left three quarters into it
+    for cycle in [0, 11_025] {
+        assert!(peak_near(&auto_pan, 0, cycle + 2756) < full / 20);
+        assert!(peak_near(&auto_pan, 1, cycle + 2756) > full * 9 / 10);
+        assert!(peak_near(&auto_pan, 0, cycle + 8269) > full * 9 / 10);
+        assert!(peak_near(&auto_pan, 1, cycle + 8269) < full / 20);
+    }
+}
@@ -0,0 +1,77 @@
+//! Which units and effects feed each sample group
+
+use {
+    super::Herd,
+    crate::{GroupIdx, GroupSamples, UnitIdx, util::ArrayLenExt as _},
+};
+
+/// An effect of a [`Herd`] that processes a sample group
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupEffect {
+    /// Index into [`Herd::delays`]
+    Delay(usize),
+    /// Index into [`Herd::overdrives`]
+    Overdrive(usize),
+}
+
+/// Which units and effects feed each sample group (see [`GroupIdx`]) of a [`Herd`].
+///
+/// Made with [`Herd::group_routing`]. It's a snapshot, it doesn't change when the herd does.
+#[derive(Clone, Debug, Default)]
+pub struct GroupRouting {
+    units: [Vec<UnitIdx>; GroupSamples::LEN],
+    effects: [Vec<GroupEffect>; GroupSamples::LEN],
+}
+
+impl GroupRouting {
+    /// The units that render into `group`, in order
+    #[must_use]
+    pub fn units_in_group(&self, group: GroupIdx) -> &[UnitIdx] {
+        self.units.get(group.usize()).map_or(&[], Vec::as_slice)
+    }
+    /// The effects that process `group`, in the order they're applied
+    /// (overdrives come before delays)
+    #[must_use]
+    pub fn effects_on_group(&self, group: GroupIdx) -> &[GroupEffect] {
+        self.effects.get(group.usize()).map_or(&[], Vec::as_slice)
+    }
+    /// The groups that have no units rendering into them
+    pub fn empty_groups(&self) -> impl Iterator<Item = GroupIdx> {
+        (0..=GroupIdx::MAX.0)
+            .map(GroupIdx)
+            .filter(|&group| self.units_in_group(group).is_empty())
+    }
+}
+
+impl Herd {
+    /// Which units and effects feed each sample group.
+    ///
+    /// The units are in the group they're currently in, which changes while playing with
+    /// [`SetGroup`](crate::EventPayload::SetGroup) events, or [`Self::set_unit_group`].
+    /// A unit that is crossfading to another group counts as being in the new group.
+    #[must_use]
+    pub fn group_routing(&self) -> GroupRouting {
+        let mut routing = GroupRouting::default();
+        for (idx, unit) in self.units.enumerated() {
+            if let Some(units) = routing.units.get_mut(unit.group.usize()) {
+                units.push(idx);
+            }
+        }
+        let overdrives = self
+            .overdrives
+            .iter()
+            .enumerate()
+            .map(|(i, ovr)| (ovr.group, GroupEffect::Overdrive(i)));
+        let delays = self
+            .delays
+            .iter()
+            .enumerate()
+            .map(|(i, delay)| (delay.group, GroupEffect::Delay(i)));
+        for (group, effect) in overdrives.chain(delays) {
+            if let Some(effects) = routing.effects.get_mut(group.usize()) {
+                effects.push(effect);
+            }
+        }
+        routing
+    }
+}
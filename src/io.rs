@@ -1,4 +1,10 @@
-use {crate::result::ProjectReadError, arrayvec::ArrayVec};
+use {
+    crate::{
+        VoiceFlags,
+        result::{CheckpointError, ProjectReadError},
+    },
+    arrayvec::ArrayVec,
+};
 
 pub struct Reader<'a> {
     pub data: &'a [u8],
@@ -22,6 +28,16 @@ impl From<ReadError> for ProjectReadError {
     }
 }
 
+impl From<ReadError> for CheckpointError {
+    fn from(err: ReadError) -> Self {
+        Self::Data {
+            what: err.what,
+            cursor: err.cursor,
+            len: err.len,
+        }
+    }
+}
+
 impl Reader<'_> {
     pub fn next<T: bytemuck::AnyBitPattern>(&mut self) -> Result<T, ReadError> {
         let amount = size_of::<T>();
@@ -33,6 +49,19 @@ impl Reader<'_> {
         self.cur += amount;
         Ok(bytemuck::pod_read_unaligned(bytes))
     }
+    /// Read a [`LeField`], like a fixed size structure declared with [`io_struct!`]
+    pub fn next_le<T: LeField>(&mut self) -> Result<T, ReadError> {
+        T::read_le(self)
+    }
+    fn next_bytes<const N: usize>(&mut self, what: &'static str) -> Result<[u8; N], ReadError> {
+        let bytes = self.data.get(self.cur..self.cur + N).ok_or(ReadError {
+            what,
+            cursor: self.cur,
+            len: self.data.len(),
+        })?;
+        self.cur += N;
+        Ok(bytes.try_into().unwrap())
+    }
     pub fn fill_slice(&mut self, dst: &mut [u8]) -> Result<(), ReadError> {
         let amount = dst.len();
         let Some(src) = self.data.get(self.cur..self.cur + amount) else {
@@ -73,6 +102,84 @@ impl Reader<'_> {
     }
 }
 
+/// A value that is stored in project files as little endian bytes, with no padding
+pub trait LeField: Sized {
+    /// The number of bytes the value takes up
+    const SIZE: usize;
+    fn read_le(rd: &mut Reader) -> Result<Self, ReadError>;
+    fn write_le(&self, out: &mut Vec<u8>);
+}
+
+macro_rules! le_field_num {
+    ($($num:ty),*) => {
+        $(
+            impl LeField for $num {
+                const SIZE: usize = size_of::<$num>();
+                fn read_le(rd: &mut Reader) -> Result<Self, ReadError> {
+                    rd.next_bytes(stringify!($num)).map(Self::from_le_bytes)
+                }
+                fn write_le(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+le_field_num!(u8, u16, i16, u32, i32, f32);
+
+impl<const N: usize> LeField for [u8; N] {
+    const SIZE: usize = N;
+    fn read_le(rd: &mut Reader) -> Result<Self, ReadError> {
+        rd.next_bytes("byte array")
+    }
+    fn write_le(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl LeField for VoiceFlags {
+    const SIZE: usize = u32::SIZE;
+    fn read_le(rd: &mut Reader) -> Result<Self, ReadError> {
+        u32::read_le(rd).map(Self::from_bits_retain)
+    }
+    fn write_le(&self, out: &mut Vec<u8>) {
+        self.bits().write_le(out);
+    }
+}
+
+/// Declare a fixed size structure of the project format.
+///
+/// The fields are read and written in order, each as little endian bytes, so the layout
+/// doesn't depend on the platform or on the padding the compiler would add.
+macro_rules! io_struct {
+    (
+        $(#[$meta:meta])*
+        struct $name:ident {
+            $($field:ident: $ty:ty,)*
+        }
+    ) => {
+        $(#[$meta])*
+        struct $name {
+            $($field: $ty,)*
+        }
+
+        impl $crate::io::LeField for $name {
+            const SIZE: usize = 0 $(+ <$ty as $crate::io::LeField>::SIZE)*;
+            fn read_le(rd: &mut $crate::io::Reader) -> Result<Self, $crate::io::ReadError> {
+                Ok(Self {
+                    $($field: $crate::io::LeField::read_le(rd)?,)*
+                })
+            }
+            fn write_le(&self, out: &mut Vec<u8>) {
+                $($crate::io::LeField::write_le(&self.$field, out);)*
+            }
+        }
+    };
+}
+
+pub(crate) use io_struct;
+
 type VarintBuf = ArrayVec<u8, 5>;
 
 fn varint_to_int(buf: &VarintBuf) -> Option<u32> {
@@ -149,6 +256,10 @@ fn test_varint_equiv() {
     }
 }
 
+pub fn write_pod<T: bytemuck::NoUninit>(val: T, out: &mut Vec<u8>) {
+    out.extend_from_slice(bytemuck::bytes_of(&val));
+}
+
 pub fn write_varint(num: u32, out: &mut Vec<u8>) {
     let v_int = int_to_varint(num);
     out.extend_from_slice(&v_int);
@@ -0,0 +1,296 @@
+//! Switching between songs while playing, for game soundtracks
+
+use {
+    crate::{MooPlan, OutSample, Project, SampleRate, moo_prepare, states::Transition},
+    std::iter::zip,
+};
+
+/// Owns several [`Project`]s, and plays one of them at a time, crossfading or cutting between
+/// them as they are switched.
+///
+/// All projects are played at the sample rate of the jukebox. The projects are played with
+/// [`Herd::moo_states`](crate::Herd::moo_states), so each of them can switch between its own
+/// [music states](crate::states) too.
+///
+/// ```no_run
+/// use ptcow::{Jukebox, MooPlan, Project, StartPosPlan, states::Transition};
+///
+/// let mut jukebox = Jukebox::new(ptcow::NATIVE_SAMPLE_RATE);
+/// let mut load = |path| {
+///     let data = std::fs::read(path).unwrap();
+///     jukebox.add(Project::read(&data, ptcow::NATIVE_SAMPLE_RATE).unwrap())
+/// };
+/// let town = load("town.ptcop");
+/// let battle = load("battle.ptcop");
+/// let plan = MooPlan {
+///     start_pos: StartPosPlan::Sample(0),
+///     meas_end: None,
+///     meas_repeat: None,
+///     loop_: true,
+/// };
+/// jukebox.play(town, plan, Transition::default());
+/// let mut buf = [0i16; 4096];
+/// jukebox.moo(&mut buf);
+/// let transition = Transition {
+///     on_meas: true,
+///     fade: std::time::Duration::from_secs(1),
+/// };
+/// jukebox.play(battle, plan, transition);
+/// while jukebox.moo(&mut buf) {
+///     // Output `buf`...
+/// }
+/// ```
+pub struct Jukebox {
+    projects: Vec<Project>,
+    out_sample_rate: SampleRate,
+    current: Option<usize>,
+    /// The project that is fading out, and the progress of the fade
+    outgoing: Option<(usize, Crossfade)>,
+    /// The next project to play (`None` to stop), and how to switch to it
+    pending: Option<(Option<(usize, MooPlan)>, Transition)>,
+    /// The mix, and the samples of the outgoing project, before converting to the output format
+    mix: Vec<f32>,
+    fade_buf: Vec<f32>,
+}
+
+/// A fade between the outgoing and the current project
+struct Crossfade {
+    /// Frames played, and total length of the fade
+    pos: u32,
+    len: u32,
+}
+
+impl Jukebox {
+    /// An empty jukebox, playing at `out_sample_rate`
+    #[must_use]
+    pub const fn new(out_sample_rate: SampleRate) -> Self {
+        Self {
+            projects: Vec::new(),
+            out_sample_rate,
+            current: None,
+            outgoing: None,
+            pending: None,
+            mix: Vec::new(),
+            fade_buf: Vec::new(),
+        }
+    }
+    /// Add a project, returning its index.
+    ///
+    /// If the project was read for another sample rate, its voices and effects are rebuilt
+    /// for the sample rate of the jukebox.
+    pub fn add(&mut self, mut project: Project) -> usize {
+        if project.ins.out_sample_rate != self.out_sample_rate {
+            project.ins.out_sample_rate = self.out_sample_rate;
+            project.rebuild_tones();
+        }
+        self.projects.push(project);
+        self.projects.len() - 1
+    }
+    /// The project at `idx`
+    #[must_use]
+    pub fn project(&self, idx: usize) -> Option<&Project> {
+        self.projects.get(idx)
+    }
+    /// The project at `idx`, mutably.
+    ///
+    /// Changing its output sample rate breaks the mix.
+    pub fn project_mut(&mut self, idx: usize) -> Option<&mut Project> {
+        self.projects.get_mut(idx)
+    }
+    /// The number of projects
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.projects.len()
+    }
+    /// Whether there are no projects
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.projects.is_empty()
+    }
+    /// The index of the project that is playing, or fading in
+    #[must_use]
+    pub const fn current(&self) -> Option<usize> {
+        self.current
+    }
+    /// Whether a project is still fading out
+    #[must_use]
+    pub const fn is_fading(&self) -> bool {
+        self.outgoing.is_some()
+    }
+    /// Switch to the project at `idx`, prepared with `plan`.
+    ///
+    /// The switch happens on the next [`Self::moo`], at the start of the next measure of the
+    /// current project if [`Transition::on_meas`] is set. The current project fades out
+    /// while the new one fades in, for [`Transition::fade`]. With no fade, it's cut off.
+    ///
+    /// Switching to the current project, or to one that is still fading out, restarts it.
+    /// A switch replaces the previous one if that hasn't happened yet.
+    ///
+    /// Returns false if there is no project at `idx`.
+    pub const fn play(&mut self, idx: usize, plan: MooPlan, transition: Transition) -> bool {
+        if idx >= self.projects.len() {
+            return false;
+        }
+        self.pending = Some((Some((idx, plan)), transition));
+        true
+    }
+    /// Stop playing, fading out the current project like [`Self::play`] does.
+    pub const fn stop(&mut self, transition: Transition) {
+        self.pending = Some((None, transition));
+    }
+    /// Moo the next samples into an interleaved stereo buffer, like [`Herd::moo`].
+    ///
+    /// Returns false if nothing is playing anymore. Samples after the end of playback are
+    /// silent.
+    ///
+    /// [`Herd::moo`]: crate::Herd::moo
+    pub fn moo<T: OutSample>(&mut self, mut buf: &mut [T]) -> bool {
+        let mut playing = false;
+        while buf.len() >= 2 {
+            let mut frames = buf.len() / 2;
+            if let Some((_, transition)) = self.pending {
+                let until = match self.current {
+                    Some(cur) if transition.on_meas => {
+                        let project = &self.projects[cur];
+                        project.herd.frames_until_meas(&project.ins, &project.song)
+                    }
+                    _ => 0,
+                };
+                if until == 0 {
+                    self.apply_pending();
+                } else {
+                    frames = frames.min(until);
+                }
+            }
+            let (chunk, rest) = buf.split_at_mut(frames * 2);
+            playing |= self.moo_chunk(chunk);
+            buf = rest;
+        }
+        playing
+    }
+    fn apply_pending(&mut self) {
+        let Some((next, transition)) = self.pending.take() else {
+            return;
+        };
+        #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let len = (transition.fade.as_secs_f64() * f64::from(self.out_sample_rate.get())) as u32;
+        let next_idx = next.map(|(idx, _)| idx);
+        if let Some(cur) = self.current.filter(|&cur| Some(cur) != next_idx) {
+            self.outgoing = (len != 0).then_some((cur, Crossfade { pos: 0, len }));
+        }
+        if self.outgoing.as_ref().is_some_and(|(idx, _)| Some(*idx) == next_idx) {
+            self.outgoing = None;
+        }
+        if let Some((idx, plan)) = next {
+            let project = &mut self.projects[idx];
+            // The sample rate is the one of the jukebox, so this can only fail if that one
+            // isn't supported, in which case there is nothing to play.
+            if moo_prepare(&mut project.ins, &mut project.herd, &project.song, &plan).is_err() {
+                self.current = None;
+                return;
+            }
+        }
+        self.current = next_idx;
+    }
+    fn moo_chunk<T: OutSample>(&mut self, chunk: &mut [T]) -> bool {
+        self.mix.clear();
+        self.mix.resize(chunk.len(), 0.0);
+        let mut playing = false;
+        if let Some(cur) = self.current {
+            let Project { song, herd, ins } = &mut self.projects[cur];
+            playing |= herd.moo_states(ins, song, &mut self.mix);
+        }
+        if let Some((idx, fade)) = &mut self.outgoing {
+            self.fade_buf.clear();
+            self.fade_buf.resize(chunk.len(), 0.0);
+            let Project { song, herd, ins } = &mut self.projects[*idx];
+            herd.moo_states(ins, song, &mut self.fade_buf);
+            for (mix, out) in zip(
+                self.mix.as_chunks_mut::<2>().0,
+                self.fade_buf.as_chunks::<2>().0,
+            ) {
+                #[expect(clippy::cast_precision_loss)]
+                let t = fade.pos as f32 / fade.len as f32;
+                for (mix, out) in zip(mix, out) {
+                    *mix = (*mix - out).mul_add(t, *out);
+                }
+                fade.pos = (fade.pos + 1).min(fade.len);
+            }
+            playing = true;
+            if fade.pos >= fade.len {
+                self.outgoing = None;
+            }
+        }
+        for (dst, smp) in zip(chunk, &self.mix) {
+            #[expect(clippy::cast_possible_truncation)]
+            let moo_samp = (smp * 32768.0) as i32;
+            *dst = T::from_moo_samp(moo_samp);
+        }
+        playing
+    }
+}
+
+#[test]
+fn test_jukebox() {
+    use crate::{
+        Event, EventPayload, NATIVE_SAMPLE_RATE, StartPosPlan, Unit, UnitIdx,
+        presets::wave::factory_voices,
+    };
+    let song = |duration| {
+        let mut project = Project::new(NATIVE_SAMPLE_RATE);
+        project.units_mut().push(Unit::new());
+        project.voices_mut().extend(factory_voices());
+        project.events_mut().eves.push(Event {
+            payload: EventPayload::On { duration },
+            unit: UnitIdx(0),
+            tick: 0,
+        });
+        project.song.recalculate_length();
+        project.rebuild_tones();
+        project
+    };
+    let plan = MooPlan {
+        start_pos: StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: false,
+    };
+    let mut jukebox = Jukebox::new(NATIVE_SAMPLE_RATE);
+    let mut buf = vec![0i16; 4096];
+    assert!(!jukebox.moo(&mut buf), "nothing to play");
+    let a = jukebox.add(song(1920));
+    let b = jukebox.add(song(960));
+    assert!(!jukebox.play(2, plan, Transition::default()));
+
+    // A hard switch plays the same as the project on its own
+    assert!(jukebox.play(a, plan, Transition::default()));
+    assert!(jukebox.moo(&mut buf));
+    assert_eq!(jukebox.current(), Some(a));
+    assert!(!jukebox.is_fading());
+    let mut alone = song(1920);
+    alone.prepare(&plan).unwrap();
+    let mut expected = vec![0i16; 4096];
+    alone.herd.moo_states(&mut alone.ins, &alone.song, &mut expected);
+    assert_eq!(buf, expected);
+
+    // A crossfade mixes both until the fade is over
+    let transition = Transition {
+        on_meas: false,
+        fade: std::time::Duration::from_millis(50),
+    };
+    jukebox.play(b, plan, transition);
+    jukebox.moo(&mut buf);
+    assert_eq!(jukebox.current(), Some(b));
+    assert!(jukebox.is_fading());
+    let mut long = vec![0i16; 2 * 44_100];
+    jukebox.moo(&mut long);
+    assert!(!jukebox.is_fading());
+
+    // Stopping fades out into silence
+    jukebox.stop(transition);
+    assert!(jukebox.moo(&mut buf));
+    assert_eq!(jukebox.current(), None);
+    jukebox.moo(&mut long);
+    assert!(!jukebox.moo(&mut buf));
+    assert!(buf.iter().all(|&s| s == 0));
+}
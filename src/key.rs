@@ -0,0 +1,146 @@
+//! Pitch in 1/256 semitone units
+
+use {
+    crate::consts::KEY_PER_SEMITONE,
+    std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign},
+};
+
+/// 1/256 of a semitone.
+///
+/// A semitone is the smallest distance between keys on a piano.
+///
+/// Used both for keys ([`EventPayload::Key`](crate::EventPayload::Key),
+/// [`VoiceUnit::basic_key`](crate::VoiceUnit::basic_key)), and distances between them
+/// ([`Unit::key_margin`](crate::Unit::key_margin)).
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Key(pub i32);
+
+/// A note of the chromatic scale, see [`Key::from_note`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[expect(missing_docs)]
+pub enum Note {
+    C,
+    CSharp,
+    D,
+    DSharp,
+    E,
+    F,
+    FSharp,
+    G,
+    GSharp,
+    A,
+    ASharp,
+    B,
+}
+
+impl Note {
+    /// Semitones above [`C`](Self::C)
+    #[must_use]
+    pub const fn semitone(self) -> i32 {
+        self as i32
+    }
+}
+
+impl Key {
+    /// The distance of a semitone
+    pub const SEMITONE: Self = Self(KEY_PER_SEMITONE);
+    /// The distance of an octave
+    pub const OCTAVE: Self = Self(KEY_PER_SEMITONE * 12);
+    /// The raw value, in 1/256 semitones
+    #[must_use]
+    pub const fn get(self) -> i32 {
+        self.0
+    }
+    /// A distance of `n` semitones
+    #[must_use]
+    pub const fn semitones(n: i32) -> Self {
+        Self(n * Self::SEMITONE.0)
+    }
+    /// The key of a MIDI note number.
+    ///
+    /// A4 (note 69) is [`DEFAULT_KEY`](crate::DEFAULT_KEY).
+    #[must_use]
+    pub const fn from_midi(note: u8) -> Self {
+        Self::semitones(note as i32 + 27)
+    }
+    /// The key of `note` in `octave`, in scientific pitch notation (A4 is 440 Hz).
+    ///
+    /// ```
+    /// use ptcow::{DEFAULT_KEY, Key, Note};
+    ///
+    /// assert_eq!(Key::from_note(Note::A, 4), DEFAULT_KEY);
+    /// assert_eq!(Key::from_note(Note::C, 5) - Key::from_note(Note::B, 4), Key::SEMITONE);
+    /// ```
+    #[must_use]
+    pub const fn from_note(note: Note, octave: i32) -> Self {
+        // MIDI note 0 is C-1
+        Self::semitones((octave + 1) * 12 + note.semitone() + 27)
+    }
+}
+
+impl From<i32> for Key {
+    fn from(raw: i32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<Key> for i32 {
+    fn from(key: Key) -> Self {
+        key.0
+    }
+}
+
+impl Add for Key {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Key {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl AddAssign for Key {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+impl SubAssign for Key {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Neg for Key {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Mul<i32> for Key {
+    type Output = Self;
+
+    fn mul(self, rhs: i32) -> Self {
+        Self(self.0 * rhs)
+    }
+}
+
+#[test]
+fn test_from_note() {
+    assert_eq!(Key::from_note(Note::A, 4), Key::from_midi(69));
+    assert_eq!(Key::from_note(Note::C, -1), Key::from_midi(0));
+    assert_eq!(
+        Key::from_note(Note::A, 5) - Key::from_note(Note::A, 4),
+        Key::OCTAVE
+    );
+    assert_eq!(Key::SEMITONE * 3 + -Key::SEMITONE, Key::semitones(2));
+}
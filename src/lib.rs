@@ -2,17 +2,33 @@
 // When we return an error type, the possible errors are encoded within it.
 #![allow(clippy::missing_errors_doc)]
 
+pub mod analysis;
+pub mod compat;
+pub mod consts;
 mod delay;
+#[cfg(feature = "dsl")]
+pub mod dsl;
 mod event;
+pub mod gain;
 mod herd;
 mod io;
+mod jukebox;
+mod key;
 mod master;
+#[cfg(feature = "midi")]
+pub mod midi;
 mod noise_builder;
 mod overdrive;
+mod player;
 mod point;
+pub mod presets;
+mod project;
 mod pulse_frequency;
 mod pulse_oscillator;
+pub mod render;
+mod resampler;
 mod result;
+pub mod states;
 pub mod timing;
 mod unit;
 mod voice;
@@ -31,23 +47,47 @@ mod util {
 
 pub use {
     delay::{Delay, DelayUnit},
-    event::{DEFAULT_KEY, EveList, Event, EventPayload, Key},
+    event::{
+        ClipMap, CsvError, CsvErrorKind, Curve, DEFAULT_KEY, DEFAULT_PITCH_BEND_RANGE, Dynamic,
+        DynamicCurve, EveList, Event, EventClip, EventPayload, MergePolicy,
+    },
     herd::{
-        FmtInfo, FmtKind, FmtVer, Herd, MooInstructions, Song, Text, Units, Voices,
-        moo::{MooPlan, StartPosPlan, current_tick, do_event, moo_prepare},
-        read_song, rebuild_tones, serialize_project,
+        ChunkDiff, DowngradeBlocker, EventObserver, ExeVer, FmtInfo, FmtKind, FmtVer, GroupEffect,
+        GroupRouting, Herd, MooInstructions, ReloadReport, RenderConfig, SizeBreakdown, Song, Text,
+        TextPatch, Units, Voices, chunk_at_offset, diff_chunks,
+        moo::{
+            EventSource, IterSource, LoopPolicy, MixPolicy, MooPlan, OutSample, SeekIndex,
+            SilentMode, StartPosPlan, Tail, current_tick, do_event, moo_prepare, render_looped,
+        },
+        project_size_breakdown, read_song, read_song_from, read_song_lenient,
+        read_song_with_warnings, rebuild_tones, reload_song, rewrite_metadata, serialize_project,
+        serialize_project_extended, serialize_project_to, serialize_tune,
     },
+    jukebox::Jukebox,
+    key::{Key, Note},
     master::{LoopPoints, Master},
-    noise_builder::{NoiseDesignOscillator, NoiseTable, NoiseType, noise_to_pcm},
-    overdrive::Overdrive,
+    noise_builder::{NoiseDesignOscillator, NoiseTable, NoiseType, noise_to_pcm, noise_to_pcm_at},
+    overdrive::{Overdrive, OverdriveSettings},
+    player::Player,
     point::EnvPt,
+    project::{Project, UnusedReport},
     pulse_oscillator::{OsciArgs, OsciPt},
     pulse_oscillator::{coord, overtone},
-    result::{ProjectReadError, ReadResult},
+    resampler::{NearestResampler, Resampler},
+    result::{
+        CheckpointError, EnvelopeError, OverdriveError, ProjectReadError, ProjectWriteError,
+        ReadResult, ReadWarning, RenderError, SampleRateTooHigh, StreamReadError, StreamWriteError,
+        UnsupportedSampleRate,
+    },
     timing::{Meas, SampleT, SamplesPerTick, Tick, Tick16, Timing},
-    unit::{GroupIdx, PanTime, PanTimeBuf, PanTimeOff, Unit, UnitIdx, VoiceIdx},
+    unit::{
+        GroupIdx, GroupSamples, Interpolation, Lfo, LfoParams, PanLaw, PanTime, PanTimeBuf,
+        PanTimeOff, PanTimeScale, PortaState, Tuning, Unit, UnitIdx, UnitLevel, UnitTap, Vibrato,
+        VoiceIdx,
+    },
     voice::{
-        EnvelopeSrc, Voice, VoiceData, VoiceFlags, VoiceInstance, VoiceSlot, VoiceTone, VoiceUnit,
+        EnvStage, EnvelopeSrc, Voice, VoiceData, VoiceFlags, VoiceInstance, VoiceSlot, VoiceTone,
+        VoiceUnit,
     },
     voice_data::{
         noise::{NoiseData, NoiseDesignUnit, NoiseDesignUnitFlags},
@@ -57,6 +97,12 @@ pub use {
     },
 };
 
+#[cfg(feature = "rubato")]
+pub use resampler::RubatoResampler;
+
+#[cfg(any(feature = "symphonia", feature = "hound"))]
+pub use result::PcmImportError;
+
 /// Channel number (mono or stereo)
 #[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ChNum {
@@ -78,12 +124,111 @@ pub enum Bps {
     B16 = 16,
 }
 
-/// Wide enough to represent 48 Khz
-pub type SampleRate = u16;
-/// Some sources (Ogg/Vorbis) can have high sample rates (96 khz for example)
-pub type SourceSampleRate = u32;
+/// Output sample rate in Hz
+///
+/// Create one with [`Self::new`], which rejects rates above [`Self::MAX_SUPPORTED`].
+///
+/// Playback supports rates from [`Self::MIN_SUPPORTED`] up to [`Self::MAX_SUPPORTED`].
+/// [`moo_prepare`] fails with [`UnsupportedSampleRate`] for lower rates.
+/// `PxTone` itself offers 11025, 22050, 44100 and 48000 Hz.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SampleRate(u32);
+
+impl SampleRate {
+    /// The lowest sample rate playback supports.
+    ///
+    /// Below it, envelopes, portamento and the smoothing of note ends get too coarse to
+    /// sound right.
+    pub const MIN_SUPPORTED: Self = Self(8_000);
+    /// The highest sample rate playback supports, enough for 96 kHz and 192 kHz output.
+    ///
+    /// No [`SampleRate`] is higher than this, so every one converts exactly to any of the
+    /// numeric types [`SampleRate`] converts into.
+    pub const MAX_SUPPORTED: Self = Self(384_000);
+    /// A sample rate of `hz`, or `None` if it's above [`Self::MAX_SUPPORTED`]
+    #[must_use]
+    pub const fn new(hz: u32) -> Option<Self> {
+        if hz <= Self::MAX_SUPPORTED.0 {
+            Some(Self(hz))
+        } else {
+            None
+        }
+    }
+    /// The sample rate in Hz
+    #[must_use]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+    /// Whether playback supports this sample rate (see [`Self::MIN_SUPPORTED`])
+    #[must_use]
+    pub const fn is_supported(self) -> bool {
+        self.0 >= Self::MIN_SUPPORTED.0
+    }
+}
+
+/// Sample rate of source data (like PCM voices) in Hz
+///
+/// Some sources (Ogg/Vorbis) can have high sample rates (96 khz for example).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct SourceSampleRate(pub u32);
+
+impl SourceSampleRate {
+    /// The sample rate in Hz
+    #[must_use]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<SampleRate> for SourceSampleRate {
+    fn from(sps: SampleRate) -> Self {
+        Self(sps.0)
+    }
+}
+
+impl TryFrom<SourceSampleRate> for SampleRate {
+    type Error = SampleRateTooHigh;
+
+    fn try_from(sps: SourceSampleRate) -> Result<Self, Self::Error> {
+        Self::new(sps.0).ok_or(SampleRateTooHigh(sps))
+    }
+}
+
+macro_rules! sample_rate_into {
+    ($rate:ty => $($num:ty),*) => {
+        $(
+            impl From<$rate> for $num {
+                fn from(sps: $rate) -> Self {
+                    sps.0.into()
+                }
+            }
+        )*
+    };
+}
+
+/// Conversions that are exact because [`SampleRate`] never exceeds
+/// [`SampleRate::MAX_SUPPORTED`]
+macro_rules! sample_rate_cast {
+    ($rate:ty => $($num:ty $(: $lint:path)?),*) => {
+        $(
+            impl From<$rate> for $num {
+                $(#[expect($lint)])?
+                fn from(sps: $rate) -> Self {
+                    sps.0 as Self
+                }
+            }
+        )*
+    };
+}
+
+sample_rate_into!(SampleRate => u32, u64, f64);
+sample_rate_cast!(
+    SampleRate => i32: clippy::cast_possible_wrap, usize, f32: clippy::cast_precision_loss
+);
+sample_rate_into!(SourceSampleRate => u64, f64);
+
 /// The sample rate `PxTone` internally works with
-pub const NATIVE_SAMPLE_RATE: SampleRate = 44_100;
+pub const NATIVE_SAMPLE_RATE: SampleRate = SampleRate(44_100);
 
 #[cfg(target_endian = "big")]
 const _: () = panic!("Only little endian architectures are supported currently.");
@@ -1,5 +1,5 @@
 use crate::{
-    result::{ProjectReadError, ReadResult},
+    result::{ProjectReadError, ReadResult, ReadWarning},
     timing::{Meas, NonZeroMeas, Tick, Timing, meas_to_tick, tick_to_meas},
 };
 
@@ -63,7 +63,11 @@ impl Master {
         }
     }
 
-    pub(crate) fn read_v5(rd: &mut crate::io::Reader) -> ReadResult<Self> {
+    /// Read the `masterV5` chunk, see [`Timing::checked_read`] for `issues`
+    pub(crate) fn read_v5(
+        rd: &mut crate::io::Reader,
+        issues: Option<&mut Vec<ReadWarning>>,
+    ) -> ReadResult<Self> {
         let size = rd.next::<u32>()?;
         if size != 15 {
             return Err(ProjectReadError::FmtUnknown);
@@ -78,8 +82,50 @@ impl Master {
             ticks_per_beat,
             bpm,
             beats_per_meas,
-        };
+        }
+        .checked_read(issues)?;
+
+        Ok(Self {
+            timing,
+            loop_points: LoopPoints::from_ticks(repeat_tick, last_tick, timing),
+            meas_num: 1,
+        })
+    }
 
+    /// Read the `evenMAST` chunk of V4 and earlier, which stores the timing
+    /// as a list of events. See [`Timing::checked_read`] for `issues`.
+    pub(crate) fn read_x4x(
+        rd: &mut crate::io::Reader,
+        issues: Option<&mut Vec<ReadWarning>>,
+    ) -> ReadResult<Self> {
+        let _size = rd.next::<u32>()?;
+        let data_num = rd.next::<u16>()?;
+        let rrr = rd.next::<u16>()?;
+        let eve_num = rd.next::<u32>()?;
+        if data_num != 3 || rrr != 0 {
+            return Err(ProjectReadError::InvalidData);
+        }
+        let mut timing = Timing::default();
+        let mut repeat_tick = 0;
+        let mut last_tick = 0;
+        let mut absolute: Tick = 0;
+        for _ in 0..eve_num {
+            let kind = rd.next_varint()?;
+            absolute = absolute.wrapping_add(rd.next_varint()?);
+            let value = rd.next_varint()?;
+            // Timing can only be set at the start, and the loop points have no value
+            match kind {
+                // Values that don't fit are invalid like 0
+                7 if absolute == 0 => timing.ticks_per_beat = value.try_into().unwrap_or(0),
+                8 if absolute == 0 => timing.bpm = f32::from_bits(value),
+                9 if absolute == 0 => timing.beats_per_meas = value.try_into().unwrap_or(0),
+                10 if value == 0 => repeat_tick = absolute,
+                11 if value == 0 => last_tick = absolute,
+                7..=11 => return Err(ProjectReadError::InvalidData),
+                _ => return Err(ProjectReadError::FmtUnknown),
+            }
+        }
+        let timing = timing.checked_read(issues)?;
         Ok(Self {
             timing,
             loop_points: LoopPoints::from_ticks(repeat_tick, last_tick, timing),
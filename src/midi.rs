@@ -0,0 +1,444 @@
+//! Import of Standard MIDI Files (`.mid`)
+//!
+//! The conversion is lossy, since PxTone works quite differently from MIDI:
+//!
+//! - A beat is a quarter note, and the ticks per beat are taken from the file.
+//! - Only the first tempo and time signature are used, PxTone songs have a single tempo.
+//! - PxTone units play one note at a time, so every MIDI channel gets as many units as it
+//!   has overlapping notes. Notes that would need more than 50 units are dropped.
+//! - Program changes select the voice with the same index ([`SetVoice`](EventPayload::SetVoice)).
+//!   The voices need to be supplied separately, in
+//!   [`MooInstructions::voices`](crate::MooInstructions::voices).
+//! - Note velocities, and the volume (CC 7) and pan (CC 10) controllers are carried over.
+//!   Everything else (pitch bend, sustain, `SysEx`, ...) is ignored.
+//!
+//! Units are named after the track their channel is first used in, or `ch<channel>` if the
+//! track doesn't have a name.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let data = std::fs::read("song.mid").unwrap();
+//! let (song, herd) = ptcow::midi::import(&data).unwrap();
+//! ```
+
+use crate::{
+    EventPayload, Herd, Key, Song, Tick, UnitIdx, Units, VoiceIdx,
+    event::{DEFAULT_KEY, DEFAULT_VELOCITY, DEFAULT_VOLUME, Event},
+};
+
+/// Error that can happen when importing a MIDI file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum MidiError {
+    /// The data doesn't start with a MIDI header
+    #[error("not a MIDI file")]
+    NotMidi,
+    /// The data ended in the middle of a chunk or event
+    #[error("unexpected end of data")]
+    UnexpectedEnd,
+    /// Malformed event data
+    #[error("invalid event data")]
+    InvalidData,
+    /// Format 2 (independent sequences) files aren't supported
+    #[error("unsupported MIDI format {0}")]
+    UnsupportedFormat(u16),
+    /// The file uses SMPTE timecode instead of ticks per quarter note
+    #[error("SMPTE timing is not supported")]
+    SmpteTiming,
+}
+
+/// Import a Standard MIDI File into a [`Song`], and a [`Herd`] with the units playing it.
+///
+/// See the [module level documentation](self) for how MIDI is mapped to PxTone.
+pub fn import(data: &[u8]) -> Result<(Song, Herd), MidiError> {
+    let mut rd = Reader { data };
+    if rd.bytes(4)? != b"MThd" {
+        return Err(MidiError::NotMidi);
+    }
+    let mut header = rd.chunk_body()?;
+    let format = header.u16()?;
+    let n_tracks = header.u16()?;
+    let division = header.u16()?;
+    if format > 1 {
+        return Err(MidiError::UnsupportedFormat(format));
+    }
+    if division & 0x8000 != 0 {
+        return Err(MidiError::SmpteTiming);
+    }
+    if division == 0 {
+        return Err(MidiError::InvalidData);
+    }
+    let mut parsed = Parsed::default();
+    for _ in 0..n_tracks {
+        // Skip unknown chunks, as the spec asks us to
+        loop {
+            let code = rd.bytes(4)?;
+            let body = rd.chunk_body()?;
+            if code == b"MTrk" {
+                parsed.track(body.data)?;
+                break;
+            }
+        }
+    }
+    let mut song = Song::default();
+    let timing = &mut song.master.timing;
+    timing.ticks_per_beat = division;
+    if let Some(tempo) = parsed.tempo {
+        #[expect(clippy::cast_precision_loss)]
+        let bpm = 60_000_000.0 / tempo as f32;
+        timing.bpm = bpm;
+    }
+    if let Some((num, denom_pow)) = parsed.time_sig {
+        // Beats are quarter notes
+        let quarters = ((u32::from(num) * 4) >> denom_pow.min(31)).max(1);
+        timing.beats_per_meas = u8::try_from(quarters).unwrap_or(u8::MAX);
+    }
+    let mut herd = Herd::default();
+    song.events.eves = Converter::default().convert(&parsed, &mut herd);
+    song.events.sort();
+    song.recalculate_length();
+    Ok((song, herd))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    const fn bytes(&mut self, len: usize) -> Result<&'a [u8], MidiError> {
+        let Some((bytes, rest)) = self.data.split_at_checked(len) else {
+            return Err(MidiError::UnexpectedEnd);
+        };
+        self.data = rest;
+        Ok(bytes)
+    }
+    fn u8(&mut self) -> Result<u8, MidiError> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, MidiError> {
+        Ok(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+    fn u32(&mut self) -> Result<u32, MidiError> {
+        Ok(u32::from_be_bytes([
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+        ]))
+    }
+    /// The body of a chunk, after the chunk code
+    fn chunk_body(&mut self) -> Result<Self, MidiError> {
+        let len = self.u32()? as usize;
+        Ok(Self {
+            data: self.bytes(len)?,
+        })
+    }
+    /// Variable length quantity, at most 4 bytes
+    fn var(&mut self) -> Result<u32, MidiError> {
+        let mut num = 0;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            num = (num << 7) | u32::from(byte & 0x7F);
+            if byte & 0x80 == 0 {
+                return Ok(num);
+            }
+        }
+        Err(MidiError::InvalidData)
+    }
+}
+
+/// A note, from note on to note off
+struct Note {
+    start: Tick,
+    end: Tick,
+    channel: u8,
+    key: u8,
+    velocity: u8,
+}
+
+#[derive(Clone, Copy)]
+enum Control {
+    Program(u8),
+    Volume(u8),
+    Pan(u8),
+}
+
+#[derive(Default)]
+struct Parsed {
+    notes: Vec<Note>,
+    controls: Vec<(Tick, u8, Control)>,
+    tempo: Option<u32>,
+    time_sig: Option<(u8, u8)>,
+    channel_names: [Option<String>; 16],
+}
+
+impl Parsed {
+    fn track(&mut self, data: &[u8]) -> Result<(), MidiError> {
+        let mut rd = Reader { data };
+        let mut tick: Tick = 0;
+        let mut running_status = None;
+        let mut name = None;
+        // Notes that are on, by channel and key
+        let mut open: Vec<Note> = Vec::new();
+        while !rd.data.is_empty() {
+            tick = tick.saturating_add(rd.var()?);
+            // With running status, the status byte is left out, and data follows directly
+            let status = match rd.data.first() {
+                Some(&byte) if byte < 0x80 => running_status.ok_or(MidiError::InvalidData)?,
+                _ => rd.u8()?,
+            };
+            match status {
+                0xFF => {
+                    let kind = rd.u8()?;
+                    let len = rd.var()? as usize;
+                    let body = rd.bytes(len)?;
+                    match (kind, body) {
+                        (0x03, _) => name = Some(String::from_utf8_lossy(body).into_owned()),
+                        (0x2F, _) => break,
+                        (0x51, &[a, b, c]) if self.tempo.is_none() && [a, b, c] != [0; 3] => {
+                            self.tempo = Some(u32::from_be_bytes([0, a, b, c]));
+                        }
+                        (0x58, &[num, denom_pow, ..]) if self.time_sig.is_none() && num != 0 => {
+                            self.time_sig = Some((num, denom_pow));
+                        }
+                        _ => {}
+                    }
+                }
+                0xF0 | 0xF7 => {
+                    let len = rd.var()? as usize;
+                    rd.bytes(len)?;
+                }
+                0x80..=0xEF => {
+                    running_status = Some(status);
+                    let channel = status & 0x0F;
+                    let data1 = rd.u8()?;
+                    let data2 = if matches!(status >> 4, 0xC | 0xD) {
+                        0
+                    } else {
+                        rd.u8()?
+                    };
+                    if let Some(name) = &name {
+                        self.channel_names[usize::from(channel)]
+                            .get_or_insert_with(|| name.clone());
+                    }
+                    self.channel_msg(&mut open, tick, status >> 4, channel, data1, data2);
+                }
+                _ => return Err(MidiError::InvalidData),
+            }
+        }
+        // Notes that are never released last until the end of the track
+        for mut note in open {
+            note.end = tick;
+            self.notes.push(note);
+        }
+        Ok(())
+    }
+    fn channel_msg(
+        &mut self,
+        open: &mut Vec<Note>,
+        tick: Tick,
+        kind: u8,
+        channel: u8,
+        data1: u8,
+        data2: u8,
+    ) {
+        let is_note_off = kind == 0x8 || (kind == 0x9 && data2 == 0);
+        if is_note_off || kind == 0x9 {
+            // A note on for a key that's already on ends the previous note
+            if let Some(pos) = open.iter().position(|n| n.channel == channel && n.key == data1) {
+                let mut note = open.swap_remove(pos);
+                note.end = tick;
+                self.notes.push(note);
+            }
+        }
+        let control = match kind {
+            0x9 if !is_note_off => {
+                open.push(Note {
+                    start: tick,
+                    end: tick,
+                    channel,
+                    key: data1,
+                    velocity: data2,
+                });
+                return;
+            }
+            0xB if data1 == 7 => Control::Volume(data2),
+            0xB if data1 == 10 => Control::Pan(data2),
+            0xC => Control::Program(data1),
+            _ => return,
+        };
+        self.controls.push((tick, channel, control));
+    }
+}
+
+/// The controller state of a MIDI channel
+#[derive(Clone, Copy)]
+struct Channel {
+    program: u8,
+    volume: u8,
+    pan: u8,
+}
+
+struct ConvUnit {
+    idx: UnitIdx,
+    channel: u8,
+    busy_until: Tick,
+    /// Voice, volume, pan, velocity and note that were last set with events,
+    /// `None` for the defaults of the unit
+    state: [Option<u8>; 5],
+}
+
+impl ConvUnit {
+    const fn is_free(&self, tick: Tick) -> bool {
+        self.busy_until <= tick
+    }
+}
+
+#[derive(Default)]
+struct Converter {
+    units: Vec<ConvUnit>,
+    events: Vec<Event>,
+}
+
+impl Converter {
+    fn convert(mut self, parsed: &Parsed, herd: &mut Herd) -> Vec<Event> {
+        let mut channels = [Channel {
+            program: 0,
+            volume: 100,
+            pan: 64,
+        }; 16];
+        let mut notes: Vec<&Note> = parsed.notes.iter().collect();
+        notes.sort_by_key(|note| note.start);
+        let mut controls = parsed.controls.clone();
+        controls.sort_by_key(|(tick, ..)| *tick);
+        let mut controls = controls.into_iter().peekable();
+        for note in notes {
+            while let Some((_, channel, control)) =
+                controls.next_if(|(tick, ..)| *tick <= note.start)
+            {
+                let st = &mut channels[usize::from(channel)];
+                match control {
+                    Control::Program(program) => st.program = program,
+                    Control::Volume(volume) => st.volume = volume,
+                    Control::Pan(pan) => st.pan = pan,
+                }
+            }
+            let Some(unit) = self.unit_for(note, herd, parsed) else {
+                continue;
+            };
+            self.note(unit, note, channels[usize::from(note.channel)]);
+        }
+        self.events
+    }
+    /// Find a unit of the channel that's free to play `note`, or add a new one
+    fn unit_for(&mut self, note: &Note, herd: &mut Herd, parsed: &Parsed) -> Option<usize> {
+        let free = |unit: &ConvUnit| unit.channel == note.channel && unit.is_free(note.start);
+        if let Some(unit) = self.units.iter().position(free) {
+            return Some(unit);
+        }
+        let name = parsed.channel_names[usize::from(note.channel)]
+            .clone()
+            .unwrap_or_else(|| format!("ch{:02}", note.channel + 1));
+        if herd.units.len() >= Units::MAX_STANDARD {
+            return None;
+        }
+        let idx = herd.add_unit_unique(&name)?;
+        self.units.push(ConvUnit {
+            idx,
+            channel: note.channel,
+            busy_until: 0,
+            state: [None; 5],
+        });
+        Some(self.units.len() - 1)
+    }
+    fn note(&mut self, unit: usize, note: &Note, channel: Channel) {
+        let unit = &mut self.units[unit];
+        unit.busy_until = note.end;
+        let wanted = [
+            channel.program,
+            channel.volume,
+            channel.pan,
+            note.velocity,
+            note.key,
+        ];
+        // The defaults of units, before any events
+        #[expect(clippy::cast_possible_truncation)]
+        let defaults = [
+            0,
+            DEFAULT_VOLUME as u8,
+            64,
+            DEFAULT_VELOCITY as u8,
+            (DEFAULT_KEY.0 / 256 - 27) as u8,
+        ];
+        for (i, (current, (value, default))) in
+            unit.state.iter_mut().zip(wanted.into_iter().zip(defaults)).enumerate()
+        {
+            if current.unwrap_or(default) == value {
+                continue;
+            }
+            *current = Some(value);
+            let payload = match i {
+                0 => EventPayload::SetVoice(VoiceIdx(value)),
+                1 => EventPayload::Volume(value.into()),
+                2 => EventPayload::PanVol(value),
+                3 => EventPayload::Velocity(value.into()),
+                _ => EventPayload::Key(Key::from_midi(value)),
+            };
+            self.events.push(Event {
+                payload,
+                unit: unit.idx,
+                tick: note.start,
+            });
+        }
+        self.events.push(Event {
+            payload: EventPayload::On {
+                duration: (note.end - note.start).max(1),
+            },
+            unit: unit.idx,
+            tick: note.start,
+        });
+    }
+}
+
+#[test]
+fn test_import() {
+    fn track(events: &[u8]) -> Vec<u8> {
+        let mut out = b"MTrk".to_vec();
+        out.extend_from_slice(&u32::try_from(events.len()).unwrap().to_be_bytes());
+        out.extend_from_slice(events);
+        out
+    }
+    let mut data = b"MThd".to_vec();
+    data.extend_from_slice(&[0, 0, 0, 6, 0, 0, 0, 1, 0, 96]);
+    data.extend(track(&[
+        // Tempo 150 bpm, 3/4
+        0x00, 0xFF, 0x51, 3, 0x06, 0x1A, 0x80, //
+        0x00, 0xFF, 0x58, 4, 3, 2, 24, 8, //
+        0x00, 0xFF, 0x03, 4, b'l', b'e', b'a', b'd', //
+        0x00, 0xC0, 5, //
+        // A chord of A4 and C5, using running status for the second note
+        0x00, 0x90, 69, 100, //
+        0x00, 72, 80, //
+        0x60, 0x80, 69, 0, //
+        // Note off as note on with zero velocity
+        0x00, 0x90, 72, 0, //
+        0x00, 0xFF, 0x2F, 0,
+    ]));
+    let (song, herd) = import(&data).unwrap();
+    assert_eq!(song.master.timing.ticks_per_beat, 96);
+    assert_eq!(song.master.timing.bpm.to_bits(), 150.0f32.to_bits());
+    assert_eq!(song.master.timing.beats_per_meas, 3);
+    assert_eq!(herd.units.len(), 2);
+    assert_eq!(herd.units[UnitIdx(0)].name, "lead");
+    assert_eq!(herd.units[UnitIdx(1)].name, "lead (2)");
+    let events: Vec<_> = song.events.iter().map(|eve| (eve.unit.0, eve.payload)).collect();
+    assert!(events.contains(&(0, EventPayload::SetVoice(VoiceIdx(5)))));
+    assert!(events.contains(&(0, EventPayload::Volume(100))));
+    assert!(events.contains(&(1, EventPayload::Key(Key::from_midi(72)))));
+    assert!(events.contains(&(1, EventPayload::Velocity(80))));
+    assert!(!events.contains(&(0, EventPayload::Key(DEFAULT_KEY))));
+    assert!(events.contains(&(0, EventPayload::On { duration: 96 })));
+    assert!(events.contains(&(1, EventPayload::On { duration: 96 })));
+    assert_eq!(import(b"RIFF").err(), Some(MidiError::NotMidi));
+    assert_eq!(import(&data[..30]).err(), Some(MidiError::UnexpectedEnd));
+}
@@ -1,8 +1,10 @@
 use {
     crate::{
         Bps, ChNum, NATIVE_SAMPLE_RATE, SampleRate,
+        consts::{BASIC_FREQUENCY, SAMPLING_TOP},
         pulse_frequency::PULSE_FREQ,
         pulse_oscillator::{OsciArgs, OsciPt, coord, overtone},
+        unit::PanLaw,
         voice_data::{
             noise::{NoiseData, NoiseDesignUnit},
             pcm::PcmData,
@@ -18,6 +20,8 @@ type Tables = [Box<[i16]>; 16];
 /// Used by [`noise_to_pcm`].
 pub struct NoiseTable {
     pub(crate) inner: Tables,
+    /// The pan law used for the pan of noise design units
+    pub pan_law: PanLaw,
 }
 
 struct Rng {
@@ -119,7 +123,10 @@ impl NoiseTable {
             [0; SMP_NUM_U].into(),
         ];
 
-        let mut this = Self { inner: tables };
+        let mut this = Self {
+            inner: tables,
+            pan_law: PanLaw::default(),
+        };
 
         let osci = OsciArgs {
             volume: 128,
@@ -168,6 +175,25 @@ impl NoiseTable {
         fill_rect3_onward(&mut this);
         this
     }
+    /// Sample the wave of type `type_` at `phase` (`0.0..1.0`), for use as a low frequency
+    /// oscillator.
+    ///
+    /// Random waves step through the random table, so they produce a new random value
+    /// every sample.
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    pub(crate) fn lfo_sample(&self, type_: NoiseType, phase: f32) -> i16 {
+        // Random2 doesn't have its own table, the noise builder also uses the Random one
+        let tbl = match type_ {
+            NoiseType::Random2 => &self.inner[NoiseType::Random as usize],
+            _ => &self.inner[type_ as usize],
+        };
+        let idx = (phase * tbl.len() as f32) as usize;
+        tbl.get(idx).copied().unwrap_or(0)
+    }
     /// (testing-only) Get the inner wave table
     #[cfg(feature = "testing")]
     #[must_use]
@@ -178,7 +204,12 @@ impl NoiseTable {
 
 /// Build PCM data out of [`NoiseData`].
 pub fn noise_to_pcm(noise: &mut NoiseData, table: &NoiseTable) -> PcmData {
-    let sps = NATIVE_SAMPLE_RATE;
+    noise_to_pcm_at(noise, table, NATIVE_SAMPLE_RATE)
+}
+
+/// Like [`noise_to_pcm`], but the PCM data is built at `sps` instead of
+/// [`NATIVE_SAMPLE_RATE`]. It lasts just as long, in more or fewer samples.
+pub fn noise_to_pcm_at(noise: &mut NoiseData, table: &NoiseTable, sps: SampleRate) -> PcmData {
     let bps = Bps::B16;
     noise.fix();
 
@@ -187,6 +218,7 @@ pub fn noise_to_pcm(noise: &mut NoiseData, table: &NoiseTable) -> PcmData {
     let mut nb_units = vec![NoiseBuilderUnit::default(); unit_num];
     for (nb_u, u) in zip(&mut nb_units, &noise.units) {
         build_unit(nb_u, u, &table.inner, sps);
+        nb_u.pan_law = table.pan_law;
     }
     #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     let smp_num =
@@ -224,9 +256,7 @@ fn build_pcm_samp<'a>(
     let mut byte4: i32;
     store = 0.;
     for unit in units {
-        let mut po;
-
-        po = &unit.main;
+        let po = &unit.main;
         match po.ran_type {
             RandomType::None => {
                 offset = po.offset as i32;
@@ -258,27 +288,10 @@ fn build_pcm_samp<'a>(
         }
         work *= po.volume;
 
-        po = &unit.volu;
-        match po.ran_type {
-            RandomType::None => {
-                offset = po.offset as i32;
-                vol = f64::from(po.samp.get(offset as usize).copied().unwrap_or(0));
-            }
-            RandomType::Saw => {
-                vol =
-                    f64::from(po.rdm_start + po.rdm_margin * po.offset as i32 / i32::from(SMP_NUM));
-            }
-            RandomType::Rect => {
-                vol = f64::from(po.rdm_start);
-            }
-        }
-        if po.reverse {
-            vol *= -1.0;
-        }
-        vol *= po.volume;
-
+        vol = unit.volu.sample();
         work = work * (vol + f64::from(SAMPLING_TOP)) / (f64::from(SAMPLING_TOP) * 2.0);
-        work *= unit.pan[channel];
+        let pan = unit.pan + unit.pan_osc.sample() * 100.0 / f64::from(SAMPLING_TOP);
+        work *= pan_vols(pan, unit.pan_law)[channel];
 
         if unit.enve_index < unit.enve_num {
             work *= unit.enve_mag_start
@@ -312,11 +325,7 @@ fn build_unit<'smp>(
     sps: SampleRate,
 ) {
     unit.enve_num = design_unit.enves.len();
-    unit.pan = match design_unit.pan.cmp(&0) {
-        Ordering::Less => [1., (100.0 + f64::from(design_unit.pan)) / 100.],
-        Ordering::Equal => [1., 1.],
-        Ordering::Greater => [(100.0 - f64::from(design_unit.pan)) / 100., 1.],
-    };
+    unit.pan = f64::from(design_unit.pan);
 
     unit.enves = vec![Pt::ZERO; unit.enve_num];
     for e in 0..design_unit.enves.len() {
@@ -359,6 +368,28 @@ fn build_unit<'smp>(
         tbl,
         &tables[NoiseType::Random as usize],
     );
+    let tbl = &tables[design_unit.pan_osc.type_ as usize];
+    set_ocsillator(
+        &mut unit.pan_osc,
+        &design_unit.pan_osc,
+        sps,
+        tbl,
+        &tables[NoiseType::Random as usize],
+    );
+}
+
+/// Left and right channel volumes for `pan` (-100 is left, 100 is right)
+fn pan_vols(pan: f64, law: PanLaw) -> [f64; 2] {
+    let pan = pan.clamp(-100.0, 100.0);
+    if law != PanLaw::Linear {
+        return law.gains(pan / 100.0);
+    }
+    // Keep PxTone's exact arithmetic
+    match pan.partial_cmp(&0.0) {
+        Some(Ordering::Less) => [1., (100.0 + pan) / 100.],
+        Some(Ordering::Greater) => [(100.0 - pan) / 100., 1.],
+        _ => [1., 1.],
+    }
 }
 
 #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
@@ -388,6 +419,7 @@ fn build_unit_noise(unit: &mut NoiseBuilderUnit<'_>, rand_tbl: &[i16]) {
     );
     unit.freq.increment(unit.freq.incriment, rand_tbl);
     unit.volu.increment(unit.volu.incriment, rand_tbl);
+    unit.pan_osc.increment(unit.pan_osc.incriment, rand_tbl);
 
     if unit.enve_index < unit.enve_num {
         unit.enve_count += 1;
@@ -521,6 +553,24 @@ struct Oscillator<'s> {
 }
 
 impl Oscillator<'_> {
+    /// The current value of a modulating oscillator, scaled by its volume
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn sample(&self) -> f64 {
+        let mut val = match self.ran_type {
+            RandomType::None => {
+                let offset = self.offset as i32;
+                f64::from(self.samp.get(offset as usize).copied().unwrap_or(0))
+            }
+            RandomType::Saw => f64::from(
+                self.rdm_start + self.rdm_margin * self.offset as i32 / i32::from(SMP_NUM),
+            ),
+            RandomType::Rect => f64::from(self.rdm_start),
+        };
+        if self.reverse {
+            val *= -1.0;
+        }
+        val * self.volume
+    }
     fn increment(&mut self, by: f64, rand_tbl: &[i16]) {
         self.offset += by;
         if self.offset > f64::from(SMP_NUM) {
@@ -582,16 +632,20 @@ fn set_ocsillator<'smp>(
     to.rdm_margin = i32::from(p.get(to.rdm_index).copied().unwrap_or(0));
 }
 
-const BASIC_FREQUENCY: u8 = 100;
-const SAMPLING_TOP: i16 = 32767;
 const KEY_TOP: i32 = 0x3200;
-const SMP_NUM_RAND: SampleRate = NATIVE_SAMPLE_RATE;
-const SMP_NUM: u16 = NATIVE_SAMPLE_RATE / BASIC_FREQUENCY as u16;
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "the native sample rate fits"
+)]
+const SMP_NUM_RAND: u16 = NATIVE_SAMPLE_RATE.get() as u16;
+const SMP_NUM: u16 = SMP_NUM_RAND / BASIC_FREQUENCY;
 const SMP_NUM_U: usize = SMP_NUM as usize;
 
 #[derive(Default, Clone)]
 struct NoiseBuilderUnit<'smp> {
-    pan: [f64; 2],
+    /// Base pan (-100 is left, 100 is right)
+    pan: f64,
+    pan_law: PanLaw,
     enve_index: usize,
     enve_mag_start: f64,
     enve_mag_margin: f64,
@@ -601,6 +655,7 @@ struct NoiseBuilderUnit<'smp> {
     main: Oscillator<'smp>,
     freq: Oscillator<'smp>,
     volu: Oscillator<'smp>,
+    pan_osc: Oscillator<'smp>,
 }
 
 #[derive(Clone)]
@@ -615,7 +670,8 @@ impl Pt {
 
 /// Types of waves for noise generation
 #[expect(missing_docs)]
-#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[repr(u8)]
 pub enum NoiseType {
     #[default]
     Sine,
@@ -636,8 +692,39 @@ pub enum NoiseType {
     Saw8,
 }
 
+impl NoiseType {
+    /// All the wave types, in order
+    pub const ALL: [Self; 16] = [
+        Self::Sine,
+        Self::Saw,
+        Self::Rect,
+        Self::Random,
+        Self::Saw2,
+        Self::Rect2,
+        Self::Tri,
+        Self::Random2,
+        Self::Rect3,
+        Self::Rect4,
+        Self::Rect8,
+        Self::Rect16,
+        Self::Saw3,
+        Self::Saw4,
+        Self::Saw6,
+        Self::Saw8,
+    ];
+    /// Get the wave type from its index in [`Self::ALL`]
+    #[must_use]
+    pub const fn from_index(idx: u8) -> Option<Self> {
+        if (idx as usize) < Self::ALL.len() {
+            Some(Self::ALL[idx as usize])
+        } else {
+            None
+        }
+    }
+}
+
 /// An oscillator for generating different kinds of noise waveforms.
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Default, PartialEq)]
 pub struct NoiseDesignOscillator {
     /// The type of wave to use
     pub type_: NoiseType,
@@ -1,11 +1,18 @@
-use crate::unit::{GroupIdx, GroupSamples};
+use crate::{
+    result::OverdriveError,
+    unit::{GroupIdx, GroupSamples},
+};
 
 /// Overdrive effect that amplifies and cuts the samples of a sample group
 ///
 /// The samples are signed 32 bit samples, but the effective range is signed 16 bit
 #[must_use]
+#[derive(Clone)]
 pub struct Overdrive {
     /// Whether this effect is on
+    ///
+    /// Project files have no place for this, so like in `PxTone`, it's not saved,
+    /// and every overdrive that's read is on.
     pub on: bool,
     /// Which sample group this effect operates on
     pub group: GroupIdx,
@@ -36,6 +43,30 @@ impl Overdrive {
     pub const CUT_VALID_RANGE: std::ops::RangeInclusive<f32> = 50.0..=99.9;
     /// The amplitude multiplication factor must be within this range
     pub const AMP_VALID_RANGE: std::ops::RangeInclusive<f32> = 0.1..=8.0;
+    /// The current settings of this effect
+    #[must_use]
+    pub const fn settings(&self) -> OverdriveSettings {
+        OverdriveSettings {
+            on: self.on,
+            group: self.group,
+            cut_percent: self.cut_percent,
+            amp_mul: self.amp_mul,
+        }
+    }
+    /// Change the settings of this effect, and [rebuild](Self::rebuild) it.
+    ///
+    /// # Errors
+    ///
+    /// If the settings are [invalid](OverdriveSettings::validate). Nothing is changed then.
+    pub fn update(&mut self, settings: OverdriveSettings) -> Result<(), OverdriveError> {
+        settings.validate()?;
+        self.on = settings.on;
+        self.group = settings.group;
+        self.cut_percent = settings.cut_percent;
+        self.amp_mul = settings.amp_mul;
+        self.rebuild();
+        Ok(())
+    }
     #[expect(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
     pub(crate) fn tone_supple(&self, group_smps: &mut GroupSamples) {
         if !self.on {
@@ -55,3 +86,37 @@ impl Overdrive {
         self.cut_16bit_top = (32767.0 * (100.0 - self.cut_percent) / 100.0) as i32;
     }
 }
+
+/// The settings of an [`Overdrive`] that can be edited, see [`Overdrive::update`]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OverdriveSettings {
+    /// See [`Overdrive::on`]
+    pub on: bool,
+    /// See [`Overdrive::group`]
+    pub group: GroupIdx,
+    /// See [`Overdrive::cut_percent`]
+    pub cut_percent: f32,
+    /// See [`Overdrive::amp_mul`]
+    pub amp_mul: f32,
+}
+
+impl OverdriveSettings {
+    /// Check that the settings are within the ranges that can be played and saved.
+    ///
+    /// # Errors
+    ///
+    /// If the group doesn't exist, or the cut or amplification is outside of
+    /// [`Overdrive::CUT_VALID_RANGE`] or [`Overdrive::AMP_VALID_RANGE`].
+    pub fn validate(self) -> Result<(), OverdriveError> {
+        if self.group.0 > GroupIdx::MAX.0 {
+            return Err(OverdriveError::GroupOutOfRange(self.group));
+        }
+        if !Overdrive::CUT_VALID_RANGE.contains(&self.cut_percent) {
+            return Err(OverdriveError::CutOutOfRange(self.cut_percent));
+        }
+        if !Overdrive::AMP_VALID_RANGE.contains(&self.amp_mul) {
+            return Err(OverdriveError::AmpOutOfRange(self.amp_mul));
+        }
+        Ok(())
+    }
+}
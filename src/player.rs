@@ -0,0 +1,167 @@
+//! A simple high level API for playing songs
+
+use crate::{
+    Herd, MooInstructions, MooPlan, ReadResult, SampleRate, SampleT, SeekIndex, Song, StartPosPlan,
+    moo_prepare, read_song,
+};
+
+/// Plays a song, without having to deal with [`Song`], [`Herd`], and [`MooInstructions`].
+///
+/// The lower level API is likely to change as ptcow's internals get refactored, while this
+/// one is meant to stay stable. If you only want to play songs, this is what you should use.
+///
+/// ```no_run
+/// let data = std::fs::read("song.ptcop").unwrap();
+/// let mut player = ptcow::Player::load(&data, ptcow::NATIVE_SAMPLE_RATE).unwrap();
+/// player.set_loop(false);
+/// let mut buf = [0; 4096];
+/// while player.render(&mut buf) {
+///     // Output `buf`...
+/// }
+/// ```
+pub struct Player {
+    song: Song,
+    herd: Box<Herd>,
+    ins: Box<MooInstructions>,
+    seek_index: Option<SeekIndex>,
+}
+
+impl Player {
+    /// Load a `.ptcop` or `.pttune` song from `data`, to be played at `out_sample_rate`.
+    ///
+    /// The song loops by default.
+    pub fn load(data: &[u8], out_sample_rate: SampleRate) -> ReadResult<Self> {
+        // Boxing in a separate function keeps the large parts off of this function's stack
+        let mut player = read_song(data, out_sample_rate).map(Self::from_parts)?;
+        let plan = MooPlan {
+            start_pos: StartPosPlan::Sample(0),
+            meas_end: None,
+            meas_repeat: None,
+            loop_: true,
+        };
+        moo_prepare(&mut player.ins, &mut player.herd, &player.song, &plan)?;
+        Ok(player)
+    }
+    fn from_parts((song, herd, ins): (Song, Herd, MooInstructions)) -> Self {
+        Self {
+            song,
+            herd: Box::new(herd),
+            ins: Box::new(ins),
+            seek_index: None,
+        }
+    }
+    /// Render the next samples of the song into `buf`, as interleaved 16 bit stereo.
+    ///
+    /// Returns false if the song has already ended (see [`Self::set_loop`]).
+    /// The part of `buf` after the end of the song is filled with silence.
+    pub fn render(&mut self, buf: &mut [i16]) -> bool {
+        buf.fill(0);
+        self.herd.moo(&self.ins, &self.song, buf, true)
+    }
+    /// Seek to `sample` (counted in samples per channel at the output sample rate).
+    ///
+    /// Notes that are playing at `sample` continue seamlessly.
+    /// The first seek prepares an index of the song, which can take a moment for long songs.
+    ///
+    /// Returns false if `sample` is past the end of the song, and the song isn't looping.
+    pub fn seek(&mut self, sample: SampleT) -> bool {
+        let index = self
+            .seek_index
+            .get_or_insert_with(|| self.herd.build_seek_index(&self.ins, &self.song, 1));
+        self.herd.seek_indexed(&self.ins, &self.song, index, sample)
+    }
+    /// Set whether the song loops back to its repeat position when it reaches the end.
+    ///
+    /// Turning looping back on after the song has ended doesn't restart it, use [`Self::seek`]
+    /// for that.
+    pub const fn set_loop(&mut self, loop_: bool) {
+        self.herd.set_loop(loop_);
+    }
+    /// Whether the song loops (see [`Self::set_loop`])
+    #[must_use]
+    pub const fn loops(&self) -> bool {
+        self.herd.loops()
+    }
+    /// Whether the song has ended. Looping songs never end.
+    #[must_use]
+    pub const fn has_ended(&self) -> bool {
+        self.herd.moo_end
+    }
+    /// The current playback position in samples
+    #[must_use]
+    pub const fn position(&self) -> SampleT {
+        self.herd.smp_count
+    }
+    /// The length of the song in samples, up to the end of the last measure
+    #[must_use]
+    pub const fn total_samples(&self) -> SampleT {
+        self.herd.smp_end
+    }
+    /// The position in samples the song jumps back to when looping
+    #[must_use]
+    pub const fn repeat_sample(&self) -> SampleT {
+        self.herd.smp_repeat
+    }
+    /// The output sample rate
+    #[must_use]
+    pub const fn sample_rate(&self) -> SampleRate {
+        self.ins.out_sample_rate
+    }
+    /// The name of the song
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.song.text.name
+    }
+    /// The comment (short description) of the song
+    #[must_use]
+    pub fn comment(&self) -> &str {
+        &self.song.text.comment
+    }
+    /// The tempo of the song in beats per minute
+    #[must_use]
+    pub const fn bpm(&self) -> f32 {
+        self.song.master.timing.bpm
+    }
+}
+
+#[test]
+fn test_player() {
+    use crate::{
+        Event, EventPayload, NATIVE_SAMPLE_RATE, Unit, UnitIdx, presets::wave::factory_voices,
+    };
+    let mut song = Song::default();
+    song.text.name = "player".into();
+    song.events.eves = (0..4)
+        .map(|i| Event {
+            payload: EventPayload::On { duration: 1000 },
+            unit: UnitIdx(0),
+            tick: i * 960,
+        })
+        .collect();
+    song.recalculate_length();
+    let mut herd = Herd::default();
+    herd.units.push(Unit::new());
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(factory_voices());
+    let data = crate::serialize_project(&song, &herd, &ins).unwrap();
+
+    let mut player = Player::load(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(player.name(), "player");
+    assert!(player.loops());
+    let total = usize::try_from(player.total_samples()).unwrap();
+    let mut full = vec![0; 2 * total];
+    assert!(player.render(&mut full));
+    assert!(full.iter().any(|&s| s != 0));
+    assert_eq!(player.position(), 0, "looped back to the start");
+    let target = player.total_samples() / 2 + 123;
+    assert!(player.seek(target));
+    let mut buf = vec![0; 4096];
+    player.render(&mut buf);
+    assert_eq!(buf, full[2 * usize::try_from(target).unwrap()..][..4096]);
+    player.set_loop(false);
+    let mut rest = vec![1; 2 * total];
+    assert!(player.render(&mut rest));
+    assert!(player.has_ended());
+    assert!(rest[2 * (total - usize::try_from(target).unwrap())..].iter().all(|&s| s == 0));
+    assert!(!player.render(&mut buf));
+}
@@ -0,0 +1,7 @@
+//! Ready-made instrument definitions
+//!
+//! Useful for building songs programmatically, or for quick experiments, without having to
+//! craft the instrument parameters from scratch.
+
+pub mod noise;
+pub mod wave;
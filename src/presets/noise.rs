@@ -0,0 +1,235 @@
+//! Drum sounds made with the noise generator
+//!
+//! Each function returns a [`NoiseData`] that can be turned into a voice with
+//! [`VoiceData::Noise`](crate::VoiceData::Noise).
+
+use {
+    crate::{
+        EnvPt, NoiseDesignOscillator, NoiseType,
+        voice_data::noise::{NoiseData, NoiseDesignUnit, NoiseDesignUnitFlags},
+    },
+    arrayvec::ArrayVec,
+};
+
+/// Deep, long kick with a slow downward pitch sweep, in the style of the TR-808
+#[must_use]
+pub fn kick_808() -> NoiseData {
+    noise(
+        500,
+        [unit(
+            &[env(0, 100), env(500, 0)],
+            osc(NoiseType::Sine, 55.0, 100.0),
+            Some(osc(NoiseType::Saw, 2.0, 30.0)),
+        )],
+    )
+}
+
+/// Short kick with a fast pitch drop and a click on the attack
+#[must_use]
+pub fn kick_punchy() -> NoiseData {
+    noise(
+        250,
+        [
+            unit(
+                &[env(0, 100), env(60, 60), env(190, 0)],
+                osc(NoiseType::Sine, 70.0, 100.0),
+                Some(osc(NoiseType::Saw, 4.0, 50.0)),
+            ),
+            unit(
+                &[env(0, 60), env(15, 0)],
+                osc(NoiseType::Random, 44_100.0, 60.0),
+                None,
+            ),
+        ],
+    )
+}
+
+/// Snare with a tonal body and a noisy tail
+#[must_use]
+pub fn snare() -> NoiseData {
+    noise(
+        300,
+        [
+            unit(
+                &[env(0, 100), env(120, 0)],
+                osc(NoiseType::Tri, 180.0, 80.0),
+                Some(osc(NoiseType::Saw, 8.0, 10.0)),
+            ),
+            unit(
+                &[env(0, 100), env(280, 0)],
+                osc(NoiseType::Random, 20_000.0, 90.0),
+                None,
+            ),
+        ],
+    )
+}
+
+/// Short, dry snare
+#[must_use]
+pub fn snare_tight() -> NoiseData {
+    noise(
+        150,
+        [
+            unit(
+                &[env(0, 100), env(60, 0)],
+                osc(NoiseType::Tri, 220.0, 80.0),
+                None,
+            ),
+            unit(
+                &[env(0, 100), env(140, 0)],
+                osc(NoiseType::Random, 30_000.0, 90.0),
+                None,
+            ),
+        ],
+    )
+}
+
+/// Closed hi-hat
+#[must_use]
+pub fn hihat_closed() -> NoiseData {
+    noise(
+        60,
+        [unit(
+            &[env(0, 80), env(50, 0)],
+            osc(NoiseType::Random, 44_100.0, 80.0),
+            None,
+        )],
+    )
+}
+
+/// Open hi-hat
+#[must_use]
+pub fn hihat_open() -> NoiseData {
+    noise(
+        350,
+        [unit(
+            &[env(0, 80), env(30, 50), env(300, 0)],
+            osc(NoiseType::Random, 44_100.0, 80.0),
+            None,
+        )],
+    )
+}
+
+/// Bright crash cymbal
+#[must_use]
+pub fn crash() -> NoiseData {
+    noise(
+        1600,
+        [
+            unit(
+                &[env(0, 100), env(1500, 0)],
+                osc(NoiseType::Random, 44_100.0, 80.0),
+                None,
+            ),
+            unit(
+                &[env(0, 60), env(800, 0)],
+                osc(NoiseType::Random2, 12_000.0, 50.0),
+                None,
+            ),
+        ],
+    )
+}
+
+/// Darker, washier crash cymbal
+#[must_use]
+pub fn crash_dark() -> NoiseData {
+    noise(
+        2000,
+        [unit(
+            &[env(0, 50), env(40, 100), env(1900, 0)],
+            osc(NoiseType::Random, 15_000.0, 90.0),
+            Some(osc(NoiseType::Sine, 3.0, 2.0)),
+        )],
+    )
+}
+
+fn noise<const N: usize>(length_ms: u32, units: [NoiseDesignUnit; N]) -> NoiseData {
+    NoiseData {
+        smp_num_44k: length_ms * 441 / 10,
+        units: units.into_iter().collect(),
+    }
+}
+
+fn unit(
+    enves: &[EnvPt],
+    main: NoiseDesignOscillator,
+    freq: Option<NoiseDesignOscillator>,
+) -> NoiseDesignUnit {
+    let mut ser_flags = NoiseDesignUnitFlags::ENVELOPE | NoiseDesignUnitFlags::OSC_MAIN;
+    if freq.is_some() {
+        ser_flags |= NoiseDesignUnitFlags::OSC_FREQ;
+    }
+    NoiseDesignUnit {
+        enves: enves.iter().copied().collect::<ArrayVec<_, 3>>(),
+        pan: 0,
+        main,
+        freq: freq.unwrap_or_default(),
+        volu: NoiseDesignOscillator::default(),
+        pan_osc: NoiseDesignOscillator::default(),
+        ser_flags,
+    }
+}
+
+const fn osc(type_: NoiseType, freq: f32, volume: f32) -> NoiseDesignOscillator {
+    NoiseDesignOscillator {
+        type_,
+        freq,
+        volume,
+        offset: 0.0,
+        invert: false,
+    }
+}
+
+const fn env(x: u16, y: u8) -> EnvPt {
+    EnvPt { x, y }
+}
+
+#[test]
+fn test_presets_roundtrip() {
+    let table = crate::NoiseTable::generate();
+    for mut preset in [
+        kick_808(),
+        kick_punchy(),
+        snare(),
+        snare_tight(),
+        hihat_closed(),
+        hihat_open(),
+        crash(),
+        crash_dark(),
+    ] {
+        let bytes = preset.to_ptnoise();
+        let reread = NoiseData::from_ptnoise(&bytes).unwrap();
+        assert_eq!(bytes, reread.to_ptnoise());
+        let pcm = crate::noise_to_pcm(&mut preset, &table);
+        assert!(pcm.smp.iter().any(|&b| b != 0));
+    }
+}
+
+#[test]
+fn test_pan_oscillator() {
+    let table = crate::NoiseTable::generate();
+    let mut noise = hihat_open();
+    let unit = &mut noise.units[0];
+    unit.pan_osc = osc(NoiseType::Sine, 10.0, 100.0);
+    unit.ser_flags |= NoiseDesignUnitFlags::OSC_PAN;
+    let bytes = noise.to_ptnoise();
+    let mut reread = NoiseData::from_ptnoise(&bytes).unwrap();
+    assert_eq!(reread.units[0].pan_osc.volume.to_bits(), 100f32.to_bits());
+    assert_eq!(bytes, reread.to_ptnoise());
+    let pcm = crate::noise_to_pcm(&mut reread, &table);
+    let frames: Vec<[i16; 2]> = pcm
+        .smp
+        .as_chunks::<4>()
+        .0
+        .iter()
+        .map(|b| {
+            [
+                i16::from_le_bytes([b[0], b[1]]),
+                i16::from_le_bytes([b[2], b[3]]),
+            ]
+        })
+        .collect();
+    // The pan swings back and forth, so both channels get silenced at some point
+    assert!(frames.iter().any(|[l, r]| *l == 0 && *r != 0));
+    assert!(frames.iter().any(|[l, r]| *l != 0 && *r == 0));
+}
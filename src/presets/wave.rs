@@ -0,0 +1,187 @@
+//! Classic synthesizer waveforms for wave ([`WaveData`]) voices
+
+use crate::{
+    EnvPt, EnvelopeSrc, Key, OsciPt, Voice, VoiceData, VoiceFlags, VoiceUnit, WaveData,
+    WaveDataPoints,
+};
+
+/// Basic key of a single wave cycle.
+///
+/// Wave voices render one cycle into 400 samples at 44.1 kHz, which is 110.25 Hz, or
+/// roughly A2.
+const WAVE_BASIC_KEY: Key = Key::semitones(45);
+
+/// Amplitude used for the coordinate based waves
+const AMP: i16 = 64;
+/// Horizontal resolution used for the coordinate based waves
+const RESO: u16 = 100;
+/// Default volume of the generated waves
+const VOLUME: i16 = 64;
+/// Default (center) pan of the generated waves
+const PAN: i16 = 64;
+
+/// Pure sine wave
+#[must_use]
+pub fn sine() -> WaveData {
+    overtone(vec![pt(1, 128)])
+}
+
+/// Square wave (50% pulse)
+#[must_use]
+pub fn square() -> WaveData {
+    pulse(50)
+}
+
+/// Pulse wave with a duty cycle of `duty` percent.
+///
+/// The duty cycle is clamped to `1..=99`. Common values are 50 (square), 25 and 12.5
+/// (which is approximated by 12).
+#[must_use]
+pub fn pulse(duty: u8) -> WaveData {
+    let edge = u16::from(duty.clamp(1, 99)) * RESO / 100;
+    coord(vec![
+        pt(0, AMP),
+        pt(edge - 1, AMP),
+        pt(edge, -AMP),
+        pt(RESO - 1, -AMP),
+    ])
+}
+
+/// Triangle wave
+#[must_use]
+pub fn triangle() -> WaveData {
+    coord(vec![pt(0, 0), pt(RESO / 4, AMP), pt(RESO * 3 / 4, -AMP)])
+}
+
+/// Sawtooth wave
+#[must_use]
+pub fn saw() -> WaveData {
+    coord(vec![pt(0, AMP), pt(RESO - 1, -AMP)])
+}
+
+/// Drawbar organ, in the style of tonewheel organs.
+///
+/// Each element of `drawbars` is the level (`0..=8`) of the corresponding drawbar,
+/// in the traditional order: 16', 5⅓', 8', 4', 2⅔', 2', 1⅗', 1⅓', 1'.
+///
+/// Because of the 16' sub-octave drawbar, the resulting wave cycle is an octave lower than
+/// the played note. [`organ_voice`] compensates for this with the basic key.
+#[must_use]
+pub fn organ(drawbars: [u8; 9]) -> WaveData {
+    // Harmonic of each drawbar, relative to the 16' drawbar
+    const HARMONICS: [u16; 9] = [1, 3, 2, 4, 6, 8, 10, 12, 16];
+    let levels = drawbars.map(|level| i32::from(level.min(8)));
+    // Normalize so that pulling out all the drawbars doesn't clip too hard
+    let total: i32 = levels.iter().sum::<i32>().max(8);
+    let points = HARMONICS
+        .iter()
+        .zip(levels)
+        .filter(|&(_, level)| level != 0)
+        .map(|(&x, level)| {
+            // `overtone` divides the amplitude by the harmonic, so we multiply it back
+            let y = 128 * level * i32::from(x) / total;
+            pt(x, y.try_into().unwrap_or(i16::MAX))
+        })
+        .collect();
+    overtone(points)
+}
+
+/// A [`Voice`] playing an [`organ`] with the given drawbar setting
+#[must_use]
+pub fn organ_voice(drawbars: [u8; 9]) -> Voice {
+    // The 16' drawbar makes the wave cycle an octave lower than the note
+    voice("organ", organ(drawbars), WAVE_BASIC_KEY - Key::OCTAVE)
+}
+
+/// A basic set of instruments for getting started composing without any external instrument
+/// files, similar to what the PxTone editor offers out of the box.
+///
+/// Contains, in order: sine, square, pulse 25%, pulse 12%, triangle, saw, organ,
+/// and a plucked saw with a decaying envelope.
+#[must_use]
+pub fn factory_voices() -> Vec<Voice> {
+    let basic_key = WAVE_BASIC_KEY;
+    let mut pluck = saw();
+    pluck.envelope = envelope(&[(5, 128), (300, 48), (1500, 0)], 100);
+    vec![
+        voice("sine", sine(), basic_key),
+        voice("square", square(), basic_key),
+        voice("pulse 25%", pulse(25), basic_key),
+        voice("pulse 12%", pulse(12), basic_key),
+        voice("triangle", triangle(), basic_key),
+        voice("saw", saw(), basic_key),
+        organ_voice([8, 8, 8, 0, 0, 0, 0, 0, 0]),
+        voice("pluck", pluck, basic_key),
+    ]
+}
+
+fn voice(name: &str, mut data: WaveData, basic_key: Key) -> Voice {
+    if data.envelope.points.is_empty() {
+        // Short attack and release to avoid clicks
+        data.envelope = envelope(&[(5, 128)], 30);
+    }
+    let unit = VoiceUnit {
+        basic_key,
+        tuning: 1.0,
+        flags: VoiceFlags::WAVE_LOOP | VoiceFlags::SMOOTH,
+    };
+    let mut voice = Voice::from_unit_and_data(unit, VoiceData::Wave(data));
+    voice.name = name.into();
+    voice
+}
+
+/// Create an envelope with 1000 points per second from `head` points, and a release time
+fn envelope(head: &[(u16, u8)], release_ms: u16) -> EnvelopeSrc {
+    let mut points: Vec<EnvPt> = head.iter().map(|&(x, y)| EnvPt { x, y }).collect();
+    points.push(EnvPt {
+        x: release_ms,
+        y: 0,
+    });
+    EnvelopeSrc {
+        seconds_per_point: 1000,
+        points,
+    }
+}
+
+const fn coord(points: Vec<OsciPt>) -> WaveData {
+    WaveData {
+        points: WaveDataPoints::Coord {
+            points,
+            resolution: RESO,
+        },
+        envelope: EnvelopeSrc {
+            seconds_per_point: 0,
+            points: Vec::new(),
+        },
+        volume: VOLUME,
+        pan: PAN,
+    }
+}
+
+const fn overtone(points: Vec<OsciPt>) -> WaveData {
+    WaveData {
+        points: WaveDataPoints::Overtone { points },
+        envelope: EnvelopeSrc {
+            seconds_per_point: 0,
+            points: Vec::new(),
+        },
+        volume: VOLUME,
+        pan: PAN,
+    }
+}
+
+const fn pt(x: u16, y: i16) -> OsciPt {
+    OsciPt { x, y }
+}
+
+#[test]
+fn test_factory_voices_roundtrip() {
+    let table = crate::NoiseTable::generate();
+    for mut voice in factory_voices() {
+        let bytes = voice.to_ptvoice().unwrap();
+        let reread = Voice::from_ptvoice(&bytes).unwrap();
+        assert_eq!(bytes, reread.to_ptvoice().unwrap());
+        voice.recalculate(&table, crate::NATIVE_SAMPLE_RATE);
+        assert!(voice.base.inst.sample_buf.iter().any(|&b| b != 0));
+    }
+}
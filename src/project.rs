@@ -0,0 +1,428 @@
+//! Everything that makes up a song, in one place
+
+use crate::{
+    DowngradeBlocker, EveList, EventPayload, FmtVer, GroupIdx, Herd, MooInstructions, MooPlan,
+    OutSample, ReadResult, SampleRate, Song, UnitIdx, Units, VoiceIdx, Voices,
+    herd::{
+        rebuild_tones, serialize_project, serialize_project_extended, serialize_project_to,
+        serialize_tune,
+    },
+    moo_prepare, read_song, read_song_from,
+    result::{StreamReadError, StreamWriteError, UnsupportedSampleRate, WriteResult},
+};
+
+/// A [`Song`], its [`Herd`], and its [`MooInstructions`], bundled together.
+///
+/// Almost every operation on a song needs all three of them, so this saves passing them
+/// around separately. The fields are public, so the lower level functions that take the parts
+/// can still be used.
+///
+/// ```no_run
+/// use ptcow::{MooPlan, Project, StartPosPlan};
+///
+/// let data = std::fs::read("song.ptcop").unwrap();
+/// let mut project = Project::read(&data, ptcow::NATIVE_SAMPLE_RATE).unwrap();
+/// for unit in project.units_mut().iter_mut() {
+///     unit.mute = unit.name.contains("drum");
+/// }
+/// project.prepare(&MooPlan {
+///     start_pos: StartPosPlan::Sample(0),
+///     meas_end: None,
+///     meas_repeat: None,
+///     loop_: false,
+/// })
+/// .unwrap();
+/// let mut buf = [0i16; 4096];
+/// while project.moo(&mut buf) {
+///     // Output `buf`...
+/// }
+/// ```
+pub struct Project {
+    /// The song data
+    pub song: Song,
+    /// The units, and the playback state
+    pub herd: Herd,
+    /// The voices, and other data required for playback
+    pub ins: MooInstructions,
+}
+
+impl Project {
+    /// An empty project, to be played at `out_sample_rate`
+    #[must_use]
+    pub fn new(out_sample_rate: SampleRate) -> Self {
+        Self {
+            song: Song::default(),
+            herd: Herd::default(),
+            ins: MooInstructions::new(out_sample_rate),
+        }
+    }
+    /// Read a `.ptcop` or `.pttune` project from `data`, see [`read_song`]
+    pub fn read(data: &[u8], out_sample_rate: SampleRate) -> ReadResult<Self> {
+        read_song(data, out_sample_rate).map(Self::from)
+    }
+    /// Read a `.ptcop` or `.pttune` project from a stream, see [`read_song_from`]
+    pub fn read_from(
+        src: impl std::io::Read,
+        out_sample_rate: SampleRate,
+    ) -> Result<Self, StreamReadError> {
+        read_song_from(src, out_sample_rate).map(Self::from)
+    }
+    /// Split the project into its parts
+    #[must_use]
+    pub fn into_parts(self) -> (Song, Herd, MooInstructions) {
+        (self.song, self.herd, self.ins)
+    }
+    /// Prepare for playback, see [`moo_prepare`]
+    ///
+    /// # Errors
+    ///
+    /// If the output sample rate is not [supported](SampleRate::is_supported).
+    pub fn prepare(&mut self, plan: &MooPlan) -> Result<(), UnsupportedSampleRate> {
+        moo_prepare(&mut self.ins, &mut self.herd, &self.song, plan)
+    }
+    /// Moo the next samples into an interleaved stereo buffer, see [`Herd::moo`]
+    ///
+    /// Returns false if the song has already ended.
+    pub fn moo<T: OutSample>(&mut self, buf: &mut [T]) -> bool {
+        self.herd.moo(&self.ins, &self.song, buf, true)
+    }
+    /// Adjust the voices and effects to the output sample rate, after editing them.
+    ///
+    /// See [`rebuild_tones`].
+    pub fn rebuild_tones(&mut self) {
+        let out_sample_rate = self.ins.out_sample_rate;
+        rebuild_tones(
+            &mut self.ins,
+            out_sample_rate,
+            &mut self.herd.delays,
+            &mut self.herd.overdrives,
+            &self.song.master,
+        );
+    }
+    /// Move a unit to another sample group, both in the song and while playing.
+    ///
+    /// The unit crossfades to `group` like with [`Herd::set_unit_group`], and its
+    /// [`SetGroup`](crate::EventPayload::SetGroup) event at tick 0 is updated
+    /// (see [`EveList::set_initial_group`]), so the change is saved with the project.
+    ///
+    /// Returns false if there is no such unit, or `group` is out of range.
+    /// Nothing is changed then.
+    pub fn set_unit_group(&mut self, unit: UnitIdx, group: GroupIdx) -> bool {
+        if !self.herd.set_unit_group(unit, group) {
+            return false;
+        }
+        self.song.events.set_initial_group(unit, group);
+        true
+    }
+    /// What keeps the project from being written for an older PxTone version,
+    /// see [`Song::can_downgrade_to`]
+    #[must_use]
+    pub fn can_downgrade_to(&self, ver: FmtVer) -> Vec<DowngradeBlocker> {
+        self.song.can_downgrade_to(ver, &self.ins)
+    }
+    /// Serialize the project, see [`serialize_project`]
+    pub fn serialize(&self) -> WriteResult<Vec<u8>> {
+        serialize_project(&self.song, &self.herd, &self.ins)
+    }
+    /// Serialize the project to a stream, see [`serialize_project_to`]
+    pub fn serialize_to(&self, dst: impl std::io::Write) -> Result<(), StreamWriteError> {
+        serialize_project_to(dst, &self.song, &self.herd, &self.ins)
+    }
+    /// Serialize the project with support for more than 50 units,
+    /// see [`serialize_project_extended`]
+    pub fn serialize_extended(&self) -> WriteResult<Vec<u8>> {
+        serialize_project_extended(&self.song, &self.herd, &self.ins)
+    }
+    /// Serialize the project as a tune, see [`serialize_tune`]
+    pub fn serialize_tune(&self) -> WriteResult<Vec<u8>> {
+        serialize_tune(&self.song, &self.herd, &self.ins)
+    }
+    /// The units (cows) of the project
+    #[must_use]
+    pub const fn units(&self) -> &Units {
+        &self.herd.units
+    }
+    /// The units (cows) of the project, mutably
+    pub const fn units_mut(&mut self) -> &mut Units {
+        &mut self.herd.units
+    }
+    /// The voices the units play with
+    #[must_use]
+    pub const fn voices(&self) -> &Voices {
+        &self.ins.voices
+    }
+    /// The voices the units play with, mutably.
+    ///
+    /// Call [`Self::rebuild_tones`] after editing them.
+    pub const fn voices_mut(&mut self) -> &mut Voices {
+        &mut self.ins.voices
+    }
+    /// The events of the song
+    #[must_use]
+    pub const fn events(&self) -> &EveList {
+        &self.song.events
+    }
+    /// The events of the song, mutably
+    pub const fn events_mut(&mut self) -> &mut EveList {
+        &mut self.song.events
+    }
+    /// Find the voices and units the song doesn't use
+    #[must_use]
+    pub fn find_unused(&self) -> UnusedReport {
+        let mut voice_used = vec![false; self.voices().len().into()];
+        // Units start out with the first voice
+        let mut voice_set = vec![false; self.units().len().into()];
+        let mut unit_plays = vec![false; voice_set.len()];
+        for eve in self.events().iter() {
+            let unit = eve.unit.usize();
+            match eve.payload {
+                EventPayload::SetVoice(voice) => {
+                    if let Some(used) = voice_used.get_mut(voice.usize()) {
+                        *used = true;
+                    }
+                    if let Some(set) = voice_set.get_mut(unit) {
+                        *set = true;
+                    }
+                }
+                EventPayload::On { .. } if unit < unit_plays.len() => {
+                    unit_plays[unit] = true;
+                    if !voice_set[unit]
+                        && let Some(used) = voice_used.first_mut()
+                    {
+                        *used = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        UnusedReport {
+            voices: self
+                .voices()
+                .enumerated()
+                .filter(|(idx, _)| !voice_used[idx.usize()])
+                .map(|(idx, _)| idx)
+                .collect(),
+            units: self
+                .units()
+                .enumerated()
+                .filter(|(idx, _)| !unit_plays[idx.usize()])
+                .map(|(idx, _)| idx)
+                .collect(),
+        }
+    }
+    /// Remove the voices and units [`Self::find_unused`] finds, and return what was removed.
+    ///
+    /// The events of the removed units are removed too, and the events (and
+    /// [music states](crate::states::MusicState)) of the other units are changed to their new indices.
+    /// Removing units can leave voices unused that only they referred to, call this again to
+    /// remove those too.
+    ///
+    /// [Prepare](Self::prepare) the project again before playing it.
+    pub fn strip_unused(&mut self) -> UnusedReport {
+        let report = self.find_unused();
+        let units = report.units.iter().map(|unit| unit.0).collect::<Vec<_>>();
+        let voices = report.voices.iter().map(|voice| voice.0).collect::<Vec<_>>();
+        let unit_map = |unit: UnitIdx| shifted_index(&units, unit.0).map(UnitIdx);
+        self.song.events.remap_units(unit_map);
+        self.song
+            .events
+            .remap_voices(|voice| shifted_index(&voices, voice.0).map(VoiceIdx));
+        self.herd.states.remap_units(unit_map);
+        let mut idx = 0;
+        self.herd.units.retain(|_| {
+            idx += 1;
+            !units.contains(&(idx - 1))
+        });
+        self.ins.voices.retain(|voice| !voices.contains(&voice.0));
+        report
+    }
+}
+
+/// The index an item ends up at after the items at the sorted indices `removed` are removed,
+/// or `None` if it's removed itself
+fn shifted_index(removed: &[u8], idx: u8) -> Option<u8> {
+    if removed.contains(&idx) {
+        return None;
+    }
+    // Fits, there are fewer removed indices before `idx` than `idx` itself
+    #[expect(clippy::cast_possible_truncation)]
+    Some(idx - removed.iter().filter(|&&r| r < idx).count() as u8)
+}
+
+/// The voices and units a [`Project`] doesn't use, found by [`Project::find_unused`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnusedReport {
+    /// Voices no unit plays with.
+    ///
+    /// No [`SetVoice`](EventPayload::SetVoice) event refers to them, and for the first voice,
+    /// which units start out with, no unit plays a note before setting its voice.
+    pub voices: Vec<VoiceIdx>,
+    /// Units that don't play any notes ([`On`](EventPayload::On) events)
+    pub units: Vec<UnitIdx>,
+}
+
+impl UnusedReport {
+    /// Whether everything is used
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.voices.is_empty() && self.units.is_empty()
+    }
+}
+
+impl From<(Song, Herd, MooInstructions)> for Project {
+    fn from((song, herd, ins): (Song, Herd, MooInstructions)) -> Self {
+        Self { song, herd, ins }
+    }
+}
+
+#[test]
+fn test_project() {
+    use crate::{
+        Event, EventPayload, NATIVE_SAMPLE_RATE, StartPosPlan, Unit, UnitIdx,
+        presets::wave::factory_voices,
+    };
+    let mut project = Project::new(NATIVE_SAMPLE_RATE);
+    project.units_mut().push(Unit::new());
+    project.voices_mut().extend(factory_voices());
+    project.events_mut().eves.push(Event {
+        payload: EventPayload::On { duration: 960 },
+        unit: UnitIdx(0),
+        tick: 0,
+    });
+    project.song.recalculate_length();
+    let data = project.serialize().unwrap();
+
+    let mut project = Project::read(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(project.units().len(), 1);
+    assert_eq!(project.voices().len(), 8);
+    assert_eq!(project.events().eves.len(), 1);
+    let plan = MooPlan {
+        start_pos: StartPosPlan::Sample(0),
+        meas_end: None,
+        meas_repeat: None,
+        loop_: false,
+    };
+    project.prepare(&plan).unwrap();
+    let mut buf = vec![0i16; 4096];
+    assert!(project.moo(&mut buf));
+    assert!(buf.iter().any(|&s| s != 0));
+
+    // The low level API renders the same
+    let (song, mut herd, mut ins) = project.into_parts();
+    moo_prepare(&mut ins, &mut herd, &song, &plan).unwrap();
+    let mut low = vec![0i16; 4096];
+    herd.moo(&ins, &song, &mut low, true);
+    assert_eq!(buf, low);
+}
+
+#[test]
+fn test_strip_unused() {
+    use crate::{
+        Event, NATIVE_SAMPLE_RATE, Unit, presets::wave::factory_voices, states::MusicState,
+    };
+    let mut project = Project::new(NATIVE_SAMPLE_RATE);
+    project.voices_mut().extend(factory_voices().into_iter().take(4));
+    for _ in 0..4 {
+        project.units_mut().push(Unit::new());
+    }
+    let eve = |unit, payload| Event {
+        payload,
+        unit: UnitIdx(unit),
+        tick: 0,
+    };
+    let on = EventPayload::On { duration: 480 };
+    project.events_mut().eves = vec![
+        eve(0, EventPayload::SetVoice(VoiceIdx(2))),
+        eve(1, EventPayload::SetVoice(VoiceIdx(1))),
+        eve(3, EventPayload::SetVoice(VoiceIdx(3))),
+        eve(0, on),
+        eve(1, EventPayload::Volume(10)),
+        eve(3, on),
+    ];
+    let mut state = MusicState::new("quiet");
+    state.muted = vec![UnitIdx(1), UnitIdx(3)];
+    project.herd.states.add(state);
+    let report = project.find_unused();
+    assert_eq!(
+        report,
+        UnusedReport {
+            voices: vec![VoiceIdx(0)],
+            units: vec![UnitIdx(1), UnitIdx(2)],
+        }
+    );
+    assert_eq!(project.strip_unused(), report);
+    assert_eq!(project.units().len(), 2);
+    assert_eq!(project.voices().len(), 3);
+    let events: Vec<_> = project.events().iter().map(|eve| (eve.unit.0, eve.payload)).collect();
+    assert_eq!(
+        events,
+        [
+            (0, EventPayload::SetVoice(VoiceIdx(1))),
+            (1, EventPayload::SetVoice(VoiceIdx(2))),
+            (0, on),
+            (1, on),
+        ]
+    );
+    assert_eq!(project.herd.states.states()[0].muted, [UnitIdx(1)]);
+    // Voice 1 was only used by a removed unit
+    assert_eq!(project.find_unused().voices, [VoiceIdx(0)]);
+    // A unit playing before setting its voice uses the first voice
+    project.units_mut().push(Unit::new());
+    project.events_mut().eves.push(eve(2, on));
+    assert!(project.find_unused().is_empty());
+}
+
+#[test]
+fn test_set_unit_group() {
+    use crate::{Delay, Event, GroupEffect, NATIVE_SAMPLE_RATE, Overdrive};
+    let mut project = Project::new(NATIVE_SAMPLE_RATE);
+    for name in ["lead", "bass", "drums"] {
+        project.herd.add_unit(name);
+    }
+    project.herd.delays.push(Delay {
+        group: GroupIdx(2),
+        ..Delay::default()
+    });
+    project.herd.overdrives.push(Overdrive {
+        group: GroupIdx(2),
+        ..Overdrive::default()
+    });
+    project.events_mut().eves = vec![
+        Event {
+            payload: EventPayload::SetGroup(GroupIdx(1)),
+            unit: UnitIdx(2),
+            tick: 0,
+        },
+        Event {
+            payload: EventPayload::On { duration: 480 },
+            unit: UnitIdx(0),
+            tick: 0,
+        },
+    ];
+    assert!(project.set_unit_group(UnitIdx(0), GroupIdx(2)));
+    assert!(project.set_unit_group(UnitIdx(2), GroupIdx(2)));
+    assert!(!project.set_unit_group(UnitIdx(3), GroupIdx(2)));
+    assert!(!project.set_unit_group(UnitIdx(1), GroupIdx(7)));
+    let routing = project.herd.group_routing();
+    assert_eq!(routing.units_in_group(GroupIdx(0)), [UnitIdx(1)]);
+    assert_eq!(
+        routing.units_in_group(GroupIdx(2)),
+        [UnitIdx(0), UnitIdx(2)]
+    );
+    assert_eq!(
+        routing.effects_on_group(GroupIdx(2)),
+        [GroupEffect::Overdrive(0), GroupEffect::Delay(0)]
+    );
+    assert!(routing.effects_on_group(GroupIdx(0)).is_empty());
+    assert!(routing.units_in_group(GroupIdx(200)).is_empty());
+    // The groups are saved in the song, before the note
+    let groups: Vec<_> = project.events().eves.iter().map(|eve| (eve.unit, eve.payload)).collect();
+    assert_eq!(
+        groups,
+        [
+            (UnitIdx(2), EventPayload::SetGroup(GroupIdx(2))),
+            (UnitIdx(0), EventPayload::SetGroup(GroupIdx(2))),
+            (UnitIdx(0), EventPayload::On { duration: 480 }),
+        ]
+    );
+}
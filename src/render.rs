@@ -0,0 +1,115 @@
+//! Checkpoints for long offline renders, so they can be stopped and resumed later
+//!
+//! A [`Checkpoint`] holds the playback state of a [`Herd`] as bytes, which can be written to
+//! a file and restored later, or on another machine. Rendering is deterministic, so a render
+//! resumed from a checkpoint produces exactly the same samples as an uninterrupted one.
+//!
+//! ```no_run
+//! use ptcow::{MooPlan, Project, StartPosPlan, render::Checkpoint};
+//!
+//! let data = std::fs::read("song.ptcop").unwrap();
+//! let plan = MooPlan {
+//!     start_pos: StartPosPlan::Sample(0),
+//!     meas_end: None,
+//!     meas_repeat: None,
+//!     loop_: true,
+//! };
+//! let mut project = Project::read(&data, ptcow::NATIVE_SAMPLE_RATE).unwrap();
+//! project.prepare(&plan).unwrap();
+//! if let Ok(bytes) = std::fs::read("render.chk") {
+//!     Checkpoint::from_bytes(bytes).unwrap().restore(&mut project.herd).unwrap();
+//! }
+//! let mut buf = vec![0i16; 44_100 * 2 * 60];
+//! ptcow::render::moo_checkpointed(
+//!     &mut project.herd,
+//!     &project.ins,
+//!     &project.song,
+//!     &mut buf,
+//!     44_100 * 10,
+//!     |checkpoint| std::fs::write("render.chk", checkpoint.as_bytes()).unwrap(),
+//! );
+//! ```
+
+use crate::{
+    CheckpointError, Herd, MooInstructions, OutSample, SampleT, Song, herd::checkpoint_sample,
+};
+
+/// Snapshot of the playback state of a [`Herd`]
+///
+/// This includes the playback position, the state of every unit, and the delay buffers.
+/// The song, the voices and the [`MooInstructions`] aren't included, so the herd a checkpoint
+/// is restored into has to be prepared from the same song with the same settings, like
+/// it was for the render the checkpoint was taken from.
+///
+/// Pending [music state](crate::states) transitions and fades aren't included either,
+/// so checkpoints are meant for renders with [`Herd::moo`].
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    bytes: Vec<u8>,
+}
+
+impl Checkpoint {
+    /// Take a snapshot of the playback state of `herd`
+    #[must_use]
+    pub fn capture(herd: &Herd) -> Self {
+        let mut bytes = Vec::new();
+        herd.write_checkpoint(&mut bytes);
+        Self { bytes }
+    }
+    /// Restore the playback state of `herd` from this checkpoint.
+    ///
+    /// `herd` should be prepared with [`moo_prepare`](crate::moo_prepare) first.
+    /// The [unit metering](Herd::unit_levels) starts over.
+    ///
+    /// Nothing is changed if an error is returned.
+    pub fn restore(&self, herd: &mut Herd) -> Result<(), CheckpointError> {
+        herd.read_checkpoint(&self.bytes)
+    }
+    /// The checkpoint as bytes, for saving it
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    /// Load a checkpoint saved with [`Self::as_bytes`].
+    ///
+    /// Only the header is checked here, the rest is checked by [`Self::restore`].
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, CheckpointError> {
+        checkpoint_sample(&bytes)?;
+        Ok(Self { bytes })
+    }
+    /// The sample the playback was at when the checkpoint was taken (see [`Herd::smp_count`])
+    #[must_use]
+    pub fn sample(&self) -> SampleT {
+        // Checked by `from_bytes`, and `capture` always writes a valid header
+        checkpoint_sample(&self.bytes).unwrap_or(0)
+    }
+}
+
+/// Moo into `buf` like [`Herd::moo`], taking a [`Checkpoint`] every `every` frames.
+///
+/// `on_checkpoint` is called with each checkpoint, which can be saved and used to resume the
+/// render from there. A checkpoint is also taken at the end of `buf`, or when the song ends.
+/// With an `every` of 0, that's the only checkpoint.
+///
+/// Returns false if the song already ended before mooing.
+pub fn moo_checkpointed<T: OutSample>(
+    herd: &mut Herd,
+    ins: &MooInstructions,
+    song: &Song,
+    buf: &mut [T],
+    every: usize,
+    mut on_checkpoint: impl FnMut(&Checkpoint),
+) -> bool {
+    if herd.moo_end {
+        return false;
+    }
+    let chunk_len = if every == 0 { buf.len() } else { every * 2 };
+    for chunk in buf.chunks_mut(chunk_len.max(2)) {
+        herd.moo(ins, song, chunk, true);
+        on_checkpoint(&Checkpoint::capture(herd));
+        if herd.moo_end {
+            break;
+        }
+    }
+    true
+}
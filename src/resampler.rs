@@ -0,0 +1,188 @@
+//! Sample rate conversion of PCM and Ogg/Vorbis voices
+
+use crate::{SampleRate, SourceSampleRate};
+
+/// Converts 16 bit stereo frames from one sample rate to another.
+///
+/// Used when PCM and Ogg/Vorbis voices are prepared for playback
+/// (see [`MooInstructions::resampler`](crate::MooInstructions::resampler)),
+/// and by [`PcmData::to_converted_with`](crate::PcmData::to_converted_with).
+///
+/// The default is [`NearestResampler`], which matches `PxTone`.
+pub trait Resampler: Send + Sync {
+    /// Resample `input` from `from` to `to` into `output`.
+    ///
+    /// `output` is zeroed, and has room for at least `input.len() * to / from` frames (rounded up),
+    /// followed by a few frames of padding.
+    /// Frames that aren't written to stay silent.
+    fn resample(
+        &self,
+        input: &[[i16; 2]],
+        from: SourceSampleRate,
+        to: SampleRate,
+        output: &mut [[i16; 2]],
+    );
+}
+
+/// Picks the input frame at the position rounded down, like `PxTone` does.
+///
+/// Cheap, but aliases.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NearestResampler;
+
+impl Resampler for NearestResampler {
+    fn resample(
+        &self,
+        input: &[[i16; 2]],
+        from: SourceSampleRate,
+        to: SampleRate,
+        output: &mut [[i16; 2]],
+    ) {
+        for (i, out) in output.iter_mut().enumerate() {
+            let idx = i * from.get() as usize / usize::from(to);
+            if let Some(frame) = input.get(idx) {
+                *out = *frame;
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+/// Band-limited resampling with [rubato](https://docs.rs/rubato)'s FFT resampler.
+///
+/// Sounds cleaner than [`NearestResampler`], especially for voices sampled at a rate far from
+/// the output rate, but doesn't match `PxTone`, and is slower to prepare.
+///
+/// Requires the `rubato` feature.
+#[cfg(feature = "rubato")]
+#[derive(Clone, Copy, Debug)]
+pub struct RubatoResampler {
+    /// Number of input frames processed at a time
+    pub chunk_size: usize,
+}
+
+#[cfg(feature = "rubato")]
+impl Default for RubatoResampler {
+    fn default() -> Self {
+        Self { chunk_size: 1024 }
+    }
+}
+
+#[cfg(feature = "rubato")]
+impl Resampler for RubatoResampler {
+    fn resample(
+        &self,
+        input: &[[i16; 2]],
+        from: SourceSampleRate,
+        to: SampleRate,
+        output: &mut [[i16; 2]],
+    ) {
+        use rubato::Resampler as _;
+
+        let Ok(mut rs) = rubato::FftFixedIn::<f32>::new(
+            from.get() as usize,
+            usize::from(to),
+            self.chunk_size.max(1),
+            2,
+            2,
+        ) else {
+            NearestResampler.resample(input, from, to, output);
+            return;
+        };
+        let planes: [Vec<f32>; 2] =
+            [0, 1].map(|ch| input.iter().map(|f| f32::from(f[ch]) / 32768.0).collect());
+        let out_len = (input.len() * usize::from(to)).div_ceil(from.get() as usize);
+        let delay = rs.output_delay();
+        let mut resampled: [Vec<f32>; 2] = Default::default();
+        let mut pos = 0;
+        loop {
+            let need = rs.input_frames_next();
+            let chunk = if pos + need <= input.len() {
+                rs.process(
+                    &[&planes[0][pos..pos + need], &planes[1][pos..pos + need]],
+                    None,
+                )
+            } else if pos < input.len() {
+                rs.process_partial(Some(&[&planes[0][pos..], &planes[1][pos..]]), None)
+            } else {
+                rs.process_partial::<&[f32]>(None, None)
+            };
+            let Ok(chunk) = chunk else {
+                NearestResampler.resample(input, from, to, output);
+                return;
+            };
+            for (dst, src) in resampled.iter_mut().zip(chunk) {
+                dst.extend(src);
+            }
+            pos += need;
+            if resampled[0].len() >= delay + out_len {
+                break;
+            }
+        }
+        let [left, right] = resampled;
+        let frames = left[delay..].iter().zip(&right[delay..]).take(out_len);
+        for (out, (&l, &r)) in output.iter_mut().zip(frames) {
+            #[expect(clippy::cast_possible_truncation)]
+            let to_i16 = |s: f32| (s * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+            *out = [to_i16(l), to_i16(r)];
+        }
+    }
+}
+
+#[test]
+fn test_nearest_resampler() {
+    let input: Vec<[i16; 2]> = (0..4).map(|i| [i, -i]).collect();
+    let mut output = [[0; 2]; 10];
+    NearestResampler.resample(
+        &input,
+        SourceSampleRate(22_050),
+        SampleRate(44_100),
+        &mut output,
+    );
+    assert_eq!(
+        output,
+        [
+            [0, 0],
+            [0, 0],
+            [1, -1],
+            [1, -1],
+            [2, -2],
+            [2, -2],
+            [3, -3],
+            [3, -3],
+            [0, 0],
+            [0, 0]
+        ]
+    );
+}
+
+#[cfg(feature = "rubato")]
+#[test]
+fn test_rubato_resampler() {
+    #[expect(clippy::cast_possible_truncation)]
+    let input: Vec<[i16; 2]> = (0..22_050)
+        .map(|i| {
+            let s = (f64::from(i) * 440.0 / 22_050.0 * std::f64::consts::TAU).sin() * 16_000.0;
+            [s as i16, (-s) as i16]
+        })
+        .collect();
+    let mut output = vec![[0; 2]; 44_104];
+    RubatoResampler::default().resample(
+        &input,
+        SourceSampleRate(22_050),
+        SampleRate(44_100),
+        &mut output,
+    );
+    // Same signal, twice the frames
+    for (i, frame) in output.iter().enumerate().take(44_000).skip(100) {
+        #[expect(clippy::cast_precision_loss)]
+        let expected = ((i as f64) * 440.0 / 44_100.0 * std::f64::consts::TAU).sin() * 16_000.0;
+        assert!(
+            (f64::from(frame[0]) - expected).abs() < 200.0,
+            "{i}: {frame:?}"
+        );
+        assert_eq!(frame[0], -frame[1]);
+    }
+    assert_eq!(output[44_102], [0, 0]);
+}
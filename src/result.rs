@@ -1,4 +1,7 @@
-use thiserror::Error;
+use {
+    crate::{GroupIdx, Overdrive, SampleRate, SourceSampleRate, VoiceIdx},
+    thiserror::Error,
+};
 
 /// Error that can happen when reading a PxTone project
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
@@ -28,14 +31,22 @@ pub enum ProjectReadError {
     /// Invalid/unsupported tag data
     #[error("Invalid/unsupported tag data")]
     InvalidData,
+    /// The timing of the song is unusable (zero ticks per beat or beats per meas,
+    /// or a bpm that's not a positive number)
+    #[error("Invalid timing (ticks per beat, beats per meas, or bpm)")]
+    InvalidTiming,
     /// Error reading Ogg/vorbis data
     #[error("Ogg/vorbis read error")]
     OggvReadError,
-    /// ptcow was built with Ogg/vorbis support disabled
+    /// ptcow was built with Ogg/vorbis support disabled.
+    ///
+    /// No longer returned, Ogg/Vorbis voices are read without Ogg/Vorbis support,
+    /// but they stay silent.
     #[error("Ogg/vorbis support disabled")]
     OggvSupportDisabled,
-    /// V4 (and earlier?) relies on the event list being a linked list, which would require
-    /// a lot of figuring out how to make it work with our implementation using `Vec`.
+    /// The project uses an old PxTone feature ptcow can't read.
+    ///
+    /// Old (V4 and earlier) chunks are read now, so this is no longer returned.
     #[error("Unsupported old PxTone version")]
     OldUnsupported,
     /// We internally store overtone points as 16 bit integers, but they are encoded
@@ -43,6 +54,122 @@ pub enum ProjectReadError {
     /// but an invalid song could contain such a point.
     #[error("Overtone point out of range: {0} (should be between in i32/i16 range for x/y)")]
     OvertonePointOutOfRange(u32),
+    /// Playback can't be prepared at the output sample rate, see [`UnsupportedSampleRate`]
+    #[error("{0}")]
+    UnsupportedSampleRate(UnsupportedSampleRate),
+}
+
+/// The output sample rate is lower than [`SampleRate::MIN_SUPPORTED`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error(
+    "Unsupported output sample rate: {} Hz (the lowest supported is {} Hz)",
+    .0.get(),
+    SampleRate::MIN_SUPPORTED.get()
+)]
+pub struct UnsupportedSampleRate(pub SampleRate);
+
+/// The sample rate is higher than [`SampleRate::MAX_SUPPORTED`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error(
+    "Sample rate too high: {} Hz (the highest supported is {} Hz)",
+    .0.get(),
+    SampleRate::MAX_SUPPORTED.get()
+)]
+pub struct SampleRateTooHigh(pub SourceSampleRate);
+
+impl From<UnsupportedSampleRate> for ProjectReadError {
+    fn from(err: UnsupportedSampleRate) -> Self {
+        Self::UnsupportedSampleRate(err)
+    }
+}
+
+/// Error that can happen in [`render_looped`](crate::render_looped)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum RenderError {
+    /// Playback can't be prepared at the output sample rate, see [`UnsupportedSampleRate`]
+    #[error("{0}")]
+    UnsupportedSampleRate(UnsupportedSampleRate),
+    /// The rendered samples wouldn't fit in memory
+    #[error("Render too long to fit in memory")]
+    TooLong,
+}
+
+impl From<UnsupportedSampleRate> for RenderError {
+    fn from(err: UnsupportedSampleRate) -> Self {
+        Self::UnsupportedSampleRate(err)
+    }
+}
+
+/// Something unusual about a project that was read successfully.
+///
+/// See [`read_song_with_warnings`](crate::read_song_with_warnings).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum ReadWarning {
+    /// The project doesn't have any units, so it plays silence.
+    ///
+    /// Units can be added with [`Herd::add_unit`](crate::Herd::add_unit).
+    #[error("The project doesn't have any units")]
+    NoUnits,
+    /// The project doesn't have any voices, so its units play silence.
+    ///
+    /// Voices can be added with [`MooInstructions::add_voice`](crate::MooInstructions::add_voice).
+    #[error("The project doesn't have any voices")]
+    NoVoices,
+    /// This many events belong to units that don't exist. They are ignored during playback.
+    #[error("{0} events belong to units that don't exist")]
+    MissingUnits(usize),
+    /// This many [`SetVoice`](crate::EventPayload::SetVoice) events refer to voices that
+    /// don't exist. The units fall back to the first voice.
+    #[error("{0} events refer to voices that don't exist")]
+    MissingVoices(usize),
+    /// A voice couldn't be read, and was replaced with a silent voice.
+    ///
+    /// Only reported by [`read_song_lenient`](crate::read_song_lenient).
+    #[error("Voice {} couldn't be read, it was replaced with silence: {error}", voice.0)]
+    VoiceReplaced {
+        /// The index of the replaced voice
+        voice: VoiceIdx,
+        /// Why the voice couldn't be read
+        error: ProjectReadError,
+    },
+    /// A delay effect couldn't be read, and was left out.
+    ///
+    /// Only reported by [`read_song_lenient`](crate::read_song_lenient).
+    #[error("A delay couldn't be read, it was left out: {0}")]
+    DelaySkipped(ProjectReadError),
+    /// An overdrive effect couldn't be read, and was left out.
+    ///
+    /// Only reported by [`read_song_lenient`](crate::read_song_lenient).
+    #[error("An overdrive couldn't be read, it was left out: {0}")]
+    OverdriveSkipped(ProjectReadError),
+    /// The tempo, ticks per beat or beats per measure of the song were invalid (like 0), and
+    /// were replaced by their defaults (see [`Timing::sanitized`](crate::Timing::sanitized)).
+    ///
+    /// Only reported by [`read_song_lenient`](crate::read_song_lenient).
+    #[error("The timing of the song was invalid, it was replaced by the defaults")]
+    TimingClamped,
+    /// The project was saved by a program version ptcow doesn't know
+    /// (see [`ExeVer`](crate::ExeVer)).
+    ///
+    /// Old and unofficial editors wrote some chunks differently, so if the project doesn't
+    /// sound right, this may be why.
+    #[error("Saved by an unknown program version ({0})")]
+    UnknownExeVer(u16),
+}
+
+/// Error that can happen when reading a PxTone project from a stream
+///
+/// See [`read_song_from`](crate::read_song_from).
+#[derive(Debug, Error)]
+pub enum StreamReadError {
+    /// The stream couldn't be read
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The project couldn't be read.
+    ///
+    /// In [`ProjectReadError::Data`], the length is how much of the stream was read.
+    #[error(transparent)]
+    Project(#[from] ProjectReadError),
 }
 
 /// Error that can happen when saving a PxTone project
@@ -55,6 +182,118 @@ pub enum ProjectWriteError {
     /// format only supports 8 bit points for coord waves.
     #[error("Coord wave point out of range (needs to be between 0 and 255")]
     CoordWavePointOutOfRange,
+    /// The herd has more units than the format supports (see
+    /// [`Units::MAX_STANDARD`](crate::Units::MAX_STANDARD)).
+    ///
+    /// [`serialize_project_extended`](crate::serialize_project_extended) can save them.
+    #[error("Too many units: {0} (the format supports 50)")]
+    TooManyUnits(u8),
+}
+
+/// Error that can happen when saving a PxTone project to a stream
+///
+/// See [`serialize_project_to`](crate::serialize_project_to).
+#[derive(Debug, Error)]
+pub enum StreamWriteError {
+    /// The stream couldn't be written
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The project couldn't be saved
+    #[error(transparent)]
+    Project(#[from] ProjectWriteError),
+}
+
+/// Invalid [`OverdriveSettings`](crate::OverdriveSettings)
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
+pub enum OverdriveError {
+    /// There is no overdrive at this index
+    #[error("No overdrive at index {0}")]
+    NoSuchOverdrive(usize),
+    /// The sample group doesn't exist
+    #[error("Group {} out of range (the last group is {max})", .0.0, max = GroupIdx::MAX.0)]
+    GroupOutOfRange(GroupIdx),
+    /// The cut is outside of [`Overdrive::CUT_VALID_RANGE`]
+    #[error(
+        "Cut out of range: {0}% (should be between {min}% and {max}%)",
+        min = Overdrive::CUT_VALID_RANGE.start(),
+        max = Overdrive::CUT_VALID_RANGE.end()
+    )]
+    CutOutOfRange(f32),
+    /// The amplification is outside of [`Overdrive::AMP_VALID_RANGE`]
+    #[error(
+        "Amplification out of range: {0} (should be between {min} and {max})",
+        min = Overdrive::AMP_VALID_RANGE.start(),
+        max = Overdrive::AMP_VALID_RANGE.end()
+    )]
+    AmpOutOfRange(f32),
+}
+
+/// Invalid [`EnvelopeSrc`](crate::EnvelopeSrc), see
+/// [`EnvelopeSrc::validate`](crate::EnvelopeSrc::validate)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum EnvelopeError {
+    /// The envelope has points, but [`seconds_per_point`](crate::EnvelopeSrc::seconds_per_point)
+    /// is 0
+    #[error("Seconds per point is 0")]
+    ZeroSecondsPerPoint,
+    /// The head of the envelope would be this many samples long at the highest sample rate,
+    /// more than playback allows
+    #[error("Envelope too long: {0} samples (at most 1048576 are allowed)")]
+    TooLong(usize),
+}
+
+/// Error that can happen when restoring a [`Checkpoint`](crate::render::Checkpoint)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum CheckpointError {
+    /// The data doesn't start with the checkpoint header
+    #[error("Not a checkpoint")]
+    NotACheckpoint,
+    /// The checkpoint was written by a different version of the format
+    #[error("Unsupported checkpoint version: {0}")]
+    UnsupportedVersion(u16),
+    /// Low level data read error (premature EOF)
+    #[error("Error reading `{what}` at position {cursor} (checkpoint length: {len})")]
+    Data {
+        /// What kind of data failed to read
+        what: &'static str,
+        /// Last cursor position
+        cursor: usize,
+        /// Checkpoint length
+        len: usize,
+    },
+    /// A value in the checkpoint is out of range
+    #[error("Invalid checkpoint data")]
+    InvalidData,
+    /// The checkpoint was taken from a herd with a different number of units or delays,
+    /// or different delay buffer lengths
+    #[error("The checkpoint doesn't match the units and effects of the herd")]
+    LayoutMismatch,
+}
+
+/// Error that can happen when importing audio files as [`PcmData`](crate::PcmData)
+#[cfg(any(feature = "symphonia", feature = "hound"))]
+#[derive(Debug, Error)]
+pub enum PcmImportError {
+    /// The file couldn't be read or decoded by symphonia
+    #[cfg(feature = "symphonia")]
+    #[error("Decoding error: {0}")]
+    Decode(#[from] symphonia::core::errors::Error),
+    /// The WAV file couldn't be read or written by hound
+    #[cfg(feature = "hound")]
+    #[error("WAV error: {0}")]
+    Wav(#[from] hound::Error),
+    /// The file doesn't contain an audio track that can be decoded
+    #[error("No decodable audio track")]
+    NoTrack,
+    /// The sample rate or the number of channels of the audio isn't known
+    #[error("Unknown sample rate or number of channels")]
+    UnknownFormat,
+    /// PCM voices can only be mono or stereo
+    #[error("Unsupported number of channels: {0} (only mono and stereo are supported)")]
+    UnsupportedChannels(usize),
+    /// The audio has more samples than a PCM voice can hold
+    #[error("Too many samples")]
+    TooLong,
 }
 
 /// Result of attempting to read a PxTone project
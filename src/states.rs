@@ -0,0 +1,222 @@
+//! Music states for interactive (game) music
+//!
+//! A [`MusicState`] is a named mix of the song: which units are muted, the gain of each group,
+//! and the tempo. The states are registered in [`Herd::states`], and switched between with
+//! [`Herd::trigger_state`], while playing with [`Herd::moo_states`].
+//!
+//! A [`Transition`] can wait for the start of the next measure, so the switch stays on the
+//! beat, and crossfade the group gains.
+//!
+//! # Example
+//!
+//! ```
+//! use ptcow::{Herd, states::{MusicState, Transition}};
+//!
+//! let mut herd = Herd::default();
+//! let drums = herd.add_unit("drums").unwrap();
+//! let mut calm = MusicState::new("calm");
+//! calm.muted.push(drums);
+//! let mut combat = MusicState::new("combat");
+//! combat.tempo = 1.25;
+//! herd.states.add(calm);
+//! herd.states.add(combat);
+//! let transition = Transition {
+//!     on_meas: true,
+//!     fade: std::time::Duration::from_secs(2),
+//! };
+//! assert!(herd.trigger_state("combat", transition));
+//! ```
+//!
+//! [`Herd::states`]: crate::Herd::states
+//! [`Herd::trigger_state`]: crate::Herd::trigger_state
+//! [`Herd::moo_states`]: crate::Herd::moo_states
+
+use {
+    crate::{GroupSamples, UnitIdx, util::ArrayLenExt as _},
+    std::time::Duration,
+};
+
+/// A named mix of the song
+#[derive(Debug)]
+pub struct MusicState {
+    /// The name the state is triggered with
+    pub name: String,
+    /// Units that are muted in this state. All other units are unmuted.
+    pub muted: Vec<UnitIdx>,
+    /// The gain of each group (see [`Herd::set_group_gain`](crate::Herd::set_group_gain))
+    pub group_gains: [f32; GroupSamples::LEN],
+    /// Tempo multiplier. 1.0 plays at the tempo of the song.
+    pub tempo: f32,
+}
+
+impl MusicState {
+    /// A state that plays the song as is
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            muted: Vec::new(),
+            group_gains: [1.0; _],
+            tempo: 1.0,
+        }
+    }
+}
+
+impl Clone for MusicState {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            muted: self.muted.clone(),
+            ..*self
+        }
+    }
+    /// Reuses the allocations of the name and the muted units
+    fn clone_from(&mut self, source: &Self) {
+        let mut name = std::mem::take(&mut self.name);
+        let mut muted = std::mem::take(&mut self.muted);
+        name.clone_from(&source.name);
+        muted.clone_from(&source.muted);
+        *self = Self {
+            name,
+            muted,
+            ..*source
+        };
+    }
+}
+
+/// How to switch to a [`MusicState`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Transition {
+    /// Wait for the start of the next measure before switching.
+    ///
+    /// If playback is at the start of a measure, the switch happens right away.
+    pub on_meas: bool,
+    /// How long the group gains take to crossfade from the old state to the new one.
+    ///
+    /// Units that are unmuted by the new state start playing right away, units that are
+    /// muted by it keep playing until the end of the fade.
+    /// To fade units in or out, give them their own group, and change the gain of the group.
+    pub fade: Duration,
+}
+
+/// The [`MusicState`]s of a [`Herd`](crate::Herd), and the progress of switching between them
+#[derive(Default)]
+pub struct MusicStates {
+    states: Vec<MusicState>,
+    current: Option<usize>,
+    pub(crate) pending: Option<(usize, Transition)>,
+    pub(crate) fade: Option<Fade>,
+}
+
+/// A crossfade of group gains in progress
+pub(crate) struct Fade {
+    pub from: [f32; GroupSamples::LEN],
+    pub to: [f32; GroupSamples::LEN],
+    /// Frames played, and total length of the fade
+    pub pos: u32,
+    pub len: u32,
+    /// Units to mute when the fade ends
+    pub mute_at_end: Vec<UnitIdx>,
+}
+
+impl Clone for MusicStates {
+    fn clone(&self) -> Self {
+        Self {
+            states: self.states.clone(),
+            fade: self.fade.clone(),
+            ..*self
+        }
+    }
+    /// Reuses the allocations of the states and the fade
+    fn clone_from(&mut self, source: &Self) {
+        let mut states = std::mem::take(&mut self.states);
+        let mut fade = self.fade.take();
+        states.clone_from(&source.states);
+        fade.clone_from(&source.fade);
+        *self = Self {
+            states,
+            fade,
+            ..*source
+        };
+    }
+}
+
+impl Clone for Fade {
+    fn clone(&self) -> Self {
+        Self {
+            mute_at_end: self.mute_at_end.clone(),
+            ..*self
+        }
+    }
+    /// Reuses the allocation of the units to mute
+    fn clone_from(&mut self, source: &Self) {
+        let mut mute_at_end = std::mem::take(&mut self.mute_at_end);
+        mute_at_end.clone_from(&source.mute_at_end);
+        *self = Self {
+            mute_at_end,
+            ..*source
+        };
+    }
+}
+
+impl Fade {
+    /// The gains at the current position
+    pub fn gains(&self) -> [f32; GroupSamples::LEN] {
+        #[expect(clippy::cast_precision_loss)]
+        let t = self.pos as f32 / self.len as f32;
+        std::array::from_fn(|i| (self.to[i] - self.from[i]).mul_add(t, self.from[i]))
+    }
+    pub const fn is_done(&self) -> bool {
+        self.pos >= self.len
+    }
+}
+
+impl MusicStates {
+    /// Add a state. A state with the same name is replaced.
+    pub fn add(&mut self, state: MusicState) {
+        match self.states.iter_mut().find(|old| old.name == state.name) {
+            Some(old) => *old = state,
+            None => self.states.push(state),
+        }
+    }
+    /// The registered states
+    #[must_use]
+    pub fn states(&self) -> &[MusicState] {
+        &self.states
+    }
+    /// The state that was switched to last, if any
+    #[must_use]
+    pub fn current(&self) -> Option<&MusicState> {
+        self.current.map(|idx| &self.states[idx])
+    }
+    /// Whether a transition is waiting for its measure, or fading
+    #[must_use]
+    pub const fn is_transitioning(&self) -> bool {
+        self.pending.is_some() || self.fade.is_some()
+    }
+    /// The tempo multiplier of the current state
+    pub(crate) fn tempo(&self) -> f32 {
+        self.current().map_or(1.0, |state| state.tempo)
+    }
+    pub(crate) fn trigger(&mut self, name: &str, transition: Transition) -> bool {
+        let Some(idx) = self.states.iter().position(|state| state.name == name) else {
+            return false;
+        };
+        self.pending = Some((idx, transition));
+        true
+    }
+    /// Change the units the states mute to what `map` returns, dropping the ones it returns
+    /// `None` for
+    pub(crate) fn remap_units(&mut self, mut map: impl FnMut(UnitIdx) -> Option<UnitIdx>) {
+        let fade_units = self.fade.iter_mut().map(|fade| &mut fade.mute_at_end);
+        for units in self.states.iter_mut().map(|state| &mut state.muted).chain(fade_units) {
+            *units = units.iter().filter_map(|&unit| map(unit)).collect();
+        }
+    }
+    /// Make the pending state current, and return it
+    pub(crate) fn take_pending(&mut self) -> Option<(MusicState, Transition)> {
+        let (idx, transition) = self.pending.take()?;
+        self.current = Some(idx);
+        Some((self.states[idx].clone(), transition))
+    }
+}
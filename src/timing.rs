@@ -1,5 +1,11 @@
 //! Timing related definitions and utilities
-use {crate::SampleRate, std::num::NonZeroU32};
+use {
+    crate::{
+        SampleRate, Song,
+        result::{ProjectReadError, ReadResult, ReadWarning},
+    },
+    std::num::NonZeroU32,
+};
 
 /// Clock ticks.
 ///
@@ -16,7 +22,9 @@ pub type Meas = u32;
 pub type NonZeroMeas = NonZeroU32;
 
 /// The smallest unit of time we deal with; an audio sample.
-pub type SampleT = u32;
+///
+/// 64 bits wide, so the playback position doesn't wrap around during endless playback.
+pub type SampleT = u64;
 
 /// How many samples constitute a tick.
 pub type SamplesPerTick = f32;
@@ -46,6 +54,58 @@ pub struct Timing {
 /// Beats per [`Meas`]
 pub type BpMea = u8;
 
+impl Timing {
+    /// Whether all the values are usable for playback.
+    ///
+    /// Ticks per beat and beats per meas must be non-zero, and bpm must be a positive,
+    /// finite number.
+    #[must_use]
+    pub const fn is_valid(self) -> bool {
+        self.ticks_per_beat != 0 && self.beats_per_meas != 0 && self.bpm_valid()
+    }
+    const fn bpm_valid(self) -> bool {
+        self.bpm.is_finite() && self.bpm > 0.0
+    }
+    /// Returns a copy with the invalid values replaced by their defaults.
+    ///
+    /// See [`Self::is_valid`].
+    #[must_use]
+    pub fn sanitized(self) -> Self {
+        let default = Self::default();
+        Self {
+            ticks_per_beat: if self.ticks_per_beat == 0 {
+                default.ticks_per_beat
+            } else {
+                self.ticks_per_beat
+            },
+            bpm: if self.bpm_valid() {
+                self.bpm
+            } else {
+                default.bpm
+            },
+            beats_per_meas: if self.beats_per_meas == 0 {
+                default.beats_per_meas
+            } else {
+                self.beats_per_meas
+            },
+        }
+    }
+    /// Check timing read from a project.
+    ///
+    /// Invalid timing is an error, or when reading leniently (`issues` is `Some`), it's
+    /// [sanitized](Self::sanitized), and a [`ReadWarning::TimingClamped`] is recorded.
+    pub(crate) fn checked_read(self, issues: Option<&mut Vec<ReadWarning>>) -> ReadResult<Self> {
+        if self.is_valid() {
+            return Ok(self);
+        }
+        let Some(issues) = issues else {
+            return Err(ProjectReadError::InvalidTiming);
+        };
+        issues.push(ReadWarning::TimingClamped);
+        Ok(self.sanitized())
+    }
+}
+
 impl Default for Timing {
     fn default() -> Self {
         Self {
@@ -57,8 +117,13 @@ impl Default for Timing {
 }
 
 /// Converts [`Tick`]s to [`Meas`]
+///
+/// Returns 0 for [invalid](Timing::is_valid) timing.
 #[must_use]
 pub fn tick_to_meas(tick: Tick, timing: Timing) -> Meas {
+    if timing.ticks_per_beat == 0 || timing.beats_per_meas == 0 {
+        return 0;
+    }
     tick.div_ceil(u32::from(timing.ticks_per_beat))
         .div_ceil(u32::from(timing.beats_per_meas))
 }
@@ -70,8 +135,13 @@ pub fn meas_to_tick(meas: Meas, timing: Timing) -> Tick {
 }
 
 /// Calculates how many samples make up a tick.
+///
+/// Returns 0.0 for [invalid](Timing::is_valid) timing, instead of dividing by zero.
 #[must_use]
 pub fn samples_per_tick(out_sample_rate: SampleRate, timing: Timing) -> SamplesPerTick {
+    if timing.ticks_per_beat == 0 || !timing.bpm_valid() {
+        return 0.0;
+    }
     60.0 * f32::from(out_sample_rate) / (timing.bpm * f32::from(timing.ticks_per_beat))
 }
 
@@ -97,3 +167,54 @@ pub fn meas_to_sample(meas: Meas, samples_per_tick: SamplesPerTick, timing: Timi
         * f64::from(timing.ticks_per_beat)
         * f64::from(samples_per_tick)) as SampleT)
 }
+
+/// Calculates how many samples it takes to play `song` with `loops` loops.
+///
+/// The song plays from the start up to the end, then jumps back to the repeat point
+/// (see [`LoopPoints`](crate::LoopPoints)) every time it reaches the end again.
+/// The result is the intro (start to repeat), plus `loops` times the repeating part
+/// (repeat to end), so 1 plays the song once through, and 2 plays the repeating part twice.
+///
+/// This uses the same math as [`moo_prepare`](crate::moo_prepare), so after mooing this many
+/// samples (starting from sample 0), the playback is exactly at the end of the song.
+///
+/// Saturates at [`SampleT::MAX`].
+#[must_use]
+pub fn play_length(song: &Song, loops: u32, sps: SampleRate) -> SampleT {
+    let timing = song.master.timing;
+    let samples_per_tick = samples_per_tick(sps, timing);
+    let end = meas_to_sample(song.master.end_meas(), samples_per_tick, timing);
+    let repeat = meas_to_sample(song.master.loop_points.repeat, samples_per_tick, timing).min(end);
+    (end - repeat).saturating_mul(loops.into()).saturating_add(repeat)
+}
+
+#[test]
+fn test_degenerate_timing() {
+    use crate::NATIVE_SAMPLE_RATE;
+    let timing = Timing {
+        ticks_per_beat: 0,
+        bpm: f32::NAN,
+        beats_per_meas: 0,
+    };
+    assert!(!timing.is_valid());
+    assert_eq!(tick_to_meas(1000, timing), 0);
+    assert!(samples_per_tick(NATIVE_SAMPLE_RATE, timing) == 0.0);
+    assert_eq!(
+        meas_to_sample(10, samples_per_tick(NATIVE_SAMPLE_RATE, timing), timing),
+        0
+    );
+    let sanitized = timing.sanitized();
+    assert!(sanitized.is_valid());
+    assert_eq!(sanitized.ticks_per_beat, DEFAULT_TICKS_PER_BEAT);
+    assert_eq!(
+        timing.checked_read(None).err(),
+        Some(ProjectReadError::InvalidTiming)
+    );
+    let mut issues = Vec::new();
+    let clamped = timing.checked_read(Some(&mut issues)).unwrap();
+    assert!(clamped.is_valid());
+    assert_eq!(issues, [ReadWarning::TimingClamped]);
+    let valid = Timing::default().checked_read(Some(&mut issues)).unwrap();
+    assert!(valid.is_valid());
+    assert_eq!(issues.len(), 1);
+}
@@ -1,16 +1,22 @@
 use std::{iter::zip, ops::RangeInclusive};
 
 use crate::{
-    Key, MooInstructions, NATIVE_SAMPLE_RATE, SampleRate, SampleT, Timing,
-    event::{DEFAULT_BASICKEY, DEFAULT_KEY, DEFAULT_TUNING, DEFAULT_VELOCITY, DEFAULT_VOLUME},
-    pulse_frequency::PULSE_FREQ,
+    Key, MooInstructions, NATIVE_SAMPLE_RATE, SampleRate, SampleT, Timing, Voices,
+    consts::{DEFAULT_BASICKEY, SAMPLING_TOP, key_ratio},
+    event::{
+        DEFAULT_KEY, DEFAULT_PITCH_BEND_RANGE, DEFAULT_TUNING, DEFAULT_VELOCITY, DEFAULT_VOLUME,
+        EventPayload,
+    },
+    io::{Reader, write_pod},
+    noise_builder::{NoiseTable, NoiseType},
+    result::CheckpointError,
     util::ArrayLenExt as _,
-    voice::{Voice, VoiceFlags, VoiceSlot, VoiceTone},
+    voice::{EnvStage, VoiceFlags, VoiceSlot, VoiceTone},
 };
 
 /// Unit index
 ///
-/// Maximum allowed number of units by PxTone is 50.
+/// Maximum allowed number of units by PxTone is 50, see [`Units`](crate::Units).
 #[repr(transparent)]
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub struct UnitIdx(pub u8);
@@ -48,12 +54,14 @@ pub const MAX_CHANNEL: u8 = 2;
 pub const MAX_CH_LEN: usize = MAX_CHANNEL as usize;
 
 /// Buffer to store a [`Unit`]'s audio samples before applying the pan time effect.
-pub type PanTimeBuf = [i32; 64];
+///
+/// Long enough for [`PanTimeScale::RealTime`] at [`SampleRate::MAX_SUPPORTED`].
+/// Only the first [`PanTimeScale::buf_len`] samples are used as the ring buffer.
+pub type PanTimeBuf = [i32; real_time_buf_len(SampleRate::MAX_SUPPORTED)];
 
-#[derive(Clone)]
 /// A 🐄 cow that moos a channel of your song, otherwise known as a unit.
 ///
-/// A unit needs a [`Voice`] to be able to moo. Otherwise it's a silent cow.
+/// A unit needs a [`Voice`](crate::Voice) to be able to moo. Otherwise it's a silent cow.
 ///
 /// A song will set the voice using a [`SetVoice`](crate::EventPayload::SetVoice) event.
 ///
@@ -76,14 +84,20 @@ pub struct Unit {
     /// The name of the unit
     pub name: String,
     /// The key at which we are mooing now
+    ///
+    /// This doesn't include pitch bend and vibrato.
     pub key_now: Key,
-    /// They key at which we start mooing
+    /// The key the current portamento slide starts from
     pub key_start: Key,
-    /// Used in portamento for the target key to slide to
+    /// The distance of the target key from [`key_start`](Self::key_start)
+    ///
+    /// See [`Self::porta_state`] for how portamento works.
     pub key_margin: Key,
-    /// Where we are during portamento slide
+    /// How many samples of the current portamento slide have been played
     pub porta_pos: SampleT,
-    /// Where we need to go during portamento slide
+    /// The length of portamento slides in samples. 0 means no portamento.
+    ///
+    /// Set by [`Portament`](crate::EventPayload::Portament) events.
     pub porta_destination: SampleT,
     /// The left and right channels are each multiplied by this
     pub pan_vols: [i16; MAX_CH_LEN],
@@ -91,7 +105,9 @@ pub struct Unit {
     pub pan_time_offs: [PanTimeOff; MAX_CH_LEN],
     /// This is where the unit's samples are written to before applying the pan time effect, and
     /// writing the unit's sample data to the group buffers.
-    pub pan_time_bufs: [PanTimeBuf; MAX_CH_LEN],
+    ///
+    /// Read it with [`Herd::unit_tap`](crate::Herd::unit_tap).
+    pub(crate) pan_time_bufs: [PanTimeBuf; MAX_CH_LEN],
     /// Determines the output volume of the unit along with [`velocity`](Self::velocity).
     ///
     /// Normally ranges from 0 to 128, but some songs can set it to values otuside of that range.
@@ -104,6 +120,8 @@ pub struct Unit {
     pub velocity: i16,
     /// Sample group this unit belongs to
     pub group: GroupIdx,
+    /// Crossfade from the previous group, see [`Herd::set_unit_group`](crate::Herd::set_unit_group)
+    group_fade: Option<GroupFade>,
     /// Fine tuning of the mooing frequency, where 1.0 is the normal frequency
     pub tuning: f32,
     /// Which voice the unit should be playing
@@ -112,18 +130,368 @@ pub struct Unit {
     pub tones: [VoiceTone; MAX_CH_LEN],
     /// Whether this unit is muted
     pub mute: bool,
+    /// Current pitch bend, set by [`PitchBend`](EventPayload::PitchBend) events
+    pub pitch_bend: i16,
+    /// How far the pitch bend can go, set by [`PitchBendRange`](EventPayload::PitchBendRange)
+    /// events
+    pub pitch_bend_range: Key,
+    /// Vibrato LFO that modulates the key we are mooing at
+    pub vibrato: Vibrato,
+    /// LFO that modulates the output volume
+    ///
+    /// [`LfoParams::depth`] is in the range of `0..=128`, where 128 means the volume
+    /// periodically goes all the way down to silence.
+    pub tremolo: Lfo,
+    /// LFO that modulates the panning
+    ///
+    /// [`LfoParams::depth`] is in the range of `0..=64`, where 64 means the sound periodically
+    /// pans all the way to the left and the right.
+    pub auto_pan: Lfo,
+}
+
+/// Parameters of an [`Lfo`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LfoParams {
+    /// How many cycles happen per second, in 1/100 Hz
+    pub rate: u16,
+    /// How strong the effect is. The exact meaning depends on the effect.
+    pub depth: u8,
+    /// The shape of the wave
+    pub shape: NoiseType,
+}
+
+impl LfoParams {
+    /// Pack the parameters into a single value for serialization
+    pub(crate) fn to_bits(self) -> u32 {
+        u32::from(self.rate) | (u32::from(self.depth) << 16) | (u32::from(self.shape as u8) << 24)
+    }
+    /// Unpack parameters packed by [`Self::to_bits`]
+    #[expect(clippy::cast_possible_truncation)]
+    pub(crate) const fn from_bits(bits: u32) -> Option<Self> {
+        let Some(shape) = NoiseType::from_index((bits >> 24) as u8) else {
+            return None;
+        };
+        Some(Self {
+            rate: bits as u16,
+            depth: (bits >> 16) as u8,
+            shape,
+        })
+    }
+}
+
+/// Low frequency oscillator of a [`Unit`], used for the tremolo and auto-pan effects.
+///
+/// The wave shapes are the same ones used for noise generation, sampled from the
+/// [`NoiseTable`] of [`MooInstructions`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Lfo {
+    /// Settings of the oscillator
+    pub params: LfoParams,
+    /// Current phase of the oscillator (`0.0..1.0`)
+    pub phase: f32,
+}
+
+impl Lfo {
+    /// Whether the LFO has any effect
+    #[must_use]
+    pub const fn is_active(&self) -> bool {
+        self.params.depth != 0 && self.params.rate != 0
+    }
+    fn sample(self, table: &NoiseTable) -> i32 {
+        i32::from(table.lfo_sample(self.params.shape, self.phase))
+    }
+    /// Volume multiplier in the range of `0..=128`
+    fn tremolo_volume(self, table: &NoiseTable) -> i32 {
+        let depth = i32::from(self.params.depth.min(128));
+        // Map the wave from `-top..=top` to `0..=depth`
+        let dip = depth * (SAMPLING_TOP_I32 - self.sample(table)) / (SAMPLING_TOP_I32 * 2);
+        128 - dip
+    }
+    /// Left and right volume multipliers in the range of `0..=64`
+    fn pan_vols(self, table: &NoiseTable) -> [i32; MAX_CH_LEN] {
+        let depth = i32::from(self.params.depth.min(64));
+        let offset = depth * self.sample(table) / SAMPLING_TOP_I32;
+        if offset >= 0 {
+            [64 - offset, 64]
+        } else {
+            [64, 64 + offset]
+        }
+    }
+    fn advance(&mut self, sps: SampleRate) {
+        let rate = f32::from(self.params.rate) / 100.0;
+        self.phase = (self.phase + rate / f32::from(sps)).fract();
+    }
+}
+
+const SAMPLING_TOP_I32: i32 = SAMPLING_TOP as i32;
+
+/// Low frequency oscillator that periodically modulates the pitch of a [`Unit`].
+///
+/// The modulation is applied on top of [`Unit::key_now`], so it doesn't interfere with
+/// portamento.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vibrato {
+    /// How many times per second the pitch goes up and down
+    pub rate: f32,
+    /// Maximum deviation from the current key, in [`Key`] units
+    pub depth: Key,
+    /// Current phase of the oscillator (`0.0..1.0`)
+    pub phase: f32,
+}
+
+impl Vibrato {
+    /// Whether the vibrato has any effect
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.depth.0 != 0 && self.rate != 0.0
+    }
+    #[expect(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn key_offset(&self) -> Key {
+        let sin = (self.phase * std::f32::consts::TAU).sin();
+        Key((sin * self.depth.0 as f32) as i32)
+    }
+    fn advance(&mut self, sps: SampleRate) {
+        self.phase = (self.phase + self.rate / f32::from(sps)).fract();
+    }
 }
 
 /// Pan-time offset.
-pub type PanTimeOff = u8;
+pub type PanTimeOff = u16;
+
+/// Read-only view of the most recent samples a [`Unit`] rendered, for custom per-unit effects
+/// (like a chorus that only applies to one unit).
+///
+/// Each unit keeps its last [`Self::depth`] samples per channel in a ring buffer, which is what
+/// the pan time effect reads from.
+/// The samples have volume, velocity and pan volume applied, but not pan time.
+///
+/// Get one with [`Herd::unit_tap`](crate::Herd::unit_tap) after rendering.
+#[derive(Clone, Copy)]
+pub struct UnitTap<'a> {
+    bufs: &'a [PanTimeBuf; MAX_CH_LEN],
+    /// Ring buffer index of the most recent sample
+    newest: usize,
+    len: usize,
+}
+
+impl UnitTap<'_> {
+    /// How many recent samples are kept per channel, see [`PanTimeScale::buf_len`]
+    #[must_use]
+    pub const fn depth(&self) -> usize {
+        self.len
+    }
+    /// The sample of channel `ch` (0 is left, 1 is right) rendered `age` samples ago,
+    /// where 0 is the most recent sample.
+    ///
+    /// Returns `None` if `ch` is not a channel, or `age` is not less than [`Self::depth`].
+    #[must_use]
+    pub fn sample(&self, ch: u8, age: usize) -> Option<i32> {
+        if age >= self.len {
+            return None;
+        }
+        let buf = self.bufs.get(usize::from(ch))?;
+        // `len` is a power of two, so masking wraps the index around the ring
+        Some(buf[self.newest.wrapping_sub(age) & (self.len - 1)])
+    }
+    /// The kept samples of channel `ch`, from the most recent to the oldest.
+    ///
+    /// Empty if `ch` is not a channel.
+    pub fn recent(&self, ch: u8) -> impl Iterator<Item = i32> {
+        (0..self.len).map_while(move |age| self.sample(ch, age))
+    }
+}
+
+/// How loud a unit is, for drawing level meters.
+///
+/// Get them with [`Herd::unit_levels`](crate::Herd::unit_levels) after rendering.
+/// The levels are on the 16 bit scale, and measure what the unit adds to the mix, so muted
+/// units are silent.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UnitLevel {
+    /// The loudest sample of each channel since the peaks were last reset with
+    /// [`Herd::reset_unit_peaks`](crate::Herd::reset_unit_peaks)
+    pub peak: [u32; MAX_CH_LEN],
+    /// The root mean square of each channel over the last rendered block
+    pub rms: [f32; MAX_CH_LEN],
+}
+
+/// Measures the [`UnitLevel`]s while rendering
+#[derive(Default)]
+pub struct LevelMeter {
+    levels: Vec<UnitLevel>,
+    /// Sums of the squared samples of the current block
+    sums: Vec<[u64; MAX_CH_LEN]>,
+    frames: u32,
+}
+
+impl LevelMeter {
+    pub fn levels(&self) -> &[UnitLevel] {
+        &self.levels
+    }
+    /// Make room for `units` units
+    pub fn fit(&mut self, units: usize) {
+        if self.levels.len() != units {
+            self.levels.resize(units, UnitLevel::default());
+            self.sums.resize(units, [0; _]);
+        }
+    }
+    /// Measure sample `smp` of channel `ch` of the unit at `idx`
+    pub fn add(&mut self, idx: usize, ch: u8, smp: i32) {
+        let abs = smp.unsigned_abs();
+        let peak = &mut self.levels[idx].peak[ch as usize];
+        *peak = (*peak).max(abs);
+        self.sums[idx][ch as usize] += u64::from(abs) * u64::from(abs);
+    }
+    pub const fn end_frame(&mut self) {
+        self.frames += 1;
+    }
+    /// Calculate the RMS of the block, and start a new one
+    #[expect(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn end_block(&mut self) {
+        if self.frames == 0 {
+            return;
+        }
+        for (level, sums) in zip(&mut self.levels, &mut self.sums) {
+            for (rms, sum) in zip(&mut level.rms, sums) {
+                *rms = (*sum as f64 / f64::from(self.frames)).sqrt() as f32;
+                *sum = 0;
+            }
+        }
+        self.frames = 0;
+    }
+    pub fn reset_peaks(&mut self) {
+        for level in &mut self.levels {
+            level.peak = [0; _];
+        }
+    }
+}
+
+/// How the pan position of a sound is turned into left and right channel volumes.
+///
+/// Used for [`PanVol`](crate::EventPayload::PanVol) events, and the pan of noise design units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanLaw {
+    /// `PxTone`'s pan law. The channel the sound is panned towards stays at full volume,
+    /// and the other one is faded out linearly.
+    ///
+    /// Sounds panned to the side are perceived as quieter than ones in the center.
+    #[default]
+    Linear,
+    /// The total power of the channels stays the same at every position, so the perceived
+    /// loudness doesn't change while panning.
+    ///
+    /// Centered sounds are identical to [`PanLaw::Linear`], sounds panned to the side are
+    /// up to 3 dB louder on that side.
+    EqualPower,
+}
+
+impl PanLaw {
+    /// Left and right volume multipliers for `pan` (-1.0 is left, 1.0 is right).
+    ///
+    /// Both are 1.0 at the center.
+    #[must_use]
+    pub fn gains(self, pan: f64) -> [f64; 2] {
+        let pan = pan.clamp(-1.0, 1.0);
+        match self {
+            Self::Linear => [1.0 - pan.max(0.0), 1.0 + pan.min(0.0)],
+            Self::EqualPower => {
+                let angle = (pan + 1.0) * std::f64::consts::FRAC_PI_4;
+                [
+                    angle.cos() * std::f64::consts::SQRT_2,
+                    angle.sin() * std::f64::consts::SQRT_2,
+                ]
+            }
+        }
+    }
+    /// Left and right [`Unit::pan_vols`] for a [`PanVol`](crate::EventPayload::PanVol) value
+    #[expect(clippy::cast_possible_truncation)]
+    pub(crate) fn unit_pan_vols(self, vol: u8) -> [i16; MAX_CH_LEN] {
+        match self {
+            Self::Linear => {
+                if vol >= 64 {
+                    [128 - i16::from(vol), 64]
+                } else {
+                    [64, i16::from(vol)]
+                }
+            }
+            Self::EqualPower => {
+                let gains = self.gains((f64::from(vol) - 64.0) / 64.0);
+                gains.map(|gain| (gain * 64.0).round() as i16)
+            }
+        }
+    }
+}
+
+/// Pitch multiplier, as used by [`Unit::tuning`], [`VoiceUnit::tuning`](crate::VoiceUnit::tuning)
+/// and the [`Tuning`](crate::EventPayload::Tuning) event.
+///
+/// 1.0 plays at the original pitch, 2.0 an octave higher, 0.5 an octave lower.
+/// Musicians usually think in cents (1/100 semitones) instead, see [`Self::from_cents`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tuning(f32);
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self(DEFAULT_TUNING)
+    }
+}
+
+impl Tuning {
+    /// The range [`Self::from_cents`] clamps to (10 octaves up or down)
+    pub const CENTS_RANGE: RangeInclusive<f32> = -12_000.0..=12_000.0;
+    /// Create a tuning from a raw multiplier.
+    ///
+    /// Returns `None` if `mul` isn't a positive, finite number.
+    #[must_use]
+    pub fn new(mul: f32) -> Option<Self> {
+        (mul.is_finite() && mul > 0.0).then_some(Self(mul))
+    }
+    /// Tuning read from a file, which isn't validated
+    pub(crate) const fn from_raw(mul: f32) -> Self {
+        Self(mul)
+    }
+    /// Create a tuning that shifts the pitch by `cents` (1/100 semitones).
+    ///
+    /// `cents` is clamped to [`Self::CENTS_RANGE`]. Not-a-number is treated as 0.
+    #[must_use]
+    pub fn from_cents(cents: f32) -> Self {
+        let cents = if cents.is_nan() {
+            0.0
+        } else {
+            cents.clamp(*Self::CENTS_RANGE.start(), *Self::CENTS_RANGE.end())
+        };
+        Self((cents / 1200.0).exp2())
+    }
+    /// Create a tuning that shifts the pitch by `semitones` (see [`Self::from_cents`])
+    #[must_use]
+    pub fn from_semitones(semitones: f32) -> Self {
+        Self::from_cents(semitones * 100.0)
+    }
+    /// The raw multiplier
+    #[must_use]
+    pub const fn get(self) -> f32 {
+        self.0
+    }
+    /// How many cents (1/100 semitones) this tuning shifts the pitch by
+    #[must_use]
+    pub fn cents(self) -> f32 {
+        self.0.log2() * 1200.0
+    }
+}
+
+impl From<Tuning> for f32 {
+    fn from(tuning: Tuning) -> Self {
+        tuning.get()
+    }
+}
 
 impl Default for Unit {
     fn default() -> Self {
         let mut this = Self {
             name: String::default(),
-            key_now: Default::default(),
-            key_start: Default::default(),
-            key_margin: Default::default(),
+            key_now: Key(0),
+            key_start: Key(0),
+            key_margin: Key(0),
             porta_pos: Default::default(),
             porta_destination: Default::default(),
             pan_vols: Default::default(),
@@ -132,16 +500,42 @@ impl Default for Unit {
             volume: Default::default(),
             velocity: Default::default(),
             group: GroupIdx::default(),
+            group_fade: None,
             tuning: Default::default(),
             tones: [VoiceTone::default(), VoiceTone::default()],
             voice_idx: VoiceIdx(0),
             mute: false,
+            pitch_bend: 0,
+            pitch_bend_range: DEFAULT_PITCH_BEND_RANGE,
+            vibrato: Vibrato::default(),
+            tremolo: Lfo::default(),
+            auto_pan: Lfo::default(),
         };
         this.tone_init();
         this
     }
 }
 
+impl Clone for Unit {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            tones: self.tones.clone(),
+            ..*self
+        }
+    }
+    /// Reuses the allocation of the name
+    fn clone_from(&mut self, source: &Self) {
+        let mut name = std::mem::take(&mut self.name);
+        name.clone_from(&source.name);
+        *self = Self {
+            name,
+            tones: source.tones.clone(),
+            ..*source
+        };
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 #[repr(transparent)]
 /// A group index.
@@ -158,29 +552,102 @@ impl GroupIdx {
     }
 }
 
+/// The samples of each group (see [`GroupIdx`]) for one channel, before they are mixed together
 pub type GroupSamples = [i32; 7];
 
+/// A unit's output moving linearly from one group to another
+#[derive(Clone, Copy, Debug)]
+struct GroupFade {
+    from: GroupIdx,
+    pos: u16,
+    len: u16,
+}
+
+impl GroupFade {
+    /// Split `smp` into the parts for the old and the new group
+    #[expect(clippy::cast_possible_truncation)]
+    const fn split(self, smp: i32) -> (i32, i32) {
+        let new = (smp as i64 * self.pos as i64 / self.len as i64) as i32;
+        (smp - new, new)
+    }
+}
+
+/// Read a [`GroupIdx`], making sure the group exists
+fn read_group(rd: &mut Reader) -> Result<GroupIdx, CheckpointError> {
+    let group = GroupIdx(rd.next()?);
+    if group.0 > GroupIdx::MAX.0 {
+        return Err(CheckpointError::InvalidData);
+    }
+    Ok(group)
+}
+
+/// Debug view of a portamento slide, see [`Unit::porta_state`].
+///
+/// Useful for editors that want to draw pitch curves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PortaState {
+    /// The key the slide starts from
+    pub from: Key,
+    /// The key the slide ends at
+    pub to: Key,
+    /// How many samples of the slide have been played
+    pub pos: SampleT,
+    /// The length of the slide in samples
+    pub len: SampleT,
+}
+
+impl PortaState {
+    /// The key the slide is at after `pos` samples
+    #[must_use]
+    pub fn key_at(self, pos: SampleT) -> Key {
+        if pos >= self.len {
+            return self.to;
+        }
+        porta_key(self.from, self.to - self.from, pos, self.len)
+    }
+    /// How far along the slide is, from 0.0 to 1.0
+    #[must_use]
+    #[expect(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    pub fn progress(self) -> f32 {
+        (self.pos as f64 / self.len as f64) as f32
+    }
+}
+
+#[expect(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn porta_key(start: Key, margin: Key, pos: SampleT, len: SampleT) -> Key {
+    Key((f64::from(start.0) + f64::from(margin.0) * pos as f64 / len as f64) as i32)
+}
+
 impl Unit {
     pub(crate) fn tone_init(&mut self) {
         self.group = GroupIdx(0);
+        self.group_fade = None;
         self.velocity = DEFAULT_VELOCITY.cast_signed();
         self.volume = DEFAULT_VOLUME.cast_signed();
         self.tuning = DEFAULT_TUNING;
         self.porta_destination = 0;
         self.porta_pos = 0;
+        self.pitch_bend = 0;
+        self.pitch_bend_range = DEFAULT_PITCH_BEND_RANGE;
+        self.vibrato = Vibrato::default();
+        self.tremolo = Lfo::default();
+        self.auto_pan = Lfo::default();
 
         for i in 0..MAX_CHANNEL {
             self.pan_vols[i as usize] = 64;
             self.pan_time_offs[i as usize] = 0;
         }
     }
+    pub(crate) const fn clear_pan_time_bufs(&mut self) {
+        self.pan_time_bufs = [[0; _]; _];
+    }
     #[expect(
         clippy::cast_possible_truncation,
         clippy::cast_possible_wrap,
         clippy::cast_sign_loss
     )]
-    pub(crate) fn tone_envelope(&mut self, voices: &[Voice]) {
-        let Some(voice) = voices.get(self.voice_idx.usize()) else {
+    pub(crate) fn tone_envelope(&mut self, voices: &Voices) {
+        let Some(voice) = voices.get(self.voice_idx) else {
             return;
         };
 
@@ -203,10 +670,11 @@ impl Unit {
         }
     }
 
-    pub(crate) const fn tone_key_on(&mut self) {
+    pub(crate) fn tone_key_on(&mut self) {
         self.key_now = self.key_start + self.key_margin;
         self.key_start = self.key_now;
-        self.key_margin = 0;
+        self.key_margin = Key(0);
+        self.vibrato.phase = 0.0;
     }
 
     pub(crate) fn tone_zero_lives(&mut self) {
@@ -215,48 +683,160 @@ impl Unit {
         }
     }
 
-    pub(crate) const fn tone_key(&mut self, key: Key) {
+    pub(crate) fn tone_key(&mut self, key: Key) {
         self.key_start = self.key_now;
         self.key_margin = key - self.key_start;
         self.porta_pos = 0;
     }
 
-    pub(crate) fn tone_pan_volume(&mut self, vol: u8) {
-        self.pan_vols[0] = 64;
-        self.pan_vols[1] = 64;
-        if vol >= 64 {
-            self.pan_vols[0] = 128 - i16::from(vol);
-        } else {
-            self.pan_vols[1] = i16::from(vol);
-        }
+    pub(crate) fn tone_pan_volume(&mut self, vol: u8, law: PanLaw) {
+        self.pan_vols = law.unit_pan_vols(vol);
     }
 
-    pub(crate) fn tone_pan_time(&mut self, pan_time: PanTime, sps: SampleRate) {
-        self.pan_time_offs = pan_time.to_lr_offsets(sps);
+    pub(crate) fn tone_pan_time(
+        &mut self,
+        pan_time: PanTime,
+        sps: SampleRate,
+        scale: PanTimeScale,
+    ) {
+        self.pan_time_offs = pan_time.to_lr_offsets_scaled(sps, scale);
     }
 
+    /// View of the recent samples, see [`UnitTap`].
+    ///
+    /// `time_pan_index` is the ring buffer index the next sample will be written to,
+    /// and `len` the length of the ring buffer.
+    pub(crate) const fn tap(&self, time_pan_index: usize, len: usize) -> UnitTap<'_> {
+        UnitTap {
+            bufs: &self.pan_time_bufs,
+            newest: time_pan_index.wrapping_sub(1) & (len - 1),
+            len,
+        }
+    }
+
+    /// Mix the current sample of channel `ch` into its group, and return it
     pub(crate) const fn tone_supple(
         &self,
         group_smps: &mut GroupSamples,
         ch: u8,
         time_pan_index: usize,
-    ) {
-        let idx = (time_pan_index.wrapping_sub(self.pan_time_offs[ch as usize] as usize))
-            & (PanTimeBuf::LEN - 1);
-        group_smps[self.group.usize()] += self.pan_time_bufs[ch as usize][idx];
+        len: usize,
+    ) -> i32 {
+        let idx =
+            (time_pan_index.wrapping_sub(self.pan_time_offs[ch as usize] as usize)) & (len - 1);
+        let smp = self.pan_time_bufs[ch as usize][idx];
+        match self.group_fade {
+            Some(fade) => {
+                let (old, new) = fade.split(smp);
+                group_smps[fade.from.usize()] += old;
+                group_smps[self.group.usize()] += new;
+            }
+            None => group_smps[self.group.usize()] += smp,
+        }
+        smp
     }
-    #[expect(clippy::cast_possible_truncation)]
-    pub(crate) fn tone_increment_key(&mut self) -> i32 {
-        if self.porta_destination != 0 && self.key_margin != 0 {
+    /// Move the unit to `group`, crossfading from the current group over `len` samples
+    pub(crate) const fn fade_to_group(&mut self, group: GroupIdx, len: u16) {
+        self.group_fade = if len == 0 || group.0 == self.group.0 {
+            None
+        } else {
+            Some(GroupFade {
+                from: self.group,
+                pos: 0,
+                len,
+            })
+        };
+        self.group = group;
+    }
+    pub(crate) const fn tone_increment_group_fade(&mut self) {
+        if let Some(fade) = &mut self.group_fade {
+            fade.pos += 1;
+            if fade.pos >= fade.len {
+                self.group_fade = None;
+            }
+        }
+    }
+    /// The state of the current portamento slide, or `None` if the unit isn't sliding.
+    ///
+    /// Portamento works like this (the same way as in PxTone):
+    ///
+    /// - A [`Portament`](crate::EventPayload::Portament) event sets the length of
+    ///   the slides of the unit. It doesn't start or stop a slide by itself.
+    /// - A [`Key`](crate::EventPayload::Key) event starts a slide from the current key
+    ///   to the new key.
+    /// - Every sample, the slide advances linearly, until it reaches the target key.
+    ///   If the slide length is 0, the key jumps to the target immediately.
+    /// - An [`On`](crate::EventPayload::On) event jumps to the target key, ending the slide.
+    ///   Since [`Key`](crate::EventPayload::Key) events play before
+    ///   [`On`](crate::EventPayload::On) events on the same tick, notes start at their key,
+    ///   and only key changes during a note slide.
+    /// - Changing the voice resets the key to [`DEFAULT_KEY`], ending the slide.
+    #[must_use]
+    pub const fn porta_state(&self) -> Option<PortaState> {
+        if self.porta_destination == 0
+            || self.key_margin.0 == 0
+            || self.porta_pos >= self.porta_destination
+        {
+            return None;
+        }
+        Some(PortaState {
+            from: self.key_start,
+            to: Key(self.key_start.0 + self.key_margin.0),
+            pos: self.porta_pos,
+            len: self.porta_destination,
+        })
+    }
+    /// The envelope volume of channel `ch` (0 is left, 1 is right) at the last rendered
+    /// sample, from 0.0 to 1.0. See [`VoiceTone::volume`].
+    ///
+    /// Returns `None` if `ch` is not a channel.
+    #[must_use]
+    pub fn current_env_volume(&self, ch: u8) -> Option<f32> {
+        Some(self.tones.get(usize::from(ch))?.volume())
+    }
+    /// The envelope stage of channel `ch` (0 is left, 1 is right), see [`VoiceTone::stage`].
+    ///
+    /// `voices` are the voices the unit is playing with, like
+    /// [`MooInstructions::voices`].
+    ///
+    /// Returns `None` if `ch` is not a channel, or the unit's voice doesn't have it.
+    #[must_use]
+    pub fn env_stage(&self, ch: u8, voices: &Voices) -> Option<EnvStage> {
+        let slot = voices.get(self.voice_idx)?.slots().nth(usize::from(ch))?;
+        Some(self.tones.get(usize::from(ch))?.stage(&slot.inst))
+    }
+    /// Advance the key (portamento), and return the key to moo at, with pitch bend
+    /// and vibrato applied.
+    pub(crate) fn tone_increment_key(&mut self, sps: SampleRate) -> Key {
+        let mut key = self.tone_increment_porta();
+        if self.pitch_bend != 0 {
+            let bend = i64::from(self.pitch_bend_range.0) * i64::from(self.pitch_bend)
+                / i64::from(EventPayload::PITCH_BEND_MAX);
+            // Only out of range for ranges that didn't come from a file
+            #[expect(clippy::cast_possible_truncation)]
+            let bend = bend.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32;
+            key = Key(key.0.saturating_add(bend));
+        }
+        if self.vibrato.is_active() {
+            key = Key(key.0.saturating_add(self.vibrato.key_offset().0));
+            self.vibrato.advance(sps);
+        }
+        key
+    }
+    fn tone_increment_porta(&mut self) -> Key {
+        if self.porta_destination != 0 && self.key_margin.0 != 0 {
             if self.porta_pos < self.porta_destination {
                 self.porta_pos += 1;
-                self.key_now = (f64::from(self.key_start)
-                    + f64::from(self.key_margin) * f64::from(self.porta_pos)
-                        / f64::from(self.porta_destination)) as i32;
+                self.key_now = porta_key(
+                    self.key_start,
+                    self.key_margin,
+                    self.porta_pos,
+                    self.porta_destination,
+                );
             } else {
                 self.key_now = self.key_start + self.key_margin;
                 self.key_start = self.key_now;
-                self.key_margin = 0;
+                self.key_margin = Key(0);
             }
         } else {
             self.key_now = self.key_start + self.key_margin;
@@ -264,8 +844,17 @@ impl Unit {
         self.key_now
     }
 
-    pub(crate) fn tone_increment_sample(&mut self, freq: f32, voices: &[Voice]) {
-        let Some(voice) = voices.get(self.voice_idx.usize()) else {
+    /// Advance the tremolo and auto-pan LFOs by one sample
+    pub(crate) fn tone_increment_lfos(&mut self, sps: SampleRate) {
+        if self.tremolo.is_active() {
+            self.tremolo.advance(sps);
+        }
+        if self.auto_pan.is_active() {
+            self.auto_pan.advance(sps);
+        }
+    }
+    pub(crate) fn tone_increment_sample(&mut self, freq: f32, voices: &Voices) {
+        let Some(voice) = voices.get(self.voice_idx) else {
             // If for some reason there is no voice, we just don't do anything
             // instead of panicking
             return;
@@ -309,10 +898,125 @@ impl Unit {
         }
     }
 
+    /// The [`tuning`](Self::tuning) in cents (1/100 semitones)
+    #[must_use]
+    pub fn tuning_cents(&self) -> f32 {
+        Tuning::from_raw(self.tuning).cents()
+    }
+    /// Set the [`tuning`](Self::tuning) in cents (see [`Tuning::from_cents`])
+    pub fn set_tuning_cents(&mut self, cents: f32) {
+        self.tuning = Tuning::from_cents(cents).get();
+    }
+    /// Write the playback state of this unit for a [`Checkpoint`](crate::render::Checkpoint)
+    ///
+    /// The name isn't part of the playback state, so it's left out.
+    pub(crate) fn write_state(&self, out: &mut Vec<u8>) {
+        for key in [
+            self.key_now,
+            self.key_start,
+            self.key_margin,
+            self.pitch_bend_range,
+        ] {
+            write_pod(key.0, out);
+        }
+        write_pod(self.porta_pos, out);
+        write_pod(self.porta_destination, out);
+        write_pod(self.pan_vols, out);
+        write_pod(self.pan_time_offs, out);
+        write_pod(self.pan_time_bufs, out);
+        write_pod(self.volume, out);
+        write_pod(self.velocity, out);
+        write_pod(self.group.0, out);
+        match self.group_fade {
+            Some(fade) => {
+                write_pod(1u8, out);
+                write_pod(fade.from.0, out);
+                write_pod(fade.pos, out);
+                write_pod(fade.len, out);
+            }
+            None => write_pod(0u8, out),
+        }
+        write_pod(self.tuning, out);
+        write_pod(self.voice_idx.0, out);
+        for tone in &self.tones {
+            write_pod(tone.smp_pos, out);
+            write_pod(tone.offset_freq, out);
+            write_pod(tone.env_volume, out);
+            write_pod(tone.life_count, out);
+            write_pod(tone.on_count, out);
+            write_pod(tone.env_start, out);
+            write_pod(tone.env_pos as u64, out);
+            write_pod(tone.env_release_clock, out);
+        }
+        write_pod(u8::from(self.mute), out);
+        write_pod(self.pitch_bend, out);
+        write_pod(self.vibrato.rate, out);
+        write_pod(self.vibrato.depth.0, out);
+        write_pod(self.vibrato.phase, out);
+        for lfo in [self.tremolo, self.auto_pan] {
+            write_pod(lfo.params.to_bits(), out);
+            write_pod(lfo.phase, out);
+        }
+    }
+    /// Read back the playback state written by [`Self::write_state`]
+    pub(crate) fn read_state(&mut self, rd: &mut Reader) -> Result<(), CheckpointError> {
+        for key in [
+            &mut self.key_now,
+            &mut self.key_start,
+            &mut self.key_margin,
+            &mut self.pitch_bend_range,
+        ] {
+            *key = Key(rd.next()?);
+        }
+        self.porta_pos = rd.next()?;
+        self.porta_destination = rd.next()?;
+        self.pan_vols = rd.next()?;
+        self.pan_time_offs = rd.next()?;
+        self.pan_time_bufs = rd.next()?;
+        self.volume = rd.next()?;
+        self.velocity = rd.next()?;
+        self.group = read_group(rd)?;
+        self.group_fade = match rd.next::<u8>()? {
+            0 => None,
+            1 => Some(GroupFade {
+                from: read_group(rd)?,
+                pos: rd.next()?,
+                len: rd.next()?,
+            }),
+            _ => return Err(CheckpointError::InvalidData),
+        };
+        self.tuning = rd.next()?;
+        self.voice_idx = VoiceIdx(rd.next()?);
+        for tone in &mut self.tones {
+            tone.smp_pos = rd.next()?;
+            tone.offset_freq = rd.next()?;
+            tone.env_volume = rd.next()?;
+            tone.life_count = rd.next()?;
+            tone.on_count = rd.next()?;
+            tone.env_start = rd.next()?;
+            tone.env_pos =
+                rd.next::<u64>()?.try_into().map_err(|_| CheckpointError::InvalidData)?;
+            tone.env_release_clock = rd.next()?;
+        }
+        self.mute = rd.next::<u8>()? != 0;
+        self.pitch_bend = rd.next()?;
+        self.vibrato = Vibrato {
+            rate: rd.next()?,
+            depth: Key(rd.next()?),
+            phase: rd.next()?,
+        };
+        for lfo in [&mut self.tremolo, &mut self.auto_pan] {
+            *lfo = Lfo {
+                params: LfoParams::from_bits(rd.next()?).ok_or(CheckpointError::InvalidData)?,
+                phase: rd.next()?,
+            };
+        }
+        Ok(())
+    }
     pub(crate) const fn set_voice(&mut self, idx: VoiceIdx) {
         self.voice_idx = idx;
         self.key_now = DEFAULT_KEY;
-        self.key_margin = 0;
+        self.key_margin = Key(0);
         self.key_start = DEFAULT_KEY;
     }
 
@@ -323,6 +1027,9 @@ impl Unit {
         }
     }
     /// Reset the unit's voice to the voice indexed by `voice_idx`
+    ///
+    /// If the voice doesn't exist, the first voice is used instead.
+    /// If there are no voices at all, the unit stays silent.
     #[expect(
         clippy::cast_precision_loss,
         clippy::cast_possible_truncation,
@@ -330,12 +1037,11 @@ impl Unit {
     )]
     pub fn reset_voice(&mut self, ins: &MooInstructions, mut voice_idx: VoiceIdx, timing: Timing) {
         if voice_idx.0 >= ins.voices.len() {
-            eprintln!("Error: Voice index out of bounds. Setting to 0.");
             voice_idx = VoiceIdx(0);
         }
         self.set_voice(voice_idx);
         let Some(voice) = &ins.voices.get(voice_idx) else {
-            eprintln!("Error: Song doesn't have any voices");
+            self.tone_zero_lives();
             return;
         };
 
@@ -348,8 +1054,10 @@ impl Unit {
                 (inst.num_samples as f32 * timing.bpm)
                     / (f32::from(NATIVE_SAMPLE_RATE) * 60. * vu.tuning)
             } else {
-                #[expect(clippy::cast_possible_wrap)]
-                (PULSE_FREQ.get((DEFAULT_BASICKEY as i32).wrapping_sub(vu.basic_key)) * vu.tuning)
+                // Voices rendered at another rate than the native one cover the same time in
+                // more or fewer samples
+                let rate = f32::from(inst.sample_rate) / f32::from(NATIVE_SAMPLE_RATE);
+                key_ratio(Key(DEFAULT_BASICKEY.0.wrapping_sub(vu.basic_key.0))) * vu.tuning * rate
             };
         }
     }
@@ -357,14 +1065,18 @@ impl Unit {
     pub(crate) fn tone_sample(
         &mut self,
         time_pan_index: usize,
-        smooth_smp: SampleRate,
-        voices: &[Voice],
+        smooth_smp: u16,
+        voices: &Voices,
+        lfo_table: &NoiseTable,
+        interpolation: Interpolation,
     ) {
-        let Some(voice) = &voices.get(self.voice_idx.usize()) else {
+        let Some(voice) = &voices.get(self.voice_idx) else {
             // If for whatever reason there is no voice, we just don't produce any output
             // instead of panicking
             return;
         };
+        let tremolo_vol = self.tremolo.is_active().then(|| self.tremolo.tremolo_volume(lfo_table));
+        let auto_pan_vols = self.auto_pan.is_active().then(|| self.auto_pan.pan_vols(lfo_table));
 
         for ch in 0..i32::from(MAX_CHANNEL) {
             let mut time_pan_buf: i32 = 0;
@@ -387,18 +1099,33 @@ impl Unit {
                 let mut work: i32 = 0;
 
                 if voice_tone.life_count > 0 {
-                    let pos: i32 = (voice_tone.smp_pos as i32) * 4 + ch * 2;
-                    // Theoretically this shouldn't index OOB, but it can happen in weird
-                    // configurations, like low sample rate, etc.
-                    // We avoid panicking in those cases
-                    if let Some(w_sample) = smp_w.get(pos as usize / 2) {
-                        work += i32::from(*w_sample);
-                    }
+                    work += match interpolation {
+                        Interpolation::Nearest => {
+                            let pos: i32 = (voice_tone.smp_pos as i32) * 4 + ch * 2;
+                            // Theoretically this shouldn't index OOB, but it can happen in weird
+                            // configurations, like low sample rate, etc.
+                            // We avoid panicking in those cases
+                            smp_w.get(pos as usize / 2).map_or(0, |&smp| i32::from(smp))
+                        }
+                        Interpolation::Linear => linear_sample(
+                            smp_w,
+                            voice_tone.smp_pos,
+                            ch as usize,
+                            vu.flags.contains(VoiceFlags::WAVE_LOOP),
+                        ),
+                    };
 
                     work = (work * i32::from(self.velocity)) / 128;
                     work = (work * i32::from(self.volume)) / 128;
                     work = work * i32::from(self.pan_vols[ch as usize]) / 64;
 
+                    if let Some(vol) = tremolo_vol {
+                        work = work * vol / 128;
+                    }
+                    if let Some(vols) = auto_pan_vols {
+                        work = work * vols[ch as usize] / 64;
+                    }
+
                     if !voice_inst.env.is_empty() {
                         work = work * i32::from(voice_tone.env_volume) / 128;
                     }
@@ -416,19 +1143,103 @@ impl Unit {
     }
 }
 
-fn calc_pan_time(mut offset: u8, out_sps: SampleRate) -> u8 {
+fn calc_pan_time(mut offset: u8, out_sps: SampleRate, scale: PanTimeScale) -> PanTimeOff {
     if offset > 63 {
         offset = 63;
     }
-    // If conversion fails due to `out_sps` being signifcantly lower than `NATIVE_SAMPLE_RATE`,
-    // we just return 0 as a fallback.
-    ((u32::from(offset) * u32::from(NATIVE_SAMPLE_RATE)) / u32::from(out_sps))
-        .try_into()
-        .unwrap_or(0)
+    match scale {
+        // At very low sample rates, the offset doesn't fit into a `PanTimeOff`. Only the low
+        // bits matter, because the 64 sample ring buffer wraps around, like in PxTone.
+        #[expect(clippy::cast_possible_truncation)]
+        PanTimeScale::Reference => {
+            ((u32::from(offset) * u32::from(NATIVE_SAMPLE_RATE)) / u32::from(out_sps)) as u16
+        }
+        PanTimeScale::RealTime => {
+            let max = scale.buf_len(out_sps) - 1;
+            let off = u32::from(offset) * u32::from(out_sps) / u32::from(NATIVE_SAMPLE_RATE);
+            #[expect(clippy::cast_possible_truncation)]
+            (off.min(max as u32) as u16)
+        }
+    }
+}
+
+/// How [`PanTime`] offsets are converted to samples at output sample rates other than
+/// [`NATIVE_SAMPLE_RATE`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanTimeScale {
+    /// `PxTone`'s behavior, for parity with reference output.
+    ///
+    /// The ring buffer is 64 samples long, and the offsets get shorter in samples as the
+    /// sample rate goes up, so the effect gets weaker at high sample rates.
+    #[default]
+    Reference,
+    /// The offsets last as long in real time as they do at [`NATIVE_SAMPLE_RATE`], and the
+    /// ring buffer grows to fit them at high sample rates.
+    RealTime,
+}
+
+impl PanTimeScale {
+    /// The length of the pan time ring buffer at `sps`, a power of two
+    #[must_use]
+    pub fn buf_len(self, sps: SampleRate) -> usize {
+        match self {
+            Self::Reference => 64,
+            Self::RealTime => real_time_buf_len(sps).min(PanTimeBuf::LEN),
+        }
+    }
+}
+
+/// The length of the [`PanTimeScale::RealTime`] ring buffer at `sps`
+const fn real_time_buf_len(sps: SampleRate) -> usize {
+    (64 * sps.get().div_ceil(NATIVE_SAMPLE_RATE.get())).next_power_of_two() as usize
+}
+
+/// How voice samples are fetched when playing them at a different rate than they were
+/// recorded at.
+///
+/// Voices are rendered at [`NATIVE_SAMPLE_RATE`]. Each output sample, the play position of a
+/// voice advances by the frequency of the note, times the
+/// [stride](crate::Herd::smp_stride) between the native and the output sample rate.
+/// The position is fractional, so a sample has to be picked (or made up) for it.
+///
+/// Changing this doesn't change the timing of anything, only the sound of voices that play
+/// at a rate other than their own, which is most notes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// `PxTone`'s behavior, for parity with reference output.
+    ///
+    /// The sample at the position rounded down is played. Cheap, but aliases, audibly so for
+    /// high notes, and at output sample rates other than [`NATIVE_SAMPLE_RATE`].
+    #[default]
+    Nearest,
+    /// Linear interpolation between the samples around the position.
+    ///
+    /// Smoother, especially for low notes and PCM voices, at the cost of a slight loss of
+    /// high frequencies. Doesn't match the output of `PxTone`.
+    Linear,
+}
+
+/// The sample of channel `ch` at `pos` in `smp_w` (interleaved stereo), linearly interpolated.
+///
+/// Past the last sample, looping voices interpolate towards the first sample.
+#[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn linear_sample(smp_w: &[i16], pos: f64, ch: usize, looping: bool) -> i32 {
+    let num_samples = smp_w.len() / 2;
+    let idx = pos as usize;
+    let Some(&cur) = smp_w.get(idx * 2 + ch) else {
+        return 0;
+    };
+    let next = match smp_w.get((idx + 1) * 2 + ch) {
+        Some(&next) => next,
+        None if looping && num_samples != 0 => smp_w[ch],
+        None => cur,
+    };
+    let frac = pos.fract();
+    (f64::from(next) - f64::from(cur)).mul_add(frac, f64::from(cur)) as i32
 }
 
 /// Inverse of `calc_pan_time`
-fn inv_calc_pan_time(val: u8, sps: SampleRate) -> u8 {
+fn inv_calc_pan_time(val: PanTimeOff, sps: SampleRate) -> u8 {
     if val == 0 {
         return 0;
     }
@@ -453,9 +1264,9 @@ impl Default for PanTime {
 impl PanTime {
     /// The valid range of values for pan time
     pub const RANGE: RangeInclusive<u8> = 0..=127;
-    /// Calculate the pantime from the raw left and right offsets
+    /// Calculate the pantime from the raw left and right offsets of [`PanTimeScale::Reference`]
     #[must_use]
-    pub fn from_lr_offsets(offs: [u8; 2], sps: SampleRate) -> Self {
+    pub fn from_lr_offsets(offs: [PanTimeOff; 2], sps: SampleRate) -> Self {
         match offs {
             [l, 0] if l > 0 => {
                 let off = inv_calc_pan_time(l, sps);
@@ -468,13 +1279,135 @@ impl PanTime {
             _ => Self(64),
         }
     }
-    /// Convert the pan time to left and right offsets
+    /// Convert the pan time to left and right offsets, like [`PanTimeScale::Reference`]
+    #[must_use]
+    pub fn to_lr_offsets(self, sps: SampleRate) -> [PanTimeOff; 2] {
+        self.to_lr_offsets_scaled(sps, PanTimeScale::Reference)
+    }
+    /// Convert the pan time to left and right offsets with `scale`
     #[must_use]
-    pub fn to_lr_offsets(self, sps: SampleRate) -> [u8; 2] {
+    pub fn to_lr_offsets_scaled(self, sps: SampleRate, scale: PanTimeScale) -> [PanTimeOff; 2] {
         if self.0 >= 64 {
-            [calc_pan_time(self.0 - 64, sps), 0]
+            [calc_pan_time(self.0 - 64, sps, scale), 0]
         } else {
-            [0, calc_pan_time(64 - self.0, sps)]
+            [0, calc_pan_time(64 - self.0, sps, scale)]
         }
     }
 }
+
+#[test]
+fn test_tuning_cents() {
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+    assert!(close(Tuning::from_cents(1200.0).get(), 2.0));
+    assert!(close(Tuning::from_semitones(-12.0).get(), 0.5));
+    assert!(close(Tuning::from_cents(-35.5).cents(), -35.5));
+    assert_eq!(Tuning::from_cents(f32::NAN), Tuning::default());
+    assert!(close(Tuning::from_cents(1e9).cents(), 12_000.0));
+    assert_eq!(Tuning::new(0.0), None);
+    assert_eq!(Tuning::new(f32::INFINITY), None);
+    let mut unit = Unit::new();
+    assert!(close(unit.tuning_cents(), 0.0));
+    unit.set_tuning_cents(700.0);
+    assert!(close(unit.tuning, 1.498_307));
+    assert!(close(unit.tuning_cents(), 700.0));
+}
+
+#[test]
+fn test_pan_law() {
+    for vol in 0..=128u8 {
+        let [l, r] = PanLaw::EqualPower.unit_pan_vols(vol);
+        let power = i32::from(l).pow(2) + i32::from(r).pow(2);
+        assert!((power - 2 * 64 * 64).abs() < 200, "{vol}: {l} {r}");
+    }
+    assert_eq!(PanLaw::Linear.unit_pan_vols(0), [64, 0]);
+    assert_eq!(PanLaw::Linear.unit_pan_vols(96), [32, 64]);
+    assert_eq!(PanLaw::EqualPower.unit_pan_vols(64), [64, 64]);
+    assert_eq!(PanLaw::EqualPower.unit_pan_vols(128), [0, 91]);
+}
+
+#[test]
+fn test_portamento() {
+    let mut unit = Unit::new();
+    unit.set_voice(VoiceIdx(0));
+    unit.porta_destination = 4;
+    // Key change during a note slides linearly (PxTone's `Tone_Increment_Key`)
+    unit.tone_key(DEFAULT_KEY + Key::SEMITONE);
+    let state = unit.porta_state().unwrap();
+    assert_eq!(
+        (state.from, state.to, state.pos),
+        (DEFAULT_KEY, DEFAULT_KEY + Key::SEMITONE, 0)
+    );
+    let keys: Vec<i32> = (0..6).map(|_| (unit.tone_increment_porta() - DEFAULT_KEY).0).collect();
+    assert_eq!(keys, [64, 128, 192, 256, 256, 256]);
+    assert!(
+        (1..=4)
+            .all(|pos| (state.key_at(pos) - DEFAULT_KEY).0
+                == keys[usize::try_from(pos).unwrap() - 1])
+    );
+    assert_eq!(unit.porta_state(), None);
+    // Key on jumps to the target key
+    unit.tone_key(DEFAULT_KEY);
+    assert!(unit.porta_state().is_some());
+    unit.tone_key_on();
+    assert_eq!(unit.porta_state(), None);
+    assert_eq!(unit.tone_increment_porta(), DEFAULT_KEY);
+    // Without a portamento length, keys change immediately
+    unit.porta_destination = 0;
+    unit.tone_key(DEFAULT_KEY - Key::semitones(2));
+    assert_eq!(unit.porta_state(), None);
+    assert_eq!(unit.tone_increment_porta(), DEFAULT_KEY - Key::semitones(2));
+}
+
+#[test]
+fn test_pan_time_scale() {
+    let sps = SampleRate(48_000);
+    let full_left = PanTime(127);
+    assert_eq!(PanTimeScale::Reference.buf_len(sps), 64);
+    assert_eq!(PanTimeScale::RealTime.buf_len(sps), 128);
+    assert_eq!(PanTimeScale::RealTime.buf_len(NATIVE_SAMPLE_RATE), 64);
+    assert_eq!(PanTimeScale::RealTime.buf_len(SampleRate(65_535)), 128);
+    assert_eq!(
+        PanTimeScale::RealTime.buf_len(SampleRate::MAX_SUPPORTED),
+        PanTimeBuf::LEN
+    );
+    // The reference offsets get shorter at higher sample rates
+    assert_eq!(full_left.to_lr_offsets(sps), [57, 0]);
+    assert_eq!(
+        full_left.to_lr_offsets_scaled(sps, PanTimeScale::RealTime),
+        [68, 0]
+    );
+    assert_eq!(
+        PanTime(1).to_lr_offsets_scaled(SampleRate(65_535), PanTimeScale::RealTime),
+        [0, 93]
+    );
+    // The longest offsets fit at the highest sample rate, 63 * 384000 / 44100 is 548
+    assert_eq!(
+        full_left.to_lr_offsets_scaled(SampleRate::MAX_SUPPORTED, PanTimeScale::RealTime),
+        [548, 0]
+    );
+    // At low rates the reference offsets wrap around the ring buffer, instead of dropping to 0.
+    // 63 * 44100 / 8000 is 347, which is 27 around the ring.
+    assert_eq!(full_left.to_lr_offsets(SampleRate(8_000))[0] % 64, 27);
+    // Both are the same at the native sample rate
+    for pan_time in PanTime::RANGE.map(PanTime) {
+        assert_eq!(
+            pan_time.to_lr_offsets(NATIVE_SAMPLE_RATE),
+            pan_time.to_lr_offsets_scaled(NATIVE_SAMPLE_RATE, PanTimeScale::RealTime)
+        );
+    }
+}
+
+#[test]
+fn test_linear_sample() {
+    // Two stereo frames
+    let smp_w = [0, 0, 100, -100];
+    assert_eq!(linear_sample(&smp_w, 0.5, 0, false), 50);
+    assert_eq!(linear_sample(&smp_w, 0.5, 1, false), -50);
+    assert_eq!(linear_sample(&smp_w, 1.0, 0, false), 100);
+    // Past the last frame, looping voices head back to the first one
+    assert_eq!(linear_sample(&smp_w, 1.5, 0, false), 100);
+    assert_eq!(linear_sample(&smp_w, 1.5, 1, true), -50);
+    assert_eq!(linear_sample(&smp_w, 2.0, 0, true), 0);
+    let extremes = [i16::MIN, 0, i16::MAX, 0];
+    assert_eq!(linear_sample(&extremes, 0.5, 0, false), 0);
+}
@@ -1,11 +1,13 @@
 mod io;
 
 use crate::{
-    Key, NATIVE_SAMPLE_RATE, SampleRate,
-    event::DEFAULT_BASICKEY,
-    noise_builder::{NoiseTable, noise_to_pcm},
+    Bps, ChNum, Key, NATIVE_SAMPLE_RATE, NearestResampler, Resampler, SampleRate,
+    consts::DEFAULT_BASICKEY,
+    noise_builder::{NoiseTable, noise_to_pcm_at},
     point::EnvPt,
-    pulse_oscillator::{OsciArgs, coord, overtone},
+    pulse_oscillator::OsciArgs,
+    result::EnvelopeError,
+    unit::Tuning,
     voice_data::{
         noise::NoiseData,
         oggv::OggVData,
@@ -29,7 +31,7 @@ pub enum VoiceData {
 }
 
 /// Contains the precomputed sample and envelope data for a voice
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct VoiceInstance {
     /// Number of samples contained in the sample buffer
     ///
@@ -38,6 +40,10 @@ pub struct VoiceInstance {
     pub num_samples: u32,
     /// Contains the bytes of the samples of the voice used for rendering
     pub sample_buf: Vec<u8>,
+    /// The sample rate of the sample buffer, see [`RenderConfig::voice_sample_rate`]
+    ///
+    /// [`RenderConfig::voice_sample_rate`]: crate::RenderConfig::voice_sample_rate
+    pub sample_rate: SampleRate,
     /// Prepared envelope generated from [`VoiceUnit::envelope`].
     pub env: Vec<u8>,
     /// Envelope release
@@ -46,6 +52,18 @@ pub struct VoiceInstance {
     pub env_release: u32,
 }
 
+impl Default for VoiceInstance {
+    fn default() -> Self {
+        Self {
+            num_samples: 0,
+            sample_buf: Vec::new(),
+            sample_rate: NATIVE_SAMPLE_RATE,
+            env: Vec::new(),
+            env_release: 0,
+        }
+    }
+}
+
 impl VoiceInstance {
     /// Recalculate the envelope from the source `envelope`
     #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
@@ -70,10 +88,25 @@ impl VoiceInstance {
     /// Recalculate the sample buffer from [`WaveData`].
     pub fn recalc_wave_data(&mut self, wave: &WaveDataPoints, volume: i16, pan: i16) {
         self.num_samples = 400;
+        self.sample_rate = NATIVE_SAMPLE_RATE;
         let size = self.num_samples * 2 * 2;
         self.sample_buf = vec![0; size as usize];
         update_wave_ptv(wave, self, volume, pan);
     }
+    /// Convert the prepared sample buffer into [`PcmData`].
+    ///
+    /// The sample buffer is always 16 bit stereo, at [`Self::sample_rate`].
+    /// It's empty until the voice is [recalculated](Voice::recalculate).
+    #[must_use]
+    pub fn to_pcm_data(&self) -> PcmData {
+        PcmData {
+            ch: ChNum::Stereo,
+            sps: self.sample_rate.into(),
+            bps: Bps::B16,
+            num_samples: self.num_samples,
+            smp: self.sample_buf.clone(),
+        }
+    }
 }
 
 /// Convert relative envelope to absolute
@@ -137,24 +170,69 @@ pub struct EnvelopeSrc {
 }
 
 impl EnvelopeSrc {
+    /// Add a point to the end of the head of the envelope, right before the release point.
+    ///
+    /// `x` is the time since the previous point. An empty envelope gets a release point
+    /// of 0 too.
+    pub fn push_point(&mut self, x: u16, y: u8) {
+        let release = self.points.pop().unwrap_or(EnvPt::ZERO);
+        self.points.push(EnvPt { x, y });
+        self.points.push(release);
+    }
+    /// The length of the release (the x of the last point), if there are any points
+    #[must_use]
+    pub fn release(&self) -> Option<u16> {
+        self.points.last().map(|pt| pt.x)
+    }
+    /// Set the length of the release, adding the release point to an empty envelope
+    pub fn set_release(&mut self, x: u16) {
+        match self.points.last_mut() {
+            Some(pt) => pt.x = x,
+            None => self.points.push(EnvPt { x, y: 0 }),
+        }
+    }
+    /// Check that the envelope can be [prepared](Self::preview) at every supported sample rate.
+    ///
+    /// # Errors
+    ///
+    /// If [`Self::seconds_per_point`] is 0, or the head of the envelope is too long.
+    pub fn validate(&self) -> Result<(), EnvelopeError> {
+        if self.points.is_empty() {
+            return Ok(());
+        }
+        if self.seconds_per_point == 0 {
+            return Err(EnvelopeError::ZeroSecondsPerPoint);
+        }
+        let samples = self.prepared_len(SampleRate::MAX_SUPPORTED);
+        if samples > ENV_SIZE_SAFETY_LIMIT {
+            return Err(EnvelopeError::TooLong(samples));
+        }
+        Ok(())
+    }
+    /// The envelope prepared for playback at `out_sps`, like [`VoiceInstance::env`] holds it.
+    ///
+    /// One volume per sample, for the head of the envelope. Empty if there are no points, or
+    /// the envelope isn't [valid](Self::validate).
+    #[must_use]
+    pub fn preview(&self, out_sps: SampleRate) -> Vec<u8> {
+        self.to_prepared(out_sps).map(|(prepared, _)| prepared).unwrap_or_default()
+    }
+    /// How many samples the head of the envelope is long at `out_sps`
     #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn prepared_len(&self, out_sps: SampleRate) -> usize {
+        let head = self.points.len().saturating_sub(1);
+        let size: u64 = self.points[..head].iter().map(|pt| u64::from(pt.x)).sum();
+        let env_samples_per_second = size * u64::from(u32::from(out_sps));
+        #[expect(clippy::cast_precision_loss)]
+        let env_size = (env_samples_per_second as f64 / f64::from(self.seconds_per_point)) as usize;
+        env_size.max(1)
+    }
     fn to_prepared(&self, out_sps: SampleRate) -> Option<(Vec<u8>, usize)> {
         if self.points.is_empty() {
             return None;
         }
-        let mut size: u32 = 0;
-
         let head = self.points.len().saturating_sub(1);
-
-        for e in 0..head {
-            size += u32::from(self.points[e].x);
-        }
-        let env_samples_per_second = size * u32::from(out_sps);
-        let mut env_size =
-            (f64::from(env_samples_per_second) / f64::from(self.seconds_per_point)) as usize;
-        if env_size == 0 {
-            env_size = 1;
-        }
+        let env_size = self.prepared_len(out_sps);
 
         if env_size > ENV_SIZE_SAFETY_LIMIT {
             eprintln!("EnvelopeSrc::to_prepared: env_size too large ({env_size}).");
@@ -180,10 +258,22 @@ pub struct VoiceUnit {
     pub flags: VoiceFlags,
 }
 
+impl VoiceUnit {
+    /// The [`tuning`](Self::tuning) in cents (1/100 semitones)
+    #[must_use]
+    pub fn tuning_cents(&self) -> f32 {
+        Tuning::from_raw(self.tuning).cents()
+    }
+    /// Set the [`tuning`](Self::tuning) in cents (see [`Tuning::from_cents`])
+    pub fn set_tuning_cents(&mut self, cents: f32) {
+        self.tuning = Tuning::from_cents(cents).get();
+    }
+}
+
 impl Default for VoiceUnit {
     fn default() -> Self {
         Self {
-            basic_key: DEFAULT_BASICKEY.cast_signed(),
+            basic_key: DEFAULT_BASICKEY,
             tuning: 1.0,
             flags: VoiceFlags::SMOOTH,
         }
@@ -192,6 +282,10 @@ impl Default for VoiceUnit {
 
 bitflags::bitflags! {
     /// Different attributes a voice can have
+    ///
+    /// Bits that ptcow doesn't know about (see [`Self::unknown_bits`]) are preserved when
+    /// reading and writing voices, but have no effect on playback.
+    /// This way files that use flags from newer PxTone versions survive round-trips.
     #[derive(Clone, Copy, Default, bytemuck::AnyBitPattern, bytemuck::NoUninit, Debug)]
     #[repr(C)]
     pub struct VoiceFlags: u32 {
@@ -204,41 +298,116 @@ bitflags::bitflags! {
     }
 }
 
+impl VoiceFlags {
+    /// The flag bits that ptcow doesn't know about
+    #[must_use]
+    pub const fn unknown_bits(self) -> u32 {
+        self.bits() & !Self::all().bits()
+    }
+}
+
 /// Data keeping track of play state of a voice
+///
+/// The raw fields are an implementation detail of rendering, and their meaning can change.
+/// For inspecting the envelope, prefer [`Self::stage`] and [`Self::volume`].
 #[derive(Default, Clone)]
 pub struct VoiceTone {
     /// Keeps track of which sample of the voice we're currently at
     pub smp_pos: f64,
     /// How much to advance the sample position for the next sample
     pub offset_freq: f32,
-    /// Keeps track of what the current volume is (sourced from [`VoiceInstance::env`])
+    /// Envelope volume of the last rendered sample, where 128 is full volume.
+    ///
+    /// While the note is on, this is [`VoiceInstance::env`] at [`Self::env_pos`].
+    /// After that, it fades linearly from [`Self::env_start`] to zero.
     pub env_volume: u8,
-    /// As long as this is greater than zero, the voice keeps playing
+    /// Samples left until the voice stops playing, including the release
     pub life_count: i32,
-    /// As long as this is greater than zero, the envelope position keeps incrementing
+    /// Samples left until the note is released
     pub on_count: i32,
-    /// Plays into envelope volume calculation
+    /// The envelope volume when the note was released
     pub env_start: u8,
-    /// Keeps track of the position in the envelope
+    /// Position in [`VoiceInstance::env`] while the note is on, or in the release after that
     pub env_pos: usize,
-    /// Presumably how long the "release" stage of the volume envelope should last.
+    /// Length of the release in ticks, see [`VoiceInstance::env_release`]
     pub env_release_clock: u32,
 }
 
+/// The stage of the volume envelope of a playing [`VoiceTone`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnvStage {
+    /// The voice is not playing
+    Silent,
+    /// The note is on, and the envelope is going through [`VoiceInstance::env`]
+    Attack,
+    /// The note is on, and the envelope stays at its last value
+    /// (or at full volume if the voice has no envelope)
+    Hold,
+    /// The note is off, and the volume fades to zero
+    Release,
+}
+
+impl VoiceTone {
+    /// The envelope stage of this tone, playing `inst`
+    #[must_use]
+    pub const fn stage(&self, inst: &VoiceInstance) -> EnvStage {
+        if self.life_count <= 0 {
+            EnvStage::Silent
+        } else if self.on_count <= 0 {
+            EnvStage::Release
+        } else if self.env_pos < inst.env.len() {
+            EnvStage::Attack
+        } else {
+            EnvStage::Hold
+        }
+    }
+    /// The envelope volume of the last rendered sample, from 0.0 to 1.0.
+    ///
+    /// 0.0 if the voice is not playing.
+    #[must_use]
+    pub fn volume(&self) -> f32 {
+        if self.life_count <= 0 {
+            return 0.0;
+        }
+        f32::from(self.env_volume) / 128.0
+    }
+}
+
 /// Audio data that gives [`Unit`](crate::Unit)s a voice. In other words, an instrument.
 #[derive(Clone)]
 pub struct Voice {
     /// The base slot every voice has
     pub base: VoiceSlot,
-    /// Wave voices can have an extra slot
+    /// An extra slot that's layered on top of the base slot, see [`Self::layered`]
     pub extra: Option<VoiceSlot>,
     /// Name of the voice
     pub name: String,
+    /// The chunk the voice was read from, see [`Self::forget_original_bytes`]
+    pub(crate) original: Option<Box<OriginalChunk>>,
+}
+
+/// The project file chunk a voice was read from
+#[derive(Clone)]
+pub struct OriginalChunk {
+    /// The chunk as it was read
+    pub bytes: Vec<u8>,
+    /// The chunk as ptcow serialized the voice right after reading it
+    pub regenerated: Vec<u8>,
+}
+
+/// Pitch of a voice in V3 and earlier projects.
+///
+/// Back then, the pitch was applied to the key events of the unit playing the voice.
+pub struct X3xPitch {
+    /// The key the voice sounds at its original pitch
+    pub basic_key: Key,
+    /// Fine tuning, 0 for none
+    pub tuning: f32,
 }
 
 /// Component of a voice
 ///
-/// There can be one or two slots for a wave voice, and one slot for every other voice type.
+/// A voice has one or two slots (see [`Voice::layered`]), which play together.
 #[derive(Clone)]
 pub struct VoiceSlot {
     /// Mostly static data required to generate the voice samples
@@ -247,69 +416,125 @@ pub struct VoiceSlot {
     pub data: VoiceData,
     /// Dynamic data to keep track of voice play state
     pub inst: VoiceInstance,
+    /// Volume the samples of PCM, noise and Ogg/Vorbis data are played at (`0..=128`).
+    ///
+    /// Wave data has its own [volume](WaveData::volume), and ignores this.
+    pub volume: i16,
+    /// Panning of the samples of PCM, noise and Ogg/Vorbis data (`0..=128`, 64 is the center).
+    ///
+    /// Wave data has its own [pan](WaveData::pan), and ignores this.
+    pub pan: i16,
 }
 
 impl VoiceSlot {
-    fn from_unit_and_data(unit: VoiceUnit, data: VoiceData) -> Self {
+    /// The volume of a slot that plays its samples as they are
+    pub const DEFAULT_VOLUME: i16 = 128;
+    /// The pan of a slot that plays its samples as they are
+    pub const DEFAULT_PAN: i16 = 64;
+    /// A slot playing `data`, at the default volume and pan
+    #[must_use]
+    pub fn new(unit: VoiceUnit, data: VoiceData) -> Self {
         Self {
             unit,
             data,
             inst: VoiceInstance::default(),
+            volume: Self::DEFAULT_VOLUME,
+            pan: Self::DEFAULT_PAN,
         }
     }
+    /// Whether the volume and pan leave the samples as they are
+    pub(crate) const fn has_default_mix(&self) -> bool {
+        self.volume == Self::DEFAULT_VOLUME && self.pan == Self::DEFAULT_PAN
+    }
 }
 
 impl Voice {
     /// Create a voice with a single slot containing `unit`
     #[must_use]
     pub fn from_unit_and_data(unit: VoiceUnit, data: VoiceData) -> Self {
-        Self::from_slot(VoiceSlot::from_unit_and_data(unit, data))
+        Self::from_slot(VoiceSlot::new(unit, data))
     }
     /// Create a voice with a single slot containing `data`
     #[must_use]
     pub fn from_data(data: VoiceData) -> Self {
         Self::from_unit_and_data(VoiceUnit::default(), data)
     }
+    /// Create a voice that plays `layer` on top of `base`, for example a PCM attack with
+    /// a noise layer.
+    ///
+    /// Any kinds of data can be layered. `PxTone` only knows layered wave voices, other
+    /// layers are saved in a ptcow extension chunk, and `PxTone` only plays the base slot.
+    #[must_use]
+    pub fn layered(base: VoiceSlot, layer: VoiceSlot) -> Self {
+        Self {
+            extra: Some(layer),
+            ..Self::from_slot(base)
+        }
+    }
     fn from_slot(slot: VoiceSlot) -> Self {
         Self {
             base: slot,
             extra: None,
             name: "<no name>".into(),
+            original: None,
         }
     }
-    pub(crate) fn tone_ready_sample(&mut self, ptn_bldr: &NoiseTable) {
-        for VoiceSlot { inst, data, .. } in self.slots_mut() {
+    /// Forget the bytes this voice was read from.
+    ///
+    /// Wave and noise voices read from a project remember the bytes they were read from.
+    /// When the project is serialized, those bytes are written back unchanged if the voice
+    /// serializes the same way it did right after reading, so untouched voices are saved
+    /// byte-exact, even if ptcow doesn't model every detail of them.
+    /// Edited voices are serialized from their current state as usual.
+    ///
+    /// After calling this, the voice is always serialized from its current state.
+    pub fn forget_original_bytes(&mut self) {
+        self.original = None;
+    }
+    pub(crate) fn tone_ready_sample(
+        &mut self,
+        ptn_bldr: &NoiseTable,
+        sps: SampleRate,
+        resampler: &dyn Resampler,
+    ) {
+        for slot in self.slots_mut() {
+            let mix = (!slot.has_default_mix()).then_some((slot.volume, slot.pan));
+            let VoiceSlot { inst, data, .. } = slot;
             inst.num_samples = 0;
+            inst.sample_rate = sps;
 
             match data {
                 VoiceData::Pcm(pcm) => {
-                    let (body, buf) = pcm.to_converted(NATIVE_SAMPLE_RATE);
+                    let (body, buf) = pcm.to_converted_with(sps, resampler);
                     inst.num_samples = body;
                     inst.sample_buf = buf;
                 }
                 VoiceData::Noise(ptn) => {
-                    inst.sample_buf = noise_to_pcm(ptn, ptn_bldr).into_sample_buf();
-                    inst.num_samples = ptn.smp_num_44k;
+                    let pcm = noise_to_pcm_at(ptn, ptn_bldr, sps);
+                    inst.num_samples = pcm.num_samples;
+                    inst.sample_buf = pcm.into_sample_buf();
                 }
                 VoiceData::Wave(data) => {
                     inst.recalc_wave_data(&data.points, data.volume, data.pan);
                 }
+                // Without Ogg/Vorbis support, or if the data can't be decoded,
+                // the voice stays silent
+                #[cfg_attr(not(feature = "oggv"), expect(unused_variables))]
                 VoiceData::OggV(ogg_vdata) => {
+                    inst.sample_buf.clear();
                     #[cfg(feature = "oggv")]
-                    match crate::voice_data::oggv::decode_oggv(&ogg_vdata.raw_bytes) {
-                        Some(pcm) => {
-                            let (body, buf) = pcm.to_converted(NATIVE_SAMPLE_RATE);
-                            inst.num_samples = body;
-                            inst.sample_buf = buf;
-                        }
-                        None => {
-                            eprintln!("Failed to decode Ogg/Vorbis data");
-                        }
+                    if let Some(pcm) = crate::voice_data::oggv::decode_oggv(&ogg_vdata.raw_bytes) {
+                        let (body, buf) = pcm.to_converted_with(sps, resampler);
+                        inst.num_samples = body;
+                        inst.sample_buf = buf;
                     }
-                    #[cfg(not(feature = "oggv"))]
-                    panic!("Ogg/Vorbis support was disabled.");
                 }
             }
+            if let Some((volume, pan)) = mix
+                && !matches!(data, VoiceData::Wave(_))
+            {
+                apply_mix(&mut inst.sample_buf, volume, pan);
+            }
         }
     }
 
@@ -322,9 +547,73 @@ impl Voice {
     }
     /// Recalculate the sample and envelope data for this voice
     pub fn recalculate(&mut self, noise_tbl: &NoiseTable, out_sps: SampleRate) {
-        self.tone_ready_sample(noise_tbl);
+        self.recalculate_with(noise_tbl, out_sps, &NearestResampler);
+    }
+    /// Like [`Self::recalculate`], but PCM and Ogg/Vorbis samples are converted with `resampler`
+    pub fn recalculate_with(
+        &mut self,
+        noise_tbl: &NoiseTable,
+        out_sps: SampleRate,
+        resampler: &dyn Resampler,
+    ) {
+        self.recalculate_at(noise_tbl, out_sps, NATIVE_SAMPLE_RATE, resampler);
+    }
+    /// Like [`Self::recalculate_with`], but PCM, noise and Ogg/Vorbis voices are rendered at
+    /// `voice_sps`, see [`RenderConfig`](crate::RenderConfig).
+    pub fn recalculate_at(
+        &mut self,
+        noise_tbl: &NoiseTable,
+        out_sps: SampleRate,
+        voice_sps: SampleRate,
+        resampler: &dyn Resampler,
+    ) {
+        self.tone_ready_sample(noise_tbl, voice_sps, resampler);
         self.tone_ready_envelopes(out_sps);
     }
+    /// Render the prepared samples of this voice into [`PcmData`], mixing the slots together.
+    ///
+    /// This is exactly what the voice plays at its basic key, so it can be used to export
+    /// instruments for use in other software.
+    /// The voice needs to be [recalculated](Self::recalculate) first.
+    #[must_use]
+    pub fn to_pcm_data(&self) -> PcmData {
+        let mut pcm = self.base.inst.to_pcm_data();
+        if let Some(extra) = &self.extra {
+            let extra = &extra.inst;
+            // A wave layer on a voice rendered at another rate
+            let (num_samples, converted);
+            let sample_buf = if extra.sample_rate == self.base.inst.sample_rate {
+                num_samples = extra.num_samples;
+                &extra.sample_buf
+            } else {
+                (num_samples, converted) = extra
+                    .to_pcm_data()
+                    .to_converted_with(self.base.inst.sample_rate, &NearestResampler);
+                &converted
+            };
+            if num_samples > pcm.num_samples {
+                pcm.num_samples = num_samples;
+                pcm.smp.resize(sample_buf.len(), 0);
+            }
+            let dst = pcm.smp.as_chunks_mut::<2>().0;
+            let src = sample_buf.as_chunks::<2>().0;
+            for (dst, src) in std::iter::zip(dst, src) {
+                let mixed = i16::from_le_bytes(*dst).saturating_add(i16::from_le_bytes(*src));
+                *dst = mixed.to_le_bytes();
+            }
+        }
+        pcm
+    }
+    /// Export the prepared samples of this voice as a `.wav` file.
+    ///
+    /// See [`Self::to_pcm_data`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn export_wav(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_pcm_data().to_wav())
+    }
     /// Returns an immutable iterator over the slots of this voice
     pub fn slots(&self) -> impl Iterator<Item = &VoiceSlot> {
         std::iter::once(&self.base).chain(&self.extra)
@@ -338,7 +627,8 @@ impl Voice {
 // Never allocate an envelope larger than this (1 megabyte)
 const ENV_SIZE_SAFETY_LIMIT: usize = 1_048_576;
 
-fn update_wave_ptv(wave: &WaveDataPoints, inst: &mut VoiceInstance, volume: i16, pan: i16) {
+/// The gain of the left and right channel for `pan`, where 64 is 1.0 for both
+const fn pan_volume(pan: i16) -> [i16; 2] {
     let mut pan_volume: [i16; 2] = [64, 64];
 
     if pan > 64 {
@@ -347,6 +637,23 @@ fn update_wave_ptv(wave: &WaveDataPoints, inst: &mut VoiceInstance, volume: i16,
     if pan < 64 {
         pan_volume[1] = pan;
     }
+    pan_volume
+}
+
+/// Scale prepared stereo 16 bit samples by `volume`, and pan them
+fn apply_mix(sample_buf: &mut [u8], volume: i16, pan: i16) {
+    let gains = pan_volume(pan).map(|pan| f32::from(volume) / 128. * f32::from(pan) / 64.);
+    for frame in sample_buf.as_chunks_mut::<4>().0 {
+        for (smp, gain) in frame.as_chunks_mut::<2>().0.iter_mut().zip(gains) {
+            #[expect(clippy::cast_possible_truncation)]
+            let scaled = (f32::from(i16::from_le_bytes(*smp)) * gain) as i16;
+            *smp = scaled.to_le_bytes();
+        }
+    }
+}
+
+fn update_wave_ptv(wave: &WaveDataPoints, inst: &mut VoiceInstance, volume: i16, pan: i16) {
+    let pan_volume = pan_volume(pan);
 
     let osci = OsciArgs {
         volume,
@@ -355,15 +662,7 @@ fn update_wave_ptv(wave: &WaveDataPoints, inst: &mut VoiceInstance, volume: i16,
 
     let smp_buf_16: &mut [i16] = bytemuck::cast_slice_mut(&mut inst.sample_buf[..]);
     for s in 0..inst.num_samples {
-        let osc = match wave {
-            WaveDataPoints::Coord {
-                points: coordinates,
-                resolution,
-            } => coord(osci, coordinates, s.try_into().unwrap(), *resolution),
-            WaveDataPoints::Overtone {
-                points: coordinates,
-            } => overtone(osci, coordinates, s.try_into().unwrap()),
-        };
+        let osc = wave.osc(osci, s.try_into().unwrap());
         for c in 0..2 {
             let mut work = osc * f64::from(pan_volume[c]) / 64.;
             work = work.clamp(-1.0, 1.0);
@@ -372,3 +671,80 @@ fn update_wave_ptv(wave: &WaveDataPoints, inst: &mut VoiceInstance, volume: i16,
         }
     }
 }
+
+#[test]
+fn test_voice_to_pcm_data() {
+    let table = NoiseTable::generate();
+    let mut voices = crate::presets::wave::factory_voices();
+    let organ = &mut voices[6];
+    organ.recalculate(&table, NATIVE_SAMPLE_RATE);
+    let pcm = organ.to_pcm_data();
+    assert_eq!(pcm.num_samples, 400);
+    assert_eq!(pcm.smp.len(), 400 * 4);
+    let wav = pcm.to_wav();
+    assert_eq!(&wav[..4], b"RIFF");
+    assert_eq!(wav.len(), 44 + pcm.smp.len());
+}
+
+#[test]
+fn test_envelope_editing() {
+    let mut env = EnvelopeSrc {
+        seconds_per_point: 1000,
+        points: Vec::new(),
+    };
+    assert_eq!(env.release(), None);
+    assert!(env.preview(NATIVE_SAMPLE_RATE).is_empty());
+    env.push_point(0, 0);
+    env.push_point(100, 128);
+    env.set_release(50);
+    let xy: Vec<_> = env.points.iter().map(|pt| (pt.x, pt.y)).collect();
+    assert_eq!(xy, [(0, 0), (100, 128), (50, 0)]);
+    assert_eq!(env.validate(), Ok(()));
+    // 100 ms of attack
+    let preview = env.preview(NATIVE_SAMPLE_RATE);
+    assert_eq!(preview.len(), 4410);
+    assert_eq!(preview.first(), Some(&0));
+    assert_eq!(preview[2205], 64);
+    let mut inst = VoiceInstance::default();
+    inst.recalc_envelope(NATIVE_SAMPLE_RATE, &env);
+    assert_eq!(inst.env, preview);
+    assert_eq!(inst.env_release, 2205);
+    env.seconds_per_point = 0;
+    assert_eq!(env.validate(), Err(EnvelopeError::ZeroSecondsPerPoint));
+    env.seconds_per_point = 1;
+    env.push_point(u16::MAX, 0);
+    assert!(matches!(env.validate(), Err(EnvelopeError::TooLong(_))));
+    assert!(env.preview(NATIVE_SAMPLE_RATE).is_empty());
+}
+
+#[test]
+fn test_waveform_previews() {
+    let table = NoiseTable::generate();
+    let mut voices = crate::presets::wave::factory_voices();
+    let organ = &mut voices[6];
+    organ.recalculate(&table, NATIVE_SAMPLE_RATE);
+    let VoiceData::Wave(wave) = &organ.base.data else {
+        panic!("not a wave voice");
+    };
+    // Centered, so the preview is the left channel of the voice
+    assert_eq!(wave.pan, 64);
+    let left: Vec<i16> = organ
+        .slots()
+        .next()
+        .unwrap()
+        .inst
+        .sample_buf
+        .chunks_exact(4)
+        .map(|frame| i16::from_le_bytes([frame[0], frame[1]]))
+        .collect();
+    assert_eq!(wave.render(400, wave.volume), left);
+    assert_eq!(wave.render(16, wave.volume).len(), 16);
+
+    let snare = crate::presets::noise::snare();
+    let preview = snare.render_preview(&table, 1000);
+    assert_eq!(preview.len(), 1000);
+    assert!(preview.iter().any(|&smp| smp != 0));
+    let full = snare.render_preview(&table, u32::MAX);
+    assert_eq!(full.len(), snare.smp_num_44k as usize);
+    assert_eq!(full[..1000], preview);
+}
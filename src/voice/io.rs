@@ -1,11 +1,11 @@
 use crate::{
-    Bps, ChNum, SampleRate, VoiceData, VoiceUnit,
+    Bps, ChNum, Key, SourceSampleRate, VoiceData, VoiceUnit,
     herd::Tag,
-    io::write_varint,
+    io::{LeField as _, io_struct, write_varint},
     point::EnvPt,
     pulse_oscillator::OsciPt,
     result::{ProjectReadError, ProjectWriteError, ReadResult, WriteResult},
-    voice::{EnvelopeSrc, Voice, VoiceFlags, VoiceSlot},
+    voice::{EnvelopeSrc, Voice, VoiceFlags, VoiceSlot, X3xPitch},
     voice_data::{
         noise::NoiseData,
         oggv::OggVData,
@@ -14,30 +14,27 @@ use crate::{
     },
 };
 
-#[cfg(feature = "oggv")]
-mod oggv;
-
-#[derive(Default, bytemuck::AnyBitPattern, Clone, Copy)]
-#[repr(C)]
-struct IoPcm {
-    x3x_unit_no: u16,
-    basic_key: u16,
-    voice_flags: VoiceFlags,
-    ch: u8,
-    bps: u16,
-    sps: SampleRate,
-    tuning: f32,
-    data_size: u32,
+io_struct! {
+    struct IoPcm {
+        x3x_unit_no: u16,
+        basic_key: u16,
+        voice_flags: VoiceFlags,
+        ch: u16,
+        bps: u16,
+        sps: u32,
+        tuning: f32,
+        data_size: u32,
+    }
 }
 
-#[derive(Default, bytemuck::AnyBitPattern, bytemuck::NoUninit, Clone, Copy)]
-#[repr(C)]
-struct IoPtn {
-    x3x_unit_no: u16,
-    basic_key: u16,
-    voice_flags: VoiceFlags,
-    tuning: f32,
-    rrr: i32,
+io_struct! {
+    struct IoPtn {
+        x3x_unit_no: u16,
+        basic_key: u16,
+        voice_flags: VoiceFlags,
+        tuning: f32,
+        rrr: i32,
+    }
 }
 
 /// I/O
@@ -45,7 +42,7 @@ impl Voice {
     pub(crate) fn read_mate_pcm(rd: &mut crate::io::Reader) -> ReadResult<Self> {
         let _size = rd.next::<u32>()?;
 
-        let pcm = rd.next::<IoPcm>()?;
+        let pcm = rd.next_le::<IoPcm>()?;
 
         let bps = match pcm.bps {
             8 => Bps::B8,
@@ -61,57 +58,47 @@ impl Voice {
         let mut pcm_data = PcmData::new();
         pcm_data.create(
             chnum,
-            pcm.sps.into(),
+            SourceSampleRate(pcm.sps),
             bps,
-            pcm.data_size / u32::from(bps as u16 / 8 * u16::from(pcm.ch)),
+            pcm.data_size / u32::from(bps as u16 / 8 * pcm.ch),
         );
         let smp_buf = pcm_data.sample_mut();
         rd.fill_slice(smp_buf)?;
         let vu = VoiceUnit {
             flags: pcm.voice_flags,
-            basic_key: i32::from(pcm.basic_key),
+            basic_key: Key(pcm.basic_key.into()),
             tuning: pcm.tuning,
         };
         Ok(Self::from_unit_and_data(vu, VoiceData::Pcm(pcm_data)))
     }
 
     pub(crate) fn write_mate_pcm(&self, out: &mut Vec<u8>, data: &PcmData) {
+        self.write_mate_pcm_head(out, data);
+        out.extend_from_slice(&data.smp);
+    }
+    /// The `matePCM ` chunk, without the samples that end it
+    pub(crate) fn write_mate_pcm_head(&self, out: &mut Vec<u8>, data: &PcmData) {
         out.extend_from_slice(Tag::MatePCM.to_code());
         #[expect(clippy::cast_possible_truncation)]
-        let io_size: u32 = size_of::<IoPcm>() as u32 + data.smp.len() as u32;
+        let io_size: u32 = IoPcm::SIZE as u32 + data.smp.len() as u32;
         out.extend_from_slice(&io_size.to_le_bytes());
         let vu = &self.base.unit;
         let io_pcm = IoPcm {
             x3x_unit_no: 0,
-            basic_key: vu.basic_key.try_into().unwrap(),
+            basic_key: vu.basic_key.0.try_into().unwrap(),
             voice_flags: vu.flags,
             ch: data.ch as _,
             bps: data.bps as _,
-            // TODO: Normally this assumption shouldn't be violated, but ogg voices
-            // can have higher sps than what can fit into 16 bits.
-            //
-            // The fix for that is to not load ogg voices as pcm voices, but to properly load them as
-            // ogg voices, and serialize them as such, rather than as pcm.
-            sps: data.sps.try_into().unwrap(),
+            sps: data.sps.get(),
             tuning: vu.tuning,
             data_size: data.smp.len().try_into().unwrap(),
         };
-        let mut io_pcm_byte_buf: Vec<u8> = vec![0; size_of::<IoPcm>()];
-        // Safety: YOLO
-        unsafe {
-            std::ptr::copy(
-                (&raw const io_pcm).cast(),
-                io_pcm_byte_buf.as_mut_ptr(),
-                size_of::<IoPcm>(),
-            );
-        }
-        out.extend_from_slice(&io_pcm_byte_buf);
-        out.extend_from_slice(&data.smp);
+        io_pcm.write_le(out);
     }
 
     pub(crate) fn read_mate_ptn(rd: &mut crate::io::Reader) -> ReadResult<Self> {
         let _size = rd.next::<u32>()?;
-        let ptn = rd.next::<IoPtn>()?;
+        let ptn = rd.next_le::<IoPtn>()?;
 
         if ptn.rrr > 1 || ptn.rrr < 0 {
             return Err(ProjectReadError::FmtUnknown);
@@ -121,7 +108,7 @@ impl Voice {
         noise_data.read(rd)?;
         let vu = VoiceUnit {
             flags: ptn.voice_flags,
-            basic_key: i32::from(ptn.basic_key),
+            basic_key: Key(ptn.basic_key.into()),
             tuning: ptn.tuning,
         };
         Ok(Self::from_unit_and_data(vu, VoiceData::Noise(noise_data)))
@@ -135,22 +122,27 @@ impl Voice {
         let vu = &self.base.unit;
         let ptn = IoPtn {
             x3x_unit_no: 0,
-            basic_key: vu.basic_key.try_into().unwrap(),
+            basic_key: vu.basic_key.0.try_into().unwrap(),
             voice_flags: vu.flags,
             tuning: vu.tuning,
             rrr: 1,
         };
-        out.extend_from_slice(bytemuck::bytes_of(&ptn));
+        ptn.write_le(out);
         data.write(out);
         // Write io size retroactively
         let bytes_written: u32 = (out.len() - (io_size_pos + 4)).try_into().unwrap();
         out[io_size_pos..io_size_pos + 4].copy_from_slice(&bytes_written.to_le_bytes());
     }
 
-    pub(crate) fn read_mate_ptv(rd: &mut crate::io::Reader) -> ReadResult<Self> {
+    pub(crate) fn read_mate_ptv(rd: &mut crate::io::Reader) -> ReadResult<(Self, X3xPitch)> {
         let _size: u32 = rd.next()?;
-        let _ptv: IoPtv = rd.next()?;
-        Self::ptv_read(rd)
+        let ptv: IoPtv = rd.next_le()?;
+        let (voice, x3x_basic_key) = Self::ptv_read(rd)?;
+        let pitch = X3xPitch {
+            basic_key: x3x_basic_key,
+            tuning: ptv.x3x_tuning,
+        };
+        Ok((voice, pitch))
     }
     pub(crate) fn write_mate_ptv(&self, out: &mut Vec<u8>) -> WriteResult {
         out.extend_from_slice(Tag::MatePTV.to_code());
@@ -163,7 +155,7 @@ impl Voice {
         let size: u32 = 0;
         let idx_before_written = out.len();
         out.extend_from_slice(&size.to_le_bytes());
-        out.extend_from_slice(bytemuck::bytes_of(&io_ptv));
+        io_ptv.write_le(out);
         self.ptv_write(out)?;
         let idx_after_written = out.len();
         #[expect(clippy::cast_possible_truncation)]
@@ -177,18 +169,23 @@ impl Voice {
         Ok(())
     }
     pub(crate) fn write_mate_oggv(&self, out: &mut Vec<u8>, data: &OggVData) {
+        self.write_mate_oggv_head(out, data);
+        out.extend_from_slice(&data.raw_bytes);
+    }
+    /// The `mateOGGV` chunk, without the Ogg/Vorbis data that ends it
+    pub(crate) fn write_mate_oggv_head(&self, out: &mut Vec<u8>, data: &OggVData) {
         out.extend_from_slice(Tag::MateOGGV.to_code());
         let misc_size: u32 = 4 * 4; // ch, sps2, smp_num, size2
         #[expect(clippy::cast_possible_truncation)]
-        let size: u32 = size_of::<IoOggv>() as u32 + data.raw_bytes.len() as u32 + misc_size;
+        let size: u32 = IoOggv::SIZE as u32 + data.raw_bytes.len() as u32 + misc_size;
         out.extend_from_slice(&size.to_le_bytes());
         let io_oggv: IoOggv = IoOggv {
             xxx: 0,
-            basic_key: self.base.unit.basic_key.try_into().unwrap(),
+            basic_key: self.base.unit.basic_key.0.try_into().unwrap(),
             voice_flags: self.base.unit.flags,
             tuning: self.base.unit.tuning,
         };
-        out.extend_from_slice(bytemuck::bytes_of(&io_oggv));
+        io_oggv.write_le(out);
         let ch: i32 = data.ch;
         out.extend_from_slice(&ch.to_le_bytes());
         let sps2: i32 = data.sps2;
@@ -198,15 +195,11 @@ impl Voice {
         #[expect(clippy::cast_possible_truncation)]
         let size2: u32 = data.raw_bytes.len() as u32;
         out.extend_from_slice(&size2.to_le_bytes());
-        if size2 == 0 {
-            return;
-        }
-        out.extend_from_slice(&data.raw_bytes);
     }
     /// Read a voice from `.ptvoice` data
     pub fn from_ptvoice(data: &[u8]) -> ReadResult<Self> {
         let mut reader = crate::io::Reader { data, cur: 0 };
-        Self::ptv_read(&mut reader)
+        Self::ptv_read(&mut reader).map(|(voice, _)| voice)
     }
     /// Serialize to `.ptvoice` data
     pub fn to_ptvoice(&self) -> WriteResult<Vec<u8>> {
@@ -215,7 +208,8 @@ impl Voice {
         Ok(out)
     }
     #[expect(clippy::inconsistent_digit_grouping)]
-    fn ptv_read(rd: &mut crate::io::Reader) -> ReadResult<Self> {
+    /// Also returns the basic key stored for V3 and earlier projects
+    fn ptv_read(rd: &mut crate::io::Reader) -> ReadResult<(Self, Key)> {
         if &rd.next::<[u8; 8]>()? != b"PTVOICE-" {
             return Err(ProjectReadError::InvalidTag);
         }
@@ -225,7 +219,7 @@ impl Voice {
             return Err(ProjectReadError::FmtNewer);
         }
         let _total: i32 = rd.next()?;
-        let _x3x_basic = rd.next_varint()?;
+        let x3x_basic_key = Key(rd.next_varint()?.cast_signed());
         let work1 = rd.next_varint()?;
         let work2 = rd.next_varint()?;
         if work1 != 0 || work2 != 0 {
@@ -236,7 +230,7 @@ impl Voice {
             1 | 2 => {}
             _ => return Err(ProjectReadError::FmtUnknown),
         }
-        Ok(Self {
+        let voice = Self {
             base: read_wave_slot(rd)?,
             extra: if num == 2 {
                 Some(read_wave_slot(rd)?)
@@ -244,7 +238,9 @@ impl Voice {
                 None
             },
             name: "<no name>".into(),
-        })
+            original: None,
+        };
+        Ok((voice, x3x_basic_key))
     }
     #[expect(clippy::inconsistent_digit_grouping)]
     fn ptv_write(&self, out: &mut Vec<u8>) -> WriteResult {
@@ -260,12 +256,18 @@ impl Voice {
         let work2: u32 = 0;
         write_varint(work1, out);
         write_varint(work2, out);
-        write_varint(self.num_slots().into(), out);
-        for VoiceSlot { unit, data, .. } in self.slots() {
-            let VoiceData::Wave(data) = data else {
-                unreachable!()
-            };
-            write_varint(unit.basic_key.cast_unsigned(), out);
+        // Layers of other kinds are written to an extension chunk, see `write_layers`
+        let slots: Vec<_> = self
+            .slots()
+            .filter_map(|VoiceSlot { unit, data, .. }| match data {
+                VoiceData::Wave(data) => Some((unit, data)),
+                _ => None,
+            })
+            .collect();
+        #[expect(clippy::cast_possible_truncation)]
+        write_varint(slots.len() as u32, out);
+        for (unit, data) in slots {
+            write_varint(unit.basic_key.0.cast_unsigned(), out);
             write_varint(data.volume.cast_unsigned().into(), out);
             write_varint(data.pan.cast_unsigned().into(), out);
             write_varint(unit.tuning.to_bits(), out);
@@ -286,10 +288,11 @@ impl Voice {
         out[total_offset..total_offset + 4].copy_from_slice(&amount_written.to_le_bytes());
         Ok(())
     }
+    /// The Ogg/Vorbis data is kept as is, so it can be written back unchanged.
+    /// It's decoded when the voice is [recalculated](Self::recalculate).
     pub(crate) fn read_ogg(rd: &mut crate::io::Reader<'_>) -> ReadResult<Self> {
         let _size: u32 = rd.next()?;
-        #[cfg_attr(not(feature = "oggv"), expect(unused_variables))]
-        let io_oggv: IoOggv = rd.next()?;
+        let io_oggv: IoOggv = rd.next_le()?;
         let ch: i32 = rd.next()?;
         let sps2: i32 = rd.next()?;
         let smp_num: i32 = rd.next()?;
@@ -297,24 +300,25 @@ impl Voice {
         if size == 0 {
             return Err(ProjectReadError::InvalidData);
         }
-        #[cfg(feature = "oggv")]
-        {
-            let slot = oggv::read(rd, &io_oggv, size as usize, ch, sps2, smp_num);
-            Ok(Self::from_slot(slot))
-        }
-        #[cfg(not(feature = "oggv"))]
-        {
-            Err(ProjectReadError::OggvSupportDisabled)
-        }
-    }
-
-    const fn num_slots(&self) -> u8 {
-        if self.extra.is_some() { 2 } else { 1 }
+        let mut raw_bytes = vec![0; size as usize];
+        rd.fill_slice(&mut raw_bytes)?;
+        let data = VoiceData::OggV(OggVData {
+            raw_bytes,
+            ch,
+            sps2,
+            smp_num,
+        });
+        let unit = VoiceUnit {
+            flags: io_oggv.voice_flags,
+            basic_key: Key(io_oggv.basic_key.into()),
+            tuning: io_oggv.tuning,
+        };
+        Ok(Self::from_unit_and_data(unit, data))
     }
 }
 
 fn read_wave_slot(rd: &mut crate::io::Reader) -> ReadResult<VoiceSlot> {
-    let basic_key = rd.next_varint()?.cast_signed();
+    let basic_key = Key(rd.next_varint()?.cast_signed());
     let volume = rd.next_varint()?.cast_signed().try_into().unwrap();
     let pan = rd.next_varint()?.cast_signed().try_into().unwrap();
     let tuning = f32::from_bits(rd.next_varint()?);
@@ -343,7 +347,7 @@ fn read_wave_slot(rd: &mut crate::io::Reader) -> ReadResult<VoiceSlot> {
     if data_flags & PTV_DATAFLAG_ENVELOPE != 0 {
         read_envelope(rd, &mut envelope)?;
     }
-    Ok(VoiceSlot::from_unit_and_data(
+    Ok(VoiceSlot::new(
         vu,
         VoiceData::Wave(WaveData {
             points: wave_data_inner,
@@ -354,13 +358,13 @@ fn read_wave_slot(rd: &mut crate::io::Reader) -> ReadResult<VoiceSlot> {
     ))
 }
 
-#[derive(Clone, Copy, bytemuck::AnyBitPattern, bytemuck::NoUninit)]
-#[repr(C)]
-struct IoOggv {
-    xxx: u16,
-    basic_key: u16,
-    voice_flags: VoiceFlags,
-    tuning: f32,
+io_struct! {
+    struct IoOggv {
+        xxx: u16,
+        basic_key: u16,
+        voice_flags: VoiceFlags,
+        tuning: f32,
+    }
 }
 
 fn read_wave(rd: &mut crate::io::Reader, wave_data: &mut WaveDataPoints) -> ReadResult {
@@ -472,14 +476,75 @@ fn write_envelope(envelope: &EnvelopeSrc, out: &mut Vec<u8>) {
     }
 }
 
-#[derive(Clone, Copy, bytemuck::AnyBitPattern, bytemuck::NoUninit)]
-#[repr(C)]
-struct IoPtv {
-    x3x_unit_no: u16,
-    rrr: u16,
-    x3x_tuning: f32,
-    size: i32,
+io_struct! {
+    struct IoPtv {
+        x3x_unit_no: u16,
+        rrr: u16,
+        x3x_tuning: f32,
+        size: i32,
+    }
 }
 
 const PTV_DATAFLAG_WAVE: u32 = 1;
 const PTV_DATAFLAG_ENVELOPE: u32 = 2;
+
+#[test]
+fn test_unknown_voice_flags_roundtrip() {
+    use crate::{Herd, MooInstructions, NATIVE_SAMPLE_RATE, Song, read_song, serialize_project};
+    let flags = VoiceFlags::WAVE_LOOP | VoiceFlags::from_bits_retain(0x8000_0100);
+    assert_eq!(flags.unknown_bits(), 0x8000_0100);
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(crate::presets::wave::factory_voices());
+    ins.voices.push(Voice::from_data(VoiceData::Noise(
+        crate::presets::noise::snare(),
+    )));
+    let mut pcm = PcmData::new();
+    pcm.create(ChNum::Mono, SourceSampleRate(22_050), Bps::B8, 16);
+    ins.voices.push(Voice::from_data(VoiceData::Pcm(pcm)));
+    for voice in &mut ins.voices {
+        for slot in voice.slots_mut() {
+            slot.unit.flags = flags;
+        }
+    }
+    let data = serialize_project(&Song::default(), &Herd::default(), &ins).unwrap();
+    let (_, _, ins) = read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert!(!ins.voices.is_empty());
+    for voice in &ins.voices {
+        for slot in voice.slots() {
+            assert_eq!(slot.unit.flags.bits(), flags.bits());
+        }
+    }
+    let voice = Voice::from_ptvoice(&ins.voices[crate::VoiceIdx(0)].to_ptvoice().unwrap()).unwrap();
+    assert_eq!(voice.base.unit.flags.unknown_bits(), 0x8000_0100);
+}
+
+#[test]
+fn test_io_pcm_layout() {
+    let io_pcm = IoPcm {
+        x3x_unit_no: 0,
+        basic_key: 0x4500,
+        voice_flags: VoiceFlags::WAVE_LOOP,
+        ch: 2,
+        bps: 16,
+        sps: 96_000,
+        tuning: 1.0,
+        data_size: 4,
+    };
+    let mut out = Vec::new();
+    io_pcm.write_le(&mut out);
+    assert_eq!(out.len(), IoPcm::SIZE);
+    #[rustfmt::skip]
+    assert_eq!(out, [
+        0, 0,
+        0x00, 0x45,
+        1, 0, 0, 0,
+        2, 0,
+        16, 0,
+        0x00, 0x77, 0x01, 0,
+        0, 0, 0x80, 0x3f,
+        4, 0, 0, 0,
+    ]);
+    let read = crate::io::Reader { data: &out, cur: 0 }.next_le::<IoPcm>().unwrap();
+    assert_eq!(read.sps, 96_000);
+    assert_eq!(read.voice_flags.bits(), VoiceFlags::WAVE_LOOP.bits());
+}
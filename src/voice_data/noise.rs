@@ -4,7 +4,7 @@ use bitflags::Flags;
 use crate::{
     EnvPt, NATIVE_SAMPLE_RATE,
     io::write_varint,
-    noise_builder::{NoiseDesignOscillator, NoiseType},
+    noise_builder::{NoiseDesignOscillator, NoiseTable, NoiseType, noise_to_pcm},
     result::{ProjectReadError, ReadResult},
 };
 
@@ -37,6 +37,27 @@ impl NoiseData {
         self.write(&mut out);
         out
     }
+    /// Render the start of the noise for drawing it, as mono samples at
+    /// [`NATIVE_SAMPLE_RATE`].
+    ///
+    /// At most `max_samples` samples are rendered, so long noises stay cheap to preview.
+    /// The channels are mixed down, so panning doesn't show.
+    #[must_use]
+    pub fn render_preview(&self, table: &NoiseTable, max_samples: u32) -> Vec<i16> {
+        let mut noise = self.clone();
+        noise.smp_num_44k = noise.smp_num_44k.min(max_samples);
+        let pcm = noise_to_pcm(&mut noise, table);
+        pcm.smp
+            .chunks_exact(4)
+            .map(|frame| {
+                let left = i16::from_le_bytes([frame[0], frame[1]]);
+                let right = i16::from_le_bytes([frame[2], frame[3]]);
+                #[expect(clippy::cast_possible_truncation)]
+                let mono = i32::midpoint(left.into(), right.into()) as i16;
+                mono
+            })
+            .collect()
+    }
     pub(crate) fn read(&mut self, rd: &mut crate::io::Reader) -> ReadResult {
         let mut design_unit: &mut NoiseDesignUnit;
 
@@ -60,8 +81,9 @@ impl NoiseData {
         }
         for u in 0..unit_num {
             design_unit = &mut self.units[u as usize];
-            #[expect(clippy::cast_possible_truncation)]
-            let flags = NoiseDesignUnitFlags::from_bits_retain(rd.next_varint()? as u8);
+            let flags =
+                u8::try_from(rd.next_varint()?).map_err(|_| ProjectReadError::FmtUnknown)?;
+            let flags = NoiseDesignUnitFlags::from_bits_retain(flags);
             design_unit.ser_flags = flags;
 
             if flags.contains_unknown_bits() {
@@ -76,8 +98,14 @@ impl NoiseData {
                 design_unit.enves.clear();
                 for _ in 0..enve_num {
                     design_unit.enves.push(EnvPt {
-                        x: rd.next_varint()?.try_into().unwrap(),
-                        y: rd.next_varint()?.try_into().unwrap(),
+                        x: rd
+                            .next_varint()?
+                            .try_into()
+                            .map_err(|_| ProjectReadError::FmtUnknown)?,
+                        y: rd
+                            .next_varint()?
+                            .try_into()
+                            .map_err(|_| ProjectReadError::FmtUnknown)?,
                     });
                 }
             }
@@ -94,6 +122,9 @@ impl NoiseData {
             if flags.contains(NoiseDesignUnitFlags::OSC_VOLU) {
                 read_oscillator(&mut design_unit.volu, rd)?;
             }
+            if flags.contains(NoiseDesignUnitFlags::OSC_PAN) {
+                read_oscillator(&mut design_unit.pan_osc, rd)?;
+            }
         }
 
         Ok(())
@@ -108,13 +139,7 @@ impl NoiseData {
         let unit_num: u8 = self.units.len().try_into().unwrap();
         out.push(unit_num);
         for unit in &self.units {
-            let mut ser_flags = unit.ser_flags;
-            // We always serialize the envelope (seems to be PxTone behavior)
-            ser_flags.insert(NoiseDesignUnitFlags::ENVELOPE);
-            // We write the pan if it's not 0
-            if unit.pan != 0 {
-                ser_flags.insert(NoiseDesignUnitFlags::PAN);
-            }
+            let ser_flags = unit.written_flags();
             write_varint(ser_flags.bits().into(), out);
             let enve_num: u32 = unit.enves.len().try_into().unwrap();
             write_varint(enve_num, out);
@@ -134,6 +159,9 @@ impl NoiseData {
             if ser_flags.contains(NoiseDesignUnitFlags::OSC_VOLU) {
                 write_oscillator(&unit.volu, out);
             }
+            if ser_flags.contains(NoiseDesignUnitFlags::OSC_PAN) {
+                write_oscillator(&unit.pan_osc, out);
+            }
         }
     }
 
@@ -155,6 +183,7 @@ impl NoiseData {
             fix_unit(&mut design_unit.main);
             fix_unit(&mut design_unit.freq);
             fix_unit(&mut design_unit.volu);
+            fix_unit(&mut design_unit.pan_osc);
         }
     }
 
@@ -172,7 +201,8 @@ const MAX_NOISEEDITENVELOPENUM: usize = 3;
 const NOISEDESIGNLIMIT_SMPNUM: u32 = 48000 * 10;
 const NOISEDESIGNLIMIT_ENVE_X: u16 = 1000 * 10;
 const NOISEDESIGNLIMIT_ENVE_Y: u8 = 100;
-const NOISEDESIGNLIMIT_OSC_FREQUENCY: f32 = NATIVE_SAMPLE_RATE as f32;
+#[expect(clippy::cast_precision_loss, reason = "the native sample rate fits")]
+const NOISEDESIGNLIMIT_OSC_FREQUENCY: f32 = NATIVE_SAMPLE_RATE.get() as f32;
 const NOISEDESIGNLIMIT_OSC_VOLUME: f32 = 200.0;
 const NOISEDESIGNLIMIT_OSC_OFFSET: f32 = 100.0;
 
@@ -182,13 +212,17 @@ const fn fix_unit(osc: &mut NoiseDesignOscillator) {
     osc.offset = osc.offset.clamp(0., NOISEDESIGNLIMIT_OSC_OFFSET);
 }
 
+/// Read an oscillator.
+///
+/// Wave type 0 ("None") is a wave table of silence in `PxTone`.
+/// We don't have a [`NoiseType`] for it, so it's read as a [`NoiseType::Sine`] oscillator with
+/// zero volume, which sounds the same.
 #[expect(clippy::cast_precision_loss)]
 fn read_oscillator(osc: &mut NoiseDesignOscillator, rd: &mut crate::io::Reader) -> ReadResult {
     let wave_type = rd.next_varint()?;
 
     let type_ = match wave_type {
-        0 => panic!("None wave type detected"),
-        1 => NoiseType::Sine,
+        0 | 1 => NoiseType::Sine,
         2 => NoiseType::Saw,
         3 => NoiseType::Rect,
         4 => NoiseType::Random,
@@ -211,6 +245,9 @@ fn read_oscillator(osc: &mut NoiseDesignOscillator, rd: &mut crate::io::Reader)
     osc.freq = rd.next_varint()? as f32 / 10.;
     osc.volume = rd.next_varint()? as f32 / 10.;
     osc.offset = rd.next_varint()? as f32 / 10.;
+    if wave_type == 0 {
+        osc.volume = 0.0;
+    }
 
     Ok(())
 }
@@ -255,17 +292,43 @@ pub struct NoiseDesignUnit {
     pub freq: NoiseDesignOscillator,
     /// Volume oscillator
     pub volu: NoiseDesignOscillator,
-    /// What fields to serialize
-    // TODO: Maybe it could be inferred, but at this point I'm not sure
-    // how to determine which fields to serialize.
-    // Turning the fields into `Option` didn't quite work out, as it seems
-    // the fields are treated as non-optional in a lot of cases.
+    /// Pan oscillator
+    ///
+    /// Moves [`Self::pan`] around over time.
+    /// At a volume of 100, it swings the full range from left to right.
+    pub pan_osc: NoiseDesignOscillator,
+    /// Fields to serialize even if they are unused.
+    ///
+    /// Fields that are in use are always serialized (see [`Self::written_flags`]), so this
+    /// only matters for writing files back exactly like they were read.
     pub ser_flags: NoiseDesignUnitFlags,
 }
 
+impl NoiseDesignUnit {
+    /// What fields get serialized: the ones in [`Self::ser_flags`], and the ones in use.
+    ///
+    /// The envelope is always serialized (like `PxTone` does), the pan if it's not 0,
+    /// and the oscillators that aren't [`NoiseDesignOscillator::default`].
+    #[must_use]
+    pub fn written_flags(&self) -> NoiseDesignUnitFlags {
+        let mut flags = self.ser_flags | NoiseDesignUnitFlags::ENVELOPE;
+        let unused = NoiseDesignOscillator::default();
+        for (used, flag) in [
+            (self.pan != 0, NoiseDesignUnitFlags::PAN),
+            (self.main != unused, NoiseDesignUnitFlags::OSC_MAIN),
+            (self.freq != unused, NoiseDesignUnitFlags::OSC_FREQ),
+            (self.volu != unused, NoiseDesignUnitFlags::OSC_VOLU),
+            (self.pan_osc != unused, NoiseDesignUnitFlags::OSC_PAN),
+        ] {
+            flags.set(flag, flags.contains(flag) || used);
+        }
+        flags
+    }
+}
+
 bitflags::bitflags! {
     /// What attributes of [`NoiseDesignUnit`] to serialize
-    #[derive(Clone, Copy, Default)]
+    #[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
     pub struct NoiseDesignUnitFlags: u8 {
         /// Serialize the envelopes
         const ENVELOPE = 0x04;
@@ -277,5 +340,63 @@ bitflags::bitflags! {
         const OSC_FREQ = 0x20;
         /// Serialize the volume oscillator
         const OSC_VOLU = 0x40;
+        /// Serialize the pan oscillator
+        const OSC_PAN = 0x80;
+    }
+}
+
+#[test]
+fn test_silent_wave_type() {
+    let mut data = NOISE_TAG.to_vec();
+    data.extend_from_slice(&LATEST_VER.to_le_bytes());
+    write_varint(4410, &mut data);
+    // One unit, with only a main oscillator
+    data.push(1);
+    write_varint(NoiseDesignUnitFlags::OSC_MAIN.bits().into(), &mut data);
+    // Type, invert, freq, volume, offset
+    for val in [0, 0, 4400, 800, 0] {
+        write_varint(val, &mut data);
     }
+    let mut noise = NoiseData::from_ptnoise(&data).unwrap();
+    assert_eq!(noise.units[0].main.volume.to_bits(), 0f32.to_bits());
+    let pcm = noise_to_pcm(&mut noise, &NoiseTable::generate());
+    assert!(pcm.smp.iter().all(|&b| b == 0));
+    // Wave types past the known ones are an error.
+    // The type is the first of the 7 oscillator bytes.
+    let len = data.len();
+    data[len - 7] = 17;
+    assert!(NoiseData::from_ptnoise(&data).is_err());
+}
+
+#[test]
+fn test_written_flags() {
+    let mut unit = NoiseDesignUnit::default();
+    assert_eq!(unit.written_flags(), NoiseDesignUnitFlags::ENVELOPE);
+    unit.main.type_ = NoiseType::Saw;
+    unit.main.freq = 440.0;
+    unit.main.volume = 50.0;
+    unit.pan_osc.freq = 2.0;
+    unit.pan_osc.volume = 100.0;
+    unit.enves.push(EnvPt { x: 100, y: 100 });
+    assert_eq!(
+        unit.written_flags(),
+        NoiseDesignUnitFlags::ENVELOPE
+            | NoiseDesignUnitFlags::OSC_MAIN
+            | NoiseDesignUnitFlags::OSC_PAN
+    );
+    // Built in code, without setting any flags, and nothing is lost
+    let mut noise = NoiseData::new();
+    noise.smp_num_44k = 4410;
+    noise.units.push(unit);
+    let read = NoiseData::from_ptnoise(&noise.to_ptnoise()).unwrap();
+    let read_unit = &read.units[0];
+    assert!(read_unit.main == noise.units[0].main);
+    assert!(read_unit.pan_osc == noise.units[0].pan_osc);
+    assert!(read_unit.freq == NoiseDesignOscillator::default());
+    assert_eq!(read_unit.enves.len(), 1);
+    let table = NoiseTable::generate();
+    assert_eq!(
+        read.render_preview(&table, 4410),
+        noise.render_preview(&table, 4410)
+    );
 }
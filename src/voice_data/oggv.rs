@@ -1,4 +1,5 @@
-use crate::{Bps, ChNum, PcmData};
+#[cfg(feature = "oggv")]
+use crate::{Bps, ChNum, PcmData, SourceSampleRate};
 
 /// Ogg/Vorbis voice data
 #[derive(Clone)]
@@ -13,6 +14,7 @@ pub struct OggVData {
     pub smp_num: i32,
 }
 
+/// Decode Ogg/Vorbis data. Returns `None` if the data is invalid.
 #[cfg(feature = "oggv")]
 pub fn decode_oggv(raw_data: &[u8]) -> Option<PcmData> {
     use symphonia_core::{codecs::Decoder, formats::FormatReader as _};
@@ -24,15 +26,16 @@ pub fn decode_oggv(raw_data: &[u8]) -> Option<PcmData> {
         media_stream,
         &symphonia_core::formats::FormatOptions::default(),
     )
-    .unwrap();
+    .ok()?;
     let track = ogg_reader.default_track()?;
 
     let mut pcm = PcmData::new();
-    pcm.sps = track.codec_params.sample_rate?;
+    pcm.sps = SourceSampleRate(track.codec_params.sample_rate?);
     pcm.ch = match track.codec_params.channels?.count() {
         1 => ChNum::Mono,
         2 => ChNum::Stereo,
-        _ => panic!("Vorbis channel number >2 not supported."),
+        // Vorbis channel number >2 not supported
+        _ => return None,
     };
     pcm.bps = Bps::B16;
     let mut i16_samples: Vec<i16> = Vec::new();
@@ -40,14 +43,14 @@ pub fn decode_oggv(raw_data: &[u8]) -> Option<PcmData> {
         &track.codec_params,
         &symphonia_core::codecs::DecoderOptions { verify: true },
     )
-    .unwrap();
+    .ok()?;
     let delay = track.codec_params.delay;
     let padding = track.codec_params.padding;
     while let Ok(packet) = ogg_reader.next_packet() {
         use symphonia_core::audio::AudioBufferRef;
-        let buf_ref = vorbis_decoder.decode(&packet).unwrap();
-        let AudioBufferRef::F32(buf) = buf_ref else {
-            panic!("Expected f32 Ogg/Vorbis samples");
+        let AudioBufferRef::F32(buf) = vorbis_decoder.decode(&packet).ok()? else {
+            // Expected f32 Ogg/Vorbis samples
+            return None;
         };
         let interleaved = planar_to_interleaved(buf.planes().planes());
         for sample in interleaved {
@@ -68,6 +71,7 @@ pub fn decode_oggv(raw_data: &[u8]) -> Option<PcmData> {
     Some(pcm)
 }
 
+#[cfg(feature = "oggv")]
 fn planar_to_interleaved(planar: &[&[f32]]) -> Vec<f32> {
     let channels = planar.len();
     let frames = planar[0].len();
@@ -1,4 +1,9 @@
-use crate::{Bps, ChNum, SampleRate, SourceSampleRate};
+#[cfg(feature = "symphonia")]
+mod decode;
+#[cfg(feature = "hound")]
+mod wav;
+
+use crate::{Bps, ChNum, NearestResampler, Resampler, SampleRate, SourceSampleRate};
 
 /// Describes PCM (Pulse Code Modulation) voice data
 #[derive(Clone, Default)]
@@ -19,6 +24,34 @@ pub struct PcmData {
 }
 
 impl PcmData {
+    /// Serialize into `.wav` (RIFF WAVE) data
+    ///
+    /// # Panics
+    ///
+    /// - If the sample data is larger than [`u32::MAX`] bytes
+    #[must_use]
+    pub fn to_wav(&self) -> Vec<u8> {
+        let ch = self.ch as u16;
+        let bps = self.bps as u16;
+        let block_align = ch * bps / 8;
+        let data_len: u32 = self.smp.len().try_into().unwrap();
+        let mut out = Vec::with_capacity(44 + self.smp.len());
+        out.extend_from_slice(b"RIFF");
+        out.extend_from_slice(&(36 + data_len).to_le_bytes());
+        out.extend_from_slice(b"WAVEfmt ");
+        out.extend_from_slice(&16u32.to_le_bytes());
+        // PCM format
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&ch.to_le_bytes());
+        out.extend_from_slice(&self.sps.get().to_le_bytes());
+        out.extend_from_slice(&(self.sps.get() * u32::from(block_align)).to_le_bytes());
+        out.extend_from_slice(&block_align.to_le_bytes());
+        out.extend_from_slice(&bps.to_le_bytes());
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&data_len.to_le_bytes());
+        out.extend_from_slice(&self.smp);
+        out
+    }
     pub(crate) fn create(&mut self, ch: ChNum, sps: SourceSampleRate, bps: Bps, sample_num: u32) {
         self.ch = ch;
         self.sps = sps;
@@ -33,11 +66,26 @@ impl PcmData {
         };
     }
 
-    pub(crate) fn to_converted(&self, new_samp_rate: SampleRate) -> (u32, Vec<u8>) {
+    /// Convert to 16 bit stereo at `new_samp_rate`.
+    ///
+    /// Returns the number of samples, and the converted sample data.
+    /// The sample rate is converted with [`NearestResampler`].
+    #[must_use]
+    pub fn to_converted(&self, new_samp_rate: SampleRate) -> (u32, Vec<u8>) {
+        self.to_converted_with(new_samp_rate, &NearestResampler)
+    }
+
+    /// Like [`Self::to_converted`], but the sample rate is converted with `resampler`.
+    #[must_use]
+    pub fn to_converted_with(
+        &self,
+        new_samp_rate: SampleRate,
+        resampler: &dyn Resampler,
+    ) -> (u32, Vec<u8>) {
         let mut new = self.clone();
         new.convert_to_bps_16();
         new.convert_to_stereo();
-        new.into_converted_sps(new_samp_rate)
+        new.into_converted_sps(new_samp_rate, resampler)
     }
 
     pub(crate) fn into_sample_buf(self) -> Vec<u8> {
@@ -51,6 +99,29 @@ impl PcmData {
     pub(crate) fn new() -> Self {
         Self::default()
     }
+
+    /// 16 bit PCM data from interleaved samples of `channels` channels
+    #[cfg(any(feature = "symphonia", feature = "hound"))]
+    fn from_interleaved_16(
+        channels: usize,
+        sps: u32,
+        samples: &[i16],
+    ) -> Result<Self, crate::PcmImportError> {
+        let ch = match channels {
+            1 => ChNum::Mono,
+            2 => ChNum::Stereo,
+            _ => return Err(crate::PcmImportError::UnsupportedChannels(channels)),
+        };
+        Ok(Self {
+            ch,
+            sps: SourceSampleRate(sps),
+            bps: Bps::B16,
+            num_samples: (samples.len() / channels)
+                .try_into()
+                .map_err(|_| crate::PcmImportError::TooLong)?,
+            smp: bytemuck::pod_collect_to_vec(samples),
+        })
+    }
     fn convert_to_stereo(&mut self) {
         let sample_size: usize =
             (self.num_samples as usize) * self.ch as usize * self.bps as usize / 8;
@@ -115,7 +186,7 @@ impl PcmData {
         self.bps = Bps::B16;
     }
 
-    fn into_converted_sps(self, new_sps: SampleRate) -> (u32, Vec<u8>) {
+    fn into_converted_sps(self, new_sps: SampleRate, resampler: &dyn Resampler) -> (u32, Vec<u8>) {
         // This function should only be called after channel num and sample rate conversion
         assert!(self.ch == ChNum::Stereo && self.bps == Bps::B16);
         if self.sps == new_sps.into() {
@@ -139,24 +210,10 @@ impl PcmData {
             .try_into()
             .unwrap();
 
-        let mut work_size = head_size + body_size + tail_size;
-
-        let sample_num = work_size / 4;
-        work_size = sample_num * 4;
-        let as_u32 = bytemuck::pod_collect_to_vec::<_, u32>(&self.smp);
-        let mut u32_buf: Vec<u32> = vec![0; work_size as usize];
-        for (i, u32_samp) in u32_buf.iter_mut().take(sample_num as usize).enumerate() {
-            let idx = i * self.sps as usize / usize::from(new_sps);
-            if let Some(samp) = as_u32.get(idx) {
-                *u32_samp = *samp;
-            } else {
-                eprintln!("into_converted_sps: Out of bounds ({idx})");
-                break;
-            }
-        }
-        (
-            body_size / 4,
-            bytemuck::cast_slice(&u32_buf)[..work_size as usize].to_vec(),
-        )
+        let sample_num = (head_size + body_size + tail_size) / 4;
+        let input = bytemuck::pod_collect_to_vec::<_, [i16; 2]>(&self.smp);
+        let mut output = vec![[0; 2]; sample_num as usize];
+        resampler.resample(&input, self.sps, new_sps, &mut output);
+        (body_size / 4, bytemuck::cast_slice(&output).to_vec())
     }
 }
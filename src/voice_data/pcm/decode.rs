@@ -0,0 +1,117 @@
+//! Import of any audio file symphonia can decode
+
+use {
+    crate::{PcmData, PcmImportError},
+    symphonia::core::{
+        audio::SampleBuffer,
+        codecs::DecoderOptions,
+        errors::Error,
+        formats::FormatOptions,
+        io::{MediaSource, MediaSourceStream, MediaSourceStreamOptions},
+        meta::MetadataOptions,
+        probe::Hint,
+    },
+};
+
+impl PcmData {
+    /// Decode the first audio track of `source` with symphonia.
+    ///
+    /// MP3, FLAC, AIFF, WAV and Ogg/Vorbis files are supported. `hint` can help symphonia
+    /// figure out the format, for example with the file extension.
+    /// The result is 16 bit PCM at the sample rate of the file, which can be mono or stereo.
+    ///
+    /// Requires the `symphonia` feature.
+    pub fn from_symphonia_source(
+        source: Box<dyn MediaSource>,
+        hint: &Hint,
+    ) -> Result<Self, PcmImportError> {
+        let stream = MediaSourceStream::new(source, MediaSourceStreamOptions::default());
+        let format_opts = FormatOptions {
+            // Leave out encoder delay and padding, so the sound starts right away
+            enable_gapless: true,
+            ..FormatOptions::default()
+        };
+        let mut format = symphonia::default::get_probe()
+            .format(hint, stream, &format_opts, &MetadataOptions::default())?
+            .format;
+        let track = format.default_track().ok_or(PcmImportError::NoTrack)?;
+        let track_id = track.id;
+        let (Some(sps), Some(channels)) =
+            (track.codec_params.sample_rate, track.codec_params.channels)
+        else {
+            return Err(PcmImportError::UnknownFormat);
+        };
+        let channels = channels.count();
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(|_| PcmImportError::NoTrack)?;
+        let mut samples = Vec::new();
+        let mut buf: Option<SampleBuffer<i16>> = None;
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+            let audio = match decoder.decode(&packet) {
+                Ok(audio) => audio,
+                // A corrupt packet, the rest of the file can still be audio
+                Err(Error::DecodeError(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let buf = match &mut buf {
+                Some(buf) if buf.capacity() >= audio.capacity() * channels => buf,
+                buf => buf.insert(SampleBuffer::new(audio.capacity() as u64, *audio.spec())),
+            };
+            buf.copy_interleaved_ref(audio);
+            let frames = buf.len() / channels;
+            let start = (packet.trim_start as usize).min(frames);
+            let end = frames.saturating_sub(packet.trim_end as usize).max(start);
+            samples.extend_from_slice(&buf.samples()[start * channels..end * channels]);
+        }
+        Self::from_interleaved_16(channels, sps, &samples)
+    }
+
+    /// Decode the audio file at `path` with [`Self::from_symphonia_source`], using the file
+    /// extension as the hint.
+    ///
+    /// Requires the `symphonia` feature.
+    pub fn from_audio_file(path: impl AsRef<std::path::Path>) -> Result<Self, PcmImportError> {
+        let path = path.as_ref();
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(ext);
+        }
+        let file = std::fs::File::open(path).map_err(Error::IoError)?;
+        Self::from_symphonia_source(Box::new(file), &hint)
+    }
+}
+
+#[test]
+fn test_from_symphonia_source() {
+    use crate::{Bps, ChNum, SourceSampleRate};
+    let samples: Vec<i16> = (0..2000i16).map(|i| i.wrapping_mul(13) % 4000 - 2000).collect();
+    let pcm = PcmData {
+        ch: ChNum::Stereo,
+        sps: SourceSampleRate(22_050),
+        bps: Bps::B16,
+        num_samples: 1000,
+        smp: bytemuck::pod_collect_to_vec(&samples),
+    };
+    let mut hint = Hint::new();
+    hint.with_extension("wav");
+    let audio = PcmData::from_symphonia_source(Box::new(std::io::Cursor::new(pcm.to_wav())), &hint)
+        .unwrap();
+    assert_eq!(audio.ch, ChNum::Stereo);
+    assert_eq!(audio.sps, SourceSampleRate(22_050));
+    assert_eq!(audio.bps, Bps::B16);
+    assert_eq!(audio.num_samples, 1000);
+    assert_eq!(audio.smp, pcm.smp);
+    assert!(matches!(
+        PcmData::from_symphonia_source(Box::new(std::io::Cursor::new(vec![0; 100])), &hint),
+        Err(PcmImportError::Decode(_))
+    ));
+}
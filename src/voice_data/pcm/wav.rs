@@ -0,0 +1,108 @@
+//! WAV import and export with hound
+
+use {
+    crate::{Bps, ChNum, PcmData, PcmImportError},
+    hound::{SampleFormat, WavReader, WavSpec, WavWriter},
+};
+
+impl PcmData {
+    /// Read a `.wav` file with hound.
+    ///
+    /// 8 and 16 bit files are read as they are. Other bit depths, and floating point samples,
+    /// are converted to 16 bit.
+    ///
+    /// Requires the `hound` feature.
+    pub fn read_wav(reader: impl std::io::Read) -> Result<Self, PcmImportError> {
+        let mut reader = WavReader::new(reader)?;
+        let spec = reader.spec();
+        let channels = usize::from(spec.channels);
+        let samples: Vec<i16> = match (spec.sample_format, spec.bits_per_sample) {
+            (SampleFormat::Int, 8) => {
+                let mut pcm = Self::from_interleaved_16(channels, spec.sample_rate, &[])?;
+                pcm.bps = Bps::B8;
+                // Stored unsigned, with silence at 128
+                pcm.smp = reader
+                    .samples::<i8>()
+                    .map(|s| s.map(|s| s.cast_unsigned() ^ 0x80))
+                    .collect::<Result<_, _>>()?;
+                pcm.num_samples =
+                    (pcm.smp.len() / channels).try_into().map_err(|_| PcmImportError::TooLong)?;
+                return Ok(pcm);
+            }
+            (SampleFormat::Int, ..=16) => reader.samples::<i16>().collect::<Result<_, _>>()?,
+            (SampleFormat::Int, bits) => reader
+                .samples::<i32>()
+                .map(|s| {
+                    #[expect(clippy::cast_possible_truncation)]
+                    s.map(|s| (s >> (bits - 16)) as i16)
+                })
+                .collect::<Result<_, _>>()?,
+            (SampleFormat::Float, _) => reader
+                .samples::<f32>()
+                .map(|s| {
+                    #[expect(clippy::cast_possible_truncation)]
+                    s.map(|s| (s * 32768.0).round().clamp(-32768.0, 32767.0) as i16)
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        Self::from_interleaved_16(channels, spec.sample_rate, &samples)
+    }
+
+    /// Write the PCM data as a `.wav` file with hound.
+    ///
+    /// Unlike [`Self::to_wav`], this writes into `writer` instead of building the whole file
+    /// in memory.
+    ///
+    /// Requires the `hound` feature.
+    pub fn write_wav(
+        &self,
+        writer: impl std::io::Write + std::io::Seek,
+    ) -> Result<(), PcmImportError> {
+        let spec = WavSpec {
+            channels: match self.ch {
+                ChNum::Mono => 1,
+                ChNum::Stereo => 2,
+            },
+            sample_rate: self.sps.get(),
+            bits_per_sample: self.bps as u16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::new(writer, spec)?;
+        match self.bps {
+            Bps::B8 => {
+                for &s in &self.smp {
+                    writer.write_sample((s ^ 0x80).cast_signed())?;
+                }
+            }
+            Bps::B16 => {
+                for s in bytemuck::pod_collect_to_vec::<u8, i16>(&self.smp) {
+                    writer.write_sample(s)?;
+                }
+            }
+        }
+        writer.finalize()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_wav_roundtrip() {
+    use crate::SourceSampleRate;
+    for (ch, bps) in [(ChNum::Mono, Bps::B8), (ChNum::Stereo, Bps::B16)] {
+        let mut pcm = PcmData::new();
+        pcm.create(ch, SourceSampleRate(11_025), bps, 500);
+        for (i, s) in pcm.smp.iter_mut().enumerate() {
+            *s = i.wrapping_mul(7).to_le_bytes()[0];
+        }
+        let mut out = std::io::Cursor::new(Vec::new());
+        pcm.write_wav(&mut out).unwrap();
+        let bytes = out.into_inner();
+        assert_eq!(bytes, pcm.to_wav());
+        let read = PcmData::read_wav(bytes.as_slice()).unwrap();
+        assert_eq!(
+            (read.ch, read.sps, read.bps, read.num_samples),
+            (ch, pcm.sps, bps, 500)
+        );
+        assert_eq!(read.smp, pcm.smp);
+    }
+}
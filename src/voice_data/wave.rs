@@ -1,4 +1,7 @@
-use crate::{EnvelopeSrc, pulse_oscillator::OsciPt};
+use crate::{
+    EnvelopeSrc,
+    pulse_oscillator::{OsciArgs, OsciPt, coord, overtone},
+};
 
 /// How to generate a wave voice
 #[derive(Clone)]
@@ -29,3 +32,34 @@ pub enum WaveDataPoints {
         points: Vec<OsciPt>,
     },
 }
+
+impl WaveData {
+    /// Render one cycle of the wave for drawing it, as `resolution` mono samples.
+    ///
+    /// `volume` works like [`Self::volume`]. The voice itself is rendered with 400 samples
+    /// per cycle.
+    #[must_use]
+    pub fn render(&self, resolution: u16, volume: i16) -> Vec<i16> {
+        let osci = OsciArgs {
+            volume,
+            sample_num: resolution.into(),
+        };
+        (0..resolution)
+            .map(|s| {
+                #[expect(clippy::cast_possible_truncation)]
+                let smp = (self.points.osc(osci, s).clamp(-1.0, 1.0) * 32767.) as i16;
+                smp
+            })
+            .collect()
+    }
+}
+
+impl WaveDataPoints {
+    /// The value of the wave at sample `index`
+    pub(crate) fn osc(&self, osci: OsciArgs, index: u16) -> f64 {
+        match self {
+            Self::Coord { points, resolution } => coord(osci, points, index, *resolution),
+            Self::Overtone { points } => overtone(osci, points, index),
+        }
+    }
+}
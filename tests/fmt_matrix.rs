@@ -0,0 +1,325 @@
+//! Compatibility matrix for the PxTone format versions and kinds.
+//!
+//! The fixtures are generated in code, so every combination can be covered without
+//! checking in binary files.
+//!
+//! The contract:
+//! - Every version/kind combination (except V1 tunes, which don't exist) can be written,
+//!   and read back with the same contents, byte-exact on re-serialization.
+//! - Files using the chunk layout of V4 and earlier can be read. Their units and events are
+//!   converted to the V5 representation.
+
+use ptcow::{
+    DEFAULT_KEY, Delay, DelayUnit, Event, EventPayload, FmtKind, FmtVer, GroupIdx, Herd, Key,
+    MooInstructions, NATIVE_SAMPLE_RATE, Overdrive, ProjectReadError, Song, StreamReadError,
+    UnitIdx, Voice, VoiceData, VoiceIdx, presets, read_song, read_song_from, read_song_lenient,
+    serialize_project,
+};
+use std::io::Cursor;
+
+const VERS: [FmtVer; 5] = [FmtVer::V1, FmtVer::V2, FmtVer::V3, FmtVer::V4, FmtVer::V5];
+const KINDS: [FmtKind; 2] = [FmtKind::Collage, FmtKind::Tune];
+
+fn fixture(ver: FmtVer, kind: FmtKind) -> (Song, Herd, MooInstructions) {
+    let mut song = Song::default();
+    song.fmt.ver = ver;
+    song.fmt.kind = kind;
+    song.text.name = "matrix".into();
+    song.text.comment = "fixture".into();
+    song.master.timing.bpm = 150.0;
+    song.master.timing.beats_per_meas = 3;
+    let eve = |tick, payload| Event {
+        payload,
+        unit: UnitIdx(0),
+        tick,
+    };
+    song.events.eves = vec![
+        eve(0, EventPayload::SetVoice(VoiceIdx(1))),
+        eve(0, EventPayload::Key(DEFAULT_KEY)),
+        eve(0, EventPayload::On { duration: 480 }),
+        eve(480, EventPayload::Velocity(80)),
+    ];
+    song.recalculate_length();
+    let mut herd = Herd::default();
+    herd.add_unit("lead");
+    let mut delay = Delay::default();
+    delay.unit = DelayUnit::Meas;
+    delay.rate = 33.5;
+    delay.freq = 4.0;
+    herd.delays.push(delay);
+    let mut ovr = Overdrive::default();
+    ovr.group = GroupIdx(1);
+    ovr.cut_percent = 50.0;
+    ovr.amp_mul = 2.0;
+    herd.overdrives.push(ovr);
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    ins.voices.extend(presets::wave::factory_voices().into_iter().take(2));
+    (song, herd, ins)
+}
+
+fn header_supported(ver: FmtVer, kind: FmtKind) -> bool {
+    !(ver == FmtVer::V1 && kind == FmtKind::Tune)
+}
+
+#[test]
+fn test_matrix_roundtrip() {
+    for ver in VERS {
+        for kind in KINDS {
+            let (song, herd, ins) = fixture(ver, kind);
+            let result = serialize_project(&song, &herd, &ins);
+            if !header_supported(ver, kind) {
+                assert!(result.is_err(), "{ver:?} {kind:?} should not be writable");
+                continue;
+            }
+            let bytes = result.unwrap();
+            let Ok((song2, herd2, ins2)) = read_song(&bytes, NATIVE_SAMPLE_RATE) else {
+                panic!("{ver:?} {kind:?} failed to read");
+            };
+            assert_eq!(song2.fmt.ver, ver);
+            assert_eq!(song2.fmt.kind, kind);
+            assert_eq!(song2.text.name, "matrix");
+            assert_eq!(song2.text.comment, "fixture");
+            assert_eq!(song2.master.timing.bpm.to_bits(), 150.0f32.to_bits());
+            assert_eq!(song2.master.timing.beats_per_meas, 3);
+            assert_eq!(song2.events.len(), song.events.len());
+            assert_eq!(herd2.units.len(), 1);
+            assert_eq!(herd2.units[UnitIdx(0)].name, "lead");
+            assert_eq!(herd2.delays.len(), 1);
+            assert_eq!(herd2.delays[0].rate.to_bits(), 33.5f32.to_bits());
+            assert_eq!(herd2.overdrives.len(), 1);
+            assert_eq!(ins2.voices.len(), 2);
+            let bytes2 = serialize_project(&song2, &herd2, &ins2).unwrap();
+            assert!(bytes == bytes2, "{ver:?} {kind:?} re-serialization differs");
+        }
+    }
+}
+
+#[test]
+fn test_matrix_read_from() {
+    for ver in VERS {
+        for kind in KINDS {
+            if !header_supported(ver, kind) {
+                continue;
+            }
+            let (mut song, herd, ins) = fixture(ver, kind);
+            song.seed = Some(1234);
+            let bytes = serialize_project(&song, &herd, &ins).unwrap();
+            let Ok((song2, herd2, ins2)) = read_song_from(Cursor::new(&bytes), NATIVE_SAMPLE_RATE)
+            else {
+                panic!("{ver:?} {kind:?} failed to stream");
+            };
+            assert_eq!(song2.seed, Some(1234));
+            let bytes2 = serialize_project(&song2, &herd2, &ins2).unwrap();
+            assert!(bytes == bytes2, "{ver:?} {kind:?} streamed read differs");
+            // Cut off in the middle of the last voice
+            let cut = bytes.len() - 40;
+            let Err(StreamReadError::Project(ProjectReadError::Data { len, .. })) =
+                read_song_from(Cursor::new(&bytes[..cut]), NATIVE_SAMPLE_RATE)
+            else {
+                panic!("{ver:?} {kind:?}: truncated stream didn't fail with a data error");
+            };
+            assert_eq!(len, cut);
+        }
+    }
+}
+
+fn varint(mut num: u32, out: &mut Vec<u8>) {
+    while num >= 0x80 {
+        out.push((num.to_le_bytes()[0] & 0x7F) | 0x80);
+        num >>= 7;
+    }
+    out.push(num.to_le_bytes()[0]);
+}
+
+fn chunk(code: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut out = code.to_vec();
+    out.extend_from_slice(&u32::try_from(body.len()).unwrap().to_le_bytes());
+    out.extend_from_slice(body);
+    out
+}
+
+/// The first chunk with `code` from a serialized project
+fn extract_chunk(data: &[u8], code: &[u8]) -> Vec<u8> {
+    let pos = data.windows(8).position(|w| w == code).unwrap();
+    let size = u32::from_le_bytes(data[pos + 8..pos + 12].try_into().unwrap()) as usize;
+    data[pos..pos + 12 + size].to_vec()
+}
+
+/// `evenMAST` chunk from (kind, delta tick, value) events
+fn even_mast(eves: &[[u32; 3]]) -> Vec<u8> {
+    let mut body = [3u16, 0].map(u16::to_le_bytes).concat();
+    body.extend_from_slice(&u32::try_from(eves.len()).unwrap().to_le_bytes());
+    for &num in eves.iter().flatten() {
+        varint(num, &mut body);
+    }
+    chunk(b"evenMAST", &body)
+}
+
+/// `evenUNIT` chunk from (delta tick, value) events
+fn even_unit(unit: u16, kind: u16, eves: &[(u32, u32)]) -> Vec<u8> {
+    let mut body = [unit, kind, 2, 0].map(u16::to_le_bytes).concat();
+    body.extend_from_slice(&u32::try_from(eves.len()).unwrap().to_le_bytes());
+    for &(delta, value) in eves {
+        varint(delta, &mut body);
+        varint(value, &mut body);
+    }
+    chunk(b"evenUNIT", &body)
+}
+
+fn old_file(header: &[u8; 16], chunks: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = header.to_vec();
+    // exe version, dummy
+    data.extend_from_slice(&[0; 4]);
+    for chunk in chunks {
+        data.extend_from_slice(chunk);
+    }
+    data.extend_from_slice(b"pxtoneND");
+    data.extend_from_slice(&[0; 4]);
+    data
+}
+
+fn events(song: &Song) -> Vec<(u8, u32, EventPayload)> {
+    song.events.iter().map(|eve| (eve.unit.0, eve.tick, eve.payload)).collect()
+}
+
+#[test]
+fn test_read_v4_events() {
+    let (song, herd, ins) = fixture(FmtVer::V5, FmtKind::Collage);
+    let v5 = serialize_project(&song, &herd, &ins).unwrap();
+    let data = old_file(
+        b"PTCOLLAGE-060930",
+        &[
+            // Tempo 150, 3 beats per measure, last tick is the end of measure 2
+            even_mast(&[[8, 0, 150.0f32.to_bits()], [9, 0, 3], [11, 2880, 0]]),
+            extract_chunk(&v5, b"num UNIT"),
+            extract_chunk(&v5, b"matePTV "),
+            even_unit(0, 5, &[(0, 100), (0, 90), (960, 50)]),
+            even_unit(0, 1, &[(0, 480), (960, 240)]),
+            even_unit(0, 12, &[(0, 0)]),
+        ],
+    );
+    let (song, herd, ins) = read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    let (streamed, ..) = read_song_from(Cursor::new(&data), NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(events(&streamed), events(&song));
+    assert_eq!(song.master.timing.bpm.to_bits(), 150.0f32.to_bits());
+    assert_eq!(song.master.timing.beats_per_meas, 3);
+    assert_eq!(
+        song.master.loop_points.last.map(std::num::NonZero::get),
+        Some(2)
+    );
+    assert_eq!(herd.units.len(), 1);
+    assert_eq!(ins.voices.len(), 1);
+    // Sorted like PxTone sorts them, the later of the two volume events at 0 wins
+    assert_eq!(
+        events(&song),
+        [
+            (0, 0, EventPayload::SetVoice(VoiceIdx(0))),
+            (0, 0, EventPayload::On { duration: 480 }),
+            (0, 0, EventPayload::Volume(90)),
+            (0, 960, EventPayload::On { duration: 240 }),
+            (0, 960, EventPayload::Volume(50)),
+        ]
+    );
+}
+
+#[test]
+fn test_read_v3_units() {
+    let mut ins = MooInstructions::new(NATIVE_SAMPLE_RATE);
+    for basic_key in [17664 - 512, 17664 + 256] {
+        let mut voice = Voice::from_data(VoiceData::Noise(presets::noise::snare()));
+        voice.base.unit.basic_key = Key(basic_key);
+        ins.voices.push(voice);
+    }
+    let v5 = serialize_project(&Song::default(), &Herd::default(), &ins).unwrap();
+    let ptn = extract_chunk(&v5, b"matePTN ");
+    let first_end = v5.windows(ptn.len()).position(|w| w == ptn).unwrap() + ptn.len();
+    let second_ptn = extract_chunk(&v5[first_end..], b"matePTN ");
+    // Noise voice, group out of range
+    let unit = chunk(b"pxtnUNIT", &[2u16, 9].map(u16::to_le_bytes).concat());
+    let data = old_file(
+        b"PTCOLLAGE-060115",
+        &[
+            unit.clone(),
+            unit,
+            ptn,
+            second_ptn,
+            even_unit(0, 2, &[(0, DEFAULT_KEY.0 as u32)]),
+        ],
+    );
+    let (song, herd, ins) = read_song(&data, NATIVE_SAMPLE_RATE).unwrap();
+    assert_eq!(herd.units.len(), 2);
+    assert_eq!(ins.voices[VoiceIdx(1)].name, "voice_01");
+    // The voice pitch moves the key events, and units without them get one
+    assert_eq!(
+        events(&song),
+        [
+            (0, 0, EventPayload::SetVoice(VoiceIdx(0))),
+            (1, 0, EventPayload::SetVoice(VoiceIdx(1))),
+            (0, 0, EventPayload::SetGroup(GroupIdx(6))),
+            (1, 0, EventPayload::SetGroup(GroupIdx(6))),
+            (0, 0, EventPayload::Key(DEFAULT_KEY - Key::semitones(2))),
+            (1, 0, EventPayload::Key(DEFAULT_KEY + Key::SEMITONE)),
+        ]
+    );
+}
+
+#[test]
+fn test_old_chunks_malformed() {
+    // Master events need 3 values per event
+    let mut mast = even_mast(&[]);
+    mast[12] = 2;
+    let data = old_file(b"PTCOLLAGE-060930", &[mast]);
+    assert_eq!(
+        read_song(&data, NATIVE_SAMPLE_RATE).err(),
+        Some(ProjectReadError::InvalidData)
+    );
+    // More units than voices
+    let unit = chunk(b"pxtnUNIT", &[0u16, 0].map(u16::to_le_bytes).concat());
+    let data = old_file(b"PTCOLLAGE-060115", &[unit]);
+    assert_eq!(
+        read_song(&data, NATIVE_SAMPLE_RATE).err(),
+        Some(ProjectReadError::InvalidData)
+    );
+    // Event values that don't fit their kind
+    let (song, herd, ins) = fixture(FmtVer::V5, FmtKind::Collage);
+    let v5 = serialize_project(&song, &herd, &ins).unwrap();
+    for (kind, value) in [
+        (3, 256),
+        (4, 40_000),
+        (5, 40_000),
+        (12, 256),
+        (13, 256),
+        (15, 300),
+    ] {
+        let data = old_file(
+            b"PTCOLLAGE-060930",
+            &[
+                extract_chunk(&v5, b"num UNIT"),
+                extract_chunk(&v5, b"matePTV "),
+                even_unit(0, kind, &[(0, value)]),
+            ],
+        );
+        assert_eq!(
+            read_song(&data, NATIVE_SAMPLE_RATE).err(),
+            Some(ProjectReadError::InvalidData)
+        );
+        assert!(matches!(
+            read_song_from(Cursor::new(&data), NATIVE_SAMPLE_RATE),
+            Err(StreamReadError::Project(ProjectReadError::InvalidData))
+        ));
+        assert_eq!(
+            read_song_lenient(&data, NATIVE_SAMPLE_RATE).err(),
+            Some(ProjectReadError::InvalidData)
+        );
+    }
+}
+
+#[test]
+fn test_unknown_header() {
+    let mut data = b"PTCOLLAGE-999999".to_vec();
+    data.extend_from_slice(&[0; 4]);
+    assert_eq!(
+        read_song(&data, NATIVE_SAMPLE_RATE).err(),
+        Some(ProjectReadError::FmtUnknown)
+    );
+}